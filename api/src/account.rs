@@ -0,0 +1,269 @@
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use mikaana_shared::{
+    Profile, UpdateProfile, UserExport, UserId, MAX_BIO_LEN, MAX_DISPLAY_NAME_LEN, MAX_WEBSITE_LEN,
+};
+
+use crate::{audit, auth, avatars, comments, config::DeletionMode, forum, terms, votes, AppState};
+
+fn query_profile(conn: &rusqlite::Connection, user_id: UserId) -> rusqlite::Result<Profile> {
+    conn.query_row(
+        "SELECT id, username, avatar_url, display_name, bio, website FROM users WHERE id = ?1",
+        [user_id],
+        |row| {
+            Ok(Profile {
+                user: auth::user_from_row(row, 0)?,
+                display_name: row.get(3)?,
+                bio: row.get(4)?,
+                website: row.get(5)?,
+            })
+        },
+    )
+}
+
+/// Trim, then map empty to `None`. Profile fields are plain text (widgets
+/// render them as text nodes), so only length and control characters are
+/// checked; newlines are allowed for the bio.
+fn optional_field(value: &str, max: usize) -> Result<Option<String>, StatusCode> {
+    let value = value.trim();
+    if value.chars().count() > max || value.chars().any(|c| c.is_control() && c != '\n') {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    Ok((!value.is_empty()).then(|| value.to_string()))
+}
+
+/// GET /api/auth/me/profile
+pub async fn get_profile(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Profile>, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+
+    let pool = state.reader.clone();
+    let profile = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        query_profile(&conn, user_id).map_err(|_| StatusCode::NOT_FOUND)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(profile))
+}
+
+/// PUT /api/auth/me — update display name, bio and website
+pub async fn update_profile(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<UpdateProfile>,
+) -> Result<Json<Profile>, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+
+    let display_name = optional_field(&payload.display_name.replace('\n', " "), MAX_DISPLAY_NAME_LEN)?;
+    let bio = optional_field(&payload.bio.replace("\r\n", "\n"), MAX_BIO_LEN)?.unwrap_or_default();
+    let website = optional_field(&payload.website, MAX_WEBSITE_LEN)?;
+    if let Some(url) = &website {
+        if !(url.starts_with("https://") || url.starts_with("http://")) {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    }
+
+    let pool = state.db.clone();
+    let profile = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        conn.execute(
+            "UPDATE users SET display_name = ?2, bio = ?3, website = ?4 WHERE id = ?1",
+            rusqlite::params![user_id, display_name, bio, website],
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        query_profile(&conn, user_id).map_err(|_| StatusCode::NOT_FOUND)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(profile))
+}
+
+// ── Data export ──
+
+const EXPORT_SCHEMA_VERSION: u32 = 1;
+
+/// GET /api/auth/me/export — everything the user has posted, as one JSON file
+pub async fn export_data(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+
+    let pool = state.reader.clone();
+    let export = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let err = |_| StatusCode::INTERNAL_SERVER_ERROR;
+
+        Ok::<_, StatusCode>(UserExport {
+            schema_version: EXPORT_SCHEMA_VERSION,
+            exported_at: conn
+                .query_row("SELECT datetime('now')", [], |row| row.get(0))
+                .map_err(err)?,
+            profile: query_profile(&conn, user_id).map_err(|_| StatusCode::NOT_FOUND)?,
+            comments: comments::query_user_comments(&conn, user_id).map_err(err)?,
+            threads: forum::query_user_threads(&conn, user_id).map_err(err)?,
+            replies: forum::query_user_replies(&conn, user_id).map_err(err)?,
+            votes: votes::query_user_votes(&conn, user_id).map_err(err)?,
+            terms_accepted: terms::query_acceptances(&conn, user_id).map_err(err)?,
+        })
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok((
+        [(
+            header::CONTENT_DISPOSITION,
+            "attachment; filename=\"mikaana-export.json\"",
+        )],
+        Json(export),
+    ))
+}
+
+// ── Account deletion ──
+
+/// Avatar shown for deleted accounts (GitHub's own "ghost" user).
+const GHOST_AVATAR: &str = "https://avatars.githubusercontent.com/u/10137?v=4";
+
+/// Delete the user's content in `DeletionMode::Remove`, together with the
+/// votes, notifications, bookmarks and attachment records that point at it (stored
+/// files are left in place but no longer linked). Threads take every reply with
+/// them, not just the user's own. Private messages they sent go too.
+fn remove_content(conn: &rusqlite::Connection, user_id: UserId) -> rusqlite::Result<()> {
+    for table in ["votes", "notifications", "vote_milestones", "attachments", "bookmarks"] {
+        conn.execute(
+            &format!(
+                "DELETE FROM {table} WHERE
+                    (target_type = 'comment' AND target_id IN
+                        (SELECT id FROM comments WHERE user_id = ?1))
+                 OR (target_type = 'thread' AND target_id IN
+                        (SELECT id FROM threads WHERE user_id = ?1))
+                 OR (target_type = 'reply' AND target_id IN
+                        (SELECT id FROM replies WHERE user_id = ?1
+                            OR thread_id IN (SELECT id FROM threads WHERE user_id = ?1)))"
+            ),
+            [user_id],
+        )?;
+    }
+    // Summaries may quote the removed replies, so drop them with the content
+    conn.execute(
+        "DELETE FROM thread_summaries WHERE thread_id IN
+            (SELECT id FROM threads WHERE user_id = ?1
+             UNION SELECT thread_id FROM replies WHERE user_id = ?1)",
+        [user_id],
+    )?;
+    conn.execute(
+        "UPDATE threads SET solution_reply_id = NULL
+         WHERE solution_reply_id IN (SELECT id FROM replies WHERE user_id = ?1)",
+        [user_id],
+    )?;
+    conn.execute(
+        "DELETE FROM replies
+         WHERE user_id = ?1 OR thread_id IN (SELECT id FROM threads WHERE user_id = ?1)",
+        [user_id],
+    )?;
+    conn.execute(
+        "DELETE FROM thread_reads WHERE thread_id IN (SELECT id FROM threads WHERE user_id = ?1)",
+        [user_id],
+    )?;
+    conn.execute("DELETE FROM threads WHERE user_id = ?1", [user_id])?;
+    conn.execute("DELETE FROM comments WHERE user_id = ?1", [user_id])?;
+    votes::discount(conn, "v.user_id = ?1", [user_id])?;
+    conn.execute("DELETE FROM votes WHERE user_id = ?1", [user_id])?;
+    conn.execute("DELETE FROM attachments WHERE user_id = ?1", [user_id])?;
+    conn.execute("DELETE FROM messages WHERE sender_id = ?1", [user_id])?;
+    Ok(())
+}
+
+/// DELETE /api/auth/me — delete the account, anonymizing or removing its
+/// content according to the `account_deletion` setting
+pub async fn delete_account(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<StatusCode, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+    let mode = state.config.load().account_deletion;
+
+    let pool = state.db.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let tx = conn
+            .transaction()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        let deleted: bool = tx
+            .query_row(
+                "SELECT deleted_at IS NOT NULL FROM users WHERE id = ?1",
+                [user_id],
+                |row| row.get(0),
+            )
+            .map_err(|_| StatusCode::NOT_FOUND)?;
+        if deleted {
+            return Err(StatusCode::GONE);
+        }
+
+        if mode == DeletionMode::Remove {
+            remove_content(&tx, user_id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        }
+
+        for sql in [
+            "DELETE FROM category_subscriptions WHERE user_id = ?1",
+            "DELETE FROM follows WHERE follower_id = ?1 OR followee_id = ?1",
+            "DELETE FROM blocks WHERE blocker_id = ?1 OR blocked_id = ?1",
+            "DELETE FROM bookmarks WHERE user_id = ?1",
+            "DELETE FROM drafts WHERE user_id = ?1",
+            "DELETE FROM thread_reads WHERE user_id = ?1",
+            "DELETE FROM category_reads WHERE user_id = ?1",
+            "DELETE FROM notifications WHERE user_id = ?1",
+            "DELETE FROM terms_acceptances WHERE user_id = ?1",
+            "UPDATE notifications SET actor_id = NULL WHERE actor_id = ?1",
+            // Anonymized content keeps no trace of where it came from
+            "UPDATE comments SET ip_hash = NULL WHERE user_id = ?1",
+            "UPDATE threads SET ip_hash = NULL WHERE user_id = ?1",
+            "UPDATE replies SET ip_hash = NULL WHERE user_id = ?1",
+        ] {
+            tx.execute(sql, [user_id])
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        }
+
+        // The row stays so authorship and audit references still resolve.
+        // Negating github_id frees it: logging in again creates a fresh
+        // account. Accounts previously merged into this one go too.
+        tx.execute(
+            "UPDATE users
+             SET github_id = -id, github_login = NULL, username = 'deleted', avatar_url = ?2,
+                 email = NULL, notify_email = 0, digest = 'off', display_name = NULL, bio = '',
+                 website = NULL, role = 'user', deleted_at = datetime('now')
+             WHERE id = ?1 OR merged_into = ?1",
+            rusqlite::params![user_id, GHOST_AVATAR],
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        audit::record(
+            &tx,
+            user_id,
+            "user.delete",
+            "user",
+            user_id.0,
+            serde_json::json!({ "mode": mode.as_str() }),
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        tx.commit().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    avatars::forget(&state.config.load().avatars.cache_dir, user_id).await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,149 @@
+//! Comments, threads and replies merged into one chronological list, for
+//! the site-wide recent activity widget and personal feeds (`follows.rs`).
+//! Only published content on the requesting site is listed.
+
+use axum::{
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use mikaana_shared::{ActivityItem, PaginatedCursor};
+use rusqlite::{Connection, ToSql};
+use serde::Deserialize;
+
+use crate::{auth, sites, AppState};
+
+/// Characters of the body kept in `ActivityItem::excerpt`.
+const EXCERPT_LEN: usize = 200;
+
+const DEFAULT_LIMIT: i64 = 10;
+const MAX_LIMIT: i64 = 50;
+
+/// Published content on site `?1`, one row per comment, thread or reply;
+/// `item_from_row` reads it followed by the author's columns (see
+/// `auth::user_from_row`).
+pub const ACTIVITY: &str = "SELECT 'comment' AS kind, c.id, c.user_id, c.post_slug AS title, c.body,
+        c.post_slug, NULL AS thread_id, c.created_at
+     FROM comments c
+     WHERE c.site_id = ?1 AND c.status = 'published'
+     UNION ALL
+     SELECT 'thread', t.id, t.user_id, t.title, t.body, NULL, t.id, t.created_at
+     FROM threads t
+     WHERE t.site_id = ?1 AND t.status = 'published' AND t.moved_to IS NULL
+     UNION ALL
+     SELECT 'reply', r.id, r.user_id, t.title, r.body, NULL, t.id, r.created_at
+     FROM replies r JOIN threads t ON t.id = r.thread_id
+     WHERE t.site_id = ?1 AND t.status = 'published' AND r.status = 'published'";
+
+fn excerpt(body: &str) -> String {
+    let mut chars = body.chars();
+    let head: String = chars.by_ref().take(EXCERPT_LEN).collect();
+    if chars.next().is_some() {
+        format!("{}\u{2026}", head.trim_end())
+    } else {
+        head
+    }
+}
+
+pub fn item_from_row(row: &rusqlite::Row) -> rusqlite::Result<ActivityItem> {
+    Ok(ActivityItem {
+        kind: row.get(0)?,
+        id: row.get(1)?,
+        title: row.get(3)?,
+        excerpt: excerpt(&row.get::<_, String>(4)?),
+        post_slug: row.get(5)?,
+        thread_id: row.get(6)?,
+        created_at: row.get(7)?,
+        user: auth::user_from_row(row, 8)?,
+    })
+}
+
+/// Position of an item in the list, as `<timestamp>,<kind>,<id>`.
+fn cursor(item: &ActivityItem) -> String {
+    format!("{},{},{}", item.created_at, item.kind, item.id)
+}
+
+pub fn parse_cursor(cursor: &str) -> Option<(String, String, i64)> {
+    let (rest, id) = cursor.rsplit_once(',')?;
+    let (at, kind) = rest.rsplit_once(',')?;
+    Some((at.to_string(), kind.to_string(), id.parse().ok()?))
+}
+
+/// Newest first, after `before` (a cursor from a previous page). `filter`
+/// is an extra condition on `a.user_id`, with its parameters numbered from
+/// `?5`.
+pub fn query_page(
+    conn: &Connection,
+    site: &str,
+    filter: &str,
+    filter_params: &[&dyn ToSql],
+    before: Option<(String, String, i64)>,
+    per_page: i64,
+) -> rusqlite::Result<PaginatedCursor<ActivityItem>> {
+    let (before_at, before_kind, before_id) = match before {
+        Some((at, kind, id)) => (Some(at), Some(kind), Some(id)),
+        None => (None, None, None),
+    };
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT a.*, u.id, u.username, u.avatar_url, u.display_name
+         FROM ({ACTIVITY}) a JOIN users u ON u.id = a.user_id
+         WHERE ({filter})
+           AND (?2 IS NULL OR (a.created_at, a.kind, a.id) < (?2, ?3, ?4))
+         ORDER BY a.created_at DESC, a.kind DESC, a.id DESC
+         LIMIT {}",
+        per_page + 1
+    ))?;
+    let mut params: Vec<&dyn ToSql> = vec![&site, &before_at, &before_kind, &before_id];
+    params.extend_from_slice(filter_params);
+    let mut items: Vec<ActivityItem> = stmt
+        .query_map(params.as_slice(), item_from_row)?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let next = if items.len() as i64 > per_page {
+        items.truncate(per_page as usize);
+        items.last().map(cursor)
+    } else {
+        None
+    };
+    Ok(PaginatedCursor {
+        items,
+        next,
+        per_page,
+    })
+}
+
+#[derive(Deserialize)]
+pub struct ActivityParams {
+    limit: Option<i64>,
+}
+
+/// GET /api/activity?limit=10 — the newest comments, threads and replies on
+/// the site, at most 50
+pub async fn recent_activity(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<ActivityParams>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let site = sites::resolve(&headers, &state.config.load())?;
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    let pool = state.reader.clone();
+    let items = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        query_page(&conn, &site, "1", &[], None, limit)
+            .map(|page| page.items)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok((
+        [
+            (header::CACHE_CONTROL, "public, max-age=60"),
+            (header::VARY, sites::VARY),
+        ],
+        Json(items),
+    ))
+}
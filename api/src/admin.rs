@@ -0,0 +1,319 @@
+use axum::{
+    body::{Body, Bytes},
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use mikaana_shared::{MergeSummary, MergeUsers, UserId};
+use serde::{Deserialize, Serialize};
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::{audit, auth, config, request_id, votes, AppState};
+
+// ── Configuration ──
+
+/// POST /api/admin/config/reload — re-read the config file without restarting
+pub async fn reload_config(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<StatusCode, StatusCode> {
+    let admin_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+
+    let pool = state.db.clone();
+    let shared = state.config.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_admin(&conn, admin_id)?;
+
+        config::reload(&shared, &conn).map_err(|e| {
+            request_id::log(format_args!("Configuration reload failed, keeping previous: {e}"));
+            StatusCode::UNPROCESSABLE_ENTITY
+        })?;
+
+        audit::record(&conn, admin_id, "config.reload", "config", 0, serde_json::json!({}))
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok(StatusCode::NO_CONTENT)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+}
+
+// ── Audit export ──
+
+#[derive(Deserialize)]
+pub struct AuditExportParams {
+    /// Inclusive lower bound on `created_at` (e.g. `2025-01-01`).
+    from: Option<String>,
+    /// Exclusive upper bound on `created_at`.
+    to: Option<String>,
+}
+
+/// One line of the audit export. Field names and meanings are a stable
+/// contract with operators' archives; bump `schema_version` on any change.
+#[derive(Serialize)]
+struct AuditRecord {
+    schema_version: u32,
+    id: i64,
+    created_at: String,
+    actor_id: Option<UserId>,
+    action: String,
+    target_type: String,
+    target_id: i64,
+    details: serde_json::Value,
+}
+
+const AUDIT_SCHEMA_VERSION: u32 = 1;
+
+/// GET /api/admin/audit/export?from=&to= — newline-delimited JSON, oldest first
+pub async fn export_audit(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<AuditExportParams>,
+) -> Result<Response, StatusCode> {
+    let admin_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+
+    let pool = state.reader.clone();
+    let conn = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_admin(&conn, admin_id)?;
+        Ok::<_, StatusCode>(conn)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    // Rows are serialized on a blocking thread and streamed as they are read,
+    // so large ranges never sit in memory.
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<Bytes, std::io::Error>>(64);
+    tokio::task::spawn_blocking(move || {
+        let mut stmt = match conn.prepare_cached(
+            "SELECT id, created_at, actor_id, action, target_type, target_id, details
+             FROM audit_log
+             WHERE (?1 IS NULL OR created_at >= ?1) AND (?2 IS NULL OR created_at < ?2)
+             ORDER BY id ASC",
+        ) {
+            Ok(stmt) => stmt,
+            Err(e) => {
+                let _ = tx.blocking_send(Err(std::io::Error::other(e)));
+                return;
+            }
+        };
+
+        let rows = stmt.query_map(rusqlite::params![params.from, params.to], |row| {
+            let details: String = row.get(6)?;
+            Ok(AuditRecord {
+                schema_version: AUDIT_SCHEMA_VERSION,
+                id: row.get(0)?,
+                created_at: row.get(1)?,
+                actor_id: row.get(2)?,
+                action: row.get(3)?,
+                target_type: row.get(4)?,
+                target_id: row.get(5)?,
+                details: serde_json::from_str(&details).unwrap_or(serde_json::Value::Null),
+            })
+        });
+
+        let rows = match rows {
+            Ok(rows) => rows,
+            Err(e) => {
+                let _ = tx.blocking_send(Err(std::io::Error::other(e)));
+                return;
+            }
+        };
+
+        for record in rows.filter_map(|r| r.ok()) {
+            let mut line = serde_json::to_vec(&record).unwrap_or_default();
+            line.push(b'\n');
+            if tx.blocking_send(Ok(Bytes::from(line))).is_err() {
+                return; // client went away
+            }
+        }
+    });
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/x-ndjson"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"audit.ndjson\"",
+            ),
+        ],
+        Body::from_stream(ReceiverStream::new(rx)),
+    )
+        .into_response())
+}
+
+// ── Account merge ──
+
+/// POST /api/admin/users/merge — reassign everything from one account to another
+pub async fn merge_users(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<MergeUsers>,
+) -> Result<Json<MergeSummary>, StatusCode> {
+    let admin_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+
+    let from = payload.from_user_id;
+    let into = payload.into_user_id;
+    if from == into {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let pool = state.db.clone();
+    let summary = tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_admin(&conn, admin_id)?;
+
+        let tx = conn
+            .transaction()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        // Both accounts must exist, and the target must be neither merged
+        // away nor deleted
+        for (id, must_be_live) in [(from, false), (into, true)] {
+            let gone: bool = tx
+                .query_row(
+                    "SELECT merged_into IS NOT NULL OR deleted_at IS NOT NULL FROM users WHERE id = ?1",
+                    [id],
+                    |row| row.get(0),
+                )
+                .map_err(|_| StatusCode::NOT_FOUND)?;
+            if must_be_live && gone {
+                return Err(StatusCode::CONFLICT);
+            }
+        }
+
+        let reassign = |table: &str| {
+            tx.execute(
+                &format!("UPDATE {table} SET user_id = ?2 WHERE user_id = ?1"),
+                [from, into],
+            )
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+        };
+        let comments = reassign("comments")?;
+        let threads = reassign("threads")?;
+        let replies = reassign("replies")?;
+
+        // Where both accounts voted on the same item, the surviving account's vote wins
+        votes::discount(
+            &tx,
+            "v.user_id = ?1 AND EXISTS (
+                 SELECT 1 FROM votes o
+                 WHERE o.user_id = ?2 AND o.target_type = v.target_type AND o.target_id = v.target_id)",
+            [from, into],
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let votes_dropped = tx
+            .execute(
+                "DELETE FROM votes WHERE user_id = ?1 AND EXISTS (
+                     SELECT 1 FROM votes v
+                     WHERE v.user_id = ?2
+                       AND v.target_type = votes.target_type
+                       AND v.target_id = votes.target_id)",
+                [from, into],
+            )
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let votes_moved = reassign("votes")?;
+
+        // Subscriptions are keyed by user, so copy missing ones then drop the rest
+        let subscriptions = tx
+            .execute(
+                "INSERT OR IGNORE INTO category_subscriptions (user_id, category_id, created_at)
+                 SELECT ?2, category_id, created_at FROM category_subscriptions WHERE user_id = ?1",
+                [from, into],
+            )
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        tx.execute("DELETE FROM category_subscriptions WHERE user_id = ?1", [from])
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        // Bookmarks likewise
+        tx.execute(
+            "INSERT OR IGNORE INTO bookmarks (user_id, target_type, target_id, created_at)
+             SELECT ?2, target_type, target_id, created_at FROM bookmarks WHERE user_id = ?1",
+            [from, into],
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        tx.execute("DELETE FROM bookmarks WHERE user_id = ?1", [from])
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        // Same for follows and blocks, on both sides, except that neither
+        // account may end up following or blocking itself
+        for (table, source, target) in [
+            ("follows", "follower_id", "followee_id"),
+            ("blocks", "blocker_id", "blocked_id"),
+        ] {
+            for sql in [
+                format!(
+                    "INSERT OR IGNORE INTO {table} ({source}, {target}, created_at)
+                     SELECT ?2, {target}, created_at FROM {table} WHERE {source} = ?1 AND {target} != ?2"
+                ),
+                format!(
+                    "INSERT OR IGNORE INTO {table} ({source}, {target}, created_at)
+                     SELECT {source}, ?2, created_at FROM {table} WHERE {target} = ?1 AND {source} != ?2"
+                ),
+            ] {
+                tx.execute(&sql, [from, into])
+                    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            }
+            tx.execute(
+                &format!("DELETE FROM {table} WHERE {source} = ?1 OR {target} = ?1"),
+                [from],
+            )
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        }
+        reassign("notifications")?;
+        // Messages the two accounts sent each other would become notes to self
+        tx.execute(
+            "DELETE FROM messages WHERE (sender_id = ?1 AND recipient_id = ?2)
+                                     OR (sender_id = ?2 AND recipient_id = ?1)",
+            [from, into],
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        for column in ["sender_id", "recipient_id"] {
+            tx.execute(
+                &format!("UPDATE messages SET {column} = ?2 WHERE {column} = ?1"),
+                [from, into],
+            )
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        }
+        // Read markers only drive "new" badges, and drafts are kept locally
+        // too; neither is worth reconciling
+        for table in ["thread_reads", "category_reads", "drafts"] {
+            tx.execute(&format!("DELETE FROM {table} WHERE user_id = ?1"), [from])
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        }
+
+        // Future logins with the old account land on the merged one
+        tx.execute(
+            "UPDATE users SET merged_into = ?2 WHERE id = ?1 OR merged_into = ?1",
+            [from, into],
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        let summary = MergeSummary {
+            comments,
+            threads,
+            replies,
+            subscriptions,
+            votes_moved,
+            votes_dropped,
+        };
+
+        audit::record(
+            &tx,
+            admin_id,
+            "user.merge",
+            "user",
+            from.0,
+            serde_json::json!({ "into_user_id": into, "summary": summary }),
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        tx.commit().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok::<_, StatusCode>(summary)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(summary))
+}
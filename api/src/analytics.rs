@@ -0,0 +1,145 @@
+//! Cookieless counts of widget interactions for site owners.
+//!
+//! `POST /api/events` adds one to the day's counter for the event and
+//! nothing else: no cookie is set, and neither the visitor's address nor
+//! their account is looked at or stored. Browsers asking not to be tracked
+//! (`DNT: 1`, `Sec-GPC: 1`) get the same answer without being counted.
+//! Counters older than `analytics.retention_days` are dropped as new ones
+//! come in.
+
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use mikaana_shared::{
+    AnalyticsEvent, AnalyticsSummary, DailyEventCount, EventCount, RecordEvent,
+};
+use serde::Deserialize;
+
+use crate::{auth, sites, AppState};
+
+/// Days summed up when `?days=` is left out.
+const DEFAULT_DAYS: u32 = 30;
+
+/// Whether the browser opted out of tracking.
+fn opted_out(headers: &HeaderMap) -> bool {
+    ["DNT", "Sec-GPC"]
+        .iter()
+        .any(|name| headers.get(*name).is_some_and(|v| v.as_bytes() == b"1"))
+}
+
+/// POST /api/events — count one widget interaction. 404 when analytics are
+/// off.
+pub async fn record_event(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<RecordEvent>,
+) -> Result<StatusCode, StatusCode> {
+    let config = state.config.load();
+    let retention_days = config
+        .analytics
+        .as_ref()
+        .ok_or(StatusCode::NOT_FOUND)?
+        .retention_days;
+    let site = sites::resolve(&headers, &config)?;
+    if opted_out(&headers) {
+        return Ok(StatusCode::NO_CONTENT);
+    }
+
+    let pool = state.db.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        conn.execute(
+            "INSERT INTO analytics_events (site_id, day, event, count)
+             VALUES (?1, date('now'), ?2, 1)
+             ON CONFLICT (site_id, day, event) DO UPDATE SET count = count + 1",
+            rusqlite::params![site, payload.event.as_str()],
+        )
+        .and_then(|_| {
+            conn.execute(
+                "DELETE FROM analytics_events WHERE day < date('now', ?1)",
+                [format!("-{retention_days} days")],
+            )
+        })
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+pub struct SummaryParams {
+    days: Option<u32>,
+}
+
+/// GET /api/admin/analytics?days= — the site's counts over the last `days`
+/// days, 30 by default (admins)
+pub async fn summary(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<SummaryParams>,
+) -> Result<Json<AnalyticsSummary>, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+    let config = state.config.load();
+    let retention_days = config
+        .analytics
+        .as_ref()
+        .ok_or(StatusCode::NOT_FOUND)?
+        .retention_days;
+    let site = sites::resolve(&headers, &config)?;
+    let days = params.days.unwrap_or(DEFAULT_DAYS).clamp(1, retention_days);
+    let pool = state.reader.clone();
+
+    let daily = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_admin(&conn, user_id)?;
+        let rows: Vec<(String, String, i64)> = conn
+            .prepare_cached(
+                "SELECT day, event, count FROM analytics_events
+                 WHERE site_id = ?1 AND day > date('now', ?2)
+                 ORDER BY day, event",
+            )
+            .and_then(|mut stmt| {
+                stmt.query_map(rusqlite::params![site, format!("-{days} days")], |row| {
+                    Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+                })?
+                .collect()
+            })
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        // Events since dropped from `AnalyticsEvent` are left out
+        Ok::<_, StatusCode>(
+            rows.into_iter()
+                .filter_map(|(day, event, count)| {
+                    Some(DailyEventCount {
+                        day,
+                        event: AnalyticsEvent::parse(&event)?,
+                        count,
+                    })
+                })
+                .collect::<Vec<_>>(),
+        )
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    let mut totals: Vec<EventCount> = Vec::new();
+    for d in &daily {
+        match totals.iter_mut().find(|t| t.event == d.event) {
+            Some(total) => total.count += d.count,
+            None => totals.push(EventCount {
+                event: d.event,
+                count: d.count,
+            }),
+        }
+    }
+    totals.sort_by_key(|t| std::cmp::Reverse(t.count));
+
+    Ok(Json(AnalyticsSummary {
+        days,
+        totals,
+        daily,
+    }))
+}
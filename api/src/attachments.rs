@@ -0,0 +1,463 @@
+//! File attachments on comments, threads and replies.
+//!
+//! `POST /api/uploads` stores a file and returns an unattached
+//! `Attachment`; creating content with its id in `attachment_ids` claims it.
+//! Files live either in a local directory, served back by
+//! `GET /api/uploads/{id}`, or in an S3-compatible bucket, served from the
+//! bucket's public URL. Either way the URL is fixed at upload time.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use mikaana_shared::{Attachment, UserId, MAX_ATTACHMENTS};
+use rand::RngCore;
+use rusqlite::OptionalExtension;
+use serde::Deserialize;
+
+use crate::{auth, config::StorageConfig, request_id, sites, AppState};
+
+/// Request body limit on the upload route; `uploads.max_bytes` can't exceed it.
+pub const BODY_LIMIT: usize = 50 * 1024 * 1024;
+
+const MAX_FILENAME_LEN: usize = 200;
+
+#[derive(Deserialize)]
+pub struct UploadParams {
+    #[serde(default)]
+    filename: String,
+}
+
+// ── Queries ──
+
+/// Attachments of one comment, thread or reply, in upload order.
+pub fn load(
+    conn: &rusqlite::Connection,
+    target_type: &str,
+    target_id: i64,
+) -> rusqlite::Result<Vec<Attachment>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, url, filename, content_type, size FROM attachments
+         WHERE target_type = ?1 AND target_id = ?2 ORDER BY id",
+    )?;
+    let rows = stmt
+        .query_map(rusqlite::params![target_type, target_id], |row| {
+            Ok(Attachment {
+                id: row.get(0)?,
+                url: row.get(1)?,
+                filename: row.get(2)?,
+                content_type: row.get(3)?,
+                size: row.get(4)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
+
+/// Whether `user_id` may attach all of `ids`: their own uploads on this
+/// site, not yet used elsewhere.
+pub fn check_claimable(
+    conn: &rusqlite::Connection,
+    site: &str,
+    user_id: UserId,
+    ids: &[i64],
+) -> Result<(), StatusCode> {
+    if ids.len() > MAX_ATTACHMENTS {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    for &id in ids {
+        let claimable: bool = conn
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM attachments
+                 WHERE id = ?1 AND site_id = ?2 AND user_id = ?3 AND target_id IS NULL)",
+                rusqlite::params![id, site, user_id],
+                |row| row.get(0),
+            )
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if !claimable {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    }
+    Ok(())
+}
+
+/// Attach uploads already vetted by `check_claimable` to new content.
+pub fn attach(
+    conn: &rusqlite::Connection,
+    user_id: UserId,
+    target_type: &str,
+    target_id: i64,
+    ids: &[i64],
+) -> rusqlite::Result<()> {
+    for &id in ids {
+        conn.execute(
+            "UPDATE attachments SET target_type = ?1, target_id = ?2
+             WHERE id = ?3 AND user_id = ?4 AND target_id IS NULL",
+            rusqlite::params![target_type, target_id, id, user_id],
+        )?;
+    }
+    Ok(())
+}
+
+// ── Validation ──
+
+/// File extension for a stored upload; only used to make keys readable.
+fn extension(content_type: &str) -> &'static str {
+    match content_type {
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        "application/pdf" => "pdf",
+        "text/plain" => "txt",
+        _ => "bin",
+    }
+}
+
+/// Whether the leading bytes agree with the declared type, for the types we
+/// can recognize. Stops a script uploaded as `image/png` from being served
+/// as one.
+pub fn content_matches(content_type: &str, data: &[u8]) -> bool {
+    match content_type {
+        "image/png" => data.starts_with(b"\x89PNG\r\n\x1a\n"),
+        "image/jpeg" => data.starts_with(b"\xff\xd8\xff"),
+        "image/gif" => data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a"),
+        "image/webp" => data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP",
+        "application/pdf" => data.starts_with(b"%PDF-"),
+        _ => true,
+    }
+}
+
+/// The base name of a client-supplied file name, without control or quote
+/// characters (it ends up in a `Content-Disposition` header).
+fn clean_filename(name: &str) -> String {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or_default();
+    let cleaned: String = base
+        .chars()
+        .filter(|c| !c.is_control() && *c != '"')
+        .take(MAX_FILENAME_LEN)
+        .collect();
+    if cleaned.trim().is_empty() {
+        "file".to_string()
+    } else {
+        cleaned.trim().to_string()
+    }
+}
+
+// ── Storage ──
+
+async fn store(
+    storage: &StorageConfig,
+    api_url: &str,
+    id: i64,
+    key: &str,
+    content_type: &str,
+    data: Bytes,
+) -> Result<String, String> {
+    match storage {
+        StorageConfig::Local { dir } => {
+            let path = std::path::Path::new(dir).join(key);
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent)
+                    .await
+                    .map_err(|e| format!("creating {}: {e}", parent.display()))?;
+            }
+            tokio::fs::write(&path, &data)
+                .await
+                .map_err(|e| format!("writing {}: {e}", path.display()))?;
+            Ok(format!("{api_url}/api/uploads/{id}"))
+        }
+        StorageConfig::S3 {
+            endpoint,
+            bucket,
+            region,
+            access_key_id,
+            secret_access_key,
+            public_url,
+        } => {
+            let bucket = S3Bucket {
+                endpoint,
+                bucket,
+                region,
+                access_key_id,
+                secret_access_key,
+            };
+            s3_put(&bucket, key, content_type, data).await?;
+            Ok(format!("{}/{key}", public_url.trim_end_matches('/')))
+        }
+    }
+}
+
+/// Credentials and address of an S3-compatible bucket.
+pub struct S3Bucket<'a> {
+    pub endpoint: &'a str,
+    pub bucket: &'a str,
+    pub region: &'a str,
+    pub access_key_id: &'a str,
+    pub secret_access_key: &'a str,
+}
+
+/// Upload `data` to `key` in the bucket, addressed path-style.
+pub async fn s3_put(bucket: &S3Bucket<'_>, key: &str, content_type: &str, data: Bytes) -> Result<(), String> {
+    let url = format!("{}/{}/{key}", bucket.endpoint.trim_end_matches('/'), bucket.bucket);
+    let url = reqwest::Url::parse(&url).map_err(|e| e.to_string())?;
+    let signed = sign_s3_put(&url, bucket.region, bucket.access_key_id, bucket.secret_access_key, &data);
+    let resp = reqwest::Client::new()
+        .put(url)
+        .header("x-amz-date", &signed.amz_date)
+        .header("x-amz-content-sha256", &signed.payload_hash)
+        .header(header::AUTHORIZATION, &signed.authorization)
+        .header(header::CONTENT_TYPE, content_type)
+        .body(data)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("S3 PUT answered {}", resp.status()));
+    }
+    Ok(())
+}
+
+struct SignedRequest {
+    amz_date: String,
+    payload_hash: String,
+    authorization: String,
+}
+
+/// AWS Signature Version 4 for a single-part `PUT`, signing only the headers
+/// S3 requires.
+fn sign_s3_put(
+    url: &reqwest::Url,
+    region: &str,
+    access_key_id: &str,
+    secret_access_key: &str,
+    data: &[u8],
+) -> SignedRequest {
+    let (date, amz_date) = amz_timestamps(SystemTime::now());
+    let payload_hash = sha256_hex(data);
+    let host = match url.port() {
+        Some(port) => format!("{}:{port}", url.host_str().unwrap_or_default()),
+        None => url.host_str().unwrap_or_default().to_string(),
+    };
+
+    const SIGNED_HEADERS: &str = "host;x-amz-content-sha256;x-amz-date";
+    // Keys are generated from [a-z0-9./-], so the path needs no further encoding
+    let canonical_request = format!(
+        "PUT\n{}\n\nhost:{host}\nx-amz-content-sha256:{payload_hash}\nx-amz-date:{amz_date}\n\n{SIGNED_HEADERS}\n{payload_hash}",
+        url.path()
+    );
+    let scope = format!("{date}/{region}/s3/aws4_request");
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+        sha256_hex(canonical_request.as_bytes())
+    );
+
+    let mut key = hmac(format!("AWS4{secret_access_key}").as_bytes(), date.as_bytes());
+    for part in [region, "s3", "aws4_request"] {
+        key = hmac(&key, part.as_bytes());
+    }
+    let signature = hex(&hmac(&key, string_to_sign.as_bytes()));
+
+    SignedRequest {
+        authorization: format!(
+            "AWS4-HMAC-SHA256 Credential={access_key_id}/{scope}, SignedHeaders={SIGNED_HEADERS}, Signature={signature}"
+        ),
+        amz_date,
+        payload_hash,
+    }
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let key = ring::hmac::Key::new(ring::hmac::HMAC_SHA256, key);
+    ring::hmac::sign(&key, data).as_ref().to_vec()
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    hex(ring::digest::digest(&ring::digest::SHA256, data).as_ref())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// `(YYYYMMDD, YYYYMMDDTHHMMSSZ)` in UTC.
+fn amz_timestamps(now: SystemTime) -> (String, String) {
+    let secs = now.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs()) as i64;
+    let (days, rem) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+
+    // Days since the epoch to a civil date (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    let date = format!("{year:04}{month:02}{day:02}");
+    let time = format!("{:02}{:02}{:02}", rem / 3600, rem % 3600 / 60, rem % 60);
+    (date.clone(), format!("{date}T{time}Z"))
+}
+
+fn new_key(site: &str, content_type: &str) -> String {
+    let mut bytes = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    format!("{site}/{}.{}", hex(&bytes), extension(content_type))
+}
+
+// ── Handlers ──
+
+/// POST /api/uploads?filename=photo.png — raw file body, typed by `Content-Type`
+pub async fn upload(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<UploadParams>,
+    body: Bytes,
+) -> Result<Json<Attachment>, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+    let config = state.config.load();
+    let site = sites::resolve(&headers, &config)?;
+    let Some(uploads) = config.uploads.clone() else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.split(';').next().unwrap_or_default().trim().to_ascii_lowercase())
+        .unwrap_or_default();
+    if !uploads.allowed_types.contains(&content_type) || !content_matches(&content_type, &body) {
+        return Err(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+    if body.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if body.len() > uploads.max_bytes {
+        return Err(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+    let filename = clean_filename(&params.filename);
+    let key = new_key(&site, &content_type);
+    let size = body.len() as i64;
+
+    // Reserve the row first so local URLs can carry the id
+    let pool = state.db.clone();
+    let row = (key.clone(), filename.clone(), content_type.clone());
+    let id = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_active(&conn, user_id)?;
+        let (key, filename, content_type) = row;
+        conn.execute(
+            "INSERT INTO attachments (site_id, user_id, storage_key, url, filename, content_type, size)
+             VALUES (?1, ?2, ?3, '', ?4, ?5, ?6)",
+            rusqlite::params![site, user_id, key, filename, content_type, size],
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        Ok::<_, StatusCode>(conn.last_insert_rowid())
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    let stored = store(
+        &uploads.storage,
+        &state.api_url,
+        id,
+        &key,
+        &content_type,
+        body,
+    )
+    .await;
+
+    let pool = state.db.clone();
+    let url = match stored {
+        Ok(url) => url,
+        Err(e) => {
+            request_id::log(format_args!("upload {id}: {e}"));
+            let _ = tokio::task::spawn_blocking(move || {
+                let conn = pool.get().ok()?;
+                conn.execute("DELETE FROM attachments WHERE id = ?1", [id]).ok()
+            })
+            .await;
+            return Err(StatusCode::BAD_GATEWAY);
+        }
+    };
+
+    let attachment = Attachment {
+        id,
+        url: url.clone(),
+        filename,
+        content_type,
+        size,
+    };
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        conn.execute(
+            "UPDATE attachments SET url = ?1 WHERE id = ?2",
+            rusqlite::params![url, id],
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(attachment))
+}
+
+/// GET /api/uploads/:id — files kept in local storage
+pub async fn serve_upload(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let Some(StorageConfig::Local { dir }) =
+        state.config.load().uploads.as_ref().map(|u| u.storage.clone())
+    else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+
+    let pool = state.reader.clone();
+    let (key, filename, content_type) = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        conn.query_row(
+            "SELECT storage_key, filename, content_type FROM attachments WHERE id = ?1",
+            [id],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?)),
+        )
+        .optional()
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    let data = tokio::fs::read(std::path::Path::new(&dir).join(&key))
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+
+    // Images display inline; anything else downloads rather than rendering
+    // on the API's origin
+    let disposition = if content_type.starts_with("image/") {
+        "inline".to_string()
+    } else {
+        let ascii: String = filename
+            .chars()
+            .map(|c| if c.is_ascii() { c } else { '_' })
+            .collect();
+        format!("attachment; filename=\"{ascii}\"")
+    };
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::CONTENT_DISPOSITION, disposition),
+            (header::CACHE_CONTROL, "public, max-age=31536000, immutable".to_string()),
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+        ],
+        data,
+    ))
+}
@@ -0,0 +1,20 @@
+use mikaana_shared::UserId;
+use rusqlite::Connection;
+
+/// Append an entry to the audit log. Call inside the same transaction as
+/// the action being recorded so the log never disagrees with the data.
+pub fn record(
+    conn: &Connection,
+    actor_id: UserId,
+    action: &str,
+    target_type: &str,
+    target_id: i64,
+    details: serde_json::Value,
+) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO audit_log (actor_id, action, target_type, target_id, details)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        rusqlite::params![actor_id, action, target_type, target_id, details.to_string()],
+    )?;
+    Ok(())
+}
@@ -1,42 +1,192 @@
 use axum::{
     extract::{Query, State},
-    http::{HeaderMap, StatusCode},
-    response::{IntoResponse, Redirect},
+    http::{header, HeaderMap, StatusCode},
+    response::{AppendHeaders, Html, IntoResponse, Redirect, Response},
     Json,
 };
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
-use mikaana_shared::User;
+use mikaana_shared::{User, UserId};
 use serde::{Deserialize, Serialize};
 
-use crate::AppState;
+use crate::{avatars, sites, AppState};
 
 // ── JWT Claims ──
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
-    pub sub: i64,    // user id
-    pub exp: usize,  // expiry (unix timestamp)
+    pub sub: UserId,
+    pub exp: usize, // expiry (unix timestamp)
+    /// Admin viewing the site as `sub`; see `impersonation.rs`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub impersonated_by: Option<UserId>,
 }
 
 impl Claims {
-    pub fn new(user_id: i64) -> Self {
+    pub fn new(user_id: UserId) -> Self {
         let exp = chrono_like_exp(); // 30 days from now
-        Self { sub: user_id, exp }
+        Self {
+            sub: user_id,
+            exp,
+            impersonated_by: None,
+        }
+    }
+
+    /// Whether requests made with this token may leave read marks behind;
+    /// an admin viewing as the user shouldn't change what's unread for them.
+    pub fn records_activity(&self) -> bool {
+        self.impersonated_by.is_none()
+    }
+
+    /// Short-lived token for `admin_id` to view the site as `user_id`.
+    pub fn impersonating(user_id: UserId, admin_id: UserId, ttl_secs: usize) -> Self {
+        Self {
+            sub: user_id,
+            exp: unix_now() + ttl_secs,
+            impersonated_by: Some(admin_id),
+        }
     }
 }
 
 fn chrono_like_exp() -> usize {
+    unix_now() + 30 * 24 * 60 * 60 // 30 days
+}
+
+fn unix_now() -> usize {
     use std::time::{SystemTime, UNIX_EPOCH};
-    let now = SystemTime::now()
+    SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .unwrap()
-        .as_secs() as usize;
-    now + 30 * 24 * 60 * 60 // 30 days
+        .as_secs() as usize
+}
+
+// ── OAuth state ──
+
+/// Contents of the OAuth `state` parameter, signed with the JWT secret.
+/// The nonce is also set as a cookie on the login redirect, so a callback
+/// only succeeds in the browser that started the login. (No `sub` field,
+/// so a state token can never pass as a session token or vice versa.)
+#[derive(Serialize, Deserialize)]
+struct OAuthState {
+    redirect: String,
+    nonce: String,
+    exp: usize,
+    /// Login runs in a popup; answer with `postMessage` instead of a redirect.
+    #[serde(default)]
+    popup: bool,
+}
+
+const OAUTH_NONCE_COOKIE: &str = "mikaana_oauth_nonce";
+
+/// How long a login may take between leaving for GitHub and coming back.
+const OAUTH_STATE_TTL_SECS: usize = 10 * 60;
+
+fn new_nonce() -> String {
+    use rand::Rng;
+    let bytes: [u8; 16] = rand::thread_rng().gen();
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// `Set-Cookie` value for the nonce; `Max-Age=0` clears it.
+fn nonce_cookie(state: &AppState, value: &str, max_age: usize) -> String {
+    let secure = if state.api_url.starts_with("https://") { "; Secure" } else { "" };
+    format!(
+        "{OAUTH_NONCE_COOKIE}={value}; Path=/api/auth; Max-Age={max_age}; HttpOnly; SameSite=Lax{secure}"
+    )
+}
+
+fn cookie_value<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(k, _)| *k == name)
+        .map(|(_, v)| v)
+}
+
+/// Whether `target` may receive a freshly issued token: its origin must be
+/// on the configured allow-list, or be `CORS_ORIGIN` when the list is empty.
+fn redirect_allowed(state: &AppState, target: &str) -> bool {
+    let Ok(url) = reqwest::Url::parse(target) else {
+        return false;
+    };
+    if !matches!(url.scheme(), "http" | "https") {
+        return false;
+    }
+    let origin = url.origin();
+    let same_origin = |allowed: &str| {
+        reqwest::Url::parse(allowed).is_ok_and(|allowed| allowed.origin() == origin)
+    };
+
+    let config = state.config.load();
+    if sites::is_site_origin(&config, &url) {
+        return true;
+    }
+    if config.redirect_allow_list.is_empty() {
+        same_origin(&state.cors_origin)
+    } else {
+        config.redirect_allow_list.iter().any(|a| same_origin(a))
+    }
+}
+
+/// Check the signature, expiry and nonce of a callback's `state`.
+fn verify_oauth_state(
+    headers: &HeaderMap,
+    state_param: Option<&str>,
+    jwt_secret: &str,
+) -> Result<OAuthState, StatusCode> {
+    let token = state_param.ok_or(StatusCode::BAD_REQUEST)?;
+    let data = decode::<OAuthState>(
+        token,
+        &DecodingKey::from_secret(jwt_secret.as_bytes()),
+        &Validation::default(),
+    )
+    .map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    let cookie = cookie_value(headers, OAUTH_NONCE_COOKIE).ok_or(StatusCode::BAD_REQUEST)?;
+    if cookie != data.claims.nonce {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    Ok(data.claims)
+}
+
+/// Page served to a popup login: hands the token to the opening widget and
+/// closes. Without an opener (popup blocked into a tab) it falls back to
+/// the usual redirect.
+fn popup_response(redirect_to: &str, jwt: &str, fallback_url: &str) -> Html<String> {
+    let origin = reqwest::Url::parse(redirect_to)
+        .map(|u| u.origin().ascii_serialization())
+        .unwrap_or_default();
+    // JSON string literals, with `<` escaped so nothing can close the script
+    let js = |s: &str| serde_json::to_string(s).unwrap_or_default().replace('<', "\\u003c");
+
+    Html(format!(
+        r#"<!doctype html>
+<meta charset="utf-8">
+<title>Signing in…</title>
+<script>
+  if (window.opener) {{
+    window.opener.postMessage({{ type: "mikaana-token", token: {token} }}, {origin});
+    window.close();
+  }} else {{
+    location.replace({fallback});
+  }}
+</script>"#,
+        token = js(jwt),
+        origin = js(&origin),
+        fallback = js(fallback_url),
+    ))
 }
 
 // ── Extract authenticated user from Authorization header ──
 
-pub fn extract_user_id(headers: &HeaderMap, jwt_secret: &str) -> Result<i64, StatusCode> {
+pub fn extract_user_id(headers: &HeaderMap, jwt_secret: &str) -> Result<UserId, StatusCode> {
+    extract_claims(headers, jwt_secret).map(|claims| claims.sub)
+}
+
+pub fn extract_claims(headers: &HeaderMap, jwt_secret: &str) -> Result<Claims, StatusCode> {
     let token = headers
         .get("Authorization")
         .and_then(|v| v.to_str().ok())
@@ -50,7 +200,83 @@ pub fn extract_user_id(headers: &HeaderMap, jwt_secret: &str) -> Result<i64, Sta
     )
     .map_err(|_| StatusCode::UNAUTHORIZED)?;
 
-    Ok(data.claims.sub)
+    Ok(data.claims)
+}
+
+/// Ensure the user holds the admin role.
+pub fn require_admin(conn: &rusqlite::Connection, user_id: UserId) -> Result<(), StatusCode> {
+    let role: String = conn
+        .query_row("SELECT role FROM users WHERE id = ?1", [user_id], |row| {
+            row.get(0)
+        })
+        .map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+    if role == "admin" {
+        Ok(())
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+/// Ensure the user may moderate content (moderators and admins).
+pub fn require_moderator(conn: &rusqlite::Connection, user_id: UserId) -> Result<(), StatusCode> {
+    let role: String = conn
+        .query_row("SELECT role FROM users WHERE id = ?1", [user_id], |row| {
+            row.get(0)
+        })
+        .map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+    if role == "admin" || role == "moderator" {
+        Ok(())
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+/// Ensure the user may post and vote: not banned, not deleted, not merged
+/// into another account (whose sessions carry on as that account's).
+pub fn require_active(conn: &rusqlite::Connection, user_id: UserId) -> Result<(), StatusCode> {
+    let (banned, deleted): (bool, bool) = conn
+        .query_row(
+            "SELECT banned_at IS NOT NULL, deleted_at IS NOT NULL OR merged_into IS NOT NULL
+             FROM users WHERE id = ?1",
+            [user_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|_| StatusCode::UNAUTHORIZED)?;
+
+    if deleted {
+        Err(StatusCode::UNAUTHORIZED)
+    } else if banned {
+        Err(StatusCode::FORBIDDEN)
+    } else {
+        Ok(())
+    }
+}
+
+// ── User rows ──
+
+/// Build a `User` from `id, username, avatar_url, display_name` columns
+/// starting at `offset`. The avatar is pointed at our proxy rather than the
+/// stored upstream URL.
+pub fn user_from_row(row: &rusqlite::Row, offset: usize) -> rusqlite::Result<User> {
+    let id = row.get(offset)?;
+    let upstream: String = row.get(offset + 2)?;
+    Ok(User {
+        id,
+        username: row.get(offset + 1)?,
+        avatar_url: avatars::url(id, &upstream),
+        display_name: row.get(offset + 3)?,
+        badges: Vec::new(),
+    })
+}
+
+pub fn query_user(conn: &rusqlite::Connection, id: UserId) -> rusqlite::Result<User> {
+    conn.query_row(
+        "SELECT id, username, avatar_url, display_name FROM users WHERE id = ?1",
+        [id],
+        |row| user_from_row(row, 0),
+    )
 }
 
 // ── GitHub OAuth types ──
@@ -58,6 +284,8 @@ pub fn extract_user_id(headers: &HeaderMap, jwt_secret: &str) -> Result<i64, Sta
 #[derive(Deserialize)]
 pub struct LoginParams {
     redirect: Option<String>,
+    #[serde(default)]
+    popup: bool,
 }
 
 #[derive(Deserialize)]
@@ -76,6 +304,8 @@ struct GitHubUser {
     id: i64,
     login: String,
     avatar_url: String,
+    /// Public profile email, if the user has one
+    email: Option<String>,
 }
 
 // ── Handlers ──
@@ -84,26 +314,57 @@ struct GitHubUser {
 pub async fn github_login(
     State(state): State<AppState>,
     Query(params): Query<LoginParams>,
-) -> impl IntoResponse {
+) -> Result<impl IntoResponse, StatusCode> {
     let redirect_after = params
         .redirect
         .unwrap_or_else(|| state.cors_origin.clone());
+    if !redirect_allowed(&state, &redirect_after) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let nonce = new_nonce();
+    let oauth_state = encode(
+        &Header::default(),
+        &OAuthState {
+            redirect: redirect_after,
+            nonce: nonce.clone(),
+            exp: unix_now() + OAUTH_STATE_TTL_SECS,
+            popup: params.popup,
+        },
+        &EncodingKey::from_secret(state.jwt_secret.as_bytes()),
+    )
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     let url = format!(
         "https://github.com/login/oauth/authorize?client_id={}&redirect_uri={}/api/auth/callback&state={}",
         state.github_client_id,
         state.api_url,
-        urlencoding::encode(&redirect_after),
+        urlencoding::encode(&oauth_state),
     );
 
-    Redirect::temporary(&url)
+    Ok((
+        AppendHeaders([(
+            header::SET_COOKIE,
+            nonce_cookie(&state, &nonce, OAUTH_STATE_TTL_SECS),
+        )]),
+        Redirect::temporary(&url),
+    ))
 }
 
 /// GET /api/auth/callback — exchange code, upsert user, redirect with JWT
 pub async fn github_callback(
     State(state): State<AppState>,
+    headers: HeaderMap,
     Query(params): Query<CallbackParams>,
-) -> Result<impl IntoResponse, StatusCode> {
+) -> Result<Response, StatusCode> {
+    // Reject forged or replayed callbacks before talking to GitHub
+    let oauth_state = verify_oauth_state(&headers, params.state.as_deref(), &state.jwt_secret)?;
+    let redirect_to = oauth_state.redirect;
+    // Checked again here: the allow-list may have shrunk since the login started
+    if !redirect_allowed(&state, &redirect_to) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
     // Exchange code for access token
     let client = reqwest::Client::new();
     let token_resp = client
@@ -136,22 +397,33 @@ pub async fn github_callback(
     // Upsert user in DB
     let pool = state.db.clone();
     let gh_id = gh_user.id;
-    let username = gh_user.login.clone();
+    let login = gh_user.login.clone();
     let avatar = gh_user.avatar_url.clone();
+    let email = gh_user.email.clone();
+    let is_admin = state.config.load().admin_github_ids.contains(&gh_id);
 
     let user_id = tokio::task::spawn_blocking(move || {
         let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        // The username is only taken from the login once, so it survives
+        // GitHub renames and edits
         conn.execute(
-            "INSERT INTO users (github_id, username, avatar_url)
-             VALUES (?1, ?2, ?3)
-             ON CONFLICT(github_id) DO UPDATE SET username = ?2, avatar_url = ?3",
-            rusqlite::params![gh_id, username, avatar],
+            "INSERT INTO users (github_id, github_login, username, avatar_url, email)
+             VALUES (?1, ?2, ?2, ?3, ?4)
+             ON CONFLICT(github_id) DO UPDATE
+             SET github_login = ?2, avatar_url = ?3, email = COALESCE(?4, email)",
+            rusqlite::params![gh_id, login, avatar, email],
         )
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-        let id: i64 = conn
+        if is_admin {
+            conn.execute("UPDATE users SET role = 'admin' WHERE github_id = ?1", [gh_id])
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        }
+
+        // Accounts merged away log in as the account they were merged into
+        let id: UserId = conn
             .query_row(
-                "SELECT id FROM users WHERE github_id = ?1",
+                "SELECT COALESCE(merged_into, id) FROM users WHERE github_id = ?1",
                 [gh_id],
                 |row| row.get(0),
             )
@@ -171,15 +443,15 @@ pub async fn github_callback(
     )
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    // Redirect back to the frontend with the token
-    let redirect_to = params
-        .state
-        .unwrap_or_else(|| state.cors_origin.clone());
-
+    // Redirect back to the frontend with the token, spending the nonce
     let separator = if redirect_to.contains('?') { "&" } else { "?" };
     let url = format!("{}{separator}token={jwt}", redirect_to);
+    let clear_nonce = AppendHeaders([(header::SET_COOKIE, nonce_cookie(&state, "", 0))]);
 
-    Ok(Redirect::temporary(&url))
+    if oauth_state.popup {
+        return Ok((clear_nonce, popup_response(&redirect_to, &jwt, &url)).into_response());
+    }
+    Ok((clear_nonce, Redirect::temporary(&url)).into_response())
 }
 
 /// GET /api/auth/me — return current user
@@ -189,21 +461,22 @@ pub async fn me(
 ) -> Result<Json<User>, StatusCode> {
     let user_id = extract_user_id(&headers, &state.jwt_secret)?;
 
-    let pool = state.db.clone();
+    let pool = state.reader.clone();
     let user = tokio::task::spawn_blocking(move || {
         let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-        conn.query_row(
-            "SELECT id, username, avatar_url FROM users WHERE id = ?1",
-            [user_id],
-            |row| {
-                Ok(User {
-                    id: row.get(0)?,
-                    username: row.get(1)?,
-                    avatar_url: row.get(2)?,
-                })
-            },
-        )
-        .map_err(|_| StatusCode::NOT_FOUND)
+        // Tokens outlive deleted and merged-away accounts; reject them so
+        // widgets sign out
+        let deleted: bool = conn
+            .query_row(
+                "SELECT deleted_at IS NOT NULL OR merged_into IS NOT NULL FROM users WHERE id = ?1",
+                [user_id],
+                |row| row.get(0),
+            )
+            .map_err(|_| StatusCode::NOT_FOUND)?;
+        if deleted {
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+        query_user(&conn, user_id).map_err(|_| StatusCode::NOT_FOUND)
     })
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
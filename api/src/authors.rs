@@ -0,0 +1,31 @@
+//! The blog author's comments and forum replies. Those by `authors.user_ids`
+//! are flagged `by_author` (their users also get the "Author" badge, see
+//! `badges.rs`), and with `authors.float` are listed ahead of everyone
+//! else's (otherwise in the order asked for).
+//!
+//! Applied to lists as they're served rather than stored, so changing the
+//! config takes effect on the next request.
+
+use mikaana_shared::UserId;
+
+use crate::{config::Config, content::Item};
+
+pub fn is_author(config: &Config, user_id: UserId) -> bool {
+    config.authors.user_ids.contains(&user_id)
+}
+
+/// Flag comments or replies by the author, and float them if configured.
+pub fn mark<T: Item>(config: &Config, items: &mut [T]) {
+    if config.authors.user_ids.is_empty() {
+        return;
+    }
+    let is_author = |item: &T| is_author(config, item.user_id());
+    for item in items.iter_mut() {
+        let flag = is_author(item);
+        *item.parts().by_author = flag;
+    }
+    if config.authors.float {
+        // Stable, so each group keeps its order
+        items.sort_by_key(|item| !is_author(item));
+    }
+}
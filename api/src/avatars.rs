@@ -0,0 +1,188 @@
+//! Avatars are served from `/api/avatars/{user_id}` instead of hotlinking
+//! GitHub, so readers' browsers never contact GitHub.
+//!
+//! The first request fetches the stored upstream URL at `avatars.size`
+//! pixels (GitHub's avatar CDN resizes via its `s` parameter) and keeps the
+//! result under `avatars.cache_dir`. Cached copies are refetched after
+//! `avatars.max_age_hours`; if GitHub is unreachable a stale copy is served
+//! rather than nothing.
+
+use std::path::{Path as FsPath, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+};
+use mikaana_shared::UserId;
+use rusqlite::OptionalExtension;
+
+use crate::{attachments, config::AvatarsConfig, request_id, AppState};
+
+/// Upstream avatars larger than this are refused.
+const MAX_AVATAR_BYTES: usize = 1024 * 1024;
+
+const IMAGE_TYPES: [&str; 4] = ["image/png", "image/jpeg", "image/gif", "image/webp"];
+
+static API_URL: OnceLock<String> = OnceLock::new();
+
+/// Record the public API origin that avatar URLs point at. Called once at
+/// startup, before any `User` is built.
+pub fn init(api_url: &str) {
+    let _ = API_URL.set(api_url.trim_end_matches('/').to_string());
+}
+
+/// Public URL of a user's avatar. The version parameter changes with the
+/// upstream URL, so browsers drop their copy when the user's avatar does.
+pub fn url(user_id: UserId, upstream: &str) -> String {
+    let base = API_URL.get().map_or("", String::as_str);
+    let version = &attachments::sha256_hex(upstream.as_bytes())[..12];
+    format!("{base}/api/avatars/{user_id}?v={version}")
+}
+
+/// Drop every cached avatar of a user, e.g. when the account is deleted.
+pub async fn forget(cache_dir: &str, user_id: UserId) {
+    prune(FsPath::new(cache_dir), user_id, None).await;
+}
+
+/// Remove the user's cache files other than `keep`. In-flight temporary
+/// files are left to their writers.
+async fn prune(cache_dir: &FsPath, user_id: UserId, keep: Option<&FsPath>) {
+    let Ok(mut entries) = tokio::fs::read_dir(cache_dir).await else {
+        return;
+    };
+    let prefix = format!("{user_id}-");
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with(&prefix) && !name.ends_with(".tmp") && Some(path.as_path()) != keep {
+            let _ = tokio::fs::remove_file(path).await;
+        }
+    }
+}
+
+/// Cache file for one upstream URL at one size; either changing picks a
+/// new file.
+fn cache_path(settings: &AvatarsConfig, user_id: UserId, upstream: &str) -> PathBuf {
+    let key = attachments::sha256_hex(format!("{upstream} {}", settings.size).as_bytes());
+    FsPath::new(&settings.cache_dir).join(format!("{user_id}-{}", &key[..16]))
+}
+
+fn image_type(data: &[u8]) -> Option<&'static str> {
+    IMAGE_TYPES
+        .into_iter()
+        .find(|t| attachments::content_matches(t, data))
+}
+
+/// The cached file and whether it is still fresh.
+async fn read_cached(path: &FsPath, max_age: Duration) -> Option<(Vec<u8>, bool)> {
+    let modified = tokio::fs::metadata(path).await.ok()?.modified().ok()?;
+    let data = tokio::fs::read(path).await.ok()?;
+    let fresh = modified.elapsed().is_ok_and(|age| age < max_age);
+    Some((data, fresh))
+}
+
+async fn fetch(upstream: &str, size: u32) -> Result<Vec<u8>, String> {
+    let mut url = reqwest::Url::parse(upstream).map_err(|e| format!("{upstream:?}: {e}"))?;
+    if url.scheme() != "https" {
+        return Err(format!("{upstream:?}: not an https URL"));
+    }
+    let query: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(k, _)| k != "s")
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+    url.query_pairs_mut()
+        .clear()
+        .extend_pairs(query)
+        .append_pair("s", &size.to_string());
+
+    let client = reqwest::Client::builder()
+        .user_agent("mikaana-api")
+        .timeout(Duration::from_secs(10))
+        .build()
+        .map_err(|e| e.to_string())?;
+    let resp = client.get(url).send().await.map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("{upstream}: {}", resp.status()));
+    }
+    if resp.content_length().is_some_and(|n| n > MAX_AVATAR_BYTES as u64) {
+        return Err(format!("{upstream}: too large"));
+    }
+    let data = resp.bytes().await.map_err(|e| e.to_string())?;
+    if data.len() > MAX_AVATAR_BYTES {
+        return Err(format!("{upstream}: too large"));
+    }
+    if image_type(&data).is_none() {
+        return Err(format!("{upstream}: not an image"));
+    }
+    Ok(data.to_vec())
+}
+
+/// Make `data` the user's cached avatar. Written to a temporary
+/// file first so concurrent requests never read a partial image.
+async fn store(settings: &AvatarsConfig, user_id: UserId, path: &FsPath, data: &[u8]) -> Result<(), String> {
+    tokio::fs::create_dir_all(&settings.cache_dir)
+        .await
+        .map_err(|e| format!("creating {}: {e}", settings.cache_dir))?;
+    let tmp = path.with_extension(format!("{:016x}.tmp", rand::random::<u64>()));
+    tokio::fs::write(&tmp, data)
+        .await
+        .map_err(|e| format!("writing {}: {e}", tmp.display()))?;
+    tokio::fs::rename(&tmp, path)
+        .await
+        .map_err(|e| format!("renaming {}: {e}", tmp.display()))?;
+    // Copies for the user's previous upstream URL or size
+    prune(FsPath::new(&settings.cache_dir), user_id, Some(path)).await;
+    Ok(())
+}
+
+/// GET /api/avatars/:user_id
+pub async fn get_avatar(
+    State(state): State<AppState>,
+    Path(user_id): Path<UserId>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let settings = state.config.load().avatars.clone();
+
+    let pool = state.reader.clone();
+    let upstream: String = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        conn.query_row("SELECT avatar_url FROM users WHERE id = ?1", [user_id], |row| row.get(0))
+            .optional()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .ok_or(StatusCode::NOT_FOUND)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    let max_age = Duration::from_secs(settings.max_age_hours * 3600);
+    let path = cache_path(&settings, user_id, &upstream);
+    let data = match read_cached(&path, max_age).await {
+        Some((data, true)) => data,
+        stale => match fetch(&upstream, settings.size).await {
+            Ok(data) => {
+                if let Err(e) = store(&settings, user_id, &path, &data).await {
+                    request_id::log(format_args!("Could not cache avatar of user {user_id}: {e}"));
+                }
+                data
+            }
+            Err(e) => {
+                request_id::log(format_args!("Avatar fetch failed for user {user_id}: {e}"));
+                stale.map(|(data, _)| data).ok_or(StatusCode::BAD_GATEWAY)?
+            }
+        },
+    };
+
+    let content_type = image_type(&data).ok_or(StatusCode::BAD_GATEWAY)?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (header::CACHE_CONTROL, format!("public, max-age={}", max_age.as_secs())),
+            (header::X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+        ],
+        data,
+    ))
+}
@@ -0,0 +1,193 @@
+//! Database backups, so nobody has to copy a live `.db` file and hope.
+//!
+//! Snapshots use SQLite's online backup API, which copies a consistent
+//! state a few pages at a time while the server keeps writing.
+//! `POST /api/admin/backup` writes one to the configured `backup` target;
+//! `mikaana-api backup <file>` writes one from the command line and
+//! `mikaana-api restore <file>` copies a snapshot over `DATABASE_URL`. Stop
+//! the server before restoring; the next start migrates an older snapshot.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use mikaana_shared::BackupInfo;
+use rusqlite::{backup::Backup, Connection, OpenFlags};
+
+use crate::{
+    attachments::{self, S3Bucket},
+    audit, auth,
+    config::BackupTarget,
+    request_id, AppState,
+};
+
+/// Pages copied per step of a restore.
+const PAGES_PER_STEP: std::ffi::c_int = 256;
+const STEP_PAUSE: Duration = Duration::from_millis(5);
+
+fn copy(from: &Connection, to: &mut Connection) -> rusqlite::Result<()> {
+    Backup::new(from, to)?.run_to_completion(PAGES_PER_STEP, STEP_PAUSE, None)
+}
+
+/// Write a snapshot of the database behind the read-only `conn` to a new
+/// file `dest`. It's copied in one step, a single read transaction, which
+/// in WAL mode doesn't hold up the writer; a copy in several steps would
+/// start over after every write landing in between.
+fn snapshot(conn: &Connection, dest: &Path) -> rusqlite::Result<()> {
+    let mut target = Connection::open(dest)?;
+    let backup = Backup::new(conn, &mut target)?;
+    backup.run_to_completion(std::ffi::c_int::MAX, STEP_PAUSE, None)
+}
+
+// ── CLI ──
+
+/// Run `backup <file>` or `restore <file>` against `database_url`. `None`
+/// when `args` aren't one of these, and the server should start instead.
+pub fn run_cli(args: &[String], database_url: &str) -> Option<Result<(), String>> {
+    match args {
+        [command, file] if command == "backup" => Some(backup_to(database_url, Path::new(file))),
+        [command, file] if command == "restore" => Some(restore_from(database_url, Path::new(file))),
+        _ => None,
+    }
+}
+
+fn backup_to(database_url: &str, file: &Path) -> Result<(), String> {
+    if file.exists() {
+        return Err(format!("{} already exists", file.display()));
+    }
+    let conn = Connection::open_with_flags(database_url, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("opening {database_url}: {e}"))?;
+    snapshot(&conn, file).map_err(|e| format!("writing {}: {e}", file.display()))?;
+    println!("Backed up {database_url} to {}", file.display());
+    Ok(())
+}
+
+fn restore_from(database_url: &str, file: &Path) -> Result<(), String> {
+    let source = Connection::open_with_flags(file, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("opening {}: {e}", file.display()))?;
+    let check: String = source
+        .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+        .map_err(|e| format!("{}: {e}", file.display()))?;
+    if check != "ok" {
+        return Err(format!("{}: integrity check failed: {check}", file.display()));
+    }
+    let has_users: bool = source
+        .query_row(
+            "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'users')",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("{}: {e}", file.display()))?;
+    if !has_users {
+        return Err(format!("{}: not a mikaana database", file.display()));
+    }
+
+    let mut target =
+        Connection::open(database_url).map_err(|e| format!("opening {database_url}: {e}"))?;
+    copy(&source, &mut target).map_err(|e| format!("restoring {database_url}: {e}"))?;
+    println!("Restored {database_url} from {}", file.display());
+    Ok(())
+}
+
+// ── Handlers ──
+
+/// POST /api/admin/backup — `404` when no `backup` target is configured
+pub async fn create_backup(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<BackupInfo>, StatusCode> {
+    let admin_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+    let target = state.config.load().backup.clone().ok_or(StatusCode::NOT_FOUND)?;
+
+    // Local snapshots go straight to their directory, S3 ones via a temp file
+    let staging_dir = match &target {
+        BackupTarget::Local { dir } => PathBuf::from(dir),
+        BackupTarget::S3 { .. } => std::env::temp_dir(),
+    };
+    let pool = state.reader.clone();
+    let (path, name, created_at) = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_admin(&conn, admin_id)?;
+
+        let (stamp, created_at): (String, String) = conn
+            .query_row(
+                "SELECT strftime('%Y%m%dT%H%M%SZ', 'now'), datetime('now')",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let name = format!("mikaana-{stamp}.db");
+        let path = staging_dir.join(&name);
+        let partial = staging_dir.join(format!("{name}.partial"));
+
+        let written = std::fs::create_dir_all(&staging_dir)
+            .map_err(|e| e.to_string())
+            .and_then(|_| snapshot(&conn, &partial).map_err(|e| e.to_string()))
+            .and_then(|_| std::fs::rename(&partial, &path).map_err(|e| e.to_string()));
+        if let Err(e) = written {
+            let _ = std::fs::remove_file(&partial);
+            eprintln!("Backup to {} failed: {e}", path.display());
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+        Ok::<_, StatusCode>((path, name, created_at))
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    let bytes = tokio::fs::metadata(&path)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .len();
+    let location = match &target {
+        BackupTarget::Local { .. } => path.display().to_string(),
+        BackupTarget::S3 {
+            endpoint,
+            bucket,
+            region,
+            access_key_id,
+            secret_access_key,
+            prefix,
+        } => {
+            let key = format!("{prefix}{name}");
+            let data = tokio::fs::read(&path).await;
+            let _ = tokio::fs::remove_file(&path).await;
+            let data = data.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            let bucket_ref = S3Bucket {
+                endpoint,
+                bucket,
+                region,
+                access_key_id,
+                secret_access_key,
+            };
+            attachments::s3_put(&bucket_ref, &key, "application/vnd.sqlite3", Bytes::from(data))
+                .await
+                .map_err(|e| {
+                    request_id::log(format_args!("Backup upload to {bucket}/{key} failed: {e}"));
+                    StatusCode::BAD_GATEWAY
+                })?;
+            format!("s3://{bucket}/{key}")
+        }
+    };
+
+    let pool = state.db.clone();
+    let info = BackupInfo {
+        location,
+        bytes,
+        created_at,
+    };
+    let details = serde_json::json!({ "location": info.location, "bytes": info.bytes });
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        audit::record(&conn, admin_id, "backup.create", "database", 0, details)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(info))
+}
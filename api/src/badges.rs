@@ -0,0 +1,65 @@
+//! Badges next to names on comments, threads and replies: staff
+//! ("Moderator"), the blog's author (`authors.user_ids`) and new members.
+//! Filled in by the handlers whose responses the widgets badge, for all the
+//! users in a response with one query.
+
+use std::collections::HashMap;
+
+use mikaana_shared::{User, UserBadge, UserId, NEW_MEMBER_DAYS};
+use rusqlite::Connection;
+
+use crate::{authors, config::Config};
+
+/// Set `badges` on `users`. Deleted accounts and imported guests get none.
+pub fn apply<'a>(
+    conn: &Connection,
+    config: &Config,
+    users: impl IntoIterator<Item = &'a mut User>,
+) -> rusqlite::Result<()> {
+    let mut users: Vec<&mut User> = users.into_iter().collect();
+    let mut ids: Vec<UserId> = users.iter().map(|u| u.id).collect();
+    ids.sort_unstable();
+    ids.dedup();
+    if ids.is_empty() {
+        return Ok(());
+    }
+
+    let placeholders = vec!["?"; ids.len()].join(", ");
+    let first_post = |table: &str| {
+        format!("COALESCE((SELECT MIN(created_at) FROM {table} WHERE user_id = u.id), u.created_at)")
+    };
+    let sql = format!(
+        "SELECT u.id, u.role IN ('moderator', 'admin'),
+                MIN(u.created_at, {}, {}, {}) > datetime('now', '-{NEW_MEMBER_DAYS} days')
+         FROM users u
+         WHERE u.id IN ({placeholders}) AND u.deleted_at IS NULL AND u.guest_key IS NULL",
+        first_post("comments"),
+        first_post("threads"),
+        first_post("replies"),
+    );
+    let mut badges: HashMap<UserId, Vec<UserBadge>> = HashMap::new();
+    // Not cached: the `IN` list changes length with every page
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(rusqlite::params_from_iter(&ids), |row| {
+        Ok((row.get::<_, UserId>(0)?, row.get::<_, bool>(1)?, row.get::<_, bool>(2)?))
+    })?;
+    for row in rows {
+        let (id, staff, new_member) = row?;
+        let list = badges.entry(id).or_default();
+        if staff {
+            list.push(UserBadge::Moderator);
+        }
+        let author = authors::is_author(config, id);
+        if author {
+            list.push(UserBadge::Author);
+        }
+        if new_member && !staff && !author {
+            list.push(UserBadge::NewMember);
+        }
+    }
+
+    for user in users.iter_mut() {
+        user.badges = badges.get(&user.id).cloned().unwrap_or_default();
+    }
+    Ok(())
+}
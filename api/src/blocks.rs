@@ -0,0 +1,129 @@
+//! Blocking other users. A blocked user's comments and replies still come
+//! back in lists fetched by the blocker, in place so threads keep their
+//! shape, but flagged `blocked` with their body and attachments left out.
+//! Blocks are private: the blocked user isn't told and can carry on as
+//! before.
+
+use std::collections::HashSet;
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use mikaana_shared::{User, UserId};
+use rusqlite::Connection;
+
+use crate::{auth, content::Item, AppState};
+
+/// POST /api/users/:id/block
+pub async fn block(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(blocked): Path<UserId>,
+) -> Result<StatusCode, StatusCode> {
+    set_block(state, headers, blocked, true).await
+}
+
+/// DELETE /api/users/:id/block
+pub async fn unblock(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(blocked): Path<UserId>,
+) -> Result<StatusCode, StatusCode> {
+    set_block(state, headers, blocked, false).await
+}
+
+async fn set_block(
+    state: AppState,
+    headers: HeaderMap,
+    blocked: UserId,
+    blocking: bool,
+) -> Result<StatusCode, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+    if blocked == user_id {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let pool = state.db.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        if blocking {
+            auth::require_active(&conn, user_id)?;
+            let exists: bool = conn
+                .query_row(
+                    "SELECT EXISTS (SELECT 1 FROM users
+                     WHERE id = ?1 AND deleted_at IS NULL AND merged_into IS NULL)",
+                    [blocked],
+                    |row| row.get(0),
+                )
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            if !exists {
+                return Err(StatusCode::NOT_FOUND);
+            }
+        }
+
+        let sql = if blocking {
+            "INSERT OR IGNORE INTO blocks (blocker_id, blocked_id) VALUES (?1, ?2)"
+        } else {
+            "DELETE FROM blocks WHERE blocker_id = ?1 AND blocked_id = ?2"
+        };
+        conn.execute(sql, [user_id, blocked])
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok(StatusCode::NO_CONTENT)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+}
+
+/// GET /api/blocks — users the viewer blocked, sorted by name
+pub async fn list_blocked(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<User>>, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+
+    let pool = state.reader.clone();
+    let users = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT u.id, u.username, u.avatar_url, u.display_name
+                 FROM blocks b JOIN users u ON u.id = b.blocked_id
+                 WHERE b.blocker_id = ?1
+                 ORDER BY COALESCE(u.display_name, u.username) COLLATE NOCASE",
+            )
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let users = stmt
+            .query_map([user_id], |row| auth::user_from_row(row, 0))
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok::<_, StatusCode>(users)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(users))
+}
+
+fn blocked_ids(conn: &Connection, viewer: UserId) -> rusqlite::Result<HashSet<UserId>> {
+    conn.prepare_cached("SELECT blocked_id FROM blocks WHERE blocker_id = ?1")?
+        .query_map([viewer], |row| row.get(0))?
+        .collect()
+}
+
+/// Blank out comments or replies by users `viewer` blocked.
+pub fn hide<T: Item>(conn: &Connection, viewer: UserId, items: &mut [T]) -> rusqlite::Result<()> {
+    let blocked = blocked_ids(conn, viewer)?;
+    for item in items.iter_mut().filter(|i| blocked.contains(&i.user_id())) {
+        let parts = item.parts();
+        *parts.body = String::new();
+        *parts.body_length = 0;
+        *parts.attachments = Vec::new();
+        *parts.blocked = true;
+    }
+    Ok(())
+}
@@ -0,0 +1,111 @@
+//! Threads, replies and comments users saved for later. Bookmarks are
+//! private to their owner and listed per site; bookmarked content that is
+//! removed or held for moderation drops out of the list.
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use mikaana_shared::{Bookmark, CreateBookmark};
+
+use crate::{activity, auth, sites, AppState};
+
+const TARGET_TYPES: [&str; 3] = ["thread", "reply", "comment"];
+
+/// GET /api/bookmarks — the viewer's bookmarks on this site, newest first
+pub async fn list_bookmarks(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<Bookmark>>, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+    let site = sites::resolve(&headers, &state.config.load())?;
+
+    let pool = state.reader.clone();
+    let bookmarks = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let mut stmt = conn
+            .prepare_cached(&format!(
+                "SELECT a.*, u.id, u.username, u.avatar_url, u.display_name, b.created_at
+                 FROM bookmarks b
+                 JOIN ({}) a ON a.kind = b.target_type AND a.id = b.target_id
+                 JOIN users u ON u.id = a.user_id
+                 WHERE b.user_id = ?2
+                 ORDER BY b.created_at DESC, b.rowid DESC",
+                activity::ACTIVITY
+            ))
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let bookmarks = stmt
+            .query_map(rusqlite::params![site, user_id], |row| {
+                Ok(Bookmark {
+                    item: activity::item_from_row(row)?,
+                    bookmarked_at: row.get(12)?,
+                })
+            })
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok::<_, StatusCode>(bookmarks)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(bookmarks))
+}
+
+/// POST /api/bookmarks — bookmarking twice is a no-op
+pub async fn add_bookmark(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<CreateBookmark>,
+) -> Result<StatusCode, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+    let site = sites::resolve(&headers, &state.config.load())?;
+    if !TARGET_TYPES.contains(&payload.target_type.as_str()) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let pool = state.db.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_active(&conn, user_id)?;
+        if !sites::owns(&conn, &site, &payload.target_type, payload.target_id)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        {
+            return Err(StatusCode::NOT_FOUND);
+        }
+
+        conn.execute(
+            "INSERT OR IGNORE INTO bookmarks (user_id, target_type, target_id) VALUES (?1, ?2, ?3)",
+            rusqlite::params![user_id, payload.target_type, payload.target_id],
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok(StatusCode::NO_CONTENT)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+}
+
+/// DELETE /api/bookmarks/:target_type/:target_id
+pub async fn remove_bookmark(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path((target_type, target_id)): Path<(String, i64)>,
+) -> Result<StatusCode, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+
+    let pool = state.db.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        conn.execute(
+            "DELETE FROM bookmarks WHERE user_id = ?1 AND target_type = ?2 AND target_id = ?3",
+            rusqlite::params![user_id, target_type, target_id],
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok(StatusCode::NO_CONTENT)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+}
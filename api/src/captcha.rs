@@ -0,0 +1,91 @@
+//! Optional Cloudflare Turnstile or hCaptcha check on posts from new
+//! accounts.
+//!
+//! While an account has fewer than `captcha.min_posts` published comments,
+//! threads and replies, creating one needs a `captcha_token`. Without one
+//! the handler answers `428 Precondition Required`, which tells the widget
+//! to show the challenge; a token the provider doesn't accept is `403`.
+
+use axum::http::StatusCode;
+use mikaana_shared::{CaptchaProvider, UserId};
+use serde::Deserialize;
+
+use crate::{config::CaptchaConfig, request_id, AppState};
+
+#[derive(Deserialize)]
+struct VerifyResponse {
+    success: bool,
+}
+
+fn verify_url(provider: CaptchaProvider) -> &'static str {
+    match provider {
+        CaptchaProvider::Turnstile => "https://challenges.cloudflare.com/turnstile/v0/siteverify",
+        CaptchaProvider::Hcaptcha => "https://api.hcaptcha.com/siteverify",
+    }
+}
+
+/// Ask the provider whether `token` is a solved, unused challenge.
+pub async fn verify(config: &CaptchaConfig, token: &str) -> Result<bool, String> {
+    let resp: VerifyResponse = reqwest::Client::new()
+        .post(verify_url(config.provider))
+        .timeout(std::time::Duration::from_secs(10))
+        .form(&[
+            ("secret", config.secret_key.as_str()),
+            ("response", token),
+            ("sitekey", config.site_key.as_str()),
+        ])
+        .send()
+        .await
+        .map_err(|e| e.to_string())?
+        .json()
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(resp.success)
+}
+
+/// Published comments, threads and replies by the user.
+fn post_count(conn: &rusqlite::Connection, user_id: UserId) -> rusqlite::Result<i64> {
+    conn.query_row(
+        "SELECT (SELECT COUNT(*) FROM comments WHERE user_id = ?1 AND status = 'published')
+              + (SELECT COUNT(*) FROM threads
+                 WHERE user_id = ?1 AND status = 'published' AND moved_to IS NULL)
+              + (SELECT COUNT(*) FROM replies WHERE user_id = ?1 AND status = 'published')",
+        [user_id],
+        |row| row.get(0),
+    )
+}
+
+/// Check the challenge for a post by `user_id`, if their account still
+/// needs one.
+pub async fn require(
+    state: &AppState,
+    user_id: UserId,
+    token: Option<&str>,
+) -> Result<(), StatusCode> {
+    let Some(config) = state.config.load().captcha.clone() else {
+        return Ok(());
+    };
+
+    let pool = state.reader.clone();
+    let posts = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        post_count(&conn, user_id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+    if posts >= config.min_posts {
+        return Ok(());
+    }
+
+    let token = token
+        .filter(|t| !t.is_empty())
+        .ok_or(StatusCode::PRECONDITION_REQUIRED)?;
+    match verify(&config, token).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(StatusCode::FORBIDDEN),
+        Err(e) => {
+            request_id::log(format_args!("Captcha verification failed: {e}"));
+            Err(StatusCode::BAD_GATEWAY)
+        }
+    }
+}
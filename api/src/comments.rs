@@ -1,117 +1,296 @@
 use axum::{
     extract::{Path, Query, State},
-    http::{HeaderMap, StatusCode},
-    Json,
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse},
+    Extension, Json,
 };
-use mikaana_shared::{Comment, CreateComment, User};
+use mikaana_shared::{Comment, CommentId, CreateComment, UserId};
 use serde::Deserialize;
 
-use crate::{auth, AppState};
+use crate::{
+    attachments, audit, auth, authors, badges, blocks, captcha,
+    content::{self, Item},
+    discussions, emails,
+    flood::{self, PostError},
+    ip_bans::IpHash,
+    limits::ValidJson,
+    notifications, post_settings, response_cache, sites, unfurl, webhooks, webmentions,
+    word_filters, AppState,
+};
 
 #[derive(Deserialize)]
 pub struct ListParams {
     slug: String,
 }
 
+// ── Queries ──
+
+/// Columns read by `comment_from_row`, in order.
+const COMMENT_SELECT: &str = "SELECT c.id, c.post_slug, c.body, c.created_at,
+        u.id, u.username, u.avatar_url, u.display_name, c.vote_count
+ FROM comments c
+ JOIN users u ON c.user_id = u.id";
+
+fn comment_from_row(row: &rusqlite::Row) -> rusqlite::Result<Comment> {
+    let body: String = row.get(2)?;
+    Ok(Comment {
+        id: row.get(0)?,
+        post_slug: row.get(1)?,
+        body_length: body.chars().count(),
+        body,
+        created_at: row.get(3)?,
+        user: auth::user_from_row(row, 4)?,
+        vote_count: row.get(8)?,
+        attachments: Vec::new(),
+        pending: false,
+        by_author: false,
+        blocked: false,
+    })
+}
+
+/// All comments on a site's post, oldest first.
+pub fn query_comments(
+    conn: &rusqlite::Connection,
+    site: &str,
+    slug: &str,
+) -> rusqlite::Result<Vec<Comment>> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "{COMMENT_SELECT} WHERE c.site_id = ?1 AND c.post_slug = ?2 AND c.status = 'published'
+         ORDER BY c.created_at ASC"
+    ))?;
+    let mut rows: Vec<Comment> = stmt
+        .query_map([site, slug], comment_from_row)?
+        .filter_map(|r| r.ok())
+        .collect();
+    content::load_attachments(conn, &mut rows)?;
+    Ok(rows)
+}
+
+pub fn query_user_comments(
+    conn: &rusqlite::Connection,
+    user_id: UserId,
+) -> rusqlite::Result<Vec<Comment>> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "{COMMENT_SELECT} WHERE c.user_id = ?1 ORDER BY c.created_at ASC"
+    ))?;
+    let rows = stmt
+        .query_map([user_id], comment_from_row)?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
+
+pub fn query_comment(conn: &rusqlite::Connection, id: CommentId) -> rusqlite::Result<Comment> {
+    let mut comment = conn.query_row(
+        &format!("{COMMENT_SELECT} WHERE c.id = ?1"),
+        [id],
+        comment_from_row,
+    )?;
+    comment.attachments = attachments::load(conn, Comment::TARGET_TYPE, id.0)?;
+    Ok(comment)
+}
+
+// ── Handlers ──
+
+/// A post's comments on the requesting site, through the response cache.
+async fn site_comments(
+    state: &AppState,
+    headers: &HeaderMap,
+    slug: String,
+) -> Result<Vec<Comment>, StatusCode> {
+    let site = sites::resolve(headers, &state.config.load())?;
+    let cache = state.config.load().cache.clone();
+    let cache_key = format!("comments:{site}:{slug}");
+    let mut comments = match response_cache::get(&cache, &cache_key) {
+        Some(comments) => comments,
+        None => {
+            let pool = state.reader.clone();
+            let config = state.config.load_full();
+            let comments = tokio::task::spawn_blocking(move || {
+                let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+                let mut comments = query_comments(&conn, &site, &slug)
+                    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+                badges::apply(&conn, &config, comments.iter_mut().map(|c| &mut c.user))
+                    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+                Ok::<_, StatusCode>(comments)
+            })
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+            response_cache::put(&cache, cache_key, comments.clone());
+            comments
+        }
+    };
+
+    authors::mark(&state.config.load(), &mut comments);
+    Ok(comments)
+}
+
 /// GET /api/comments?slug=...
 pub async fn list_comments(
     State(state): State<AppState>,
+    headers: HeaderMap,
     Query(params): Query<ListParams>,
 ) -> Result<Json<Vec<Comment>>, StatusCode> {
-    let pool = state.db.clone();
-    let slug = params.slug;
-
-    let comments = tokio::task::spawn_blocking(move || {
-        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-        let mut stmt = conn
-            .prepare(
-                "SELECT c.id, c.post_slug, c.body, c.created_at,
-                        u.id, u.username, u.avatar_url,
-                        COALESCE((SELECT SUM(value) FROM votes
-                                  WHERE target_type = 'comment' AND target_id = c.id), 0)
-                 FROM comments c
-                 JOIN users u ON c.user_id = u.id
-                 WHERE c.post_slug = ?1
-                 ORDER BY c.created_at ASC",
-            )
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-        let rows = stmt
-            .query_map([&slug], |row| {
-                Ok(Comment {
-                    id: row.get(0)?,
-                    post_slug: row.get(1)?,
-                    body: row.get(2)?,
-                    created_at: row.get(3)?,
-                    user: User {
-                        id: row.get(4)?,
-                        username: row.get(5)?,
-                        avatar_url: row.get(6)?,
-                    },
-                    vote_count: row.get(7)?,
-                })
-            })
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-            .filter_map(|r| r.ok())
-            .collect::<Vec<_>>();
+    let mut comments = site_comments(&state, &headers, params.slug).await?;
+    // Cached for everyone, so the viewer's blocks go on afterwards
+    if let Ok(viewer) = auth::extract_user_id(&headers, &state.jwt_secret) {
+        let pool = state.reader.clone();
+        comments = tokio::task::spawn_blocking(move || {
+            let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            blocks::hide(&conn, viewer, &mut comments)
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            Ok::<_, StatusCode>(comments)
+        })
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+    }
+    Ok(Json(comments))
+}
 
-        Ok::<_, StatusCode>(rows)
-    })
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+/// GET /api/comments/html?slug=... — the comments as a static HTML fragment
+/// in the widget's markup, for pages to embed at build time (or in
+/// `<noscript>`) so they can be read and indexed without the WASM widget.
+pub async fn list_comments_html(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<ListParams>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let comments = site_comments(&state, &headers, params.slug).await?;
+    Ok((
+        [
+            (header::CACHE_CONTROL, "public, max-age=300"),
+            (header::VARY, sites::VARY),
+        ],
+        Html(render_comments(&comments)),
+    ))
+}
 
-    Ok(Json(comments))
+/// Bodies are escaped and shown as typed, as the widget does (see
+/// `Markup::Plain`).
+fn render_comments(comments: &[Comment]) -> String {
+    let mut html = format!(
+        "<div class=\"mikaana-comments-static\">\n<h3>Comments ({})</h3>\n",
+        comments.len()
+    );
+    if comments.is_empty() {
+        html.push_str("<p class=\"mikaana-hint\">No comments yet.</p>\n");
+    }
+    for c in comments {
+        let date = c.created_at.get(..10).unwrap_or(&c.created_at);
+        html.push_str(&format!(
+            "<article class=\"mikaana-comment\" id=\"comment-{id}\">\n\
+             <div class=\"mikaana-comment-header\">\
+             <img src=\"{avatar}\" alt=\"\" class=\"mikaana-avatar\" width=\"24\" height=\"24\" loading=\"lazy\">\
+             <strong>{name}</strong>{badges} <time datetime=\"{at}\">{date}</time></div>\n\
+             <div class=\"mikaana-comment-body\">{body}</div>\n",
+            id = c.id,
+            badges = c
+                .user
+                .badges
+                .iter()
+                .map(|b| format!(" <span class=\"mikaana-user-badge\">{}</span>", b.label()))
+                .collect::<String>(),
+            avatar = emails::escape(&c.user.avatar_url),
+            name = emails::escape(c.user.name()),
+            at = emails::escape(&c.created_at),
+            date = emails::escape(date),
+            body = emails::escape(&c.body),
+        ));
+        if !c.attachments.is_empty() {
+            html.push_str("<ul class=\"mikaana-attachment-files\">\n");
+            for a in &c.attachments {
+                html.push_str(&format!(
+                    "<li><a href=\"{}\" rel=\"nofollow\">{}</a></li>\n",
+                    emails::escape(&a.url),
+                    emails::escape(&a.filename)
+                ));
+            }
+            html.push_str("</ul>\n");
+        }
+        html.push_str("</article>\n");
+    }
+    html.push_str("</div>\n");
+    html
 }
 
 /// POST /api/comments
 pub async fn create_comment(
     State(state): State<AppState>,
     headers: HeaderMap,
-    Json(payload): Json<CreateComment>,
-) -> Result<Json<Comment>, StatusCode> {
+    Extension(IpHash(ip_hash)): Extension<IpHash>,
+    ValidJson(payload): ValidJson<CreateComment>,
+) -> Result<Json<Comment>, PostError> {
     let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
-    let body = ammonia::clean(&payload.body);
+    let site = sites::resolve(&headers, &state.config.load())?;
+    let body = content::sanitize(&payload.body);
 
     if body.trim().is_empty() {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(StatusCode::BAD_REQUEST.into());
+    }
+    let slug = payload.post_slug.clone();
+    let kind = flood::Kind::Comment {
+        site: site.clone(),
+        slug: slug.clone(),
+    };
+    let repeat = flood::is_repeat(&state, user_id, &kind, &body).await?;
+    if !repeat {
+        captcha::require(&state, user_id, payload.captcha_token.as_deref()).await?;
     }
 
     let pool = state.db.clone();
-    let slug = payload.post_slug.clone();
+    let attachment_ids = payload.attachment_ids;
+    let post_title = payload.post_title;
+    let config = state.config.load_full();
+    let cors_origin = state.cors_origin.clone();
 
     let comment = tokio::task::spawn_blocking(move || {
-        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let mut conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let tx = conn
+            .transaction()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if let Some(earlier) = flood::check(&tx, &config.flood, user_id, &kind, &body)? {
+            return Ok(content::posted(&tx, &config, earlier.id, earlier.pending)?);
+        }
+        // What it repeated went away since, and it skipped the captcha
+        if repeat {
+            return Err(StatusCode::CONFLICT.into());
+        }
+        auth::require_active(&tx, user_id)?;
+        let comment_state = post_settings::comment_state(&tx, &site, &slug)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if comment_state.closed {
+            return Err(StatusCode::FORBIDDEN.into());
+        }
+        attachments::check_claimable(&tx, &site, user_id, &attachment_ids)?;
+        let status = word_filters::check(&tx, &[&body])?;
 
-        conn.execute(
-            "INSERT INTO comments (post_slug, user_id, body) VALUES (?1, ?2, ?3)",
-            rusqlite::params![slug, user_id, body],
+        tx.execute(
+            "INSERT INTO comments (site_id, post_slug, user_id, body, status, ip_hash)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            rusqlite::params![site, slug, user_id, body, status, ip_hash],
         )
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-        let id = conn.last_insert_rowid();
-
-        conn.query_row(
-            "SELECT c.id, c.post_slug, c.body, c.created_at,
-                    u.id, u.username, u.avatar_url
-             FROM comments c JOIN users u ON c.user_id = u.id
-             WHERE c.id = ?1",
-            [id],
-            |row| {
-                Ok(Comment {
-                    id: row.get(0)?,
-                    post_slug: row.get(1)?,
-                    body: row.get(2)?,
-                    created_at: row.get(3)?,
-                    user: User {
-                        id: row.get(4)?,
-                        username: row.get(5)?,
-                        avatar_url: row.get(6)?,
-                    },
-                    vote_count: 0,
-                })
-            },
-        )
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+        let id = tx.last_insert_rowid();
+        attachments::attach(&tx, user_id, "comment", id, &attachment_ids)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        let comment: Comment = content::posted(&tx, &config, id, status == "pending")?;
+        if !comment.pending {
+            notifications::announce(&tx, "comment", id)
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            webhooks::enqueue(&tx, &site, webhooks::COMMENT_CREATED, &comment)
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            webmentions::enqueue_sends(&tx, &config, &cors_origin, &site, &comment)
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            unfurl::enqueue(&tx, &config, &comment.body)
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            discussions::open(&tx, &config, &cors_origin, &site, &slug, post_title.as_deref())
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        }
+
+        tx.commit().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        Ok::<_, PostError>(comment)
     })
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
@@ -123,25 +302,35 @@ pub async fn create_comment(
 pub async fn delete_comment(
     State(state): State<AppState>,
     headers: HeaderMap,
-    Path(id): Path<i64>,
+    Path(id): Path<CommentId>,
 ) -> Result<StatusCode, StatusCode> {
     let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+    let site = sites::resolve(&headers, &state.config.load())?;
 
     let pool = state.db.clone();
     tokio::task::spawn_blocking(move || {
-        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-        let affected = conn
-            .execute(
-                "DELETE FROM comments WHERE id = ?1 AND user_id = ?2",
-                rusqlite::params![id, user_id],
-            )
+        let mut conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let tx = conn
+            .transaction()
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-        if affected == 0 {
-            Err(StatusCode::NOT_FOUND)
-        } else {
-            Ok(StatusCode::NO_CONTENT)
-        }
+        content::soft_delete(&tx, &site, user_id, "comment", id.0)?;
+        let slug: String = tx
+            .query_row("SELECT post_slug FROM comments WHERE id = ?1", [id], |row| row.get(0))
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        audit::record(
+            &tx,
+            user_id,
+            "comment.delete",
+            "comment",
+            id.0,
+            serde_json::json!({ "post_slug": slug, "author_id": user_id }),
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        tx.commit().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        Ok(StatusCode::NO_CONTENT)
     })
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
@@ -0,0 +1,884 @@
+//! Runtime settings that can change without a restart.
+//!
+//! Settings come from an optional TOML file at `CONFIG_PATH`, with a few
+//! environment variables taking precedence for backwards compatibility.
+//! Sending SIGHUP or calling `POST /api/admin/config/reload` re-reads them;
+//! handlers always see the latest snapshot via `AppState::config`.
+//! Boot-time settings (database path, secrets, CORS origin) stay in env vars
+//! and still require a restart.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use arc_swap::ArcSwap;
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use mikaana_shared::{
+    Branding, CaptchaInfo, CaptchaProvider, ContentLimits, Features, Markup, PublicConfig,
+    TermsInfo, UploadLimits, UserId, VoteMode,
+};
+use serde::Deserialize;
+
+use crate::{sites, AppState, DbPool};
+
+pub type SharedConfig = Arc<ArcSwap<Config>>;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// GitHub account ids granted the admin role on login.
+    pub admin_github_ids: Vec<i64>,
+    /// What happens to a user's content when they delete their account.
+    pub account_deletion: DeletionMode,
+    /// External service that writes thread summaries; unset disables them.
+    pub summarizer: Option<SummarizerConfig>,
+    pub branding: BrandingConfig,
+    /// Origins (`https://blog.example.com`) that may receive a token after
+    /// login. Empty means only `CORS_ORIGIN`.
+    pub redirect_allow_list: Vec<String>,
+    /// Further sites served by this instance; see `sites.rs`.
+    pub sites: Vec<SiteConfig>,
+    pub github_stats: GitHubStatsConfig,
+    /// Scores at which authors of comments and replies are notified.
+    pub vote_milestones: Vec<i64>,
+    /// `updown`, `up-only` (likes) or `disabled`.
+    pub vote_mode: VoteMode,
+    /// Language tag for dates and numbers in the widgets; unset follows
+    /// each visitor's browser.
+    pub locale: Option<String>,
+    /// File attachments; unset disables uploads.
+    pub uploads: Option<UploadsConfig>,
+    pub avatars: AvatarsConfig,
+    pub cache: CacheConfig,
+    /// Challenge for posts from new accounts; unset disables it.
+    pub captcha: Option<CaptchaConfig>,
+    /// Terms of service users accept before posting; unset disables it.
+    pub terms: Option<TermsConfig>,
+    /// Header a reverse proxy puts the client address in (`Fly-Client-IP`,
+    /// `X-Forwarded-For`, ...); unset uses the connection's peer address.
+    pub client_ip_header: Option<String>,
+    /// Target of admin-triggered database backups; unset disables them.
+    pub backup: Option<BackupTarget>,
+    pub database: DatabaseConfig,
+    /// Service that sends email on the API's behalf; unset disables email.
+    pub mailer: Option<MailerConfig>,
+    /// Public address of the forum page (`https://blog.example.com/discuss/`),
+    /// for links in emails and the forum sitemap.
+    pub forum_url: Option<String>,
+    /// Receiving (and optionally sending) webmentions; unset disables them.
+    pub webmentions: Option<WebmentionsConfig>,
+    /// Previews of links in posts; unset disables them.
+    pub unfurl: Option<UnfurlConfig>,
+    /// Counts of widget interactions; unset disables them.
+    pub analytics: Option<AnalyticsConfig>,
+    pub authors: AuthorsConfig,
+    pub flood: FloodConfig,
+    /// Forum threads opened for blog posts on their first comment, tried in
+    /// order; see `discussions.rs`.
+    pub discussions: Vec<DiscussionConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            admin_github_ids: Vec::new(),
+            account_deletion: DeletionMode::default(),
+            summarizer: None,
+            branding: BrandingConfig::default(),
+            redirect_allow_list: Vec::new(),
+            sites: Vec::new(),
+            github_stats: GitHubStatsConfig::default(),
+            vote_milestones: vec![1, 10, 50],
+            vote_mode: VoteMode::default(),
+            locale: None,
+            uploads: None,
+            avatars: AvatarsConfig::default(),
+            cache: CacheConfig::default(),
+            captcha: None,
+            terms: None,
+            client_ip_header: None,
+            backup: None,
+            database: DatabaseConfig::default(),
+            mailer: None,
+            forum_url: None,
+            webmentions: None,
+            unfurl: None,
+            analytics: None,
+            authors: AuthorsConfig::default(),
+            flood: FloodConfig::default(),
+            discussions: Vec::new(),
+        }
+    }
+}
+
+/// GitHub only reports bytes per language; lines are estimated from these.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GitHubStatsConfig {
+    pub default_bytes_per_line: i64,
+    /// Per language, keyed by GitHub's language name (`Rust`, `C++`, ...).
+    /// Entries override `BUILTIN_BYTES_PER_LINE`.
+    pub bytes_per_line: HashMap<String, i64>,
+}
+
+/// Measured against the actual line count of a large Rust workspace.
+const BUILTIN_BYTES_PER_LINE: &[(&str, i64)] = &[("Rust", 53)];
+
+impl Default for GitHubStatsConfig {
+    fn default() -> Self {
+        GitHubStatsConfig {
+            default_bytes_per_line: 40,
+            bytes_per_line: HashMap::new(),
+        }
+    }
+}
+
+impl GitHubStatsConfig {
+    pub fn bytes_per_line(&self, language: &str) -> i64 {
+        self.bytes_per_line
+            .get(language)
+            .copied()
+            .or_else(|| {
+                BUILTIN_BYTES_PER_LINE
+                    .iter()
+                    .find(|(name, _)| *name == language)
+                    .map(|&(_, n)| n)
+            })
+            .unwrap_or(self.default_bytes_per_line)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.default_bytes_per_line <= 0 {
+            return Err("github_stats.default_bytes_per_line: must be positive".to_string());
+        }
+        if let Some((language, _)) = self.bytes_per_line.iter().find(|(_, &n)| n <= 0) {
+            return Err(format!("github_stats.bytes_per_line.{language}: must be positive"));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SiteConfig {
+    /// Stored with the site's content; changing it orphans that content.
+    pub id: String,
+    /// Browser origins (`https://blog.example.com`) whose requests belong to
+    /// this site. They are also allowed by CORS and as login redirects.
+    #[serde(default)]
+    pub origins: Vec<String>,
+    /// Sent as `X-Mikaana-Site-Key` by clients without an Origin (bots, imports).
+    #[serde(default)]
+    pub api_key: Option<String>,
+    /// Replaces the top-level `[branding]` for this site.
+    #[serde(default)]
+    pub branding: Option<BrandingConfig>,
+    /// Replaces the top-level `vote_mode` for this site.
+    #[serde(default)]
+    pub vote_mode: Option<VoteMode>,
+    /// Replaces the top-level `locale` for this site.
+    #[serde(default)]
+    pub locale: Option<String>,
+    /// Replaces the top-level `forum_url` for this site.
+    #[serde(default)]
+    pub forum_url: Option<String>,
+}
+
+impl SiteConfig {
+    fn validate(&self) -> Result<(), String> {
+        let id = &self.id;
+        if id.is_empty()
+            || !id.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        {
+            return Err(format!("sites: id must be lowercase letters, digits and '-', got {id:?}"));
+        }
+        for origin in &self.origins {
+            reqwest::Url::parse(origin).map_err(|e| format!("sites.{id}.origins: {origin:?}: {e}"))?;
+        }
+        if let Some(branding) = &self.branding {
+            branding.validate().map_err(|e| format!("sites.{id}.{e}"))?;
+        }
+        if let Some(locale) = &self.locale {
+            validate_locale(locale).map_err(|e| format!("sites.{id}.{e}"))?;
+        }
+        if let Some(url) = &self.forum_url {
+            reqwest::Url::parse(url).map_err(|e| format!("sites.{id}.forum_url: {url:?}: {e}"))?;
+        }
+        Ok(())
+    }
+}
+
+/// Loose BCP 47 check (`en`, `en-GB`, `zh-Hant-TW`); the browser decides
+/// what it actually supports and falls back on its own.
+fn validate_locale(locale: &str) -> Result<(), String> {
+    let valid = locale
+        .split('-')
+        .all(|part| (1..=8).contains(&part.len()) && part.chars().all(|c| c.is_ascii_alphanumeric()));
+    if !valid {
+        return Err(format!("locale: expected a language tag like \"en-GB\", got {locale:?}"));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BrandingConfig {
+    pub site_name: Option<String>,
+    pub accent_color: Option<String>,
+    pub powered_by: bool,
+    pub custom_css_url: Option<String>,
+    /// Image shown in link previews of threads without an image attachment.
+    pub card_image_url: Option<String>,
+}
+
+impl Default for BrandingConfig {
+    fn default() -> Self {
+        BrandingConfig {
+            site_name: None,
+            accent_color: None,
+            powered_by: true,
+            custom_css_url: None,
+            card_image_url: None,
+        }
+    }
+}
+
+impl BrandingConfig {
+    /// The accent color ends up in a CSS custom property and the stylesheet
+    /// in a `<link>`, so only accept values that can't smuggle in anything else.
+    fn validate(&self) -> Result<(), String> {
+        if let Some(color) = &self.accent_color {
+            let hex = color.strip_prefix('#').unwrap_or("");
+            if ![3, 6, 8].contains(&hex.len()) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(format!("branding.accent_color: expected #rgb, #rrggbb or #rrggbbaa, got {color:?}"));
+            }
+        }
+        if let Some(url) = &self.custom_css_url {
+            if !url.starts_with("https://") && !url.starts_with('/') {
+                return Err(format!("branding.custom_css_url: expected https:// or /path, got {url:?}"));
+            }
+        }
+        // Link unfurlers fetch it from elsewhere, so it must be absolute
+        if let Some(url) = &self.card_image_url {
+            if !url.starts_with("https://") {
+                return Err(format!("branding.card_image_url: expected https://, got {url:?}"));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SummarizerConfig {
+    /// Endpoint receiving `POST` requests; see `summaries.rs` for the contract.
+    pub url: String,
+    /// Sent as `Authorization: Bearer <token>` when set.
+    #[serde(default)]
+    pub token: Option<String>,
+    /// Threads with fewer replies than this get no summary.
+    #[serde(default = "default_min_replies")]
+    pub min_replies: i64,
+}
+
+fn default_min_replies() -> i64 {
+    20
+}
+
+/// See `emails.rs` for the request the service receives.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MailerConfig {
+    pub url: String,
+    /// Sent as `Authorization: Bearer <token>` when set.
+    #[serde(default)]
+    pub token: Option<String>,
+    /// Sender address, e.g. `Blog <noreply@blog.example.com>`.
+    pub from: String,
+}
+
+/// See `webmentions.rs`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebmentionsConfig {
+    /// Notify pages linked from new comments.
+    pub send: bool,
+}
+
+/// See `unfurl.rs`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UnfurlConfig {
+    /// Links per comment, thread or reply that get a preview.
+    pub max_links: usize,
+}
+
+impl Default for UnfurlConfig {
+    fn default() -> Self {
+        UnfurlConfig { max_links: 3 }
+    }
+}
+
+/// The blog's author(s), whose comments and replies are badged; see
+/// `authors.rs`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthorsConfig {
+    /// Account ids (not GitHub ids) of the authors.
+    pub user_ids: Vec<UserId>,
+    /// List their comments and replies first, not just badged.
+    pub float: bool,
+}
+
+/// Which posts get a discussion thread, and where; see `discussions.rs`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DiscussionConfig {
+    #[serde(default = "default_discussion_site")]
+    pub site: String,
+    /// Only posts whose slug starts with this (`/blog/`); empty for all.
+    #[serde(default)]
+    pub prefix: String,
+    /// Slug of the site's forum category the threads go in.
+    pub category: String,
+    /// Pin the threads in their category.
+    #[serde(default = "default_discussion_pin")]
+    pub pin: bool,
+    /// Account the threads are posted as; unset uses the first of
+    /// `authors.user_ids`.
+    #[serde(default)]
+    pub user_id: Option<UserId>,
+}
+
+fn default_discussion_site() -> String {
+    sites::DEFAULT_SITE.to_string()
+}
+
+fn default_discussion_pin() -> bool {
+    true
+}
+
+impl DiscussionConfig {
+    /// Who posts the threads.
+    pub fn author(&self, authors: &AuthorsConfig) -> Option<UserId> {
+        self.user_id.or_else(|| authors.user_ids.first().copied())
+    }
+}
+
+/// Per-user posting limits; see `flood.rs`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FloodConfig {
+    pub threads: PostRate,
+    pub comments: PostRate,
+    pub replies: PostRate,
+    /// How long a user can't post the same text again; 0 allows repeats.
+    pub duplicate_window_seconds: u64,
+}
+
+impl Default for FloodConfig {
+    fn default() -> Self {
+        FloodConfig {
+            threads: PostRate { max: 1, per_seconds: 120 },
+            comments: PostRate { max: 5, per_seconds: 60 },
+            replies: PostRate { max: 5, per_seconds: 60 },
+            duplicate_window_seconds: 600,
+        }
+    }
+}
+
+impl FloodConfig {
+    fn validate(&self) -> Result<(), String> {
+        for (name, rate) in [
+            ("threads", self.threads),
+            ("comments", self.comments),
+            ("replies", self.replies),
+        ] {
+            if rate.max > 0 && rate.per_seconds == 0 {
+                return Err(format!("flood.{name}.per_seconds: must be positive"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// At most `max` posts in any `per_seconds`.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PostRate {
+    /// 0 lifts the limit.
+    pub max: u32,
+    pub per_seconds: u64,
+}
+
+/// See `analytics.rs`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AnalyticsConfig {
+    /// Days of counts kept.
+    pub retention_days: u32,
+}
+
+impl Default for AnalyticsConfig {
+    fn default() -> Self {
+        AnalyticsConfig { retention_days: 365 }
+    }
+}
+
+/// See `captcha.rs`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CaptchaConfig {
+    pub provider: CaptchaProvider,
+    /// Public key the widget renders with.
+    pub site_key: String,
+    pub secret_key: String,
+    /// Accounts with fewer published comments, threads and replies than
+    /// this are challenged on every post.
+    #[serde(default = "default_captcha_min_posts")]
+    pub min_posts: i64,
+}
+
+fn default_captcha_min_posts() -> i64 {
+    1
+}
+
+/// See `terms.rs`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TermsConfig {
+    /// Any label (`2026-10`, `3`); changing it asks everyone to accept again.
+    pub version: String,
+    /// Page with the terms, linked from the acceptance dialog.
+    pub url: String,
+}
+
+/// Avatar proxy cache; see `avatars.rs`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AvatarsConfig {
+    /// Directory for cached avatar images; created on first use.
+    pub cache_dir: String,
+    /// Edge length in pixels. Widgets show avatars at up to 48 CSS pixels,
+    /// so the default covers 2x displays.
+    pub size: u32,
+    /// How long a cached avatar is served before being refetched.
+    pub max_age_hours: u64,
+}
+
+impl Default for AvatarsConfig {
+    fn default() -> Self {
+        AvatarsConfig {
+            cache_dir: "avatar-cache".to_string(),
+            size: 96,
+            max_age_hours: 24,
+        }
+    }
+}
+
+impl AvatarsConfig {
+    fn validate(&self) -> Result<(), String> {
+        // GitHub serves avatars up to 460 pixels
+        if !(1..=460).contains(&self.size) {
+            return Err("avatars.size: must be between 1 and 460".to_string());
+        }
+        if self.max_age_hours == 0 {
+            return Err("avatars.max_age_hours: must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// SQLite journal, checkpoint and statement cache settings; see `wal.rs`. All but
+/// `checkpoint_interval_seconds` are read once at startup.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    /// Write-ahead logging, which replication tools like Litestream need.
+    pub wal: bool,
+    /// How long a query waits for a lock before failing.
+    pub busy_timeout_ms: u64,
+    /// WAL size at which SQLite checkpoints on its own; 0 turns automatic
+    /// checkpoints off (do that when a replicator manages them).
+    pub autocheckpoint_pages: u32,
+    /// Run a passive checkpoint this often; 0 disables it.
+    pub checkpoint_interval_seconds: u64,
+    /// Prepared statements each pooled connection keeps for reuse; handlers
+    /// go through `prepare_cached`, so this should cover the distinct
+    /// queries a busy page runs.
+    pub statement_cache_capacity: usize,
+    /// Read-only connections for GET endpoints that don't write; writes
+    /// share a single connection.
+    pub read_connections: u32,
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        DatabaseConfig {
+            wal: true,
+            busy_timeout_ms: 5000,
+            autocheckpoint_pages: 1000,
+            checkpoint_interval_seconds: 0,
+            statement_cache_capacity: 128,
+            read_connections: 8,
+        }
+    }
+}
+
+impl DatabaseConfig {
+    fn validate(&self) -> Result<(), String> {
+        if self.read_connections == 0 {
+            return Err("database.read_connections: must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Response cache for anonymous reads; see `response_cache.rs`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    /// How long a cached response is served; 0 disables the cache.
+    pub ttl_seconds: u64,
+    pub max_entries: usize,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            ttl_seconds: 30,
+            max_entries: 1000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UploadsConfig {
+    /// Largest accepted file, at most `attachments::BODY_LIMIT`.
+    #[serde(default = "default_max_upload_bytes")]
+    pub max_bytes: usize,
+    /// MIME types accepted. SVG is left out by default as it can carry scripts.
+    #[serde(default = "default_upload_types")]
+    pub allowed_types: Vec<String>,
+    pub storage: StorageConfig,
+}
+
+fn default_max_upload_bytes() -> usize {
+    5 * 1024 * 1024
+}
+
+fn default_upload_types() -> Vec<String> {
+    ["image/png", "image/jpeg", "image/gif", "image/webp", "application/pdf", "text/plain"]
+        .map(String::from)
+        .to_vec()
+}
+
+impl UploadsConfig {
+    fn validate(&self) -> Result<(), String> {
+        if self.max_bytes == 0 || self.max_bytes > crate::attachments::BODY_LIMIT {
+            return Err(format!(
+                "uploads.max_bytes: must be between 1 and {}",
+                crate::attachments::BODY_LIMIT
+            ));
+        }
+        if let StorageConfig::S3 {
+            endpoint,
+            public_url,
+            ..
+        } = &self.storage
+        {
+            for (key, url) in [("endpoint", endpoint), ("public_url", public_url)] {
+                reqwest::Url::parse(url)
+                    .map_err(|e| format!("uploads.storage.{key}: {url:?}: {e}"))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Where uploaded files are kept.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase", deny_unknown_fields)]
+pub enum StorageConfig {
+    /// A directory on this host; files are served by `GET /api/uploads/{id}`.
+    Local { dir: String },
+    /// An S3-compatible bucket, addressed path-style
+    /// (`{endpoint}/{bucket}/{key}`) and served from `public_url/{key}`.
+    S3 {
+        endpoint: String,
+        bucket: String,
+        region: String,
+        access_key_id: String,
+        secret_access_key: String,
+        public_url: String,
+    },
+}
+
+/// Where database snapshots go; see `backup.rs`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase", deny_unknown_fields)]
+pub enum BackupTarget {
+    /// A directory on this host, preferably not on the database's volume.
+    Local { dir: String },
+    /// An S3-compatible bucket, addressed path-style; snapshot keys start
+    /// with `prefix`.
+    S3 {
+        endpoint: String,
+        bucket: String,
+        region: String,
+        access_key_id: String,
+        secret_access_key: String,
+        #[serde(default)]
+        prefix: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeletionMode {
+    /// Keep comments, threads, replies and votes, attributed to a scrubbed
+    /// "deleted" account.
+    #[default]
+    Anonymize,
+    /// Delete everything the user posted, including replies other people
+    /// left in their threads.
+    Remove,
+}
+
+impl DeletionMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DeletionMode::Anonymize => "anonymize",
+            DeletionMode::Remove => "remove",
+        }
+    }
+}
+
+impl Config {
+    /// Read the config file (if any) and apply environment overrides.
+    pub fn load() -> Result<Self, String> {
+        let mut config = match std::env::var("CONFIG_PATH") {
+            Ok(path) => {
+                let text = std::fs::read_to_string(&path)
+                    .map_err(|e| format!("reading {path}: {e}"))?;
+                toml::from_str(&text).map_err(|e| format!("parsing {path}: {e}"))?
+            }
+            Err(_) => Config::default(),
+        };
+
+        if let Ok(ids) = std::env::var("ADMIN_GITHUB_IDS") {
+            config.admin_github_ids = ids
+                .split(',')
+                .filter_map(|id| id.trim().parse().ok())
+                .collect();
+        }
+
+        config.branding.validate()?;
+        config.github_stats.validate()?;
+        config.avatars.validate()?;
+        config.flood.validate()?;
+        config.database.validate()?;
+        for (i, discussion) in config.discussions.iter().enumerate() {
+            if discussion.category.trim().is_empty() {
+                return Err(format!("discussions[{i}].category: is required"));
+            }
+            if discussion.author(&config.authors).is_none() {
+                return Err(format!(
+                    "discussions[{i}].user_id: is required without authors.user_ids"
+                ));
+            }
+        }
+        if let Some(uploads) = &config.uploads {
+            uploads.validate()?;
+        }
+        if let Some(captcha) = &config.captcha {
+            if captcha.site_key.is_empty() || captcha.secret_key.is_empty() {
+                return Err("captcha: site_key and secret_key are required".to_string());
+            }
+            if captcha.min_posts < 0 {
+                return Err("captcha.min_posts: must not be negative".to_string());
+            }
+        }
+        if let Some(terms) = &config.terms {
+            if terms.version.trim().is_empty() {
+                return Err("terms.version: is required".to_string());
+            }
+            reqwest::Url::parse(&terms.url)
+                .map_err(|e| format!("terms.url: {:?}: {e}", terms.url))?;
+        }
+        if config.analytics.as_ref().is_some_and(|a| a.retention_days == 0) {
+            return Err("analytics.retention_days: must be at least 1".to_string());
+        }
+        if let Some(locale) = &config.locale {
+            validate_locale(locale)?;
+        }
+        if let Some(mailer) = &config.mailer {
+            reqwest::Url::parse(&mailer.url)
+                .map_err(|e| format!("mailer.url: {:?}: {e}", mailer.url))?;
+            if mailer.from.is_empty() {
+                return Err("mailer.from: is required".to_string());
+            }
+        }
+        if let Some(url) = &config.forum_url {
+            reqwest::Url::parse(url).map_err(|e| format!("forum_url: {url:?}: {e}"))?;
+        }
+        if config.vote_milestones.iter().any(|&m| m <= 0) {
+            return Err("vote_milestones: must be positive".to_string());
+        }
+        for origin in &config.redirect_allow_list {
+            reqwest::Url::parse(origin)
+                .map_err(|e| format!("redirect_allow_list: {origin:?}: {e}"))?;
+        }
+        for (i, site) in config.sites.iter().enumerate() {
+            site.validate()?;
+            let earlier = &config.sites[..i];
+            if earlier.iter().any(|s| s.id == site.id) {
+                return Err(format!("sites: duplicate id {:?}", site.id));
+            }
+            if site.api_key.is_some() && earlier.iter().any(|s| s.api_key == site.api_key) {
+                return Err(format!("sites.{}: api_key is shared with another site", site.id));
+            }
+        }
+
+        Ok(config)
+    }
+
+    pub fn site(&self, id: &str) -> Option<&SiteConfig> {
+        self.sites.iter().find(|s| s.id == id)
+    }
+
+    pub fn vote_mode(&self, site_id: &str) -> VoteMode {
+        self.site(site_id)
+            .and_then(|s| s.vote_mode)
+            .unwrap_or(self.vote_mode)
+    }
+
+    pub fn locale(&self, site_id: &str) -> Option<&str> {
+        self.site(site_id)
+            .and_then(|s| s.locale.as_deref())
+            .or(self.locale.as_deref())
+    }
+
+    pub fn branding(&self, site_id: &str) -> &BrandingConfig {
+        self.site(site_id)
+            .and_then(|s| s.branding.as_ref())
+            .unwrap_or(&self.branding)
+    }
+
+    pub fn forum_url(&self, site_id: &str) -> Option<&str> {
+        self.site(site_id)
+            .and_then(|s| s.forum_url.as_deref())
+            .or(self.forum_url.as_deref())
+    }
+
+    /// The subset of settings that is safe to hand to any visitor of `site_id`.
+    pub fn public(&self, site_id: &str, features: Features) -> PublicConfig {
+        let branding = self.branding(site_id);
+        PublicConfig {
+            branding: Branding {
+                site_name: branding.site_name.clone(),
+                accent_color: branding.accent_color.clone(),
+                powered_by: branding.powered_by,
+                custom_css_url: branding.custom_css_url.clone(),
+            },
+            vote_mode: if features.votes {
+                self.vote_mode(site_id)
+            } else {
+                VoteMode::Disabled
+            },
+            uploads: self.uploads.as_ref().map(|u| UploadLimits {
+                max_bytes: u.max_bytes,
+                allowed_types: u.allowed_types.clone(),
+            }),
+            captcha: self.captcha.as_ref().map(|c| CaptchaInfo {
+                provider: c.provider,
+                site_key: c.site_key.clone(),
+            }),
+            terms: self.terms.as_ref().map(|t| TermsInfo {
+                version: t.version.clone(),
+                url: t.url.clone(),
+            }),
+            features,
+            limits: ContentLimits::default(),
+            locale: self.locale(site_id).map(str::to_string),
+            markup: Markup::default(),
+            webmentions: self.webmentions.is_some(),
+            link_previews: self.unfurl.as_ref().map_or(0, |u| u.max_links),
+            analytics: self.analytics.is_some(),
+        }
+    }
+}
+
+/// Subsystems switched off with `ENABLE_FORUM=false`, `ENABLE_VOTES=false`
+/// or `ENABLE_GITHUB_STATS=false`. They decide which routes exist, so unlike
+/// the rest of this file they are read once at startup.
+pub fn features_from_env() -> Features {
+    let enabled = |name: &str| {
+        std::env::var(name).map_or(true, |v| {
+            !matches!(v.trim().to_ascii_lowercase().as_str(), "false" | "0" | "no" | "off")
+        })
+    };
+    Features {
+        forum: enabled("ENABLE_FORUM"),
+        votes: enabled("ENABLE_VOTES"),
+        github_stats: enabled("ENABLE_GITHUB_STATS"),
+    }
+}
+
+/// GET /api/config — non-secret settings for the widgets
+pub async fn public_config(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, StatusCode> {
+    let config = state.config.load();
+    let site = sites::resolve(&headers, &config)?;
+    Ok((
+        // Widgets fetch this on every page view; reloads show up within minutes
+        [
+            (header::CACHE_CONTROL, "public, max-age=300"),
+            (header::VARY, sites::VARY),
+        ],
+        Json(config.public(&site, state.features)),
+    ))
+}
+
+/// Re-read the config and swap it in. On error the current config stays.
+pub fn reload(shared: &SharedConfig, conn: &rusqlite::Connection) -> Result<(), String> {
+    let config = Config::load()?;
+    sites::seed_all(conn, &config).map_err(|e| format!("seeding site categories: {e}"))?;
+    shared.store(Arc::new(config));
+    Ok(())
+}
+
+/// Reload on SIGHUP for the lifetime of the process.
+#[cfg(unix)]
+pub fn watch_sighup(shared: SharedConfig, pool: DbPool) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    tokio::spawn(async move {
+        let Ok(mut hangups) = signal(SignalKind::hangup()) else {
+            eprintln!("Could not install SIGHUP handler; config reload via API only");
+            return;
+        };
+        while hangups.recv().await.is_some() {
+            let (shared, pool) = (shared.clone(), pool.clone());
+            let result = tokio::task::spawn_blocking(move || {
+                let conn = pool.get().map_err(|e| e.to_string())?;
+                reload(&shared, &conn)
+            })
+            .await;
+            match result.unwrap_or_else(|e| Err(e.to_string())) {
+                Ok(()) => println!("Configuration reloaded"),
+                Err(e) => eprintln!("Configuration reload failed, keeping previous: {e}"),
+            }
+        }
+    });
+}
+
+#[cfg(not(unix))]
+pub fn watch_sighup(_shared: SharedConfig, _pool: DbPool) {}
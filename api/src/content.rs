@@ -0,0 +1,169 @@
+//! What comments and forum replies have in common: a sanitized body by a
+//! user, with votes (see `votes.rs`) and attachments, listed under a post or
+//! a thread. Code that works on either (attachments, author flags, blocking,
+//! answering a post) is written once against `Item`. Threads carry a title
+//! and much else besides, and aren't `Item`s, but share the vote and
+//! deletion helpers at the bottom, which go by target type.
+
+use axum::http::StatusCode;
+use mikaana_shared::{
+    expand_shortcodes, Attachment, Comment, CommentId, Reply, ReplyId, User, UserId,
+};
+use rusqlite::Connection;
+
+use crate::{
+    attachments, authors, badges, comments, config::Config, forum, moderation, sites, votes,
+};
+
+/// User-written text (bodies, titles) as it's stored: shortcodes expanded,
+/// HTML cut down to what's allowed.
+pub fn sanitize(text: &str) -> String {
+    ammonia::clean(&expand_shortcodes(text))
+}
+
+/// The fields of an `Item` the shared code fills in or blanks out.
+pub struct Parts<'a> {
+    pub body: &'a mut String,
+    pub body_length: &'a mut usize,
+    pub user: &'a mut User,
+    pub attachments: &'a mut Vec<Attachment>,
+    pub pending: &'a mut bool,
+    pub by_author: &'a mut bool,
+    pub blocked: &'a mut bool,
+}
+
+pub trait Item: Sized {
+    /// What votes, attachments, bookmarks and notifications call it.
+    const TARGET_TYPE: &'static str;
+
+    fn id(&self) -> i64;
+    fn user_id(&self) -> UserId;
+    fn parts(&mut self) -> Parts<'_>;
+    /// The item with its attachments, whatever its status.
+    fn query(conn: &Connection, id: i64) -> rusqlite::Result<Self>;
+}
+
+impl Item for Comment {
+    const TARGET_TYPE: &'static str = "comment";
+
+    fn id(&self) -> i64 {
+        self.id.0
+    }
+
+    fn user_id(&self) -> UserId {
+        self.user.id
+    }
+
+    fn parts(&mut self) -> Parts<'_> {
+        Parts {
+            body: &mut self.body,
+            body_length: &mut self.body_length,
+            user: &mut self.user,
+            attachments: &mut self.attachments,
+            pending: &mut self.pending,
+            by_author: &mut self.by_author,
+            blocked: &mut self.blocked,
+        }
+    }
+
+    fn query(conn: &Connection, id: i64) -> rusqlite::Result<Self> {
+        comments::query_comment(conn, CommentId(id))
+    }
+}
+
+impl Item for Reply {
+    const TARGET_TYPE: &'static str = "reply";
+
+    fn id(&self) -> i64 {
+        self.id.0
+    }
+
+    fn user_id(&self) -> UserId {
+        self.user.id
+    }
+
+    fn parts(&mut self) -> Parts<'_> {
+        Parts {
+            body: &mut self.body,
+            body_length: &mut self.body_length,
+            user: &mut self.user,
+            attachments: &mut self.attachments,
+            pending: &mut self.pending,
+            by_author: &mut self.by_author,
+            blocked: &mut self.blocked,
+        }
+    }
+
+    fn query(conn: &Connection, id: i64) -> rusqlite::Result<Self> {
+        forum::query_reply(conn, ReplyId(id))
+    }
+}
+
+/// Fill in `attachments` on listed items.
+pub fn load_attachments<T: Item>(conn: &Connection, items: &mut [T]) -> rusqlite::Result<()> {
+    for item in items.iter_mut() {
+        let id = item.id();
+        *item.parts().attachments = attachments::load(conn, T::TARGET_TYPE, id)?;
+    }
+    Ok(())
+}
+
+/// Item `id` as its author gets it back after posting.
+pub fn posted<T: Item>(conn: &Connection, config: &Config, id: i64, pending: bool) -> Result<T, StatusCode> {
+    let mut item = T::query(conn, id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let by_author = authors::is_author(config, item.user_id());
+    let parts = item.parts();
+    *parts.pending = pending;
+    *parts.by_author = by_author;
+    badges::apply(conn, config, [parts.user]).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(item)
+}
+
+/// Take the votes on an item out of the counts and forget them, along with
+/// the milestones they reached.
+pub fn drop_votes(conn: &Connection, target_type: &str, target_id: i64) -> rusqlite::Result<()> {
+    votes::discount(
+        conn,
+        "v.target_type = ?1 AND v.target_id = ?2",
+        rusqlite::params![target_type, target_id],
+    )?;
+    for table in ["votes", "vote_milestones"] {
+        conn.execute(
+            &format!("DELETE FROM {table} WHERE target_type = ?1 AND target_id = ?2"),
+            rusqlite::params![target_type, target_id],
+        )?;
+    }
+    Ok(())
+}
+
+/// Delete an item on behalf of its author. The row stays, marked
+/// 'deleted', so replies and audit entries keep pointing somewhere; every
+/// listing already skips anything not published. `NOT_FOUND` unless
+/// `user_id` wrote it on `site` and it isn't deleted already.
+pub fn soft_delete(
+    conn: &Connection,
+    site: &str,
+    user_id: UserId,
+    target_type: &str,
+    target_id: i64,
+) -> Result<(), StatusCode> {
+    let table = moderation::content_table(target_type).ok_or(StatusCode::BAD_REQUEST)?;
+    if !sites::owns(conn, site, target_type, target_id)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let changed = conn
+        .execute(
+            &format!(
+                "UPDATE {table} SET status = 'deleted'
+                 WHERE id = ?1 AND user_id = ?2 AND status != 'deleted'"
+            ),
+            rusqlite::params![target_id, user_id],
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if changed == 0 {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    drop_votes(conn, target_type, target_id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
@@ -2,6 +2,7 @@ use crate::DbPool;
 
 pub fn run_migrations(pool: &DbPool) -> Result<(), Box<dyn std::error::Error>> {
     let conn = pool.get()?;
+    let had_post_targets = table_exists(&conn, "post_targets")?;
 
     conn.execute_batch(
         "
@@ -59,6 +60,322 @@ pub fn run_migrations(pool: &DbPool) -> Result<(), Box<dyn std::error::Error>> {
         );
         CREATE INDEX IF NOT EXISTS idx_replies_thread ON replies(thread_id);
 
+        CREATE TABLE IF NOT EXISTS audit_log (
+            id          INTEGER PRIMARY KEY AUTOINCREMENT,
+            actor_id    INTEGER REFERENCES users(id),
+            action      TEXT NOT NULL,
+            target_type TEXT NOT NULL,
+            target_id   INTEGER NOT NULL,
+            details     TEXT NOT NULL DEFAULT '{}',
+            created_at  TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        CREATE INDEX IF NOT EXISTS idx_audit_created ON audit_log(created_at);
+
+        CREATE TABLE IF NOT EXISTS category_subscriptions (
+            user_id     INTEGER NOT NULL REFERENCES users(id),
+            category_id INTEGER NOT NULL REFERENCES categories(id),
+            created_at  TEXT NOT NULL DEFAULT (datetime('now')),
+            PRIMARY KEY (user_id, category_id)
+        );
+
+        -- Read markers for unread badges
+        CREATE TABLE IF NOT EXISTS thread_reads (
+            user_id      INTEGER NOT NULL REFERENCES users(id),
+            thread_id    INTEGER NOT NULL REFERENCES threads(id),
+            last_read_at TEXT NOT NULL,
+            PRIMARY KEY (user_id, thread_id)
+        );
+        CREATE TABLE IF NOT EXISTS category_reads (
+            user_id      INTEGER NOT NULL REFERENCES users(id),
+            category_id  INTEGER NOT NULL REFERENCES categories(id),
+            last_read_at TEXT NOT NULL,
+            PRIMARY KEY (user_id, category_id)
+        );
+
+        -- email_state: 'none' (in-app only), 'pending' (awaiting the mailer),
+        -- 'sent' or 'failed'
+        CREATE TABLE IF NOT EXISTS notifications (
+            id          INTEGER PRIMARY KEY AUTOINCREMENT,
+            user_id     INTEGER NOT NULL REFERENCES users(id),
+            kind        TEXT NOT NULL,
+            actor_id    INTEGER REFERENCES users(id),
+            target_type TEXT NOT NULL,
+            target_id   INTEGER NOT NULL,
+            summary     TEXT NOT NULL DEFAULT '',
+            read_at     TEXT,
+            email_state TEXT NOT NULL DEFAULT 'none',
+            created_at  TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        CREATE INDEX IF NOT EXISTS idx_notifications_user ON notifications(user_id, created_at);
+
+        -- read_at: when the recipient opened the conversation
+        CREATE TABLE IF NOT EXISTS messages (
+            id           INTEGER PRIMARY KEY AUTOINCREMENT,
+            sender_id    INTEGER NOT NULL REFERENCES users(id),
+            recipient_id INTEGER NOT NULL REFERENCES users(id),
+            body         TEXT NOT NULL,
+            read_at      TEXT,
+            created_at   TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        CREATE INDEX IF NOT EXISTS idx_messages_recipient ON messages(recipient_id, read_at);
+        CREATE INDEX IF NOT EXISTS idx_messages_sender ON messages(sender_id, recipient_id);
+
+        CREATE TABLE IF NOT EXISTS follows (
+            follower_id INTEGER NOT NULL REFERENCES users(id),
+            followee_id INTEGER NOT NULL REFERENCES users(id),
+            created_at  TEXT NOT NULL DEFAULT (datetime('now')),
+            PRIMARY KEY (follower_id, followee_id)
+        );
+        CREATE INDEX IF NOT EXISTS idx_follows_followee ON follows(followee_id);
+
+        -- Users whose comments and replies blocker_id doesn't want to see
+        CREATE TABLE IF NOT EXISTS blocks (
+            blocker_id INTEGER NOT NULL REFERENCES users(id),
+            blocked_id INTEGER NOT NULL REFERENCES users(id),
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            PRIMARY KEY (blocker_id, blocked_id)
+        );
+
+        CREATE TABLE IF NOT EXISTS bookmarks (
+            user_id     INTEGER NOT NULL REFERENCES users(id),
+            target_type TEXT NOT NULL,
+            target_id   INTEGER NOT NULL,
+            created_at  TEXT NOT NULL DEFAULT (datetime('now')),
+            PRIMARY KEY (user_id, target_type, target_id)
+        );
+
+        -- key names the form, see `Draft::key`
+        CREATE TABLE IF NOT EXISTS drafts (
+            user_id     INTEGER NOT NULL REFERENCES users(id),
+            site_id     TEXT NOT NULL,
+            key         TEXT NOT NULL,
+            title       TEXT NOT NULL DEFAULT '',
+            body        TEXT NOT NULL,
+            updated_at  TEXT NOT NULL DEFAULT (datetime('now')),
+            PRIMARY KEY (user_id, site_id, key)
+        );
+
+        -- revision identifies the reply set a summary was generated from
+        CREATE TABLE IF NOT EXISTS thread_summaries (
+            thread_id    INTEGER PRIMARY KEY REFERENCES threads(id),
+            revision     TEXT NOT NULL,
+            reply_count  INTEGER NOT NULL,
+            summary      TEXT NOT NULL,
+            generated_at TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+
+        CREATE TABLE IF NOT EXISTS reports (
+            id          INTEGER PRIMARY KEY AUTOINCREMENT,
+            reporter_id INTEGER NOT NULL REFERENCES users(id),
+            target_type TEXT NOT NULL,
+            target_id   INTEGER NOT NULL,
+            reason      TEXT NOT NULL DEFAULT '',
+            resolved_at TEXT,
+            resolved_by INTEGER REFERENCES users(id),
+            created_at  TEXT NOT NULL DEFAULT (datetime('now')),
+            UNIQUE(reporter_id, target_type, target_id)
+        );
+        CREATE INDEX IF NOT EXISTS idx_reports_open ON reports(resolved_at, created_at);
+
+        -- key: the GitHub repo; data: GitHubStats as JSON
+        CREATE TABLE IF NOT EXISTS stats_cache (
+            key         TEXT PRIMARY KEY,
+            data        TEXT NOT NULL,
+            fetched_at  TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+
+        -- Milestones already announced, so toggling votes doesn't repeat them
+        CREATE TABLE IF NOT EXISTS vote_milestones (
+            target_type TEXT NOT NULL,
+            target_id   INTEGER NOT NULL,
+            milestone   INTEGER NOT NULL,
+            PRIMARY KEY (target_type, target_id, milestone)
+        );
+
+        -- Ids for post-level votes (target_type 'post')
+        CREATE TABLE IF NOT EXISTS post_targets (
+            id          INTEGER PRIMARY KEY AUTOINCREMENT,
+            site_id     TEXT NOT NULL DEFAULT 'default',
+            slug        TEXT NOT NULL,
+            created_at  TEXT NOT NULL DEFAULT (datetime('now')),
+            UNIQUE(site_id, slug)
+        );
+
+        -- events: comma-separated subscriptions, empty for all
+        CREATE TABLE IF NOT EXISTS webhooks (
+            id          INTEGER PRIMARY KEY AUTOINCREMENT,
+            site_id     TEXT NOT NULL DEFAULT 'default',
+            url         TEXT NOT NULL,
+            secret      TEXT NOT NULL,
+            events      TEXT NOT NULL DEFAULT '',
+            created_at  TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+
+        CREATE TABLE IF NOT EXISTS webhook_deliveries (
+            id              INTEGER PRIMARY KEY AUTOINCREMENT,
+            webhook_id      INTEGER NOT NULL REFERENCES webhooks(id),
+            event           TEXT NOT NULL,
+            payload         TEXT NOT NULL,
+            attempts        INTEGER NOT NULL DEFAULT 0,
+            next_attempt_at TEXT NOT NULL DEFAULT (datetime('now')),
+            delivered_at    TEXT,
+            last_error      TEXT,
+            created_at      TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_due
+            ON webhook_deliveries(delivered_at, next_attempt_at);
+
+        -- Received webmentions; listed once verified_at is set. pending marks
+        -- a (re)sent mention whose source hasn't been checked yet
+        CREATE TABLE IF NOT EXISTS webmentions (
+            id              INTEGER PRIMARY KEY AUTOINCREMENT,
+            site_id         TEXT NOT NULL,
+            post_slug       TEXT NOT NULL,
+            source          TEXT NOT NULL,
+            target          TEXT NOT NULL,
+            title           TEXT NOT NULL DEFAULT '',
+            pending         INTEGER NOT NULL DEFAULT 1,
+            verified_at     TEXT,
+            attempts        INTEGER NOT NULL DEFAULT 0,
+            next_attempt_at TEXT NOT NULL DEFAULT (datetime('now')),
+            last_error      TEXT,
+            created_at      TEXT NOT NULL DEFAULT (datetime('now')),
+            UNIQUE(source, target)
+        );
+        CREATE INDEX IF NOT EXISTS idx_webmentions_post ON webmentions(site_id, post_slug);
+        CREATE INDEX IF NOT EXISTS idx_webmentions_due ON webmentions(pending, next_attempt_at);
+
+        -- Webmentions to send for links in comments; done_at is set once
+        -- sent or when the target has no endpoint
+        CREATE TABLE IF NOT EXISTS webmention_sends (
+            id              INTEGER PRIMARY KEY AUTOINCREMENT,
+            source          TEXT NOT NULL,
+            target          TEXT NOT NULL,
+            attempts        INTEGER NOT NULL DEFAULT 0,
+            next_attempt_at TEXT NOT NULL DEFAULT (datetime('now')),
+            done_at         TEXT,
+            last_error      TEXT,
+            created_at      TEXT NOT NULL DEFAULT (datetime('now')),
+            UNIQUE(source, target)
+        );
+        CREATE INDEX IF NOT EXISTS idx_webmention_sends_due
+            ON webmention_sends(done_at, next_attempt_at);
+
+        -- Pages linked from posts; data holds a LinkPreview as JSON, and
+        -- stays NULL when the page had nothing to show
+        CREATE TABLE IF NOT EXISTS link_previews (
+            url             TEXT PRIMARY KEY,
+            data            TEXT,
+            fetched_at      TEXT,
+            attempts        INTEGER NOT NULL DEFAULT 0,
+            next_attempt_at TEXT NOT NULL DEFAULT (datetime('now')),
+            last_error      TEXT,
+            created_at      TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        CREATE INDEX IF NOT EXISTS idx_link_previews_due
+            ON link_previews(fetched_at, next_attempt_at);
+
+        -- target_type/target_id stay NULL until content claims the upload
+        CREATE TABLE IF NOT EXISTS attachments (
+            id           INTEGER PRIMARY KEY AUTOINCREMENT,
+            site_id      TEXT NOT NULL,
+            user_id      INTEGER NOT NULL REFERENCES users(id),
+            storage_key  TEXT NOT NULL,
+            url          TEXT NOT NULL,
+            filename     TEXT NOT NULL,
+            content_type TEXT NOT NULL,
+            size         INTEGER NOT NULL,
+            target_type  TEXT,
+            target_id    INTEGER,
+            created_at   TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+        CREATE INDEX IF NOT EXISTS idx_attachments_target ON attachments(target_type, target_id);
+
+        -- action: 'reject' the post outright or 'queue' it for moderation
+        CREATE TABLE IF NOT EXISTS word_filters (
+            id          INTEGER PRIMARY KEY AUTOINCREMENT,
+            pattern     TEXT NOT NULL,
+            is_regex    INTEGER NOT NULL DEFAULT 0,
+            action      TEXT NOT NULL,
+            created_at  TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+
+        -- ip_hash as recorded on the content the ban was placed from
+        CREATE TABLE IF NOT EXISTS ip_bans (
+            id          INTEGER PRIMARY KEY AUTOINCREMENT,
+            ip_hash     TEXT NOT NULL UNIQUE,
+            reason      TEXT NOT NULL DEFAULT '',
+            source_type TEXT NOT NULL,
+            source_id   INTEGER NOT NULL,
+            created_by  INTEGER NOT NULL REFERENCES users(id),
+            created_at  TEXT NOT NULL DEFAULT (datetime('now'))
+        );
+
+        -- Responses to POSTs sent with an Idempotency-Key, replayed on
+        -- repeats; status is NULL while the first request is running
+        CREATE TABLE IF NOT EXISTS idempotency_keys (
+            scope        TEXT NOT NULL,
+            key          TEXT NOT NULL,
+            fingerprint  TEXT NOT NULL,
+            status       INTEGER,
+            content_type TEXT,
+            body         BLOB,
+            created_at   TEXT NOT NULL DEFAULT (datetime('now')),
+            PRIMARY KEY (scope, key)
+        );
+
+        -- Items brought over from other comment systems by `mikaana-api
+        -- import`, so importing again skips them; external_id is the item's
+        -- id in the source
+        CREATE TABLE IF NOT EXISTS imports (
+            source      TEXT NOT NULL,
+            external_id TEXT NOT NULL,
+            target_type TEXT NOT NULL,
+            target_id   INTEGER NOT NULL,
+            created_at  TEXT NOT NULL DEFAULT (datetime('now')),
+            PRIMARY KEY (source, external_id)
+        );
+
+        -- One row per terms-of-service version a user accepted; see terms.rs
+        CREATE TABLE IF NOT EXISTS terms_acceptances (
+            user_id     INTEGER NOT NULL REFERENCES users(id),
+            version     TEXT NOT NULL,
+            accepted_at TEXT NOT NULL DEFAULT (datetime('now')),
+            PRIMARY KEY (user_id, version)
+        );
+
+        -- Widget interactions counted per site, event and UTC day; nothing
+        -- about who; see analytics.rs
+        CREATE TABLE IF NOT EXISTS analytics_events (
+            site_id TEXT NOT NULL,
+            day     TEXT NOT NULL,
+            event   TEXT NOT NULL,
+            count   INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (site_id, day, event)
+        );
+
+        -- Per-post comment settings; posts without a row take comments.
+        -- See post_settings.rs
+        CREATE TABLE IF NOT EXISTS post_settings (
+            site_id          TEXT NOT NULL,
+            post_slug        TEXT NOT NULL,
+            comments_closed  INTEGER NOT NULL DEFAULT 0,
+            close_after_days INTEGER,
+            published_at     TEXT,
+            updated_at       TEXT NOT NULL DEFAULT (datetime('now')),
+            PRIMARY KEY (site_id, post_slug)
+        );
+
+        -- Forum threads opened for posts' comments, one per post; thread_id
+        -- is NULL while it's being created. See discussions.rs
+        CREATE TABLE IF NOT EXISTS post_discussions (
+            site_id    TEXT NOT NULL,
+            post_slug  TEXT NOT NULL,
+            thread_id  INTEGER REFERENCES threads(id),
+            created_at TEXT NOT NULL DEFAULT (datetime('now')),
+            PRIMARY KEY (site_id, post_slug)
+        );
+
         -- Seed default categories if empty
         INSERT OR IGNORE INTO categories (id, name, slug, description) VALUES
             (1, 'General',  'general',  'General discussion'),
@@ -67,5 +384,277 @@ pub fn run_migrations(pool: &DbPool) -> Result<(), Box<dyn std::error::Error>> {
         ",
     )?;
 
+    // Columns added after the initial schema
+    add_column(&conn, "threads", "locked", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column(&conn, "threads", "archived", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column(&conn, "threads", "solution_reply_id", "INTEGER REFERENCES replies(id)")?;
+    // Set on the stub a move leaves behind in the old category
+    add_column(&conn, "threads", "moved_to", "INTEGER REFERENCES threads(id)")?;
+    // Pinned threads lead their category's listing
+    add_column(&conn, "threads", "pinned", "INTEGER NOT NULL DEFAULT 0")?;
+    // When a 'scheduled' thread goes live; see scheduled.rs
+    add_column(&conn, "threads", "publish_at", "TEXT")?;
+    add_column(&conn, "categories", "read_only", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column(&conn, "users", "role", "TEXT NOT NULL DEFAULT 'user'")?;
+    add_column(&conn, "users", "merged_into", "INTEGER REFERENCES users(id)")?;
+    add_column(&conn, "users", "email", "TEXT")?;
+    add_column(&conn, "users", "notify_email", "INTEGER NOT NULL DEFAULT 0")?;
+    // digest: 'off', 'daily' or 'weekly'; see digests.rs
+    add_column(&conn, "users", "digest", "TEXT NOT NULL DEFAULT 'off'")?;
+    add_column(&conn, "users", "digest_sent_at", "TEXT")?;
+    add_column(&conn, "users", "display_name", "TEXT")?;
+    // The GitHub login, refreshed on every login; `username` is only set
+    // from it when the account is created
+    if !has_column(&conn, "users", "github_login")? {
+        add_column(&conn, "users", "github_login", "TEXT")?;
+        conn.execute("UPDATE users SET github_login = username WHERE github_id > 0", [])?;
+    }
+    add_column(&conn, "users", "bio", "TEXT NOT NULL DEFAULT ''")?;
+    add_column(&conn, "users", "website", "TEXT")?;
+    add_column(&conn, "users", "deleted_at", "TEXT")?;
+    add_column(&conn, "users", "banned_at", "TEXT")?;
+    add_column(&conn, "users", "ban_reason", "TEXT")?;
+    // Set on accounts standing in for commenters brought over by `import`
+    // without a GitHub account (`email:{address}` or `name:{name}`); they
+    // can't log in and are left out of mentions, messages and user search
+    add_column(&conn, "users", "guest_key", "TEXT")?;
+    // status: 'published', 'pending' (awaiting a moderator), 'scheduled'
+    // (threads only, until `publish_at`), 'removed' or 'deleted' (by its
+    // author)
+    for table in ["comments", "threads", "replies"] {
+        add_column(&conn, table, "status", "TEXT NOT NULL DEFAULT 'published'")?;
+    }
+    // Keyed hash of the poster's address, for IP bans; see ip_bans.rs
+    for table in ["comments", "threads", "replies"] {
+        add_column(&conn, table, "ip_hash", "TEXT")?;
+    }
+    // Replies belong to their thread's site
+    for table in ["comments", "threads"] {
+        add_column(&conn, table, "site_id", "TEXT NOT NULL DEFAULT 'default'")?;
+    }
+    scope_uniques_to_site(&conn)?;
+    create_search_index(&conn)?;
+    conn.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_comments_site_slug ON comments(site_id, post_slug);
+         CREATE UNIQUE INDEX IF NOT EXISTS idx_users_guest_key ON users(guest_key);
+         CREATE INDEX IF NOT EXISTS idx_threads_scheduled ON threads(publish_at)
+             WHERE status = 'scheduled';
+         CREATE INDEX IF NOT EXISTS idx_comments_user ON comments(user_id, created_at);
+         CREATE INDEX IF NOT EXISTS idx_threads_user ON threads(user_id, created_at);
+         CREATE INDEX IF NOT EXISTS idx_replies_user ON replies(user_id, created_at);",
+    )?;
+    if !had_post_targets {
+        migrate_hashed_post_votes(&conn)?;
+    }
+    // Running vote totals, so listings don't sum votes per row
+    for (table, target_type) in [
+        ("post_targets", "post"),
+        ("comments", "comment"),
+        ("threads", "thread"),
+        ("replies", "reply"),
+    ] {
+        if !has_column(&conn, table, "vote_count")? {
+            add_column(&conn, table, "vote_count", "INTEGER NOT NULL DEFAULT 0")?;
+            conn.execute(
+                &format!(
+                    "UPDATE {table} SET vote_count = (
+                         SELECT COALESCE(SUM(value), 0) FROM votes
+                         WHERE target_type = ?1 AND target_id = {table}.id)"
+                ),
+                [target_type],
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Category slugs and votes were unique per instance; with several sites
+/// they are unique per site. SQLite can't change a table's constraints, so
+/// rebuild both tables once, keeping ids.
+fn scope_uniques_to_site(conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+    if has_column(conn, "categories", "site_id")? {
+        return Ok(());
+    }
+
+    conn.execute_batch(
+        "
+        BEGIN;
+
+        CREATE TABLE categories_new (
+            id          INTEGER PRIMARY KEY AUTOINCREMENT,
+            site_id     TEXT NOT NULL DEFAULT 'default',
+            name        TEXT NOT NULL,
+            slug        TEXT NOT NULL,
+            description TEXT NOT NULL DEFAULT '',
+            read_only   INTEGER NOT NULL DEFAULT 0,
+            UNIQUE(site_id, slug)
+        );
+        INSERT INTO categories_new (id, name, slug, description, read_only)
+            SELECT id, name, slug, description, read_only FROM categories;
+        DROP TABLE categories;
+        ALTER TABLE categories_new RENAME TO categories;
+
+        CREATE TABLE votes_new (
+            id          INTEGER PRIMARY KEY AUTOINCREMENT,
+            site_id     TEXT NOT NULL DEFAULT 'default',
+            user_id     INTEGER NOT NULL REFERENCES users(id),
+            target_type TEXT NOT NULL,
+            target_id   INTEGER NOT NULL,
+            value       INTEGER NOT NULL,
+            created_at  TEXT NOT NULL DEFAULT (datetime('now')),
+            UNIQUE(site_id, user_id, target_type, target_id)
+        );
+        INSERT INTO votes_new (id, user_id, target_type, target_id, value, created_at)
+            SELECT id, user_id, target_type, target_id, value, created_at FROM votes;
+        DROP TABLE votes;
+        ALTER TABLE votes_new RENAME TO votes;
+        CREATE INDEX idx_votes_target ON votes(target_type, target_id);
+
+        COMMIT;
+        ",
+    )
+}
+
+/// Full-text indexes of thread and reply text for forum search, kept in
+/// step by triggers. Built from the existing rows when first created.
+fn create_search_index(conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+    let existed = table_exists(conn, "threads_fts")?;
+
+    conn.execute_batch(
+        "
+        CREATE VIRTUAL TABLE IF NOT EXISTS threads_fts USING fts5(
+            title, body, content='threads', content_rowid='id',
+            tokenize='unicode61 remove_diacritics 2'
+        );
+        CREATE TRIGGER IF NOT EXISTS threads_fts_insert AFTER INSERT ON threads BEGIN
+            INSERT INTO threads_fts (rowid, title, body) VALUES (new.id, new.title, new.body);
+        END;
+        CREATE TRIGGER IF NOT EXISTS threads_fts_delete AFTER DELETE ON threads BEGIN
+            INSERT INTO threads_fts (threads_fts, rowid, title, body)
+                VALUES ('delete', old.id, old.title, old.body);
+        END;
+        CREATE TRIGGER IF NOT EXISTS threads_fts_update AFTER UPDATE OF title, body ON threads BEGIN
+            INSERT INTO threads_fts (threads_fts, rowid, title, body)
+                VALUES ('delete', old.id, old.title, old.body);
+            INSERT INTO threads_fts (rowid, title, body) VALUES (new.id, new.title, new.body);
+        END;
+
+        CREATE VIRTUAL TABLE IF NOT EXISTS replies_fts USING fts5(
+            body, content='replies', content_rowid='id',
+            tokenize='unicode61 remove_diacritics 2'
+        );
+        CREATE TRIGGER IF NOT EXISTS replies_fts_insert AFTER INSERT ON replies BEGIN
+            INSERT INTO replies_fts (rowid, body) VALUES (new.id, new.body);
+        END;
+        CREATE TRIGGER IF NOT EXISTS replies_fts_delete AFTER DELETE ON replies BEGIN
+            INSERT INTO replies_fts (replies_fts, rowid, body) VALUES ('delete', old.id, old.body);
+        END;
+        CREATE TRIGGER IF NOT EXISTS replies_fts_update AFTER UPDATE OF body ON replies BEGIN
+            INSERT INTO replies_fts (replies_fts, rowid, body) VALUES ('delete', old.id, old.body);
+            INSERT INTO replies_fts (rowid, body) VALUES (new.id, new.body);
+        END;
+        ",
+    )?;
+    if !existed {
+        conn.execute_batch(
+            "INSERT INTO threads_fts (threads_fts) VALUES ('rebuild');
+             INSERT INTO replies_fts (replies_fts) VALUES ('rebuild');",
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Post votes used to be keyed by a hash of the slug computed in the browser.
+/// Move those we can attribute (the slug has comments) to `post_targets`;
+/// park the rest as `post_legacy` so they can't collide with new ids.
+fn migrate_hashed_post_votes(conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+    fn slug_hash(slug: &str) -> i64 {
+        slug.bytes()
+            .fold(0i64, |acc, b| acc.wrapping_mul(31).wrapping_add(b as i64))
+            .wrapping_abs()
+    }
+
+    let slugs: Vec<(String, String)> = conn
+        .prepare("SELECT DISTINCT site_id, post_slug FROM comments")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    conn.execute_batch("BEGIN")?;
+    for (site, slug) in slugs {
+        let hash = slug_hash(&slug);
+        let voted: bool = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM votes
+                           WHERE site_id = ?1 AND target_type = 'post' AND target_id = ?2)",
+            rusqlite::params![site, hash],
+            |row| row.get(0),
+        )?;
+        if !voted {
+            continue;
+        }
+        conn.execute(
+            "INSERT OR IGNORE INTO post_targets (site_id, slug) VALUES (?1, ?2)",
+            [&site, &slug],
+        )?;
+        conn.execute(
+            "UPDATE votes SET target_type = 'post_migrated',
+                 target_id = (SELECT id FROM post_targets WHERE site_id = ?1 AND slug = ?2)
+             WHERE site_id = ?1 AND target_type = 'post' AND target_id = ?3",
+            rusqlite::params![site, slug, hash],
+        )?;
+    }
+    conn.execute_batch(
+        "UPDATE votes SET target_type = 'post_legacy' WHERE target_type = 'post';
+         UPDATE votes SET target_type = 'post' WHERE target_type = 'post_migrated';
+         COMMIT;",
+    )
+}
+
+/// Seed the default categories for a site that has none yet.
+pub fn seed_categories(conn: &rusqlite::Connection, site_id: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO categories (site_id, name, slug, description)
+         SELECT ?1, name, slug, description FROM (
+             SELECT 1 AS pos, 'General' AS name, 'general' AS slug,
+                    'General discussion' AS description
+             UNION ALL SELECT 2, 'Projects', 'projects', 'Discuss projects and ideas'
+             UNION ALL SELECT 3, 'Help', 'help', 'Ask for help or advice'
+         )
+         WHERE NOT EXISTS (SELECT 1 FROM categories WHERE site_id = ?1)
+         ORDER BY pos",
+        [site_id],
+    )?;
+    Ok(())
+}
+
+fn table_exists(conn: &rusqlite::Connection, table: &str) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        [table],
+        |row| row.get::<_, i64>(0).map(|n| n > 0),
+    )
+}
+
+fn has_column(conn: &rusqlite::Connection, table: &str, column: &str) -> rusqlite::Result<bool> {
+    conn.query_row(
+        &format!("SELECT COUNT(*) FROM pragma_table_info('{table}') WHERE name = ?1"),
+        [column],
+        |row| row.get::<_, i64>(0).map(|n| n > 0),
+    )
+}
+
+/// Add a column to an existing table unless it is already there.
+/// SQLite has no `ADD COLUMN IF NOT EXISTS`, so check `table_info` first.
+fn add_column(
+    conn: &rusqlite::Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> rusqlite::Result<()> {
+    if !has_column(conn, table, column)? {
+        conn.execute_batch(&format!("ALTER TABLE {table} ADD COLUMN {column} {definition}"))?;
+    }
+
     Ok(())
 }
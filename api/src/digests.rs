@@ -0,0 +1,306 @@
+//! Daily or weekly emails summing up forum activity for users who opt in
+//! (`digest` in `PUT /api/notifications/preferences`): new threads in the
+//! categories they follow and new replies in threads they started or
+//! replied to. Other people's posts only, published ones only.
+//!
+//! A scheduler checks for due digests every `CHECK_INTERVAL`. A user is due
+//! once a day or week has passed since their last digest; periods without
+//! activity send nothing. A digest that fails to send is retried on the
+//! next check. Every digest carries an unsubscribe link (see `emails.rs`).
+
+use std::time::Duration;
+
+use mikaana_shared::{DigestFrequency, ThreadId, UserId};
+use rusqlite::Connection;
+
+use crate::{
+    config::SharedConfig,
+    emails::{self, Email, List, Template, Vars},
+    sites, AppState, DbPool,
+};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(15 * 60);
+/// Users handled per check, so a backlog is worked off gradually.
+const BATCH_SIZE: i64 = 100;
+/// Threads listed per section; the rest are counted.
+const MAX_ITEMS: i64 = 25;
+
+// ── Collection ──
+
+struct Subscriber {
+    id: UserId,
+    email: String,
+    frequency: DigestFrequency,
+    since: String,
+}
+
+struct NewThread {
+    id: ThreadId,
+    site_id: String,
+    title: String,
+    author: String,
+    category: String,
+}
+
+struct ThreadActivity {
+    id: ThreadId,
+    site_id: String,
+    title: String,
+    replies: i64,
+}
+
+fn period(frequency: DigestFrequency) -> &'static str {
+    match frequency {
+        DigestFrequency::Weekly => "-7 days",
+        _ => "-1 day",
+    }
+}
+
+fn due_subscribers(conn: &Connection) -> rusqlite::Result<Vec<Subscriber>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, email, digest, digest_sent_at FROM users
+         WHERE email IS NOT NULL AND deleted_at IS NULL AND banned_at IS NULL
+           AND merged_into IS NULL AND digest_sent_at IS NOT NULL
+           AND ((digest = 'daily' AND digest_sent_at <= datetime('now', '-1 day'))
+             OR (digest = 'weekly' AND digest_sent_at <= datetime('now', '-7 days')))
+         ORDER BY digest_sent_at
+         LIMIT ?1",
+    )?;
+    let rows = stmt
+        .query_map([BATCH_SIZE], |row| {
+            Ok(Subscriber {
+                id: row.get(0)?,
+                email: row.get(1)?,
+                frequency: DigestFrequency::parse(&row.get::<_, String>(2)?).unwrap_or_default(),
+                since: row.get(3)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
+
+fn new_threads(conn: &Connection, user: &Subscriber) -> rusqlite::Result<(Vec<NewThread>, i64)> {
+    const FROM: &str = "FROM threads t
+         JOIN category_subscriptions s ON s.category_id = t.category_id AND s.user_id = ?1
+         JOIN categories c ON c.id = t.category_id
+         JOIN users u ON u.id = t.user_id
+         WHERE t.status = 'published' AND t.moved_to IS NULL AND t.user_id != ?1
+           AND t.created_at > MAX(?2, datetime('now', ?3))";
+    let params = rusqlite::params![user.id, user.since, period(user.frequency)];
+
+    let total: i64 = conn.query_row(&format!("SELECT COUNT(*) {FROM}"), params, |row| row.get(0))?;
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT t.id, t.site_id, t.title, COALESCE(u.display_name, u.username), c.name {FROM} ORDER BY t.id DESC LIMIT {MAX_ITEMS}"
+    ))?;
+    let threads = stmt
+        .query_map(params, |row| {
+            Ok(NewThread {
+                id: row.get(0)?,
+                site_id: row.get(1)?,
+                title: row.get(2)?,
+                author: row.get(3)?,
+                category: row.get(4)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok((threads, total))
+}
+
+fn watched_activity(
+    conn: &Connection,
+    user: &Subscriber,
+) -> rusqlite::Result<(Vec<ThreadActivity>, i64)> {
+    const FROM: &str = "FROM replies r JOIN threads t ON t.id = r.thread_id
+         WHERE r.status = 'published' AND t.status = 'published' AND r.user_id != ?1
+           AND r.created_at > MAX(?2, datetime('now', ?3))
+           AND (t.user_id = ?1 OR EXISTS (
+               SELECT 1 FROM replies mine WHERE mine.thread_id = t.id AND mine.user_id = ?1))";
+    let params = rusqlite::params![user.id, user.since, period(user.frequency)];
+
+    let total: i64 = conn.query_row(
+        &format!("SELECT COUNT(DISTINCT t.id) {FROM}"),
+        params,
+        |row| row.get(0),
+    )?;
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT t.id, t.site_id, t.title, COUNT(*) {FROM}
+         GROUP BY t.id ORDER BY MAX(r.id) DESC LIMIT {MAX_ITEMS}"
+    ))?;
+    let threads = stmt
+        .query_map(params, |row| {
+            Ok(ThreadActivity {
+                id: row.get(0)?,
+                site_id: row.get(1)?,
+                title: row.get(2)?,
+                replies: row.get(3)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok((threads, total))
+}
+
+fn mark_sent(conn: &Connection, user_id: UserId) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE users SET digest_sent_at = datetime('now') WHERE id = ?1",
+        [user_id],
+    )?;
+    Ok(())
+}
+
+// ── Rendering ──
+
+/// Append one list entry to both parts, linked when the site has a
+/// `forum_url`.
+fn push_item(html: &mut String, text: &mut String, label: &str, link: Option<String>) {
+    match link {
+        Some(url) => {
+            html.push_str(&format!(
+                "<li><a href=\"{}\">{}</a></li>\n",
+                emails::escape(&url),
+                emails::escape(label)
+            ));
+            text.push_str(&format!("- {label}\n  {url}\n"));
+        }
+        None => {
+            html.push_str(&format!("<li>{}</li>\n", emails::escape(label)));
+            text.push_str(&format!("- {label}\n"));
+        }
+    }
+}
+
+fn push_more(html: &mut String, text: &mut String, shown: usize, total: i64) {
+    let more = total - shown as i64;
+    if more > 0 {
+        html.push_str(&format!("<li>and {more} more</li>\n"));
+        text.push_str(&format!("- and {more} more\n"));
+    }
+}
+
+/// Digests span all sites, so they carry the default site's branding.
+fn render(
+    ctx: &DigestContext,
+    user: &Subscriber,
+    (threads, thread_total): (Vec<NewThread>, i64),
+    (activity, activity_total): (Vec<ThreadActivity>, i64),
+) -> Email {
+    let config = ctx.config.load();
+    let link = |site_id: &str, thread_id: ThreadId| emails::thread_url(&config, site_id, thread_id);
+
+    let mut vars = Vars::new();
+    let (mut html, mut text) = (String::new(), String::new());
+    for t in &threads {
+        let label = format!("{} by {} in {}", t.title, t.author, t.category);
+        push_item(&mut html, &mut text, &label, link(&t.site_id, t.id));
+    }
+    push_more(&mut html, &mut text, threads.len(), thread_total);
+    vars.set("new_threads_html", html);
+    vars.set("new_threads_text", text.trim_end());
+
+    let (mut html, mut text) = (String::new(), String::new());
+    for t in &activity {
+        let noun = if t.replies == 1 { "reply" } else { "replies" };
+        let label = format!("{}: {} new {noun}", t.title, t.replies);
+        push_item(&mut html, &mut text, &label, link(&t.site_id, t.id));
+    }
+    push_more(&mut html, &mut text, activity.len(), activity_total);
+    vars.set("activity_html", html);
+    vars.set("activity_text", text.trim_end());
+
+    let frequency = match user.frequency {
+        DigestFrequency::Weekly => "weekly",
+        _ => "daily",
+    };
+    vars.set("frequency", frequency);
+    emails::render(
+        &config,
+        sites::DEFAULT_SITE,
+        Template::Digest,
+        &user.email,
+        vars,
+        Some(emails::unsubscribe_url(&ctx.api_url, &ctx.secret, List::Digest, user.id)),
+    )
+}
+
+// ── Scheduler ──
+
+struct DigestContext {
+    pool: DbPool,
+    config: SharedConfig,
+    api_url: String,
+    secret: String,
+}
+
+async fn send_due(ctx: &DigestContext, client: &reqwest::Client) -> Result<(), String> {
+    let Some(mailer) = ctx.config.load().mailer.clone() else {
+        return Ok(());
+    };
+
+    let db = ctx.pool.clone();
+    let due = tokio::task::spawn_blocking(move || {
+        let conn = db.get().map_err(|e| e.to_string())?;
+        due_subscribers(&conn).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    for user in due {
+        let db = ctx.pool.clone();
+        let user_id = user.id;
+        let (user, threads, activity) = tokio::task::spawn_blocking(move || {
+            let conn = db.get().map_err(|e| e.to_string())?;
+            let threads = new_threads(&conn, &user).map_err(|e| e.to_string())?;
+            let activity = watched_activity(&conn, &user).map_err(|e| e.to_string())?;
+            Ok::<_, String>((user, threads, activity))
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+
+        if threads.1 > 0 || activity.1 > 0 {
+            let email = render(ctx, &user, threads, activity);
+            if let Err(e) = emails::send(client, &mailer, &email).await {
+                eprintln!("Digest for user {user_id} not sent: {e}");
+                continue;
+            }
+        }
+
+        let db = ctx.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            let conn = db.get().map_err(|e| e.to_string())?;
+            mark_sent(&conn, user_id).map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+    }
+    Ok(())
+}
+
+/// Send due digests for the lifetime of the process. Does nothing while no
+/// `[mailer]` is configured.
+pub fn spawn_scheduler(state: &AppState) {
+    let ctx = DigestContext {
+        pool: state.db.clone(),
+        config: state.config.clone(),
+        api_url: state.api_url.clone(),
+        secret: state.jwt_secret.clone(),
+    };
+    tokio::spawn(async move {
+        let client = match emails::client() {
+            Ok(client) => client,
+            Err(e) => {
+                eprintln!("Digest emails disabled: {e}");
+                return;
+            }
+        };
+
+        let mut ticks = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            ticks.tick().await;
+            if let Err(e) = send_due(&ctx, &client).await {
+                eprintln!("Digest run failed: {e}");
+            }
+        }
+    });
+}
@@ -0,0 +1,116 @@
+//! Forum threads for blog posts. When a post matching a `[[discussions]]`
+//! entry gets its first published comment, a "Discussion of: <post>" thread
+//! linking to the post is opened in the entry's category (pinned unless
+//! `pin = false`) and remembered in `post_discussions`, so each post gets
+//! one. The comments widget links to it through `CommentState`.
+//!
+//! Threads deleted by a moderator stay gone; later comments don't open
+//! another. Comments held for moderation don't open one when approved; the
+//! post's next comment does.
+
+use mikaana_shared::{ThreadId, MAX_TITLE_LEN};
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::{config::Config, content, emails, forum, notifications, request_id, unfurl, webhooks};
+
+const TITLE_PREFIX: &str = "Discussion of: ";
+
+/// The thread discussing `slug`, if it has one that's still there.
+pub fn thread_of(conn: &Connection, site: &str, slug: &str) -> rusqlite::Result<Option<ThreadId>> {
+    conn.query_row(
+        "SELECT t.id FROM post_discussions d JOIN threads t ON t.id = d.thread_id
+         WHERE d.site_id = ?1 AND d.post_slug = ?2 AND t.status = 'published'",
+        [site, slug],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// Open the thread for `slug` if the config asks for one and it hasn't got
+/// one yet. Called as a comment on the post is published.
+pub fn open(
+    conn: &Connection,
+    config: &Config,
+    cors_origin: &str,
+    site: &str,
+    slug: &str,
+    post_title: Option<&str>,
+) -> rusqlite::Result<()> {
+    let Some(entry) = config
+        .discussions
+        .iter()
+        .find(|d| d.site == site && slug.starts_with(&d.prefix))
+    else {
+        return Ok(());
+    };
+    let Some(user_id) = entry.author(&config.authors) else {
+        return Ok(());
+    };
+    let category_id: Option<i64> = conn
+        .query_row(
+            "SELECT id FROM categories WHERE site_id = ?1 AND slug = ?2",
+            [site, &entry.category],
+            |row| row.get(0),
+        )
+        .optional()?;
+    let Some(category_id) = category_id else {
+        request_id::log(format_args!(
+            "discussions: no category {:?} on site {site:?}",
+            entry.category
+        ));
+        return Ok(());
+    };
+
+    // Claim the post first, so comments arriving together open one thread
+    let claimed = conn.execute(
+        "INSERT INTO post_discussions (site_id, post_slug) VALUES (?1, ?2)
+         ON CONFLICT (site_id, post_slug) DO NOTHING",
+        [site, slug],
+    )?;
+    if claimed == 0 {
+        return Ok(());
+    }
+
+    let name = post_title
+        .map(|t| content::sanitize(t.trim()))
+        .filter(|t| !t.is_empty())
+        .unwrap_or_else(|| title_from_slug(slug));
+    let title: String = format!("{TITLE_PREFIX}{name}")
+        .chars()
+        .take(MAX_TITLE_LEN)
+        .collect();
+    let body = match emails::post_url(config, cors_origin, site, slug) {
+        Some(url) => format!("Comments on {url}"),
+        None => format!("Comments on {slug}"),
+    };
+
+    conn.execute(
+        "INSERT INTO threads (site_id, category_id, user_id, title, body, status, pinned)
+         VALUES (?1, ?2, ?3, ?4, ?5, 'published', ?6)",
+        rusqlite::params![site, category_id, user_id, title, body, entry.pin],
+    )?;
+    let id = conn.last_insert_rowid();
+    conn.execute(
+        "UPDATE post_discussions SET thread_id = ?1 WHERE site_id = ?2 AND post_slug = ?3",
+        rusqlite::params![id, site, slug],
+    )?;
+
+    let thread = forum::query_thread(conn, ThreadId(id))?;
+    notifications::announce(conn, "thread", id)?;
+    webhooks::enqueue(conn, site, webhooks::THREAD_CREATED, &thread)?;
+    unfurl::enqueue(conn, config, &thread.body)
+}
+
+/// `/blog/hello-world/` -> `Hello world`.
+fn title_from_slug(slug: &str) -> String {
+    let last = slug
+        .split('/')
+        .rfind(|s| !s.is_empty())
+        .unwrap_or(slug)
+        .replace(['-', '_'], " ");
+    let mut chars = last.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => slug.to_string(),
+    }
+}
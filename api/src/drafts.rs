@@ -0,0 +1,132 @@
+//! Server copies of unposted comments, threads and replies, so a draft
+//! started on one device can be finished on another. Widgets keep a local
+//! copy too and only sync here for signed-in users.
+//!
+//! Drafts are private and per site. Each user keeps at most `MAX_DRAFTS`;
+//! saving another drops the oldest.
+
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use mikaana_shared::{Draft, UserId};
+use rusqlite::OptionalExtension;
+use serde::Deserialize;
+
+use crate::{auth, limits::ValidJson, sites, AppState};
+
+const MAX_DRAFTS: i64 = 50;
+
+#[derive(Deserialize)]
+pub struct DraftParams {
+    key: String,
+}
+
+/// GET /api/drafts?key= — 404 when there is none
+pub async fn get_draft(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<DraftParams>,
+) -> Result<Json<Draft>, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+    let site = sites::resolve(&headers, &state.config.load())?;
+
+    let pool = state.reader.clone();
+    let draft = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        conn.query_row(
+            "SELECT key, title, body, updated_at FROM drafts
+             WHERE user_id = ?1 AND site_id = ?2 AND key = ?3",
+            rusqlite::params![user_id, site, params.key],
+            |row| {
+                Ok(Draft {
+                    key: row.get(0)?,
+                    title: row.get(1)?,
+                    body: row.get(2)?,
+                    updated_at: row.get(3)?,
+                })
+            },
+        )
+        .optional()
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(draft))
+}
+
+/// PUT /api/drafts — save a draft, replacing the one with the same key; an
+/// empty draft is deleted instead
+pub async fn save_draft(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    ValidJson(payload): ValidJson<Draft>,
+) -> Result<StatusCode, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+    let site = sites::resolve(&headers, &state.config.load())?;
+    if payload.key.is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let pool = state.db.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if payload.title.trim().is_empty() && payload.body.trim().is_empty() {
+            return delete(&conn, user_id, &site, &payload.key);
+        }
+
+        conn.execute(
+            "INSERT INTO drafts (user_id, site_id, key, title, body) VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(user_id, site_id, key) DO UPDATE
+             SET title = ?4, body = ?5, updated_at = datetime('now')",
+            rusqlite::params![user_id, site, payload.key, payload.title, payload.body],
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        conn.execute(
+            "DELETE FROM drafts WHERE user_id = ?1 AND rowid NOT IN (
+                 SELECT rowid FROM drafts WHERE user_id = ?1
+                 ORDER BY updated_at DESC, rowid DESC LIMIT ?2)",
+            rusqlite::params![user_id, MAX_DRAFTS],
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok(StatusCode::NO_CONTENT)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+}
+
+/// DELETE /api/drafts?key= — after posting or discarding
+pub async fn delete_draft(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<DraftParams>,
+) -> Result<StatusCode, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+    let site = sites::resolve(&headers, &state.config.load())?;
+
+    let pool = state.db.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        delete(&conn, user_id, &site, &params.key)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+}
+
+fn delete(
+    conn: &rusqlite::Connection,
+    user_id: UserId,
+    site: &str,
+    key: &str,
+) -> Result<StatusCode, StatusCode> {
+    conn.execute(
+        "DELETE FROM drafts WHERE user_id = ?1 AND site_id = ?2 AND key = ?3",
+        rusqlite::params![user_id, site, key],
+    )
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,528 @@
+//! Outgoing email.
+//!
+//! The API has no SMTP client: messages are rendered here and POSTed to an
+//! operator-configured endpoint (`[mailer]` in the config file) that hands
+//! them to the actual provider. Request body:
+//!
+//! ```json
+//! {"from": "Blog <noreply@blog.example.com>", "to": "someone@example.com",
+//!  "subject": "...", "text": "plain text", "html": "<!doctype html>...",
+//!  "headers": {"List-Unsubscribe": "<https://...>"}}
+//! ```
+//!
+//! Any 2xx answer counts as sent.
+//!
+//! Each `Template` has an HTML and a text body in `api/templates/email/`,
+//! placed inside that directory's `layout.html` / `layout.txt`. Templates
+//! use a small mustache subset:
+//!
+//! - `{{name}}` inserts a variable, HTML-escaped in HTML templates;
+//! - `{{{name}}}` inserts it as is, for fragments rendered in code;
+//! - `{{#name}}...{{/name}}` keeps its contents only if `name` is non-empty.
+//!
+//! Unknown variables render as nothing. Besides the template's own
+//! variables every message gets the branding of the site it is about:
+//! `site_name`, `accent_color`, `powered_by` and `unsubscribe_url`.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::Html,
+    Json,
+};
+use mikaana_shared::{EmailPreview, ThreadId, UserId};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    auth,
+    config::{Config, MailerConfig},
+    sites, AppState,
+};
+
+const LAYOUT_HTML: &str = include_str!("../templates/email/layout.html");
+const LAYOUT_TEXT: &str = include_str!("../templates/email/layout.txt");
+
+/// Accent used when the site's branding sets none.
+const DEFAULT_ACCENT: &str = "#3b82f6";
+
+/// The messages the API sends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Template {
+    /// Someone replied in the recipient's thread.
+    Reply,
+    /// Someone `@mentioned` the recipient.
+    Mention,
+    /// Daily or weekly activity summary; see `digests.rs`.
+    Digest,
+    /// A moderator approved or removed the recipient's post.
+    Moderation,
+}
+
+impl Template {
+    pub const ALL: [Template; 4] = [
+        Template::Reply,
+        Template::Mention,
+        Template::Digest,
+        Template::Moderation,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Template::Reply => "reply",
+            Template::Mention => "mention",
+            Template::Digest => "digest",
+            Template::Moderation => "moderation",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Template> {
+        Template::ALL.into_iter().find(|t| t.name() == name)
+    }
+
+    /// Subject line, HTML body and text body.
+    fn sources(self) -> (&'static str, &'static str, &'static str) {
+        match self {
+            Template::Reply => (
+                "{{actor}} replied to \"{{title}}\"",
+                include_str!("../templates/email/reply.html"),
+                include_str!("../templates/email/reply.txt"),
+            ),
+            Template::Mention => (
+                "{{actor}} mentioned you on {{site_name}}",
+                include_str!("../templates/email/mention.html"),
+                include_str!("../templates/email/mention.txt"),
+            ),
+            Template::Digest => (
+                "Your {{frequency}} digest from {{site_name}}",
+                include_str!("../templates/email/digest.html"),
+                include_str!("../templates/email/digest.txt"),
+            ),
+            Template::Moderation => (
+                "{{decision}}",
+                include_str!("../templates/email/moderation.html"),
+                include_str!("../templates/email/moderation.txt"),
+            ),
+        }
+    }
+
+    /// Made-up variables for previews.
+    fn sample(self) -> Vars {
+        let mut vars = Vars::new();
+        match self {
+            Template::Reply | Template::Mention => {
+                vars.set("actor", "octocat");
+                vars.set("title", "Getting started with the forum");
+                vars.set("url", "https://example.com/discuss/thread/1#reply-2");
+            }
+            Template::Digest => {
+                vars.set("frequency", "weekly");
+                vars.set(
+                    "new_threads_html",
+                    "<li><a href=\"https://example.com/discuss/thread/3\">Release notes by octocat in General</a></li>",
+                );
+                vars.set(
+                    "new_threads_text",
+                    "- Release notes by octocat in General\n  https://example.com/discuss/thread/3",
+                );
+                vars.set("activity_html", "<li>Getting started with the forum: 4 new replies</li>");
+                vars.set("activity_text", "- Getting started with the forum: 4 new replies");
+            }
+            Template::Moderation => {
+                vars.set("decision", "Your reply was removed by a moderator");
+                vars.set("url", "https://example.com/discuss/thread/1");
+            }
+        }
+        vars
+    }
+}
+
+/// Values for a template's placeholders.
+#[derive(Default)]
+pub struct Vars(HashMap<&'static str, String>);
+
+impl Vars {
+    pub fn new() -> Self {
+        Vars::default()
+    }
+
+    pub fn set(&mut self, name: &'static str, value: impl Into<String>) {
+        self.0.insert(name, value.into());
+    }
+
+    fn get(&self, name: &str) -> &str {
+        self.0.get(name).map_or("", String::as_str)
+    }
+
+    /// `site_id`'s branding, for anything not already set.
+    fn with_branding(mut self, config: &Config, site_id: &str) -> Self {
+        let branding = config.branding(site_id);
+        let defaults = [
+            ("site_name", branding.site_name.clone().unwrap_or_else(|| "the forum".to_string())),
+            (
+                "accent_color",
+                branding.accent_color.clone().unwrap_or_else(|| DEFAULT_ACCENT.to_string()),
+            ),
+            ("powered_by", if branding.powered_by { "yes" } else { "" }.to_string()),
+        ];
+        for (name, value) in defaults {
+            self.0.entry(name).or_insert(value);
+        }
+        self
+    }
+}
+
+/// Escape text for an HTML element or a double-quoted attribute.
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Expand the placeholders in `source`; see the module docs.
+fn fill(source: &str, vars: &Vars, html: bool) -> String {
+    let mut out = String::with_capacity(source.len());
+    let mut rest = source;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if let Some(tag) = rest.strip_prefix("{{{") {
+            let Some(end) = tag.find("}}}") else { break };
+            out.push_str(vars.get(tag[..end].trim()));
+            rest = &tag[end + 3..];
+            continue;
+        }
+        let Some(end) = rest.find("}}") else { break };
+        let tag = rest[2..end].trim();
+        rest = &rest[end + 2..];
+
+        if let Some(name) = tag.strip_prefix('#') {
+            let close = format!("{{{{/{name}}}}}");
+            let (inner, after) = rest.split_once(close.as_str()).unwrap_or((rest, ""));
+            // Tags on a line of their own don't leave a blank line behind
+            let inner = match inner.strip_prefix('\n') {
+                Some(stripped) if out.is_empty() || out.ends_with('\n') => stripped,
+                _ => inner,
+            };
+            let standalone_close = inner.is_empty() || inner.ends_with('\n');
+            if !vars.get(name).is_empty() {
+                out.push_str(&fill(inner, vars, html));
+            }
+            rest = match after.strip_prefix('\n') {
+                Some(stripped) if standalone_close => stripped,
+                _ => after,
+            };
+        } else if html {
+            out.push_str(&escape(vars.get(tag)));
+        } else {
+            out.push_str(vars.get(tag));
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// A rendered message, ready for `send`.
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+    /// One-click unsubscribe target (RFC 8058), for bulk mail.
+    pub unsubscribe_url: Option<String>,
+}
+
+/// Render `template` about content on `site_id`, addressed to `to`.
+pub fn render(
+    config: &Config,
+    site_id: &str,
+    template: Template,
+    to: &str,
+    vars: Vars,
+    unsubscribe_url: Option<String>,
+) -> Email {
+    let mut vars = vars.with_branding(config, site_id);
+    if let Some(url) = &unsubscribe_url {
+        vars.set("unsubscribe_url", url.clone());
+    }
+
+    let (subject, html, text) = template.sources();
+    let html = fill(html, &vars, true);
+    let text = fill(text, &vars, false);
+    vars.set("content", html);
+    let html = fill(LAYOUT_HTML, &vars, true);
+    vars.set("content", text.trim_end());
+    let text = fill(LAYOUT_TEXT, &vars, false);
+
+    Email {
+        to: to.to_string(),
+        subject: fill(subject, &vars, false),
+        text,
+        html,
+        unsubscribe_url,
+    }
+}
+
+// ── Unsubscribe links ──
+
+/// What an unsubscribe link turns off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum List {
+    /// Digest emails.
+    Digest,
+    /// Emails for individual notifications.
+    Notifications,
+}
+
+impl List {
+    fn as_str(self) -> &'static str {
+        match self {
+            List::Digest => "digest",
+            List::Notifications => "notifications",
+        }
+    }
+}
+
+fn unsubscribe_key(secret: &str) -> ring::hmac::Key {
+    ring::hmac::Key::new(ring::hmac::HMAC_SHA256, secret.as_bytes())
+}
+
+fn unsubscribe_message(list: List, user_id: UserId) -> String {
+    format!("unsubscribe:{}:{user_id}", list.as_str())
+}
+
+/// Link that turns `list` off for `user_id` without logging in; it
+/// carries an HMAC of both, keyed by `secret`.
+pub fn unsubscribe_url(api_url: &str, secret: &str, list: List, user_id: UserId) -> String {
+    let tag = ring::hmac::sign(&unsubscribe_key(secret), unsubscribe_message(list, user_id).as_bytes());
+    let token: String = tag.as_ref().iter().map(|b| format!("{b:02x}")).collect();
+    format!(
+        "{api_url}/api/notifications/unsubscribe?list={}&user={user_id}&token={token}",
+        list.as_str()
+    )
+}
+
+fn unsubscribe_token_valid(secret: &str, list: List, user_id: UserId, token: &str) -> bool {
+    let Some(bytes) = (0..token.len())
+        .step_by(2)
+        .map(|i| token.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok()))
+        .collect::<Option<Vec<u8>>>()
+    else {
+        return false;
+    };
+    let message = unsubscribe_message(list, user_id);
+    ring::hmac::verify(&unsubscribe_key(secret), message.as_bytes(), &bytes).is_ok()
+}
+
+// ── Links ──
+
+/// Absolute address of a comment, thread or reply, when the site's
+/// origin or `forum_url` is known.
+pub fn content_url(
+    config: &Config,
+    cors_origin: &str,
+    site_id: &str,
+    post_slug: Option<&str>,
+    thread_id: Option<ThreadId>,
+    target_type: &str,
+    target_id: i64,
+) -> Option<String> {
+    match target_type {
+        "comment" => Some(format!(
+            "{}#comment-{target_id}",
+            post_url(config, cors_origin, site_id, post_slug?)?
+        )),
+        "thread" => thread_url(config, site_id, thread_id?),
+        "reply" => Some(format!("{}#reply-{target_id}", thread_url(config, site_id, thread_id?)?)),
+        _ => None,
+    }
+}
+
+/// Absolute address of a post on the site, from its first origin (or the
+/// CORS origin, for the default site).
+pub fn post_url(config: &Config, cors_origin: &str, site_id: &str, slug: &str) -> Option<String> {
+    let origin = match config.site(site_id) {
+        Some(site) => site.origins.first()?.as_str(),
+        None if site_id == sites::DEFAULT_SITE => cors_origin,
+        None => return None,
+    };
+    Some(format!("{}{slug}", origin.trim_end_matches('/')))
+}
+
+pub fn thread_url(config: &Config, site_id: &str, thread_id: ThreadId) -> Option<String> {
+    let base = config.forum_url(site_id)?.trim_end_matches('/');
+    Some(format!("{base}/thread/{thread_id}"))
+}
+
+// ── Delivery ──
+
+#[derive(Serialize)]
+struct MailRequest<'a> {
+    from: &'a str,
+    to: &'a str,
+    subject: &'a str,
+    text: &'a str,
+    html: &'a str,
+    headers: HashMap<&'static str, String>,
+}
+
+pub fn client() -> Result<reqwest::Client, String> {
+    reqwest::Client::builder()
+        .user_agent("mikaana-api")
+        .timeout(Duration::from_secs(10))
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// Hand `email` to the mailer.
+pub async fn send(
+    client: &reqwest::Client,
+    mailer: &MailerConfig,
+    email: &Email,
+) -> Result<(), String> {
+    let mut headers = HashMap::new();
+    if let Some(url) = &email.unsubscribe_url {
+        headers.insert("List-Unsubscribe", format!("<{url}>"));
+        headers.insert("List-Unsubscribe-Post", "List-Unsubscribe=One-Click".to_string());
+    }
+
+    let mut req = client.post(&mailer.url).json(&MailRequest {
+        from: &mailer.from,
+        to: &email.to,
+        subject: &email.subject,
+        text: &email.text,
+        html: &email.html,
+        headers,
+    });
+    if let Some(token) = &mailer.token {
+        req = req.bearer_auth(token);
+    }
+    req.send()
+        .await
+        .map_err(|e| e.to_string())?
+        .error_for_status()
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// ── Handlers ──
+
+#[derive(Deserialize)]
+pub struct UnsubscribeParams {
+    list: List,
+    user: UserId,
+    token: String,
+}
+
+/// GET or POST /api/notifications/unsubscribe?list=&user=<id>&token=<hmac>
+/// — the link in bulk emails; POST is the one-click form mail clients use
+pub async fn unsubscribe(
+    State(state): State<AppState>,
+    Query(params): Query<UnsubscribeParams>,
+) -> Result<Html<&'static str>, StatusCode> {
+    if !unsubscribe_token_valid(&state.jwt_secret, params.list, params.user, &params.token) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let pool = state.db.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let sql = match params.list {
+            List::Digest => "UPDATE users SET digest = 'off' WHERE id = ?1",
+            List::Notifications => "UPDATE users SET notify_email = 0 WHERE id = ?1",
+        };
+        conn.execute(sql, [params.user])
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        Ok::<_, StatusCode>(())
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Html(
+        "<!doctype html><title>Unsubscribed</title>\
+         <p>You won't receive these emails any more.</p>",
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct PreviewParams {
+    /// Site whose branding to apply; defaults to the requesting site.
+    site: Option<String>,
+}
+
+/// GET /api/admin/emails — names of the templates that can be previewed
+pub async fn list_templates(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<&'static str>>, StatusCode> {
+    let admin_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+
+    let pool = state.reader.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_admin(&conn, admin_id)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(Template::ALL.map(Template::name).to_vec()))
+}
+
+/// GET /api/admin/emails/:name/preview?site= — the template rendered with
+/// sample data and the site's branding
+pub async fn preview(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(name): Path<String>,
+    Query(params): Query<PreviewParams>,
+) -> Result<Json<EmailPreview>, StatusCode> {
+    let admin_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+    let template = Template::parse(&name).ok_or(StatusCode::NOT_FOUND)?;
+    let config = state.config.load();
+    let site = match params.site {
+        Some(site) if site == sites::DEFAULT_SITE || config.site(&site).is_some() => site,
+        Some(_) => return Err(StatusCode::NOT_FOUND),
+        None => sites::resolve(&headers, &config)?,
+    };
+
+    let pool = state.reader.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_admin(&conn, admin_id)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    // Signed with a throwaway key, so the sample link unsubscribes nobody
+    let list = match template {
+        Template::Digest => List::Digest,
+        _ => List::Notifications,
+    };
+    let unsubscribe = unsubscribe_url(&state.api_url, "preview", list, UserId(0));
+    let email = render(
+        &config,
+        &site,
+        template,
+        "someone@example.com",
+        template.sample(),
+        Some(unsubscribe),
+    );
+    Ok(Json(EmailPreview {
+        subject: email.subject,
+        html: email.html,
+        text: email.text,
+    }))
+}
@@ -0,0 +1,274 @@
+//! JSON Feed 1.1 (https://jsonfeed.org/version/1.1) of a category's
+//! threads, a thread's replies and a post's comments, for feed readers and
+//! automation tools.
+//!
+//! Feed readers send no `Origin`, so category and comment feeds take the
+//! site as `?site=`; threads carry their own.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use mikaana_shared::{Attachment, ReplySort, ThreadId, ThreadSort, User};
+use serde::{Deserialize, Serialize};
+
+use crate::{comments, emails, forum, seo, sites, AppState};
+
+const VERSION: &str = "https://jsonfeed.org/version/1.1";
+const CONTENT_TYPE: &str = "application/feed+json";
+
+/// Newest items kept in a feed.
+const FEED_LEN: usize = 50;
+
+#[derive(Serialize)]
+struct Feed {
+    version: &'static str,
+    title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    home_page_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    items: Vec<Item>,
+}
+
+#[derive(Serialize)]
+struct Item {
+    id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    content_text: String,
+    date_published: String,
+    authors: Vec<Author>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    attachments: Vec<FeedAttachment>,
+}
+
+#[derive(Serialize)]
+struct Author {
+    name: String,
+    avatar: String,
+}
+
+#[derive(Serialize)]
+struct FeedAttachment {
+    url: String,
+    mime_type: String,
+    title: String,
+    size_in_bytes: i64,
+}
+
+impl Item {
+    /// Ids are `{type}-{id}` rather than the item's address, so they stay
+    /// the same if the forum moves.
+    fn new(
+        id: String,
+        url: Option<String>,
+        user: &User,
+        body: &str,
+        created_at: &str,
+        attachments: &[Attachment],
+    ) -> Self {
+        Item {
+            id,
+            url,
+            title: None,
+            content_text: body.to_string(),
+            date_published: rfc3339(created_at),
+            authors: vec![Author {
+                name: user.name().to_string(),
+                avatar: user.avatar_url.clone(),
+            }],
+            attachments: attachments
+                .iter()
+                .map(|a| FeedAttachment {
+                    url: a.url.clone(),
+                    mime_type: a.content_type.clone(),
+                    title: a.filename.clone(),
+                    size_in_bytes: a.size,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Database times (`YYYY-MM-DD HH:MM:SS`, UTC) as RFC 3339.
+fn rfc3339(at: &str) -> String {
+    format!("{}Z", at.replacen(' ', "T", 1))
+}
+
+/// The name in `{name}.json`; 404 for other extensions.
+fn strip_json(file: &str) -> Result<&str, StatusCode> {
+    file.strip_suffix(".json")
+        .filter(|name| !name.is_empty())
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+fn respond(feed: Feed) -> impl IntoResponse {
+    (
+        [
+            (header::CONTENT_TYPE, CONTENT_TYPE),
+            (header::CACHE_CONTROL, "public, max-age=300"),
+            (header::VARY, sites::VARY),
+        ],
+        Json(feed),
+    )
+}
+
+#[derive(Deserialize)]
+pub struct SiteParams {
+    site: Option<String>,
+}
+
+/// GET /api/feeds/category/{slug}.json?site= — the category's newest threads.
+pub async fn category_feed(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(file): Path<String>,
+    Query(params): Query<SiteParams>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let config = state.config.load_full();
+    let site = sites::resolve_named(params.site, &headers, &config)?;
+    let slug = strip_json(&file)?.to_string();
+    let pool = state.reader.clone();
+
+    let (category, threads, site) = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let category = forum::query_category(&conn, &site, &slug, None)
+            .map_err(|_| StatusCode::NOT_FOUND)?;
+        let threads = forum::query_threads(
+            &conn,
+            category.id,
+            None,
+            ThreadSort::Newest,
+            1,
+            FEED_LEN as i64,
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        Ok::<_, StatusCode>((category, threads.items, site))
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    let items = threads
+        .iter()
+        .filter(|t| t.moved_to.is_none())
+        .map(|t| Item {
+            title: Some(t.title.clone()),
+            ..Item::new(
+                format!("thread-{}", t.id),
+                emails::thread_url(&config, &site, t.id),
+                &t.user,
+                &t.body,
+                &t.created_at,
+                &t.attachments,
+            )
+        })
+        .collect();
+    Ok(respond(Feed {
+        version: VERSION,
+        title: category.name,
+        home_page_url: config
+            .forum_url(&site)
+            .map(|base| format!("{}/category/{}", base.trim_end_matches('/'), category.slug)),
+        description: Some(category.description).filter(|d| !d.is_empty()),
+        items,
+    }))
+}
+
+/// GET /api/feeds/thread/{id}.json — the thread's newest replies.
+pub async fn thread_feed(
+    State(state): State<AppState>,
+    Path(file): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let config = state.config.load_full();
+    let id: ThreadId = strip_json(&file)?.parse().map_err(|_| StatusCode::NOT_FOUND)?;
+    let pool = state.reader.clone();
+
+    let (site, thread, replies) = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let (site, thread) = seo::published_thread(&conn, id)?;
+        let mut replies = forum::query_replies(&conn, id, ReplySort::Newest)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        replies.truncate(FEED_LEN);
+        Ok::<_, StatusCode>((site, thread, replies))
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    let thread_url = emails::thread_url(&config, &site, thread.id);
+    let items = replies
+        .iter()
+        .map(|r| {
+            Item::new(
+                format!("reply-{}", r.id),
+                thread_url.as_ref().map(|url| format!("{url}#reply-{}", r.id)),
+                &r.user,
+                &r.body,
+                &r.created_at,
+                &r.attachments,
+            )
+        })
+        .collect();
+    Ok(respond(Feed {
+        version: VERSION,
+        title: thread.title,
+        home_page_url: thread_url,
+        description: None,
+        items,
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct CommentFeedParams {
+    slug: String,
+    site: Option<String>,
+}
+
+/// GET /api/feeds/comments.json?slug=&site= — the post's newest comments.
+pub async fn comment_feed(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<CommentFeedParams>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let config = state.config.load_full();
+    let site = sites::resolve_named(params.site, &headers, &config)?;
+    let slug = params.slug;
+    let pool = state.reader.clone();
+
+    let (site, slug, comments) = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let mut comments = comments::query_comments(&conn, &site, &slug)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        comments.reverse();
+        comments.truncate(FEED_LEN);
+        Ok::<_, StatusCode>((site, slug, comments))
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    let post_url = emails::post_url(&config, &state.cors_origin, &site, &slug);
+    let items = comments
+        .iter()
+        .map(|c| {
+            Item::new(
+                format!("comment-{}", c.id),
+                post_url.as_ref().map(|url| format!("{url}#comment-{}", c.id)),
+                &c.user,
+                &c.body,
+                &c.created_at,
+                &c.attachments,
+            )
+        })
+        .collect();
+    Ok(respond(Feed {
+        version: VERSION,
+        title: format!("Comments on {slug}"),
+        home_page_url: post_url,
+        description: None,
+        items,
+    }))
+}
@@ -0,0 +1,228 @@
+//! Per-user posting limits (`flood` in the config), checked by the handlers
+//! creating comments, threads and replies in the transaction that inserts
+//! the post, so two sent at once can't both get through. Posting more than a kind's `max` within `per_seconds` is a `429`
+//! saying how long to wait; posting text the user already posted within
+//! `duplicate_window_seconds` (as any kind) is a `409`; the bodies are
+//! `ApiError::PostingTooFast` and `ApiError::DuplicatePost`. Moderators and
+//! admins aren't limited.
+//!
+//! Before any of that, a comment or reply identical to one the user posted
+//! in the same place moments ago (a double click in a client without
+//! `Idempotency-Key`, a resubmitted form) isn't created again: `check`
+//! hands back the first one, for the handler to answer with. `is_repeat`
+//! looks for it ahead of time, as a repeat doesn't go through the captcha
+//! again.
+//!
+//! This is separate from rate limiting in front of the API, which counts
+//! requests; these count what was actually posted.
+
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use mikaana_shared::{ApiError, ThreadId, UserId};
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::{auth, config::FloodConfig, AppState};
+
+/// How long a repeated comment or reply counts as a double post.
+const REPEAT_WINDOW_SECONDS: u64 = 120;
+
+#[derive(Clone)]
+pub enum Kind {
+    Thread,
+    /// On the post `slug` of `site`.
+    Comment { site: String, slug: String },
+    Reply { thread_id: ThreadId },
+}
+
+impl Kind {
+    fn table(&self) -> &'static str {
+        match self {
+            Kind::Thread => "threads",
+            Kind::Comment { .. } => "comments",
+            Kind::Reply { .. } => "replies",
+        }
+    }
+}
+
+/// The user's earlier copy of a comment or reply they sent again.
+pub struct Repeat {
+    pub id: i64,
+    /// Held for moderation.
+    pub pending: bool,
+}
+
+/// Error of the create handlers: the status they'd otherwise return, or a
+/// refusal from `check` with its body.
+pub enum PostError {
+    Status(StatusCode),
+    /// Seconds to wait.
+    TooFast(u64),
+    Duplicate,
+}
+
+impl From<StatusCode> for PostError {
+    fn from(status: StatusCode) -> Self {
+        PostError::Status(status)
+    }
+}
+
+impl IntoResponse for PostError {
+    fn into_response(self) -> Response {
+        match self {
+            PostError::Status(status) => status.into_response(),
+            PostError::TooFast(secs) => {
+                let body = ApiError::PostingTooFast { retry_after: secs };
+                (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [(header::RETRY_AFTER, secs.to_string())],
+                    Json(body),
+                )
+                    .into_response()
+            }
+            PostError::Duplicate => (StatusCode::CONFLICT, Json(ApiError::DuplicatePost)).into_response(),
+        }
+    }
+}
+
+/// Refuse `user_id`'s new post of `kind` with `body` (as stored, after
+/// sanitizing) if it breaks the limits. `Some` when it's a double post, to
+/// be answered with the earlier copy instead of being created. Run on the
+/// writer, in the transaction that goes on to insert the post.
+pub fn check(
+    conn: &Connection,
+    config: &FloodConfig,
+    user_id: UserId,
+    kind: &Kind,
+    body: &str,
+) -> Result<Option<Repeat>, PostError> {
+    if let Some(repeat) =
+        earlier_copy(conn, user_id, kind, body).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    {
+        return Ok(Some(repeat));
+    }
+    if auth::require_moderator(conn, user_id).is_ok() {
+        return Ok(None);
+    }
+    if let Some(secs) =
+        wait(conn, config, user_id, kind).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    {
+        return Err(PostError::TooFast(secs));
+    }
+    if is_duplicate(conn, config, user_id, body).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)? {
+        return Err(PostError::Duplicate);
+    }
+    Ok(None)
+}
+
+/// Whether the post looks like a double post, ahead of `check`: the
+/// handlers skip the captcha for one.
+pub async fn is_repeat(
+    state: &AppState,
+    user_id: UserId,
+    kind: &Kind,
+    body: &str,
+) -> Result<bool, StatusCode> {
+    let pool = state.reader.clone();
+    let (kind, body) = (kind.clone(), body.to_string());
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        earlier_copy(&conn, user_id, &kind, &body)
+            .map(|repeat| repeat.is_some())
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+}
+
+/// `user_id`'s comment or reply with `body` in the same place from the
+/// last `REPEAT_WINDOW_SECONDS`. Rejected ones don't count.
+fn earlier_copy(conn: &Connection, user_id: UserId, kind: &Kind, body: &str) -> rusqlite::Result<Option<Repeat>> {
+    let window = format!("-{REPEAT_WINDOW_SECONDS} seconds");
+    let from_row = |row: &rusqlite::Row| {
+        Ok(Repeat {
+            id: row.get(0)?,
+            pending: row.get(1)?,
+        })
+    };
+    match kind {
+        Kind::Thread => Ok(None),
+        Kind::Comment { site, slug } => conn
+            .query_row(
+                "SELECT id, status = 'pending' FROM comments
+                 WHERE user_id = ?1 AND body = ?2 AND created_at > datetime('now', ?3)
+                   AND site_id = ?4 AND post_slug = ?5 AND status IN ('published', 'pending')
+                 ORDER BY id LIMIT 1",
+                rusqlite::params![user_id, body, window, site, slug],
+                from_row,
+            )
+            .optional(),
+        Kind::Reply { thread_id } => conn
+            .query_row(
+                "SELECT id, status = 'pending' FROM replies
+                 WHERE user_id = ?1 AND body = ?2 AND created_at > datetime('now', ?3)
+                   AND thread_id = ?4 AND status IN ('published', 'pending')
+                 ORDER BY id LIMIT 1",
+                rusqlite::params![user_id, body, window, thread_id],
+                from_row,
+            )
+            .optional(),
+    }
+}
+
+/// Seconds until `user_id` may post another `kind`, if they have to wait.
+fn wait(conn: &Connection, config: &FloodConfig, user_id: UserId, kind: &Kind) -> rusqlite::Result<Option<u64>> {
+    let rate = match kind {
+        Kind::Thread => config.threads,
+        Kind::Comment { .. } => config.comments,
+        Kind::Reply { .. } => config.replies,
+    };
+    if rate.max == 0 {
+        return Ok(None);
+    }
+    // The `max`th most recent post in the window; once it's out of the
+    // window there's room for one more
+    let sql = format!(
+        "SELECT unixepoch(created_at) + ?2 - unixepoch('now') FROM {}
+         WHERE user_id = ?1 AND created_at > datetime('now', ?3)
+         ORDER BY created_at DESC LIMIT 1 OFFSET ?4",
+        kind.table()
+    );
+    let secs: Option<i64> = conn
+        .query_row(
+            &sql,
+            rusqlite::params![
+                user_id,
+                rate.per_seconds as i64,
+                format!("-{} seconds", rate.per_seconds),
+                rate.max - 1
+            ],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(secs.map(|s| s.max(1) as u64))
+}
+
+/// Whether `user_id` posted `body` within the duplicate window.
+fn is_duplicate(conn: &Connection, config: &FloodConfig, user_id: UserId, body: &str) -> rusqlite::Result<bool> {
+    if config.duplicate_window_seconds == 0 {
+        return Ok(false);
+    }
+    conn.query_row(
+        "SELECT EXISTS (
+             SELECT 1 FROM comments WHERE user_id = ?1 AND body = ?2 AND created_at > datetime('now', ?3)
+             UNION ALL
+             SELECT 1 FROM threads WHERE user_id = ?1 AND body = ?2 AND created_at > datetime('now', ?3)
+             UNION ALL
+             SELECT 1 FROM replies WHERE user_id = ?1 AND body = ?2 AND created_at > datetime('now', ?3)
+         )",
+        rusqlite::params![
+            user_id,
+            body,
+            format!("-{} seconds", config.duplicate_window_seconds)
+        ],
+        |row| row.get(0),
+    )
+}
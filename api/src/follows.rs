@@ -0,0 +1,148 @@
+//! Following other users, and the feed of what they post.
+//!
+//! Follows are instance-wide like accounts; the feed lists what followed
+//! users posted on the requesting site (see `activity.rs`).
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use mikaana_shared::{ActivityItem, PaginatedCursor, User, UserId};
+use serde::Deserialize;
+
+use crate::{activity, auth, sites, AppState};
+
+const PAGE_SIZE: i64 = 30;
+
+/// POST /api/users/:id/follow
+pub async fn follow(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(followee): Path<UserId>,
+) -> Result<StatusCode, StatusCode> {
+    set_follow(state, headers, followee, true).await
+}
+
+/// DELETE /api/users/:id/follow
+pub async fn unfollow(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(followee): Path<UserId>,
+) -> Result<StatusCode, StatusCode> {
+    set_follow(state, headers, followee, false).await
+}
+
+async fn set_follow(
+    state: AppState,
+    headers: HeaderMap,
+    followee: UserId,
+    following: bool,
+) -> Result<StatusCode, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+    if followee == user_id {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let pool = state.db.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        if following {
+            auth::require_active(&conn, user_id)?;
+            let exists: bool = conn
+                .query_row(
+                    "SELECT EXISTS (SELECT 1 FROM users
+                     WHERE id = ?1 AND deleted_at IS NULL AND merged_into IS NULL)",
+                    [followee],
+                    |row| row.get(0),
+                )
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            if !exists {
+                return Err(StatusCode::NOT_FOUND);
+            }
+        }
+
+        let sql = if following {
+            "INSERT OR IGNORE INTO follows (follower_id, followee_id) VALUES (?1, ?2)"
+        } else {
+            "DELETE FROM follows WHERE follower_id = ?1 AND followee_id = ?2"
+        };
+        conn.execute(sql, [user_id, followee])
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok(StatusCode::NO_CONTENT)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+}
+
+/// GET /api/follows — users the viewer follows, sorted by name
+pub async fn list_following(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<User>>, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+
+    let pool = state.reader.clone();
+    let users = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT u.id, u.username, u.avatar_url, u.display_name
+                 FROM follows f JOIN users u ON u.id = f.followee_id
+                 WHERE f.follower_id = ?1
+                 ORDER BY COALESCE(u.display_name, u.username) COLLATE NOCASE",
+            )
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let users = stmt
+            .query_map([user_id], |row| auth::user_from_row(row, 0))
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok::<_, StatusCode>(users)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(users))
+}
+
+#[derive(Deserialize)]
+pub struct FeedParams {
+    /// `next` from the previous page.
+    before: Option<String>,
+}
+
+/// GET /api/feed?before= — comments, threads and replies by followed users,
+/// newest first
+pub async fn feed(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<FeedParams>,
+) -> Result<Json<PaginatedCursor<ActivityItem>>, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+    let site = sites::resolve(&headers, &state.config.load())?;
+    let before = match params.before.as_deref() {
+        None | Some("") => None,
+        Some(cursor) => Some(activity::parse_cursor(cursor).ok_or(StatusCode::BAD_REQUEST)?),
+    };
+
+    let pool = state.reader.clone();
+    let page = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        activity::query_page(
+            &conn,
+            &site,
+            "a.user_id IN (SELECT followee_id FROM follows WHERE follower_id = ?5)",
+            &[&user_id],
+            before,
+            PAGE_SIZE,
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(page))
+}
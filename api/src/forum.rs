@@ -1,12 +1,23 @@
 use axum::{
     extract::{Path, Query, State},
     http::{HeaderMap, StatusCode},
-    Json,
+    response::{IntoResponse, Response},
+    Extension, Json,
 };
 use mikaana_shared::*;
-use serde::{Deserialize, Serialize};
+use rusqlite::OptionalExtension;
+use serde::Deserialize;
 
-use crate::{auth, AppState};
+use crate::{
+    attachments, audit, auth, authors, badges, blocks, captcha,
+    content::{self, Item},
+    flood::{self, PostError},
+    ip_bans::IpHash,
+    limits::ValidJson,
+    moderation,
+    notifications,
+    response_cache, scheduled, sites, unfurl, webhooks, word_filters, AppState,
+};
 
 // ── Query params ──
 
@@ -14,14 +25,457 @@ use crate::{auth, AppState};
 pub struct ThreadListParams {
     category: String,
     page: Option<i64>,
+    /// Cursor from a previous `PaginatedCursor::next`, or empty for the
+    /// first page; switches the response to `PaginatedCursor`.
+    after: Option<String>,
+    #[serde(default)]
+    sort: ThreadSort,
+}
+
+#[derive(Deserialize)]
+pub struct ThreadParams {
+    #[serde(default)]
+    sort: ReplySort,
+}
+
+// ── Thread permissions ──
+
+/// Lock/archive state of a thread and its category.
+pub struct ThreadFlags {
+    locked: bool,
+    archived: bool,
+    read_only: bool,
+}
+
+impl ThreadFlags {
+    const SELECT: &'static str = "SELECT t.locked, t.archived, c.read_only
+         FROM threads t JOIN categories c ON t.category_id = c.id";
+
+    pub fn for_thread(conn: &rusqlite::Connection, thread_id: ThreadId) -> rusqlite::Result<Self> {
+        conn.query_row(&format!("{} WHERE t.id = ?1", Self::SELECT), [thread_id], Self::from_row)
+    }
+
+    pub fn for_target(
+        conn: &rusqlite::Connection,
+        target_type: &str,
+        target_id: i64,
+    ) -> rusqlite::Result<Option<Self>> {
+        match target_type {
+            "thread" => Self::for_thread(conn, ThreadId(target_id)).map(Some),
+            "reply" => Self::for_reply(conn, ReplyId(target_id)).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn for_reply(conn: &rusqlite::Connection, reply_id: ReplyId) -> rusqlite::Result<Self> {
+        conn.query_row(
+            &format!(
+                "{} WHERE t.id = (SELECT thread_id FROM replies WHERE id = ?1)",
+                Self::SELECT
+            ),
+            [reply_id],
+            Self::from_row,
+        )
+    }
+
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            locked: row.get(0)?,
+            archived: row.get(1)?,
+            read_only: row.get(2)?,
+        })
+    }
+
+    pub fn can_reply(&self) -> bool {
+        !self.locked && !self.archived && !self.read_only
+    }
+
+    pub fn can_vote(&self) -> bool {
+        !self.archived
+    }
+
+    pub fn disabled_reason(&self) -> Option<String> {
+        let reason = if self.archived {
+            "This thread is archived."
+        } else if self.locked {
+            "This thread is locked."
+        } else if self.read_only {
+            "This category is read-only."
+        } else {
+            return None;
+        };
+        Some(reason.to_string())
+    }
+}
+
+// ── Queries ──
+
+/// Columns read by `thread_from_row`, in order.
+const THREAD_SELECT: &str = "SELECT t.id, t.category_id, t.title, t.body, t.created_at,
+        u.id, u.username, u.avatar_url, u.display_name,
+        (SELECT COUNT(*) FROM replies WHERE thread_id = t.id AND status = 'published'),
+        (SELECT id FROM replies WHERE id = t.solution_reply_id AND status = 'published'),
+        lr.created_at, lu.id, lu.username, lu.avatar_url, lu.display_name,
+        t.moved_to, t.pinned, t.publish_at
+ FROM threads t
+ JOIN users u ON t.user_id = u.id
+ LEFT JOIN replies lr ON lr.id = (SELECT id FROM replies
+                                  WHERE thread_id = t.id AND status = 'published'
+                                  ORDER BY created_at DESC, id DESC LIMIT 1)
+ LEFT JOIN users lu ON lr.user_id = lu.id";
+
+fn thread_from_row(row: &rusqlite::Row) -> rusqlite::Result<Thread> {
+    let last_reply_at: Option<String> = row.get(11)?;
+    let last_reply_user = match last_reply_at {
+        Some(_) => Some(auth::user_from_row(row, 12)?),
+        None => None,
+    };
+    Ok(Thread {
+        id: row.get(0)?,
+        category_id: row.get(1)?,
+        title: row.get(2)?,
+        body: row.get(3)?,
+        created_at: row.get(4)?,
+        user: auth::user_from_row(row, 5)?,
+        reply_count: row.get(9)?,
+        solution_reply_id: row.get(10)?,
+        last_reply_at,
+        last_reply_user,
+        moved_to: row.get(16)?,
+        has_unread: false,
+        attachments: Vec::new(),
+        pending: false,
+        pinned: row.get(17)?,
+        publish_at: row.get(18)?,
+    })
+}
+
+/// Columns read by `reply_from_row`, in order.
+const REPLY_SELECT: &str = "SELECT r.id, r.thread_id, r.body, r.created_at,
+        u.id, u.username, u.avatar_url, u.display_name, r.vote_count
+ FROM replies r
+ JOIN users u ON r.user_id = u.id";
+
+fn reply_from_row(row: &rusqlite::Row) -> rusqlite::Result<Reply> {
+    let body: String = row.get(2)?;
+    Ok(Reply {
+        id: row.get(0)?,
+        thread_id: row.get(1)?,
+        body_length: body.chars().count(),
+        body,
+        created_at: row.get(3)?,
+        user: auth::user_from_row(row, 4)?,
+        vote_count: row.get(8)?,
+        attachments: Vec::new(),
+        pending: false,
+        by_author: false,
+        blocked: false,
+    })
+}
+
+/// Columns read by `category_from_row`, in order; `?1` is the viewer.
+const CATEGORY_SELECT: &str = "SELECT c.id, c.name, c.slug, c.description, c.read_only,
+        EXISTS(SELECT 1 FROM category_subscriptions s
+               WHERE s.category_id = c.id AND s.user_id = ?1)
+ FROM categories c";
+
+fn category_from_row(row: &rusqlite::Row) -> rusqlite::Result<ForumCategory> {
+    Ok(ForumCategory {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        slug: row.get(2)?,
+        description: row.get(3)?,
+        read_only: row.get(4)?,
+        subscribed: row.get(5)?,
+    })
+}
+
+/// A site's categories; `subscribed` reflects `viewer`'s follows.
+pub fn query_categories(
+    conn: &rusqlite::Connection,
+    site: &str,
+    viewer: Option<UserId>,
+) -> rusqlite::Result<Vec<ForumCategory>> {
+    let mut stmt =
+        conn.prepare_cached(&format!("{CATEGORY_SELECT} WHERE c.site_id = ?2 ORDER BY c.id"))?;
+    let rows = stmt
+        .query_map(rusqlite::params![viewer, site], category_from_row)?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
+
+pub fn query_category(
+    conn: &rusqlite::Connection,
+    site: &str,
+    slug: &str,
+    viewer: Option<UserId>,
+) -> rusqlite::Result<ForumCategory> {
+    conn.query_row(
+        &format!("{CATEGORY_SELECT} WHERE c.site_id = ?2 AND c.slug = ?3"),
+        rusqlite::params![viewer, site, slug],
+        category_from_row,
+    )
+}
+
+pub fn category_id(conn: &rusqlite::Connection, site: &str, slug: &str) -> rusqlite::Result<i64> {
+    conn.query_row(
+        "SELECT id FROM categories WHERE site_id = ?1 AND slug = ?2",
+        [site, slug],
+        |row| row.get(0),
+    )
+}
+
+/// One page of a category's threads, with the category's total.
+/// `has_unread` is filled in for `viewer`.
+/// Column threads are listed by under `sort`, newest first, ties broken by
+/// descending id.
+fn sort_key(sort: ThreadSort) -> &'static str {
+    match sort {
+        ThreadSort::Activity => "COALESCE(lr.created_at, t.created_at)",
+        ThreadSort::Newest => "t.created_at",
+    }
+}
+
+/// Position of a thread in a `sort` listing, as `<timestamp>,<id>`.
+fn thread_cursor(thread: &Thread, sort: ThreadSort) -> String {
+    let at = match sort {
+        ThreadSort::Activity => thread.last_reply_at.as_ref().unwrap_or(&thread.created_at),
+        ThreadSort::Newest => &thread.created_at,
+    };
+    format!("{at},{}", thread.id)
+}
+
+fn parse_cursor(cursor: &str) -> Option<(String, i64)> {
+    let (at, id) = cursor.rsplit_once(',')?;
+    Some((at.to_string(), id.parse().ok()?))
+}
+
+/// Set `has_unread` on listed threads for `viewer`.
+fn flag_unread(
+    conn: &rusqlite::Connection,
+    items: &mut [Thread],
+    viewer: Option<UserId>,
+    cat_id: i64,
+) -> rusqlite::Result<()> {
+    let Some(uid) = viewer else {
+        return Ok(());
+    };
+    let category_read = last_read(conn, "category_reads", "category_id", uid, cat_id)?;
+    for thread in items.iter_mut().filter(|t| t.moved_to.is_none()) {
+        let thread_read = last_read(conn, "thread_reads", "thread_id", uid, thread.id.0)?;
+        let seen = thread_read.max(category_read.clone());
+        let activity = thread.last_reply_at.as_ref().unwrap_or(&thread.created_at);
+        thread.has_unread = seen.is_none_or(|seen| *activity > seen);
+    }
+    Ok(())
+}
+
+pub fn query_threads(
+    conn: &rusqlite::Connection,
+    cat_id: i64,
+    viewer: Option<UserId>,
+    sort: ThreadSort,
+    page: i64,
+    per_page: i64,
+) -> rusqlite::Result<Paginated<Thread>> {
+    let total: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM threads WHERE category_id = ?1 AND status = 'published'",
+            [cat_id],
+            |row| row.get(0),
+        )
+        .unwrap_or(0);
+
+    let key = sort_key(sort);
+    let mut stmt = conn.prepare_cached(&format!(
+        "{THREAD_SELECT}
+         WHERE t.category_id = ?1 AND t.status = 'published'
+         ORDER BY t.pinned DESC, {key} DESC, t.id DESC
+         LIMIT ?2 OFFSET ?3"
+    ))?;
+    let mut items: Vec<Thread> = stmt
+        .query_map(
+            rusqlite::params![cat_id, per_page, (page - 1) * per_page],
+            thread_from_row,
+        )?
+        .filter_map(|r| r.ok())
+        .collect();
+    flag_unread(conn, &mut items, viewer, cat_id)?;
+
+    Ok(Paginated {
+        items,
+        total,
+        page,
+        per_page,
+    })
+}
+
+/// Like `query_threads`, but the page after `after` (a `thread_cursor`)
+/// instead of a page number. Pinned threads come on top of the first page
+/// and are left out of the cursor's order.
+pub fn query_threads_after(
+    conn: &rusqlite::Connection,
+    cat_id: i64,
+    viewer: Option<UserId>,
+    sort: ThreadSort,
+    after: Option<(String, i64)>,
+    per_page: i64,
+) -> rusqlite::Result<PaginatedCursor<Thread>> {
+    let key = sort_key(sort);
+    let (after_at, after_id) = match after {
+        Some((at, id)) => (Some(at), id),
+        None => (None, 0),
+    };
+    // One extra row tells whether there is a next page
+    let mut stmt = conn.prepare_cached(&format!(
+        "{THREAD_SELECT}
+         WHERE t.category_id = ?1 AND t.status = 'published' AND t.pinned = 0
+           AND (?2 IS NULL OR ({key}, t.id) < (?2, ?3))
+         ORDER BY {key} DESC, t.id DESC
+         LIMIT ?4"
+    ))?;
+    let mut items: Vec<Thread> = stmt
+        .query_map(
+            rusqlite::params![cat_id, after_at, after_id, per_page + 1],
+            thread_from_row,
+        )?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let next = if items.len() as i64 > per_page {
+        items.truncate(per_page as usize);
+        items.last().map(|t| thread_cursor(t, sort))
+    } else {
+        None
+    };
+    if after_at.is_none() {
+        let mut pinned = conn.prepare_cached(&format!(
+            "{THREAD_SELECT}
+             WHERE t.category_id = ?1 AND t.status = 'published' AND t.pinned = 1
+             ORDER BY {key} DESC, t.id DESC"
+        ))?;
+        let pinned: Vec<Thread> = pinned
+            .query_map([cat_id], thread_from_row)?
+            .filter_map(|r| r.ok())
+            .collect();
+        items.splice(0..0, pinned);
+    }
+    flag_unread(conn, &mut items, viewer, cat_id)?;
+
+    Ok(PaginatedCursor {
+        items,
+        next,
+        per_page,
+    })
 }
 
-// ── Response for thread detail ──
+pub fn query_thread(conn: &rusqlite::Connection, id: ThreadId) -> rusqlite::Result<Thread> {
+    let mut thread =
+        conn.query_row(&format!("{THREAD_SELECT} WHERE t.id = ?1"), [id], thread_from_row)?;
+    thread.attachments = attachments::load(conn, "thread", id.0)?;
+    Ok(thread)
+}
+
+pub fn query_user_threads(
+    conn: &rusqlite::Connection,
+    user_id: UserId,
+) -> rusqlite::Result<Vec<Thread>> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "{THREAD_SELECT} WHERE t.user_id = ?1 AND t.moved_to IS NULL ORDER BY t.created_at ASC"
+    ))?;
+    let rows = stmt
+        .query_map([user_id], thread_from_row)?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
 
-#[derive(Serialize)]
-pub struct ThreadDetail {
-    pub thread: Thread,
-    pub replies: Vec<Reply>,
+pub fn query_user_replies(
+    conn: &rusqlite::Connection,
+    user_id: UserId,
+) -> rusqlite::Result<Vec<Reply>> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "{REPLY_SELECT} WHERE r.user_id = ?1 ORDER BY r.created_at ASC"
+    ))?;
+    let rows = stmt
+        .query_map([user_id], reply_from_row)?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
+
+pub fn query_replies(
+    conn: &rusqlite::Connection,
+    thread_id: ThreadId,
+    sort: ReplySort,
+) -> rusqlite::Result<Vec<Reply>> {
+    let order_by = match sort {
+        ReplySort::Oldest => "r.created_at ASC, r.id ASC",
+        ReplySort::Newest => "r.created_at DESC, r.id DESC",
+        ReplySort::Top => "r.vote_count DESC, r.created_at ASC, r.id ASC",
+    };
+    let mut stmt = conn.prepare_cached(&format!(
+        "{REPLY_SELECT} WHERE r.thread_id = ?1 AND r.status = 'published' ORDER BY {order_by}"
+    ))?;
+    let mut rows: Vec<Reply> = stmt
+        .query_map([thread_id], reply_from_row)?
+        .filter_map(|r| r.ok())
+        .collect();
+    content::load_attachments(conn, &mut rows)?;
+    Ok(rows)
+}
+
+pub fn query_reply(conn: &rusqlite::Connection, id: ReplyId) -> rusqlite::Result<Reply> {
+    let mut reply =
+        conn.query_row(&format!("{REPLY_SELECT} WHERE r.id = ?1"), [id], reply_from_row)?;
+    reply.attachments = attachments::load(conn, Reply::TARGET_TYPE, id.0)?;
+    Ok(reply)
+}
+
+// ── Read tracking ──
+
+/// When `user_id` last read the thread or category `id`.
+fn last_read(
+    conn: &rusqlite::Connection,
+    table: &str,
+    column: &str,
+    user_id: UserId,
+    id: i64,
+) -> rusqlite::Result<Option<String>> {
+    conn.query_row(
+        &format!("SELECT last_read_at FROM {table} WHERE user_id = ?1 AND {column} = ?2"),
+        rusqlite::params![user_id, id],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// Record a visit to a thread or category, returning the previous one.
+fn mark_read(
+    conn: &rusqlite::Connection,
+    table: &str,
+    column: &str,
+    user_id: UserId,
+    id: i64,
+) -> rusqlite::Result<Option<String>> {
+    let previous = last_read(conn, table, column, user_id, id)?;
+    conn.execute(
+        &format!(
+            "INSERT INTO {table} (user_id, {column}, last_read_at) VALUES (?1, ?2, datetime('now'))
+             ON CONFLICT DO UPDATE SET last_read_at = excluded.last_read_at"
+        ),
+        rusqlite::params![user_id, id],
+    )?;
+    Ok(previous)
+}
+
+pub fn mark_thread_read(
+    conn: &rusqlite::Connection,
+    user_id: UserId,
+    thread_id: ThreadId,
+) -> rusqlite::Result<Option<String>> {
+    mark_read(conn, "thread_reads", "thread_id", user_id, thread_id.0)
 }
 
 // ── Handlers ──
@@ -29,174 +483,251 @@ pub struct ThreadDetail {
 /// GET /api/forum/categories
 pub async fn list_categories(
     State(state): State<AppState>,
+    headers: HeaderMap,
 ) -> Result<Json<Vec<ForumCategory>>, StatusCode> {
-    let pool = state.db.clone();
+    let viewer = auth::extract_user_id(&headers, &state.jwt_secret).ok();
+    let site = sites::resolve(&headers, &state.config.load())?;
+    let cache = state.config.load().cache.clone();
+    // Unread flags make signed-in responses personal
+    let cache_key = viewer.is_none().then(|| format!("categories:{site}"));
+    if let Some(cats) = cache_key.as_deref().and_then(|k| response_cache::get(&cache, k)) {
+        return Ok(Json(cats));
+    }
+    let pool = state.reader.clone();
 
     let cats = tokio::task::spawn_blocking(move || {
         let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-        let mut stmt = conn
-            .prepare("SELECT id, name, slug, description FROM categories ORDER BY id")
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        query_categories(&conn, &site, viewer).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
 
-        let rows = stmt
-            .query_map([], |row| {
-                Ok(ForumCategory {
-                    id: row.get(0)?,
-                    name: row.get(1)?,
-                    slug: row.get(2)?,
-                    description: row.get(3)?,
-                })
-            })
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-            .filter_map(|r| r.ok())
-            .collect::<Vec<_>>();
+    if let Some(key) = cache_key {
+        response_cache::put(&cache, key, cats.clone());
+    }
+    Ok(Json(cats))
+}
 
-        Ok::<_, StatusCode>(rows)
+/// GET /api/forum/categories/:slug
+pub async fn get_category(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(slug): Path<String>,
+) -> Result<Json<ForumCategory>, StatusCode> {
+    let viewer = auth::extract_user_id(&headers, &state.jwt_secret).ok();
+    let site = sites::resolve(&headers, &state.config.load())?;
+    let pool = state.reader.clone();
+
+    let cat = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        query_category(&conn, &site, &slug, viewer).map_err(|_| StatusCode::NOT_FOUND)
     })
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
 
-    Ok(Json(cats))
+    Ok(Json(cat))
 }
 
-/// GET /api/forum/threads?category=general&page=1
+/// GET /api/forum/threads?category=general&page=1&sort=activity|newest —
+/// `after=<cursor>` instead of `page` for a `PaginatedCursor`
 pub async fn list_threads(
     State(state): State<AppState>,
+    headers: HeaderMap,
     Query(params): Query<ThreadListParams>,
-) -> Result<Json<Paginated<Thread>>, StatusCode> {
-    let pool = state.db.clone();
+) -> Result<Response, StatusCode> {
+    let claims = auth::extract_claims(&headers, &state.jwt_secret).ok();
+    let viewer = claims.as_ref().map(|c| c.sub);
+    let site = sites::resolve(&headers, &state.config.load())?;
+    let cache = state.config.load().cache.clone();
+    // Signed-in views record the visit
+    let records_visit = claims.is_some_and(|c| c.records_activity());
+    let pool = if records_visit { state.db.clone() } else { state.reader.clone() };
     let cat_slug = params.category;
     let page = params.page.unwrap_or(1).max(1);
     let per_page: i64 = 20;
-    let offset = (page - 1) * per_page;
+    let after = match params.after.as_deref() {
+        None => None,
+        Some("") => Some(None),
+        Some(cursor) => Some(Some(parse_cursor(cursor).ok_or(StatusCode::BAD_REQUEST)?)),
+    };
 
-    let result = tokio::task::spawn_blocking(move || {
+    let cache_key = viewer.is_none().then(|| {
+        let position = match &params.after {
+            Some(after) => format!("after={after}"),
+            None => format!("page={page}"),
+        };
+        format!("threads:{site}:{cat_slug}:{}:{position}", params.sort.as_str())
+    });
+    if let Some(key) = cache_key.as_deref() {
+        if let Some(threads) = response_cache::get::<Paginated<Thread>>(&cache, key) {
+            return Ok(Json(threads).into_response());
+        }
+        if let Some(threads) = response_cache::get::<PaginatedCursor<Thread>>(&cache, key) {
+            return Ok(Json(threads).into_response());
+        }
+    }
+
+    tokio::task::spawn_blocking(move || {
         let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let cat_id = category_id(&conn, &site, &cat_slug).map_err(|_| StatusCode::NOT_FOUND)?;
+        let response = match after {
+            Some(after) => query_threads_after(&conn, cat_id, viewer, params.sort, after, per_page)
+                .map(|threads| response_cache::respond(&cache, cache_key, threads)),
+            None => query_threads(&conn, cat_id, viewer, params.sort, page, per_page)
+                .map(|threads| response_cache::respond(&cache, cache_key, threads)),
+        }
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        // Flags in this response still reflect the previous visit
+        if let Some(uid) = viewer.filter(|_| records_visit) {
+            mark_read(&conn, "category_reads", "category_id", uid, cat_id)
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        }
+        Ok::<_, StatusCode>(response)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+}
 
-        // Get category id
-        let cat_id: i64 = conn
-            .query_row(
-                "SELECT id FROM categories WHERE slug = ?1",
-                [&cat_slug],
-                |row| row.get(0),
-            )
-            .map_err(|_| StatusCode::NOT_FOUND)?;
+/// POST /api/forum/categories/:slug/subscribe — follow a category
+pub async fn subscribe_category(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(slug): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    set_subscription(state, headers, slug, true).await
+}
 
-        // Total count
-        let total: i64 = conn
-            .query_row(
-                "SELECT COUNT(*) FROM threads WHERE category_id = ?1",
-                [cat_id],
-                |row| row.get(0),
-            )
-            .unwrap_or(0);
-
-        // Threads
-        let mut stmt = conn
-            .prepare(
-                "SELECT t.id, t.category_id, t.title, t.body, t.created_at,
-                        u.id, u.username, u.avatar_url,
-                        (SELECT COUNT(*) FROM replies WHERE thread_id = t.id)
-                 FROM threads t
-                 JOIN users u ON t.user_id = u.id
-                 WHERE t.category_id = ?1
-                 ORDER BY t.created_at DESC
-                 LIMIT ?2 OFFSET ?3",
-            )
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+/// DELETE /api/forum/categories/:slug/subscribe — unfollow a category
+pub async fn unsubscribe_category(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(slug): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    set_subscription(state, headers, slug, false).await
+}
 
-        let threads = stmt
-            .query_map(rusqlite::params![cat_id, per_page, offset], |row| {
-                Ok(Thread {
-                    id: row.get(0)?,
-                    category_id: row.get(1)?,
-                    title: row.get(2)?,
-                    body: row.get(3)?,
-                    created_at: row.get(4)?,
-                    user: User {
-                        id: row.get(5)?,
-                        username: row.get(6)?,
-                        avatar_url: row.get(7)?,
-                    },
-                    reply_count: row.get(8)?,
-                })
-            })
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-            .filter_map(|r| r.ok())
-            .collect::<Vec<_>>();
+async fn set_subscription(
+    state: AppState,
+    headers: HeaderMap,
+    slug: String,
+    subscribed: bool,
+) -> Result<StatusCode, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+    let site = sites::resolve(&headers, &state.config.load())?;
 
-        Ok::<_, StatusCode>(Paginated {
-            items: threads,
-            total,
-            page,
-            per_page,
-        })
+    let pool = state.db.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let cat_id = category_id(&conn, &site, &slug).map_err(|_| StatusCode::NOT_FOUND)?;
+
+        let sql = if subscribed {
+            "INSERT OR IGNORE INTO category_subscriptions (user_id, category_id) VALUES (?1, ?2)"
+        } else {
+            "DELETE FROM category_subscriptions WHERE user_id = ?1 AND category_id = ?2"
+        };
+        conn.execute(sql, rusqlite::params![user_id, cat_id])
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok(StatusCode::NO_CONTENT)
     })
     .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
-
-    Ok(Json(result))
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
 }
 
 /// POST /api/forum/threads
 pub async fn create_thread(
     State(state): State<AppState>,
     headers: HeaderMap,
-    Json(payload): Json<CreateThread>,
-) -> Result<Json<Thread>, StatusCode> {
+    Extension(IpHash(ip_hash)): Extension<IpHash>,
+    ValidJson(payload): ValidJson<CreateThread>,
+) -> Result<Json<Thread>, PostError> {
     let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
-    let title = ammonia::clean(&payload.title);
-    let body = ammonia::clean(&payload.body);
+    let site = sites::resolve(&headers, &state.config.load())?;
+    let title = content::sanitize(&payload.title);
+    let body = content::sanitize(&payload.body);
 
     if title.trim().is_empty() || body.trim().is_empty() {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(StatusCode::BAD_REQUEST.into());
     }
+    captcha::require(&state, user_id, payload.captcha_token.as_deref()).await?;
 
     let pool = state.db.clone();
     let cat_slug = payload.category_slug;
+    let attachment_ids = payload.attachment_ids;
+    let (publish_at, pin) = (payload.publish_at, payload.pin);
+    let config = state.config.load_full();
 
     let thread = tokio::task::spawn_blocking(move || {
-        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let mut conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let tx = conn
+            .transaction()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        flood::check(&tx, &config.flood, user_id, &flood::Kind::Thread, &body)?;
+        auth::require_active(&tx, user_id)?;
+        if publish_at.is_some() || pin {
+            auth::require_moderator(&tx, user_id)?;
+        }
+        attachments::check_claimable(&tx, &site, user_id, &attachment_ids)?;
 
-        let cat_id: i64 = conn
+        let (cat_id, read_only): (i64, bool) = tx
             .query_row(
-                "SELECT id FROM categories WHERE slug = ?1",
-                [&cat_slug],
-                |row| row.get(0),
+                "SELECT id, read_only FROM categories WHERE site_id = ?1 AND slug = ?2",
+                [&site, &cat_slug],
+                |row| Ok((row.get(0)?, row.get(1)?)),
             )
             .map_err(|_| StatusCode::NOT_FOUND)?;
 
-        conn.execute(
-            "INSERT INTO threads (category_id, user_id, title, body) VALUES (?1, ?2, ?3, ?4)",
-            rusqlite::params![cat_id, user_id, title, body],
+        if read_only {
+            return Err(StatusCode::FORBIDDEN.into());
+        }
+        let mut status = word_filters::check(&tx, &[&title, &body])?;
+        // A time still to come holds the thread back for scheduled.rs
+        let publish_at = match publish_at {
+            Some(at) => scheduled::future_time(&tx, &at)?,
+            None => None,
+        };
+        if status == "published" && publish_at.is_some() {
+            status = "scheduled";
+        }
+
+        tx.execute(
+            "INSERT INTO threads
+                 (site_id, category_id, user_id, title, body, status, ip_hash, pinned, publish_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            rusqlite::params![
+                site,
+                cat_id,
+                user_id,
+                title,
+                body,
+                status,
+                ip_hash,
+                pin,
+                publish_at.filter(|_| status == "scheduled")
+            ],
         )
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-        let id = conn.last_insert_rowid();
+        let id = ThreadId(tx.last_insert_rowid());
+        attachments::attach(&tx, user_id, "thread", id.0, &attachment_ids)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        mark_thread_read(&tx, user_id, id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-        conn.query_row(
-            "SELECT t.id, t.category_id, t.title, t.body, t.created_at,
-                    u.id, u.username, u.avatar_url
-             FROM threads t JOIN users u ON t.user_id = u.id
-             WHERE t.id = ?1",
-            [id],
-            |row| {
-                Ok(Thread {
-                    id: row.get(0)?,
-                    category_id: row.get(1)?,
-                    title: row.get(2)?,
-                    body: row.get(3)?,
-                    created_at: row.get(4)?,
-                    user: User {
-                        id: row.get(5)?,
-                        username: row.get(6)?,
-                        avatar_url: row.get(7)?,
-                    },
-                    reply_count: 0,
-                })
-            },
-        )
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+        let mut thread =
+            query_thread(&tx, id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        thread.pending = status == "pending";
+        // Held threads are announced to no one, scheduled ones once they're out
+        if status == "published" {
+            notifications::announce(&tx, "thread", id.0)
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            webhooks::enqueue(&tx, &site, webhooks::THREAD_CREATED, &thread)
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            unfurl::enqueue(&tx, &config, &thread.body)
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        }
+
+        tx.commit().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        Ok::<_, PostError>(thread)
     })
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
@@ -204,79 +735,67 @@ pub async fn create_thread(
     Ok(Json(thread))
 }
 
-/// GET /api/forum/threads/:id
+/// GET /api/forum/threads/:id?sort=oldest|newest|top
 pub async fn get_thread(
     State(state): State<AppState>,
-    Path(id): Path<i64>,
+    headers: HeaderMap,
+    Path(id): Path<ThreadId>,
+    Query(params): Query<ThreadParams>,
 ) -> Result<Json<ThreadDetail>, StatusCode> {
-    let pool = state.db.clone();
+    let claims = auth::extract_claims(&headers, &state.jwt_secret).ok();
+    let viewer = claims.as_ref().map(|c| c.sub);
+    let config = state.config.load_full();
+    let site = sites::resolve(&headers, &config)?;
+    // Signed-in views record the visit
+    let records_visit = claims.is_some_and(|c| c.records_activity());
+    let pool = if records_visit { state.db.clone() } else { state.reader.clone() };
 
-    let detail = tokio::task::spawn_blocking(move || {
+    let mut detail = tokio::task::spawn_blocking(move || {
         let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-        let thread = conn
-            .query_row(
-                "SELECT t.id, t.category_id, t.title, t.body, t.created_at,
-                        u.id, u.username, u.avatar_url,
-                        (SELECT COUNT(*) FROM replies WHERE thread_id = t.id)
-                 FROM threads t JOIN users u ON t.user_id = u.id
-                 WHERE t.id = ?1",
-                [id],
-                |row| {
-                    Ok(Thread {
-                        id: row.get(0)?,
-                        category_id: row.get(1)?,
-                        title: row.get(2)?,
-                        body: row.get(3)?,
-                        created_at: row.get(4)?,
-                        user: User {
-                            id: row.get(5)?,
-                            username: row.get(6)?,
-                            avatar_url: row.get(7)?,
-                        },
-                        reply_count: row.get(8)?,
-                    })
-                },
-            )
-            .map_err(|_| StatusCode::NOT_FOUND)?;
-
-        let mut stmt = conn
-            .prepare(
-                "SELECT r.id, r.thread_id, r.body, r.created_at,
-                        u.id, u.username, u.avatar_url,
-                        COALESCE((SELECT SUM(value) FROM votes
-                                  WHERE target_type = 'reply' AND target_id = r.id), 0)
-                 FROM replies r
-                 JOIN users u ON r.user_id = u.id
-                 WHERE r.thread_id = ?1
-                 ORDER BY r.created_at ASC",
-            )
+        let visible = sites::owns(&conn, &site, "thread", id.0)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            && moderation::is_published(&conn, "thread", id.0);
+        if !visible {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        let mut thread = query_thread(&conn, id).map_err(|_| StatusCode::NOT_FOUND)?;
+        let mut replies = query_replies(&conn, id, params.sort)
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let users =
+            std::iter::once(&mut thread.user).chain(replies.iter_mut().map(|r| &mut r.user));
+        badges::apply(&conn, &config, users).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if let Some(uid) = viewer {
+            blocks::hide(&conn, uid, &mut replies)
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        }
 
-        let replies = stmt
-            .query_map([id], |row| {
-                Ok(Reply {
-                    id: row.get(0)?,
-                    thread_id: row.get(1)?,
-                    body: row.get(2)?,
-                    created_at: row.get(3)?,
-                    user: User {
-                        id: row.get(4)?,
-                        username: row.get(5)?,
-                        avatar_url: row.get(6)?,
-                    },
-                    vote_count: row.get(7)?,
-                })
-            })
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-            .filter_map(|r| r.ok())
-            .collect::<Vec<_>>();
+        let flags =
+            ThreadFlags::for_thread(&conn, id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let can_mark_solution = flags.can_vote()
+            && viewer.is_some_and(|uid| may_mark_solution(&conn, uid, &thread).is_ok());
+        let last_read_at = match viewer {
+            Some(uid) if records_visit => mark_thread_read(&conn, uid, id)
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+            Some(uid) => last_read(&conn, "thread_reads", "thread_id", uid, id.0)
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+            None => None,
+        };
 
-        Ok::<_, StatusCode>(ThreadDetail { thread, replies })
+        Ok::<_, StatusCode>(ThreadDetail {
+            thread,
+            replies,
+            can_reply: flags.can_reply(),
+            can_vote: flags.can_vote(),
+            disabled_reason: flags.disabled_reason(),
+            can_mark_solution,
+            last_read_at,
+        })
     })
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
 
+    authors::mark(&state.config.load(), &mut detail.replies);
     Ok(Json(detail))
 }
 
@@ -284,61 +803,394 @@ pub async fn get_thread(
 pub async fn create_reply(
     State(state): State<AppState>,
     headers: HeaderMap,
-    Path(thread_id): Path<i64>,
-    Json(payload): Json<CreateReply>,
-) -> Result<Json<Reply>, StatusCode> {
+    Extension(IpHash(ip_hash)): Extension<IpHash>,
+    Path(thread_id): Path<ThreadId>,
+    ValidJson(payload): ValidJson<CreateReply>,
+) -> Result<Json<Reply>, PostError> {
     let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
-    let body = ammonia::clean(&payload.body);
+    let site = sites::resolve(&headers, &state.config.load())?;
+    let body = content::sanitize(&payload.body);
 
     if body.trim().is_empty() {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(StatusCode::BAD_REQUEST.into());
+    }
+    let kind = flood::Kind::Reply { thread_id };
+    let repeat = flood::is_repeat(&state, user_id, &kind, &body).await?;
+    if !repeat {
+        captcha::require(&state, user_id, payload.captcha_token.as_deref()).await?;
     }
 
     let pool = state.db.clone();
+    let attachment_ids = payload.attachment_ids;
+    let config = state.config.load_full();
 
     let reply = tokio::task::spawn_blocking(move || {
-        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let mut conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let tx = conn
+            .transaction()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if let Some(earlier) = flood::check(&tx, &config.flood, user_id, &kind, &body)? {
+            return Ok(content::posted(&tx, &config, earlier.id, earlier.pending)?);
+        }
+        // What it repeated went away since, and it skipped the captcha
+        if repeat {
+            return Err(StatusCode::CONFLICT.into());
+        }
+        auth::require_active(&tx, user_id)?;
+        attachments::check_claimable(&tx, &site, user_id, &attachment_ids)?;
 
-        // Verify thread exists
-        let _: i64 = conn
-            .query_row("SELECT id FROM threads WHERE id = ?1", [thread_id], |row| {
-                row.get(0)
-            })
-            .map_err(|_| StatusCode::NOT_FOUND)?;
+        // Verify thread exists on this site and accepts replies
+        if !sites::owns(&tx, &site, "thread", thread_id.0)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        {
+            return Err(StatusCode::NOT_FOUND.into());
+        }
+        let flags =
+            ThreadFlags::for_thread(&tx, thread_id).map_err(|_| StatusCode::NOT_FOUND)?;
+        if !flags.can_reply() {
+            return Err(StatusCode::FORBIDDEN.into());
+        }
+        let status = word_filters::check(&tx, &[&body])?;
 
-        conn.execute(
-            "INSERT INTO replies (thread_id, user_id, body) VALUES (?1, ?2, ?3)",
-            rusqlite::params![thread_id, user_id, body],
+        tx.execute(
+            "INSERT INTO replies (thread_id, user_id, body, status, ip_hash)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![thread_id, user_id, body, status, ip_hash],
         )
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-        let id = conn.last_insert_rowid();
+        let id = tx.last_insert_rowid();
+        attachments::attach(&tx, user_id, "reply", id, &attachment_ids)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        // Your own reply isn't news to you
+        mark_thread_read(&tx, user_id, thread_id)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        let reply: Reply = content::posted(&tx, &config, id, status == "pending")?;
+        if !reply.pending {
+            notifications::announce(&tx, "reply", id)
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            webhooks::enqueue(&tx, &site, webhooks::REPLY_CREATED, &reply)
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            unfurl::enqueue(&tx, &config, &reply.body)
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        }
 
-        conn.query_row(
-            "SELECT r.id, r.thread_id, r.body, r.created_at,
-                    u.id, u.username, u.avatar_url
-             FROM replies r JOIN users u ON r.user_id = u.id
-             WHERE r.id = ?1",
-            [id],
-            |row| {
-                Ok(Reply {
-                    id: row.get(0)?,
-                    thread_id: row.get(1)?,
-                    body: row.get(2)?,
-                    created_at: row.get(3)?,
-                    user: User {
-                        id: row.get(4)?,
-                        username: row.get(5)?,
-                        avatar_url: row.get(6)?,
-                    },
-                    vote_count: 0,
-                })
-            },
-        )
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+        tx.commit().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        Ok::<_, PostError>(reply)
     })
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
 
     Ok(Json(reply))
 }
+
+/// DELETE /api/forum/threads/:id — delete your own thread
+pub async fn delete_thread(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(thread_id): Path<ThreadId>,
+) -> Result<StatusCode, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+    let site = sites::resolve(&headers, &state.config.load())?;
+
+    let pool = state.db.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let tx = conn
+            .transaction()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        content::soft_delete(&tx, &site, user_id, "thread", thread_id.0)?;
+        audit::record(
+            &tx,
+            user_id,
+            "thread.delete",
+            "thread",
+            thread_id.0,
+            serde_json::json!({ "author_id": user_id }),
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        tx.commit().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        Ok(StatusCode::NO_CONTENT)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+}
+
+/// DELETE /api/forum/replies/:id — delete your own reply
+pub async fn delete_reply(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(reply_id): Path<ReplyId>,
+) -> Result<StatusCode, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+    let site = sites::resolve(&headers, &state.config.load())?;
+
+    let pool = state.db.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let tx = conn
+            .transaction()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        content::soft_delete(&tx, &site, user_id, "reply", reply_id.0)?;
+        let thread_id: ThreadId = tx
+            .query_row(
+                "SELECT thread_id FROM replies WHERE id = ?1",
+                [reply_id],
+                |row| row.get(0),
+            )
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        audit::record(
+            &tx,
+            user_id,
+            "reply.delete",
+            "reply",
+            reply_id.0,
+            serde_json::json!({ "thread_id": thread_id, "author_id": user_id }),
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        tx.commit().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        Ok(StatusCode::NO_CONTENT)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+}
+
+/// The thread's author and moderators may pick its solution.
+fn may_mark_solution(
+    conn: &rusqlite::Connection,
+    user_id: UserId,
+    thread: &Thread,
+) -> Result<(), StatusCode> {
+    auth::require_active(conn, user_id)?;
+    if thread.user.id == user_id {
+        return Ok(());
+    }
+    auth::require_moderator(conn, user_id)
+}
+
+/// POST /api/forum/threads/:id/solution — accept a reply as the answer
+pub async fn set_solution(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(thread_id): Path<ThreadId>,
+    Json(payload): Json<SetSolution>,
+) -> Result<Json<Thread>, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+    let site = sites::resolve(&headers, &state.config.load())?;
+
+    let pool = state.db.clone();
+    let thread = tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        let visible = sites::owns(&conn, &site, "thread", thread_id.0)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            && moderation::is_published(&conn, "thread", thread_id.0);
+        if !visible {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        let thread =
+            query_thread(&conn, thread_id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        may_mark_solution(&conn, user_id, &thread)?;
+
+        // Archived threads are frozen; locked ones can still be marked solved
+        let flags = ThreadFlags::for_thread(&conn, thread_id)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if !flags.can_vote() {
+            return Err(StatusCode::FORBIDDEN);
+        }
+
+        if let Some(reply_id) = payload.reply_id {
+            let in_thread = conn
+                .query_row(
+                    "SELECT COUNT(*) FROM replies
+                     WHERE id = ?1 AND thread_id = ?2 AND status = 'published'",
+                    rusqlite::params![reply_id, thread_id],
+                    |row| row.get::<_, i64>(0),
+                )
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            if in_thread == 0 {
+                return Err(StatusCode::BAD_REQUEST);
+            }
+        }
+
+        let tx = conn
+            .transaction()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        tx.execute(
+            "UPDATE threads SET solution_reply_id = ?1 WHERE id = ?2",
+            rusqlite::params![payload.reply_id, thread_id],
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        // Authors managing their own threads aren't moderation
+        if thread.user.id != user_id {
+            audit::record(
+                &tx,
+                user_id,
+                "thread.solution",
+                "thread",
+                thread_id.0,
+                serde_json::json!({
+                    "reply_id": payload.reply_id,
+                    "previous": thread.solution_reply_id,
+                }),
+            )
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        }
+        tx.commit().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        query_thread(&conn, thread_id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(thread))
+}
+
+/// POST /api/forum/threads/:id/pin — list a thread ahead of its category's others (moderators)
+pub async fn pin_thread(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(thread_id): Path<ThreadId>,
+) -> Result<Json<Thread>, StatusCode> {
+    set_pinned(state, headers, thread_id, true).await
+}
+
+/// DELETE /api/forum/threads/:id/pin — unpin a thread (moderators)
+pub async fn unpin_thread(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(thread_id): Path<ThreadId>,
+) -> Result<Json<Thread>, StatusCode> {
+    set_pinned(state, headers, thread_id, false).await
+}
+
+async fn set_pinned(
+    state: AppState,
+    headers: HeaderMap,
+    thread_id: ThreadId,
+    pinned: bool,
+) -> Result<Json<Thread>, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+    let site = sites::resolve(&headers, &state.config.load())?;
+
+    let pool = state.db.clone();
+    let thread = tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_moderator(&conn, user_id)?;
+
+        if !sites::owns(&conn, &site, "thread", thread_id.0)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        let thread = query_thread(&conn, thread_id).map_err(|_| StatusCode::NOT_FOUND)?;
+        if thread.moved_to.is_some() {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        if thread.pinned == pinned {
+            return Ok(thread);
+        }
+
+        let tx = conn
+            .transaction()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        tx.execute(
+            "UPDATE threads SET pinned = ?1 WHERE id = ?2",
+            rusqlite::params![pinned, thread_id],
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let action = if pinned { "thread.pin" } else { "thread.unpin" };
+        audit::record(&tx, user_id, action, "thread", thread_id.0, serde_json::json!({}))
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        tx.commit().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        query_thread(&conn, thread_id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(thread))
+}
+
+/// POST /api/forum/threads/:id/move — move a thread to another category (moderators)
+pub async fn move_thread(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(thread_id): Path<ThreadId>,
+    Json(payload): Json<MoveThread>,
+) -> Result<Json<Thread>, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+    let site = sites::resolve(&headers, &state.config.load())?;
+
+    let pool = state.db.clone();
+    let thread = tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_moderator(&conn, user_id)?;
+
+        if !sites::owns(&conn, &site, "thread", thread_id.0)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        let thread = query_thread(&conn, thread_id).map_err(|_| StatusCode::NOT_FOUND)?;
+        if thread.moved_to.is_some() {
+            // Stubs only point elsewhere; move the thread itself
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        let to = category_id(&conn, &site, &payload.category_slug)
+            .map_err(|_| StatusCode::NOT_FOUND)?;
+        let from = thread.category_id;
+        if to == from {
+            return Ok(thread);
+        }
+
+        let tx = conn
+            .transaction()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        tx.execute(
+            "UPDATE threads SET category_id = ?1 WHERE id = ?2",
+            rusqlite::params![to, thread_id],
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        // A stub from an earlier move out of the new category is obsolete
+        tx.execute(
+            "DELETE FROM threads WHERE moved_to = ?1 AND category_id = ?2",
+            rusqlite::params![thread_id, to],
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if payload.leave_stub {
+            tx.execute(
+                "INSERT INTO threads (site_id, category_id, user_id, title, body, moved_to)
+                 SELECT site_id, ?1, user_id, title, '', id FROM threads WHERE id = ?2",
+                rusqlite::params![from, thread_id],
+            )
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        }
+        audit::record(
+            &tx,
+            user_id,
+            "thread.move",
+            "thread",
+            thread_id.0,
+            serde_json::json!({
+                "from_category_id": from,
+                "to_category_id": to,
+                "stub": payload.leave_stub,
+            }),
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        tx.commit().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        query_thread(&conn, thread_id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(thread))
+}
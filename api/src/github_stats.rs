@@ -1,18 +1,24 @@
-use axum::extract::Query;
+use axum::extract::{Query, State};
 use axum::http::StatusCode;
 use axum::Json;
-use mikaana_shared::GitHubStats;
+use mikaana_shared::{GitHubStats, LanguageStats};
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::sync::LazyLock;
 use tokio::sync::RwLock;
 
+use crate::{config::GitHubStatsConfig, request_id, AppState, DbPool};
+
 #[derive(Debug, Clone)]
 struct CachedStats {
     stats: GitHubStats,
     fetched_at: std::time::Instant,
 }
 
-static CACHE: LazyLock<RwLock<Option<CachedStats>>> = LazyLock::new(|| RwLock::new(None));
+/// Keyed by repo. Mirrored to the `stats_cache` table so restarts don't
+/// refetch everything at once and hit GitHub's rate limit.
+static CACHE: LazyLock<RwLock<HashMap<String, CachedStats>>> =
+    LazyLock::new(|| RwLock::new(HashMap::new()));
 
 const CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(3600);
 
@@ -29,35 +35,123 @@ struct RepoInfo {
     pushed_at: String,
 }
 
+/// Load persisted stats into the memory cache, keeping their age.
+pub async fn warm_cache(pool: &DbPool) {
+    let pool = pool.clone();
+    let rows = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|e| e.to_string())?;
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT key, data, strftime('%s', 'now') - strftime('%s', fetched_at)
+                 FROM stats_cache",
+            )
+            .map_err(|e| e.to_string())?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, i64>(2)?))
+            })
+            .map_err(|e| e.to_string())?
+            .filter_map(|r| r.ok())
+            .collect::<Vec<_>>();
+        Ok::<_, String>(rows)
+    })
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|r| r);
+
+    let rows = match rows {
+        Ok(rows) => rows,
+        Err(e) => {
+            eprintln!("Could not load cached GitHub stats: {e}");
+            return;
+        }
+    };
+
+    let now = std::time::Instant::now();
+    let mut cache = CACHE.write().await;
+    for (repo, data, age) in rows {
+        let Ok(stats) = serde_json::from_str(&data) else {
+            continue;
+        };
+        // Entries older than the process can't be represented; treat as stale
+        let Some(fetched_at) = now.checked_sub(std::time::Duration::from_secs(age.max(0) as u64))
+        else {
+            continue;
+        };
+        cache.insert(repo, CachedStats { stats, fetched_at });
+    }
+}
+
+fn persist(pool: &DbPool, repo: &str, stats: &GitHubStats) -> Result<(), String> {
+    let conn = pool.get().map_err(|e| e.to_string())?;
+    let data = serde_json::to_string(stats).map_err(|e| e.to_string())?;
+    conn.execute(
+        "INSERT INTO stats_cache (key, data, fetched_at) VALUES (?1, ?2, datetime('now'))
+         ON CONFLICT(key) DO UPDATE SET data = excluded.data, fetched_at = excluded.fetched_at",
+        [repo, &data],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Fill in line counts from byte counts. Done per response rather than per
+/// fetch so config reloads apply to cached stats too.
+fn estimate_lines(mut stats: GitHubStats, config: &GitHubStatsConfig) -> GitHubStats {
+    // Stats cached before per-language counts existed keep their estimate
+    if stats.languages.is_empty() {
+        return stats;
+    }
+    for language in &mut stats.languages {
+        language.lines = language.bytes / config.bytes_per_line(&language.name);
+    }
+    stats.lines_of_code = stats.languages.iter().map(|l| l.lines).sum();
+    stats
+}
+
 pub async fn get_github_stats(
+    State(state): State<AppState>,
     Query(query): Query<StatsQuery>,
 ) -> Result<Json<GitHubStats>, StatusCode> {
+    let config = state.config.load().github_stats.clone();
+
     // Check cache
     {
         let cache = CACHE.read().await;
-        if let Some(ref cached) = *cache {
+        if let Some(cached) = cache.get(&query.repo) {
             if cached.fetched_at.elapsed() < CACHE_TTL {
-                return Ok(Json(cached.stats.clone()));
+                return Ok(Json(estimate_lines(cached.stats.clone(), &config)));
             }
         }
     }
 
     // Fetch fresh data
     let stats = fetch_stats(&query.repo).await.map_err(|e| {
-        eprintln!("GitHub API error: {e}");
+        request_id::log(format_args!("GitHub API error: {e}"));
         StatusCode::BAD_GATEWAY
     })?;
 
     // Update cache
     {
         let mut cache = CACHE.write().await;
-        *cache = Some(CachedStats {
-            stats: stats.clone(),
-            fetched_at: std::time::Instant::now(),
-        });
+        cache.insert(
+            query.repo.clone(),
+            CachedStats {
+                stats: stats.clone(),
+                fetched_at: std::time::Instant::now(),
+            },
+        );
     }
 
-    Ok(Json(stats))
+    // Persisting is best effort; the memory cache already has the stats
+    let pool = state.db.clone();
+    let (repo, saved) = (query.repo, stats.clone());
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = persist(&pool, &repo, &saved) {
+            eprintln!("Could not persist GitHub stats: {e}");
+        }
+    });
+
+    Ok(Json(estimate_lines(stats, &config)))
 }
 
 async fn fetch_stats(repo: &str) -> Result<GitHubStats, String> {
@@ -88,8 +182,15 @@ async fn fetch_stats(repo: &str) -> Result<GitHubStats, String> {
         .await
         .map_err(|e| e.to_string())?;
 
-    let rust_bytes = languages.get("Rust").copied().unwrap_or(0);
-    let lines_of_code = rust_bytes / 53; // ~53 bytes per line of Rust (measured against actual LOC)
+    let mut languages: Vec<LanguageStats> = languages
+        .into_iter()
+        .map(|(name, bytes)| LanguageStats {
+            name,
+            bytes,
+            lines: 0,
+        })
+        .collect();
+    languages.sort_by_key(|l| std::cmp::Reverse(l.bytes));
 
     // Get commit count from Link header
     let commits_resp = client
@@ -126,12 +227,13 @@ async fn fetch_stats(repo: &str) -> Result<GitHubStats, String> {
 
     Ok(GitHubStats {
         commits,
-        lines_of_code,
+        lines_of_code: 0,
         crate_count,
         stars: repo_info.stargazers_count,
         forks: repo_info.forks_count,
         open_issues: repo_info.open_issues_count,
         last_push: repo_info.pushed_at,
+        languages,
     })
 }
 
@@ -0,0 +1,448 @@
+use async_graphql::{
+    http::GraphiQLSource, Context, EmptyMutation, EmptySubscription, Enum, Object, SimpleObject,
+};
+use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use axum::{
+    extract::State,
+    http::HeaderMap,
+    response::{Html, IntoResponse},
+};
+use mikaana_shared::{
+    Comment, CommentId, Features, ForumCategory, Reply, ReplyId, ReplySort, Thread, ThreadId,
+    ThreadSort, User, UserId,
+};
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::{auth, blocks, comments, forum, moderation, sites, votes, AppState, DbPool};
+
+pub type Schema = async_graphql::Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// Threads per page, matching the REST listing.
+const PER_PAGE: i64 = 20;
+
+pub fn build_schema(pool: DbPool, features: Features) -> Schema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(pool)
+        .data(features)
+        .limit_depth(8)
+        .limit_complexity(500)
+        .finish()
+}
+
+/// The authenticated user for this request, if any.
+struct Viewer(Option<UserId>);
+
+/// The site this request is for; see `sites::resolve`.
+struct Site(String);
+
+/// Run a query on a pooled connection off the async runtime.
+async fn with_conn<T, F>(ctx: &Context<'_>, f: F) -> async_graphql::Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&Connection) -> rusqlite::Result<T> + Send + 'static,
+{
+    let pool = ctx.data::<DbPool>()?.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|e| e.to_string())?;
+        f(&conn).map_err(|e| e.to_string())
+    })
+    .await?;
+    Ok(result?)
+}
+
+/// Refuse a field of a subsystem this instance doesn't serve, as its REST
+/// routes would.
+fn require_feature(
+    ctx: &Context<'_>,
+    name: &str,
+    enabled: fn(&Features) -> bool,
+) -> async_graphql::Result<()> {
+    if ctx.data::<Features>().is_ok_and(enabled) {
+        Ok(())
+    } else {
+        Err(format!("The {name} feature is disabled on this server").into())
+    }
+}
+
+fn viewer(ctx: &Context<'_>) -> Option<UserId> {
+    ctx.data::<Viewer>().ok().and_then(|v| v.0)
+}
+
+fn site(ctx: &Context<'_>) -> String {
+    ctx.data::<Site>()
+        .map(|s| s.0.clone())
+        .unwrap_or_else(|_| sites::DEFAULT_SITE.to_string())
+}
+
+async fn viewer_vote(
+    ctx: &Context<'_>,
+    target_type: &'static str,
+    target_id: i64,
+) -> async_graphql::Result<Option<i32>> {
+    let Some(user_id) = viewer(ctx) else {
+        return Ok(None);
+    };
+    let site = site(ctx);
+    with_conn(ctx, move |conn| {
+        Ok(votes::user_vote(conn, &site, user_id, target_type, target_id))
+    })
+    .await
+}
+
+// ── Handlers ──
+
+/// POST /api/graphql
+pub async fn graphql_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    req: GraphQLRequest,
+) -> GraphQLResponse {
+    let viewer = auth::extract_user_id(&headers, &state.jwt_secret).ok();
+    let site = match sites::resolve(&headers, &state.config.load()) {
+        Ok(site) => site,
+        Err(_) => {
+            let error = async_graphql::ServerError::new("Unknown site key", None);
+            return async_graphql::Response::from_errors(vec![error]).into();
+        }
+    };
+    state
+        .graphql
+        .execute(req.into_inner().data(Viewer(viewer)).data(Site(site)))
+        .await
+        .into()
+}
+
+/// GET /api/graphql — GraphiQL explorer
+pub async fn graphiql() -> impl IntoResponse {
+    Html(GraphiQLSource::build().endpoint("/api/graphql").finish())
+}
+
+// ── Schema ──
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+#[graphql(remote = "mikaana_shared::ReplySort")]
+enum GqlReplySort {
+    Oldest,
+    Newest,
+    Top,
+}
+
+#[derive(Enum, Clone, Copy, PartialEq, Eq)]
+#[graphql(remote = "mikaana_shared::ThreadSort")]
+enum GqlThreadSort {
+    Activity,
+    Newest,
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Comments on a blog post, oldest first.
+    async fn comments(&self, ctx: &Context<'_>, slug: String) -> async_graphql::Result<Vec<GqlComment>> {
+        let (viewer, site) = (viewer(ctx), site(ctx));
+        let rows = with_conn(ctx, move |conn| {
+            let mut rows = comments::query_comments(conn, &site, &slug)?;
+            if let Some(uid) = viewer {
+                blocks::hide(conn, uid, &mut rows)?;
+            }
+            Ok(rows)
+        })
+        .await?;
+        Ok(rows.into_iter().map(GqlComment).collect())
+    }
+
+    async fn categories(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<GqlCategory>> {
+        require_feature(ctx, "forum", |f| f.forum)?;
+        let (viewer, site) = (viewer(ctx), site(ctx));
+        let rows =
+            with_conn(ctx, move |conn| forum::query_categories(conn, &site, viewer)).await?;
+        Ok(rows.into_iter().map(GqlCategory).collect())
+    }
+
+    async fn category(&self, ctx: &Context<'_>, slug: String) -> async_graphql::Result<Option<GqlCategory>> {
+        require_feature(ctx, "forum", |f| f.forum)?;
+        let (viewer, site) = (viewer(ctx), site(ctx));
+        let cat = with_conn(ctx, move |conn| {
+            forum::query_category(conn, &site, &slug, viewer).optional()
+        })
+        .await?;
+        Ok(cat.map(GqlCategory))
+    }
+
+    async fn thread(&self, ctx: &Context<'_>, id: ThreadId) -> async_graphql::Result<Option<GqlThread>> {
+        require_feature(ctx, "forum", |f| f.forum)?;
+        let (viewer, site) = (viewer(ctx), site(ctx));
+        let thread = with_conn(ctx, move |conn| {
+            if !sites::owns(conn, &site, "thread", id.0)?
+                || !moderation::is_visible_to(conn, "thread", id.0, viewer)
+            {
+                return Ok(None);
+            }
+            forum::query_thread(conn, id).optional()
+        })
+        .await?;
+        Ok(thread.map(GqlThread))
+    }
+
+    async fn user(&self, ctx: &Context<'_>, id: UserId) -> async_graphql::Result<Option<GqlUser>> {
+        let user = with_conn(ctx, move |conn| auth::query_user(conn, id).optional()).await?;
+        Ok(user.map(GqlUser))
+    }
+
+    /// The authenticated user, or null for anonymous requests.
+    async fn me(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<GqlUser>> {
+        let Some(id) = viewer(ctx) else {
+            return Ok(None);
+        };
+        self.user(ctx, id).await
+    }
+
+    /// Vote tally for a blog post as a whole.
+    async fn post_votes(&self, ctx: &Context<'_>, slug: String) -> async_graphql::Result<VoteTally> {
+        require_feature(ctx, "votes", |f| f.votes)?;
+        let (user_id, site) = (viewer(ctx), site(ctx));
+        with_conn(ctx, move |conn| {
+            let Some(id) = votes::post_target(conn, &site, &slug) else {
+                return Ok(VoteTally {
+                    count: 0,
+                    viewer_vote: None,
+                });
+            };
+            Ok(VoteTally {
+                count: votes::vote_count(conn, &site, "post", id),
+                viewer_vote: user_id.and_then(|uid| votes::user_vote(conn, &site, uid, "post", id)),
+            })
+        })
+        .await
+    }
+
+    /// Vote tally for any votable target (`comment`, `thread`, `reply`).
+    async fn votes(
+        &self,
+        ctx: &Context<'_>,
+        target_type: String,
+        target_id: i64,
+    ) -> async_graphql::Result<VoteTally> {
+        require_feature(ctx, "votes", |f| f.votes)?;
+        let (user_id, site) = (viewer(ctx), site(ctx));
+        with_conn(ctx, move |conn| {
+            Ok(VoteTally {
+                count: votes::vote_count(conn, &site, &target_type, target_id),
+                viewer_vote: user_id
+                    .and_then(|uid| votes::user_vote(conn, &site, uid, &target_type, target_id)),
+            })
+        })
+        .await
+    }
+}
+
+#[derive(SimpleObject)]
+struct VoteTally {
+    count: i64,
+    /// The viewer's own vote (+1/-1), if any.
+    viewer_vote: Option<i32>,
+}
+
+struct GqlUser(User);
+
+#[Object(name = "User")]
+impl GqlUser {
+    async fn id(&self) -> UserId {
+        self.0.id
+    }
+    async fn username(&self) -> &str {
+        &self.0.username
+    }
+    async fn display_name(&self) -> Option<&str> {
+        self.0.display_name.as_deref()
+    }
+    async fn avatar_url(&self) -> &str {
+        &self.0.avatar_url
+    }
+}
+
+struct GqlComment(Comment);
+
+#[Object(name = "Comment")]
+impl GqlComment {
+    async fn id(&self) -> CommentId {
+        self.0.id
+    }
+    async fn post_slug(&self) -> &str {
+        &self.0.post_slug
+    }
+    async fn author(&self) -> GqlUser {
+        GqlUser(self.0.user.clone())
+    }
+    async fn body(&self) -> &str {
+        &self.0.body
+    }
+    async fn body_length(&self) -> usize {
+        self.0.body_length
+    }
+    async fn created_at(&self) -> &str {
+        &self.0.created_at
+    }
+    async fn vote_count(&self) -> i64 {
+        self.0.vote_count
+    }
+    /// By a user the viewer blocked; the body is left out.
+    async fn blocked(&self) -> bool {
+        self.0.blocked
+    }
+    async fn viewer_vote(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<i32>> {
+        viewer_vote(ctx, "comment", self.0.id.0).await
+    }
+}
+
+struct GqlCategory(ForumCategory);
+
+#[Object(name = "Category")]
+impl GqlCategory {
+    async fn id(&self) -> i64 {
+        self.0.id
+    }
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+    async fn slug(&self) -> &str {
+        &self.0.slug
+    }
+    async fn description(&self) -> &str {
+        &self.0.description
+    }
+    /// New threads are closed; existing ones stay readable.
+    async fn read_only(&self) -> bool {
+        self.0.read_only
+    }
+    /// Whether the viewer follows this category.
+    async fn subscribed(&self) -> bool {
+        self.0.subscribed
+    }
+    /// Threads in this category, most recently active first by default.
+    async fn threads(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 1)] page: i64,
+        #[graphql(default_with = "GqlThreadSort::Activity")] sort: GqlThreadSort,
+    ) -> async_graphql::Result<ThreadPage> {
+        let cat_id = self.0.id;
+        let page = page.max(1);
+        let sort = ThreadSort::from(sort);
+        let viewer = viewer(ctx);
+        let result = with_conn(ctx, move |conn| {
+            forum::query_threads(conn, cat_id, viewer, sort, page, PER_PAGE)
+        })
+        .await?;
+        Ok(ThreadPage {
+            items: result.items.into_iter().map(GqlThread).collect(),
+            total: result.total,
+            page: result.page,
+            per_page: result.per_page,
+        })
+    }
+}
+
+#[derive(SimpleObject)]
+struct ThreadPage {
+    items: Vec<GqlThread>,
+    total: i64,
+    page: i64,
+    per_page: i64,
+}
+
+struct GqlThread(Thread);
+
+#[Object(name = "Thread")]
+impl GqlThread {
+    async fn id(&self) -> ThreadId {
+        self.0.id
+    }
+    async fn category_id(&self) -> i64 {
+        self.0.category_id
+    }
+    async fn author(&self) -> GqlUser {
+        GqlUser(self.0.user.clone())
+    }
+    async fn title(&self) -> &str {
+        &self.0.title
+    }
+    async fn body(&self) -> &str {
+        &self.0.body
+    }
+    async fn created_at(&self) -> &str {
+        &self.0.created_at
+    }
+    async fn reply_count(&self) -> i64 {
+        self.0.reply_count
+    }
+    async fn solution_reply_id(&self) -> Option<i64> {
+        self.0.solution_reply_id.map(|id| id.0)
+    }
+    /// New activity since the viewer last opened the thread or its category.
+    async fn has_unread(&self) -> bool {
+        self.0.has_unread
+    }
+    /// Set on stubs left behind by a category move.
+    async fn moved_to(&self) -> Option<i64> {
+        self.0.moved_to.map(|id| id.0)
+    }
+    async fn last_reply_at(&self) -> Option<&str> {
+        self.0.last_reply_at.as_deref()
+    }
+    async fn last_reply_author(&self) -> Option<GqlUser> {
+        self.0.last_reply_user.clone().map(GqlUser)
+    }
+    async fn replies(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default_with = "GqlReplySort::Oldest")] sort: GqlReplySort,
+    ) -> async_graphql::Result<Vec<GqlReply>> {
+        let (thread_id, viewer) = (self.0.id, viewer(ctx));
+        let sort = ReplySort::from(sort);
+        let rows = with_conn(ctx, move |conn| {
+            let mut rows = forum::query_replies(conn, thread_id, sort)?;
+            if let Some(uid) = viewer {
+                blocks::hide(conn, uid, &mut rows)?;
+            }
+            Ok(rows)
+        })
+        .await?;
+        Ok(rows.into_iter().map(GqlReply).collect())
+    }
+}
+
+struct GqlReply(Reply);
+
+#[Object(name = "Reply")]
+impl GqlReply {
+    async fn id(&self) -> ReplyId {
+        self.0.id
+    }
+    async fn thread_id(&self) -> ThreadId {
+        self.0.thread_id
+    }
+    async fn author(&self) -> GqlUser {
+        GqlUser(self.0.user.clone())
+    }
+    async fn body(&self) -> &str {
+        &self.0.body
+    }
+    async fn body_length(&self) -> usize {
+        self.0.body_length
+    }
+    async fn created_at(&self) -> &str {
+        &self.0.created_at
+    }
+    async fn vote_count(&self) -> i64 {
+        self.0.vote_count
+    }
+    /// By a user the viewer blocked; the body is left out.
+    async fn blocked(&self) -> bool {
+        self.0.blocked
+    }
+    async fn viewer_vote(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<i32>> {
+        viewer_vote(ctx, "reply", self.0.id.0).await
+    }
+}
@@ -0,0 +1,65 @@
+//! Probes for orchestration. `live` only says the process is serving
+//! requests; `ready` also checks what requests depend on, so a broken
+//! database takes the instance out of rotation instead of restarting it.
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use axum::{extract::State, http::StatusCode, Json};
+use serde::Serialize;
+
+use crate::AppState;
+
+/// How long `ready` waits for a pooled connection before calling the
+/// database down.
+const CHECKOUT_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Serialize)]
+pub struct Readiness {
+    status: &'static str,
+    components: BTreeMap<&'static str, Component>,
+}
+
+#[derive(Serialize)]
+struct Component {
+    status: &'static str,
+    latency_ms: u128,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// GET /api/health/live (and /api/health)
+pub async fn live() -> &'static str {
+    "ok"
+}
+
+/// GET /api/health/ready — `503` with the failing components when not ready
+pub async fn ready(State(state): State<AppState>) -> (StatusCode, Json<Readiness>) {
+    let pool = state.reader.clone();
+    let started = Instant::now();
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = pool
+            .get_timeout(CHECKOUT_TIMEOUT)
+            .map_err(|e| e.to_string())?;
+        conn.query_row("SELECT 1", [], |row| row.get::<_, i64>(0))
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|r| r);
+
+    let database = Component {
+        status: if result.is_ok() { "ok" } else { "error" },
+        latency_ms: started.elapsed().as_millis(),
+        error: result.err(),
+    };
+
+    let healthy = database.error.is_none();
+    let components = BTreeMap::from([("database", database)]);
+    let (code, status) = if healthy {
+        (StatusCode::OK, "ok")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "error")
+    };
+    (code, Json(Readiness { status, components }))
+}
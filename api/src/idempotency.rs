@@ -0,0 +1,292 @@
+//! `Idempotency-Key` support, so a double-click or a retried request
+//! doesn't post the same comment twice.
+//!
+//! A POST carrying the header is run once per key and caller (the signed-in
+//! user, or the hashed address for anonymous requests). The first successful
+//! response is stored for a day, and repeats of the request get it back with
+//! `Idempotent-Replayed: true` instead of running again. A repeat that
+//! arrives while the first is still running gets `409 Conflict`, and reusing
+//! a key for a different request `422`. Failed requests aren't stored, so
+//! they can be retried (or corrected) under the same key.
+//!
+//! Bodies are buffered to fingerprint them, so keyed requests are held to
+//! `limits::JSON_BODY_LIMIT`. Uploads pass through unkeyed: a repeated one
+//! only leaves an unused attachment behind.
+
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    extract::{Request, State},
+    http::{header, HeaderName, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use mikaana_shared::{routes, ApiError, ErrorBody};
+
+use crate::{auth, ip_bans::IpHash, limits, request_id, AppState};
+
+static HEADER: HeaderName = HeaderName::from_static("idempotency-key");
+static REPLAYED: HeaderName = HeaderName::from_static("idempotent-replayed");
+
+const MAX_KEY_LEN: usize = 255;
+
+/// Responses larger than this aren't stored; none of ours come close.
+const MAX_STORED_BODY: usize = 1024 * 1024;
+
+/// How long a stored response is replayed.
+const RETENTION: &str = "-1 day";
+
+/// A first request still unfinished after this is taken to have died, and a
+/// repeat runs in its place.
+const ABANDONED_AFTER: &str = "-1 minute";
+
+enum Claim {
+    /// First time this key is seen; run the request.
+    Run,
+    Replay {
+        status: u16,
+        content_type: Option<String>,
+        body: Vec<u8>,
+    },
+    InFlight,
+    Mismatch,
+}
+
+fn valid_key(key: &str) -> bool {
+    !key.is_empty() && key.len() <= MAX_KEY_LEN && key.bytes().all(|b| b.is_ascii_graphic())
+}
+
+/// Hash of what the key promises to stand for.
+fn fingerprint(method: &Method, uri: &str, body: &[u8]) -> String {
+    let mut ctx = ring::digest::Context::new(&ring::digest::SHA256);
+    ctx.update(method.as_str().as_bytes());
+    ctx.update(b" ");
+    ctx.update(uri.as_bytes());
+    ctx.update(b"\n");
+    ctx.update(body);
+    ctx.finish()
+        .as_ref()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+fn claim(
+    conn: &rusqlite::Connection,
+    scope: &str,
+    key: &str,
+    fingerprint: &str,
+) -> rusqlite::Result<Claim> {
+    conn.execute(
+        &format!("DELETE FROM idempotency_keys WHERE created_at < datetime('now', '{RETENTION}')"),
+        [],
+    )?;
+    let inserted = conn.execute(
+        "INSERT OR IGNORE INTO idempotency_keys (scope, key, fingerprint) VALUES (?1, ?2, ?3)",
+        rusqlite::params![scope, key, fingerprint],
+    )?;
+    if inserted > 0 {
+        return Ok(Claim::Run);
+    }
+
+    let (stored, status, content_type, body, abandoned): (
+        String,
+        Option<u16>,
+        Option<String>,
+        Option<Vec<u8>>,
+        bool,
+    ) = conn.query_row(
+        &format!(
+            "SELECT fingerprint, status, content_type, body,
+                    created_at < datetime('now', '{ABANDONED_AFTER}')
+             FROM idempotency_keys WHERE scope = ?1 AND key = ?2"
+        ),
+        rusqlite::params![scope, key],
+        |row| {
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+            ))
+        },
+    )?;
+    if stored != fingerprint {
+        return Ok(Claim::Mismatch);
+    }
+    match status {
+        Some(status) => Ok(Claim::Replay {
+            status,
+            content_type,
+            body: body.unwrap_or_default(),
+        }),
+        None if abandoned => {
+            conn.execute(
+                "UPDATE idempotency_keys SET created_at = datetime('now')
+                 WHERE scope = ?1 AND key = ?2",
+                rusqlite::params![scope, key],
+            )?;
+            Ok(Claim::Run)
+        }
+        None => Ok(Claim::InFlight),
+    }
+}
+
+/// Store the response for `key`, or release the key when there's nothing
+/// worth replaying.
+fn settle(
+    conn: &rusqlite::Connection,
+    scope: &str,
+    key: &str,
+    stored: Option<(u16, Option<String>, Vec<u8>)>,
+) -> rusqlite::Result<()> {
+    match stored {
+        Some((status, content_type, body)) => conn.execute(
+            "UPDATE idempotency_keys SET status = ?3, content_type = ?4, body = ?5
+             WHERE scope = ?1 AND key = ?2",
+            rusqlite::params![scope, key, status, content_type, body],
+        )?,
+        None => conn.execute(
+            "DELETE FROM idempotency_keys WHERE scope = ?1 AND key = ?2",
+            rusqlite::params![scope, key],
+        )?,
+    };
+    Ok(())
+}
+
+/// One of the middleware's own refusals, as an `ErrorBody` like any other.
+fn refuse(status: StatusCode, error: ApiError, message: &str) -> Response {
+    let body = ErrorBody {
+        error,
+        message: Some(message.to_string()),
+        request_id: None,
+    };
+    (status, Json(body)).into_response()
+}
+
+/// Middleware: run keyed POSTs at most once. Needs `ip_bans::enforce`
+/// outside it for the anonymous scope.
+pub async fn deduplicate(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    if req.method() != Method::POST || req.uri().path() == routes::uploads::UPLOADS {
+        return next.run(req).await;
+    }
+    let Some(key) = req.headers().get(&HEADER) else {
+        return next.run(req).await;
+    };
+    let Some(key) = key
+        .to_str()
+        .ok()
+        .filter(|k| valid_key(k))
+        .map(str::to_string)
+    else {
+        return refuse(StatusCode::BAD_REQUEST, ApiError::BadRequest, "Invalid Idempotency-Key");
+    };
+    let scope = match auth::extract_user_id(req.headers(), &state.jwt_secret) {
+        Ok(user_id) => format!("user:{user_id}"),
+        Err(_) => match req.extensions().get::<IpHash>() {
+            Some(IpHash(hash)) => format!("ip:{hash}"),
+            None => return next.run(req).await,
+        },
+    };
+
+    let (parts, body) = req.into_parts();
+    let Ok(body) = to_bytes(body, limits::JSON_BODY_LIMIT).await else {
+        return refuse(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::PayloadTooLarge,
+            "Request body too large",
+        );
+    };
+    let uri = parts.uri.to_string();
+    let print = fingerprint(&parts.method, &uri, &body);
+
+    let pool = state.db.clone();
+    let (s, k) = (scope.clone(), key.clone());
+    let claimed = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|e| e.to_string())?;
+        claim(&conn, &s, &k, &print).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|r| r);
+
+    match claimed {
+        Ok(Claim::Run) => {}
+        Ok(Claim::Replay {
+            status,
+            content_type,
+            body,
+        }) => return replay(status, content_type, body),
+        Ok(Claim::InFlight) => {
+            return refuse(
+                StatusCode::CONFLICT,
+                ApiError::IdempotencyKeyInUse,
+                "A request with this Idempotency-Key is still running",
+            )
+        }
+        Ok(Claim::Mismatch) => {
+            return refuse(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                ApiError::IdempotencyKeyReused,
+                "Idempotency-Key was already used for a different request",
+            )
+        }
+        Err(e) => {
+            request_id::log(format_args!("Idempotency key lookup failed: {e}"));
+            return refuse(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ApiError::InternalServerError,
+                "Idempotency-Key lookup failed",
+            );
+        }
+    }
+
+    let resp = next.run(Request::from_parts(parts, Body::from(body))).await;
+
+    let (resp_parts, resp_body) = resp.into_parts();
+    let (resp, stored) = if resp_parts.status.is_success() {
+        match to_bytes(resp_body, MAX_STORED_BODY).await {
+            Ok(bytes) => {
+                let content_type = resp_parts
+                    .headers
+                    .get(header::CONTENT_TYPE)
+                    .and_then(|v| v.to_str().ok())
+                    .map(str::to_string);
+                let stored = (resp_parts.status.as_u16(), content_type, bytes.to_vec());
+                (
+                    Response::from_parts(resp_parts, Body::from(bytes)),
+                    Some(stored),
+                )
+            }
+            Err(_) => (Response::from_parts(resp_parts, Body::empty()), None),
+        }
+    } else {
+        (Response::from_parts(resp_parts, resp_body), None)
+    };
+
+    let pool = state.db.clone();
+    let settled = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|e| e.to_string())?;
+        settle(&conn, &scope, &key, stored).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())
+    .and_then(|r| r);
+    if let Err(e) = settled {
+        // The request itself went through; a repeat may run it again
+        request_id::log(format_args!("Storing idempotent response failed: {e}"));
+    }
+    resp
+}
+
+fn replay(status: u16, content_type: Option<String>, body: Vec<u8>) -> Response {
+    let mut resp = Response::new(Body::from(Bytes::from(body)));
+    *resp.status_mut() = StatusCode::from_u16(status).unwrap_or(StatusCode::OK);
+    if let Some(value) = content_type.and_then(|ct| HeaderValue::from_str(&ct).ok()) {
+        resp.headers_mut().insert(header::CONTENT_TYPE, value);
+    }
+    resp.headers_mut()
+        .insert(REPLAYED.clone(), HeaderValue::from_static("true"));
+    resp
+}
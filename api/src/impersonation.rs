@@ -0,0 +1,144 @@
+//! "View as user" for admins debugging what a particular user sees.
+//!
+//! `POST /api/admin/users/{id}/impersonate` mints a short-lived token for
+//! the user carrying an `impersonated_by` claim. `enforce` keeps such tokens
+//! read-only (writes, data exports, the unsubscribe link and the admin API
+//! are refused) and records every request made with one in the audit log,
+//! under the admin. GETs that normally leave a trace (read marks on threads,
+//! categories and messages) skip it under such a token.
+//! Staff accounts can't be impersonated, and a token stops working as soon
+//! as its admin loses the role.
+
+use axum::{
+    extract::{Path, Request, State},
+    http::{HeaderMap, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use jsonwebtoken::{encode, EncodingKey, Header};
+use mikaana_shared::{routes, Impersonation, UserId};
+
+use crate::{audit, auth, AppState};
+
+/// Long enough to look around, short enough that a forgotten tab expires.
+const TOKEN_TTL_SECS: usize = 30 * 60;
+
+/// Whether an impersonation token may make this request.
+fn allowed(method: &Method, path: &str) -> bool {
+    if path.starts_with("/api/admin/")
+        || path == "/api/auth/me/export"
+        // A GET, but it changes the user's email settings
+        || path == routes::notifications::UNSUBSCRIBE
+    {
+        return false;
+    }
+    // GraphQL has no mutations, so its POSTs are reads too
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS) || path == "/api/graphql"
+}
+
+/// POST /api/admin/users/:id/impersonate
+pub async fn impersonate(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(user_id): Path<UserId>,
+) -> Result<Json<Impersonation>, StatusCode> {
+    let claims = auth::extract_claims(&headers, &state.jwt_secret)?;
+    // `enforce` refuses these too; don't depend on the layer order
+    if claims.impersonated_by.is_some() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let admin_id = claims.sub;
+    if admin_id == user_id {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let pool = state.db.clone();
+    let user = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_admin(&conn, admin_id)?;
+
+        let (role, deleted): (String, bool) = conn
+            .query_row(
+                "SELECT role, deleted_at IS NOT NULL FROM users WHERE id = ?1",
+                [user_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .map_err(|_| StatusCode::NOT_FOUND)?;
+        if deleted {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        if role != "user" {
+            return Err(StatusCode::FORBIDDEN);
+        }
+
+        audit::record(
+            &conn,
+            admin_id,
+            "user.impersonate",
+            "user",
+            user_id.0,
+            serde_json::json!({ "ttl_secs": TOKEN_TTL_SECS }),
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        auth::query_user(&conn, user_id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    let claims = auth::Claims::impersonating(user_id, admin_id, TOKEN_TTL_SECS);
+    let token = encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(state.jwt_secret.as_bytes()),
+    )
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(Impersonation {
+        token,
+        user,
+        expires_at: claims.exp as i64,
+    }))
+}
+
+/// Middleware: refuse writes made with an impersonation token and log the
+/// rest to the audit log.
+pub async fn enforce(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let Ok(claims) = auth::extract_claims(req.headers(), &state.jwt_secret) else {
+        return next.run(req).await;
+    };
+    let Some(admin_id) = claims.impersonated_by else {
+        return next.run(req).await;
+    };
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    if !allowed(&method, &path) {
+        return (StatusCode::FORBIDDEN, "Impersonation is read-only").into_response();
+    }
+
+    let pool = state.db.clone();
+    let user_id = claims.sub;
+    let logged = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_admin(&conn, admin_id).map_err(|_| StatusCode::UNAUTHORIZED)?;
+        audit::record(
+            &conn,
+            admin_id,
+            "impersonation.request",
+            "user",
+            user_id.0,
+            serde_json::json!({ "method": method.as_str(), "path": path }),
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    .and_then(|r| r);
+
+    match logged {
+        Ok(()) => next.run(req).await,
+        // Unlogged means unserved
+        Err(status) => status.into_response(),
+    }
+}
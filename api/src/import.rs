@@ -0,0 +1,811 @@
+//! `mikaana-api import <source> ...`: bring comments over from another
+//! comment system, keyed by post slug. Imported items are recorded in
+//! `imports`, so running an import again only adds what's new.
+//!
+//! - `import giscus <owner>/<repo> [--category <name>] [--site <id>]`:
+//!   GitHub Discussions, as used by giscus.
+//! - `import utterances <owner>/<repo> [--site <id>]`: GitHub issues, as
+//!   used by utterances.
+//! - `import wordpress <export.xml> [--site <id>]`: a WordPress export
+//!   (WXR) file.
+//! - `import staticman <dir> [--prefix <path>] [--site <id>]`: Jekyll data
+//!   files as Staticman writes them, one directory of YAML entries per post
+//!   under `dir`; the post's slug is `prefix` (default `/`) and the
+//!   directory's name.
+//!
+//! The GitHub sources read the repository with `GITHUB_TOKEN`, and expect
+//! discussions or issues titled with the post's path or address (the
+//! default "pathname" and "url" mappings). Their commenters are matched to
+//! accounts by GitHub id, created if need be, so they own their comments
+//! when they log in. Commenters from the others become guests: accounts
+//! nobody logs in to, one per email address (or name, without one).
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use rusqlite::OptionalExtension;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{attachments, sites, DbPool};
+
+const GITHUB_API: &str = "https://api.github.com";
+
+/// GitHub's stand-in for deleted accounts.
+const GHOST: Author = Author {
+    id: 10137,
+    login: String::new(),
+    avatar_url: String::new(),
+};
+
+const USAGE: &str = "usage: import giscus <owner>/<repo> [--category <name>] [--site <id>]
+       import utterances <owner>/<repo> [--site <id>]
+       import wordpress <export.xml> [--site <id>]
+       import staticman <dir> [--prefix <path>] [--site <id>]";
+
+/// Run `import ...`; `None` when `args` aren't an import, and the server
+/// should start instead.
+pub async fn run_cli(args: &[String], pool: &DbPool) -> Option<Result<(), String>> {
+    let (command, rest) = args.split_first()?;
+    if command != "import" {
+        return None;
+    }
+    Some(run(rest, pool).await)
+}
+
+async fn run(args: &[String], pool: &DbPool) -> Result<(), String> {
+    let (positional, options) = parse_args(args)?;
+    let site = options
+        .get("site")
+        .cloned()
+        .unwrap_or_else(|| sites::DEFAULT_SITE.to_string());
+    let [source, from] = positional.as_slice() else {
+        return Err(USAGE.to_string());
+    };
+    let posts = match source.as_str() {
+        "giscus" => {
+            let github = GitHub::from_env()?;
+            github.discussions(from, options.get("category").map(String::as_str)).await?
+        }
+        "utterances" => GitHub::from_env()?.issues(from).await?,
+        "wordpress" => wordpress(Path::new(from))?,
+        "staticman" => {
+            let prefix = options.get("prefix").map_or("/", String::as_str);
+            staticman(Path::new(from), prefix)?
+        }
+        _ => return Err(USAGE.to_string()),
+    };
+
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let mut total = 0;
+    for post in posts {
+        let Some(slug) = slug_for(&post.page) else {
+            eprintln!("Skipped \"{}\": not a path or address", post.page);
+            continue;
+        };
+        let tx = conn.transaction().map_err(|e| e.to_string())?;
+        let added = save(&tx, source, &site, &slug, &post.comments).map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        if added > 0 {
+            println!("Imported {added} comments on {slug}");
+        }
+        total += added;
+    }
+    println!("Imported {total} comments from {source}");
+    Ok(())
+}
+
+/// Positional arguments and `--name value` options.
+fn parse_args(args: &[String]) -> Result<(Vec<String>, HashMap<String, String>), String> {
+    let mut positional = Vec::new();
+    let mut options = HashMap::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.strip_prefix("--") {
+            Some(name) => {
+                let value = args.next().ok_or(format!("--{name} needs a value"))?;
+                options.insert(name.to_string(), value.clone());
+            }
+            None => positional.push(arg.clone()),
+        }
+    }
+    Ok((positional, options))
+}
+
+/// The post slug a page names: its path when it's an address, else the
+/// page itself as a path (`blog/post/` → `/blog/post/`). `None` for pages
+/// that aren't either, as from giscus's title-based mappings.
+fn slug_for(page: &str) -> Option<String> {
+    let page = page.trim();
+    if let Ok(url) = reqwest::Url::parse(page) {
+        return matches!(url.scheme(), "http" | "https").then(|| url.path().to_string());
+    }
+    if page.is_empty() || page.contains(char::is_whitespace) {
+        return None;
+    }
+    Some(if page.starts_with('/') {
+        page.to_string()
+    } else {
+        format!("/{page}")
+    })
+}
+
+/// ISO 8601 timestamps (`2024-05-01T12:34:56Z`) as stored (`2024-05-01 12:34:56`).
+fn db_time(at: &str) -> String {
+    at.get(..19).unwrap_or(at).replacen('T', " ", 1)
+}
+
+// ── Saving ──
+
+/// A post's comments in the source.
+struct ImportedPost {
+    /// The post's path or address: a discussion or issue title, a
+    /// WordPress post's link.
+    page: String,
+    comments: Vec<ImportedComment>,
+}
+
+struct ImportedComment {
+    /// Unique within the source.
+    external_id: String,
+    author: Commenter,
+    body: String,
+    /// As stored.
+    created_at: String,
+}
+
+enum Commenter {
+    GitHub(Author),
+    Guest(Guest),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Author {
+    id: i64,
+    login: String,
+    avatar_url: String,
+}
+
+/// A commenter known only by what they typed into the comment form.
+struct Guest {
+    name: String,
+    email: Option<String>,
+    website: Option<String>,
+}
+
+impl Guest {
+    fn key(&self) -> String {
+        match &self.email {
+            Some(email) => format!("email:{}", email.trim().to_lowercase()),
+            None => format!("name:{}", self.name),
+        }
+    }
+
+    /// Their Gravatar, or an identicon. Gravatar takes SHA-256 as well as
+    /// MD5 hashes of the address; Staticman stores the MD5 hash already.
+    fn avatar_url(&self) -> String {
+        let hash = match &self.email {
+            Some(email) if is_md5_hex(email) => email.to_lowercase(),
+            Some(email) => attachments::sha256_hex(email.trim().to_lowercase().as_bytes()),
+            None => attachments::sha256_hex(self.name.as_bytes()),
+        };
+        format!("https://gravatar.com/avatar/{hash}?d=identicon")
+    }
+}
+
+fn is_md5_hex(s: &str) -> bool {
+    s.len() == 32 && s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// The account a commenter's comments go to, created on first sight.
+fn commenter_id(conn: &rusqlite::Connection, commenter: &Commenter) -> rusqlite::Result<i64> {
+    match commenter {
+        Commenter::GitHub(author) => {
+            // The username is only set when the account is created, as at login
+            let login = if author.login.is_empty() { "ghost" } else { &author.login };
+            conn.execute(
+                "INSERT INTO users (github_id, github_login, username, avatar_url)
+                 VALUES (?1, ?2, ?2, ?3)
+                 ON CONFLICT(github_id) DO NOTHING",
+                rusqlite::params![author.id, login, author.avatar_url],
+            )?;
+            conn.query_row(
+                "SELECT COALESCE(merged_into, id) FROM users WHERE github_id = ?1",
+                [author.id],
+                |row| row.get(0),
+            )
+        }
+        Commenter::Guest(guest) => {
+            let key = guest.key();
+            let existing = conn
+                .query_row(
+                    "SELECT COALESCE(merged_into, id) FROM users WHERE guest_key = ?1",
+                    [&key],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            if let Some(id) = existing {
+                return Ok(id);
+            }
+            // Like deleted accounts, guests hold their own negated id as
+            // github_id, which no login can match; a placeholder below
+            // every other one is needed until the id is known
+            conn.execute(
+                "INSERT INTO users (github_id, username, avatar_url, website, guest_key)
+                 VALUES ((SELECT MIN(COALESCE(MIN(github_id), 0), 0) FROM users) - 1,
+                         ?1, ?2, ?3, ?4)",
+                rusqlite::params![guest.name, guest.avatar_url(), guest.website, key],
+            )?;
+            let id = conn.last_insert_rowid();
+            conn.execute("UPDATE users SET github_id = -id WHERE id = ?1", [id])?;
+            Ok(id)
+        }
+    }
+}
+
+/// Add the comments not imported before; returns how many.
+fn save(
+    conn: &rusqlite::Connection,
+    source: &str,
+    site: &str,
+    slug: &str,
+    comments: &[ImportedComment],
+) -> rusqlite::Result<usize> {
+    let mut added = 0;
+    for comment in comments {
+        let seen: bool = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM imports WHERE source = ?1 AND external_id = ?2)",
+            [source, &comment.external_id],
+            |row| row.get(0),
+        )?;
+        let body = ammonia::clean(comment.body.trim());
+        if seen || body.is_empty() {
+            continue;
+        }
+
+        let user_id = commenter_id(conn, &comment.author)?;
+        conn.execute(
+            "INSERT INTO comments (site_id, post_slug, user_id, body, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![site, slug, user_id, body, comment.created_at],
+        )?;
+        conn.execute(
+            "INSERT INTO imports (source, external_id, target_type, target_id)
+             VALUES (?1, ?2, 'comment', ?3)",
+            rusqlite::params![source, comment.external_id, conn.last_insert_rowid()],
+        )?;
+        added += 1;
+    }
+    Ok(added)
+}
+
+// ── GitHub ──
+
+struct GitHub {
+    client: reqwest::Client,
+    token: String,
+}
+
+/// `owner/repo` as its two parts.
+fn split_repo(repo: &str) -> Result<(&str, &str), String> {
+    repo.split_once('/')
+        .filter(|(owner, name)| !owner.is_empty() && !name.is_empty())
+        .ok_or(format!("expected <owner>/<repo>, got {repo}"))
+}
+
+impl GitHub {
+    fn from_env() -> Result<Self, String> {
+        let token = std::env::var("GITHUB_TOKEN")
+            .map_err(|_| "GITHUB_TOKEN must be set to read the repository".to_string())?;
+        let client = reqwest::Client::builder()
+            .user_agent("mikaana-api")
+            .build()
+            .map_err(|e| e.to_string())?;
+        Ok(GitHub { client, token })
+    }
+
+    async fn get<T: serde::de::DeserializeOwned>(&self, url: &str) -> Result<T, String> {
+        self.client
+            .get(url)
+            .bearer_auth(&self.token)
+            .header("Accept", "application/vnd.github+json")
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| format!("{url}: {e}"))?
+            .json()
+            .await
+            .map_err(|e| format!("{url}: {e}"))
+    }
+
+    async fn graphql<T: serde::de::DeserializeOwned>(
+        &self,
+        query: &str,
+        variables: serde_json::Value,
+    ) -> Result<T, String> {
+        #[derive(Deserialize)]
+        struct Response<T> {
+            data: Option<T>,
+            #[serde(default)]
+            errors: Vec<GraphQlError>,
+        }
+        #[derive(Deserialize)]
+        struct GraphQlError {
+            message: String,
+        }
+
+        let response: Response<T> = self
+            .client
+            .post(format!("{GITHUB_API}/graphql"))
+            .bearer_auth(&self.token)
+            .json(&json!({ "query": query, "variables": variables }))
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| format!("GitHub GraphQL: {e}"))?
+            .json()
+            .await
+            .map_err(|e| format!("GitHub GraphQL: {e}"))?;
+        if let Some(error) = response.errors.first() {
+            return Err(format!("GitHub GraphQL: {}", error.message));
+        }
+        response.data.ok_or("GitHub GraphQL: empty response".to_string())
+    }
+}
+
+// ── Discussions (giscus) ──
+
+const DISCUSSIONS_QUERY: &str = "
+query($owner: String!, $name: String!, $after: String) {
+  repository(owner: $owner, name: $name) {
+    discussions(first: 100, after: $after) {
+      pageInfo { hasNextPage endCursor }
+      nodes { id title category { name } }
+    }
+  }
+}";
+
+/// Replies past the first 100 to a comment aren't fetched; giscus shows
+/// fewer than that.
+const COMMENTS_QUERY: &str = "
+fragment author on Actor { login avatarUrl ... on User { databaseId } ... on Bot { databaseId } }
+query($id: ID!, $after: String) {
+  node(id: $id) {
+    ... on Discussion {
+      comments(first: 50, after: $after) {
+        pageInfo { hasNextPage endCursor }
+        nodes {
+          id body createdAt isMinimized author { ...author }
+          replies(first: 100) { nodes { id body createdAt isMinimized author { ...author } } }
+        }
+      }
+    }
+  }
+}";
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PageInfo {
+    has_next_page: bool,
+    end_cursor: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Connection<T> {
+    #[serde(rename = "pageInfo")]
+    page_info: PageInfo,
+    nodes: Vec<T>,
+}
+
+#[derive(Deserialize)]
+struct Discussion {
+    id: String,
+    title: String,
+    category: Category,
+}
+
+#[derive(Deserialize)]
+struct Category {
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DiscussionComment {
+    id: String,
+    body: String,
+    created_at: String,
+    is_minimized: bool,
+    author: Option<Actor>,
+    #[serde(default)]
+    replies: Option<Replies>,
+}
+
+#[derive(Deserialize)]
+struct Replies {
+    nodes: Vec<DiscussionComment>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Actor {
+    login: String,
+    avatar_url: String,
+    database_id: Option<i64>,
+}
+
+impl DiscussionComment {
+    /// Minimized (hidden as spam, off-topic...) comments are left behind.
+    fn imported(self) -> Option<ImportedComment> {
+        if self.is_minimized {
+            return None;
+        }
+        let author = match self.author {
+            Some(Actor {
+                login,
+                avatar_url,
+                database_id: Some(id),
+            }) => Author { id, login, avatar_url },
+            _ => GHOST,
+        };
+        Some(ImportedComment {
+            external_id: self.id,
+            author: Commenter::GitHub(author),
+            body: self.body,
+            created_at: db_time(&self.created_at),
+        })
+    }
+}
+
+impl GitHub {
+    /// Every discussion (in `category`, when given) with its comments and
+    /// their replies, replies right after the comment they answer.
+    async fn discussions(&self, repo: &str, category: Option<&str>) -> Result<Vec<ImportedPost>, String> {
+        #[derive(Deserialize)]
+        struct Data {
+            repository: Repository,
+        }
+        #[derive(Deserialize)]
+        struct Repository {
+            discussions: Connection<Discussion>,
+        }
+
+        let (owner, name) = split_repo(repo)?;
+        let mut discussions = Vec::new();
+        let mut after: Option<String> = None;
+        loop {
+            let data: Data = self
+                .graphql(
+                    DISCUSSIONS_QUERY,
+                    json!({ "owner": owner, "name": name, "after": after }),
+                )
+                .await?;
+            let page = data.repository.discussions;
+            discussions.extend(
+                page.nodes
+                    .into_iter()
+                    .filter(|d| category.is_none_or(|c| d.category.name == c)),
+            );
+            match page.page_info.end_cursor.filter(|_| page.page_info.has_next_page) {
+                Some(cursor) => after = Some(cursor),
+                None => break,
+            }
+        }
+
+        let mut posts = Vec::new();
+        for discussion in discussions {
+            posts.push(ImportedPost {
+                comments: self.discussion_comments(&discussion.id).await?,
+                page: discussion.title,
+            });
+        }
+        Ok(posts)
+    }
+
+    async fn discussion_comments(&self, id: &str) -> Result<Vec<ImportedComment>, String> {
+        #[derive(Deserialize)]
+        struct Data {
+            node: Node,
+        }
+        #[derive(Deserialize)]
+        struct Node {
+            comments: Connection<DiscussionComment>,
+        }
+
+        let mut comments = Vec::new();
+        let mut after: Option<String> = None;
+        loop {
+            let data: Data = self
+                .graphql(COMMENTS_QUERY, json!({ "id": id, "after": after }))
+                .await?;
+            let page = data.node.comments;
+            for mut comment in page.nodes {
+                let replies = comment.replies.take().map(|r| r.nodes).unwrap_or_default();
+                comments.extend(comment.imported());
+                comments.extend(replies.into_iter().filter_map(DiscussionComment::imported));
+            }
+            match page.page_info.end_cursor.filter(|_| page.page_info.has_next_page) {
+                Some(cursor) => after = Some(cursor),
+                None => break,
+            }
+        }
+        Ok(comments)
+    }
+}
+
+// ── Issues (utterances) ──
+
+/// Items per page of the REST API, at its maximum.
+const PER_PAGE: usize = 100;
+
+#[derive(Deserialize)]
+struct Issue {
+    number: i64,
+    title: String,
+    comments: i64,
+    /// Set on pull requests, which the issues API lists too.
+    pull_request: Option<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct IssueComment {
+    id: i64,
+    body: Option<String>,
+    created_at: String,
+    user: Option<Author>,
+}
+
+impl GitHub {
+    /// Every issue with its comments. The issue's own body is the bot's
+    /// link back to the post, not a comment, so it's left out.
+    async fn issues(&self, repo: &str) -> Result<Vec<ImportedPost>, String> {
+        split_repo(repo)?;
+        let mut issues: Vec<Issue> = Vec::new();
+        for page in 1.. {
+            let batch: Vec<Issue> = self
+                .get(&format!(
+                    "{GITHUB_API}/repos/{repo}/issues?state=all&per_page={PER_PAGE}&page={page}"
+                ))
+                .await?;
+            let last = batch.len() < PER_PAGE;
+            issues.extend(batch.into_iter().filter(|i| i.pull_request.is_none()));
+            if last {
+                break;
+            }
+        }
+
+        let mut posts = Vec::new();
+        for issue in issues.into_iter().filter(|i| i.comments > 0) {
+            let mut comments = Vec::new();
+            for page in 1.. {
+                let batch: Vec<IssueComment> = self
+                    .get(&format!(
+                        "{GITHUB_API}/repos/{repo}/issues/{}/comments?per_page={PER_PAGE}&page={page}",
+                        issue.number
+                    ))
+                    .await?;
+                let last = batch.len() < PER_PAGE;
+                comments.extend(batch.into_iter().map(|c| ImportedComment {
+                    external_id: c.id.to_string(),
+                    author: Commenter::GitHub(c.user.unwrap_or(GHOST)),
+                    body: c.body.unwrap_or_default(),
+                    created_at: db_time(&c.created_at),
+                }));
+                if last {
+                    break;
+                }
+            }
+            posts.push(ImportedPost {
+                page: issue.title,
+                comments,
+            });
+        }
+        Ok(posts)
+    }
+}
+
+// ── WordPress ──
+
+#[derive(Deserialize)]
+struct Wxr {
+    channel: WxrChannel,
+}
+
+#[derive(Deserialize)]
+struct WxrChannel {
+    #[serde(default, rename = "item")]
+    items: Vec<WxrItem>,
+}
+
+/// A post, page or attachment. Elements are matched by local name, so
+/// `comment` is `<wp:comment>`.
+#[derive(Deserialize)]
+struct WxrItem {
+    #[serde(default)]
+    link: String,
+    #[serde(default, rename = "comment")]
+    comments: Vec<WxrComment>,
+}
+
+#[derive(Deserialize)]
+struct WxrComment {
+    comment_id: String,
+    #[serde(default)]
+    comment_author: String,
+    #[serde(default)]
+    comment_author_email: String,
+    #[serde(default)]
+    comment_author_url: String,
+    #[serde(default)]
+    comment_date: String,
+    #[serde(default)]
+    comment_date_gmt: String,
+    #[serde(default)]
+    comment_content: String,
+    /// "1" once approved; "0" while pending, else "spam" or "trash".
+    #[serde(default)]
+    comment_approved: String,
+    /// Empty or "comment" for comments; pingbacks and trackbacks are left
+    /// behind.
+    #[serde(default)]
+    comment_type: String,
+}
+
+/// Every post's approved comments. WordPress comment ids are only unique
+/// per blog, so imported ones are told apart by the post's link as well.
+fn wordpress(path: &Path) -> Result<Vec<ImportedPost>, String> {
+    let xml = std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    let wxr: Wxr = quick_xml::de::from_str(&xml).map_err(|e| format!("{}: {e}", path.display()))?;
+
+    Ok(wxr
+        .channel
+        .items
+        .into_iter()
+        .filter(|item| !item.comments.is_empty())
+        .map(|item| {
+            let comments = item
+                .comments
+                .into_iter()
+                .filter(|c| c.comment_approved == "1")
+                .filter(|c| matches!(c.comment_type.as_str(), "" | "comment"))
+                .map(|c| {
+                    // Old exports leave the GMT date zeroed
+                    let created_at = if c.comment_date_gmt.starts_with("0000") {
+                        c.comment_date
+                    } else {
+                        c.comment_date_gmt
+                    };
+                    ImportedComment {
+                        external_id: format!("{}#comment-{}", item.link, c.comment_id),
+                        author: Commenter::Guest(Guest {
+                            name: non_empty(c.comment_author).unwrap_or_else(anonymous),
+                            email: non_empty(c.comment_author_email),
+                            website: non_empty(c.comment_author_url),
+                        }),
+                        body: c.comment_content,
+                        created_at,
+                    }
+                })
+                .collect();
+            ImportedPost {
+                page: item.link,
+                comments,
+            }
+        })
+        .collect())
+}
+
+fn non_empty(s: String) -> Option<String> {
+    let s = s.trim();
+    (!s.is_empty()).then(|| s.to_string())
+}
+
+fn anonymous() -> String {
+    "Anonymous".to_string()
+}
+
+// ── Staticman ──
+
+/// A Staticman entry, with the field names of its documented Jekyll setup.
+#[derive(Deserialize)]
+struct StaticmanEntry {
+    #[serde(rename = "_id")]
+    id: Option<String>,
+    #[serde(default)]
+    name: String,
+    /// Usually stored as its MD5 hash, for Gravatar.
+    #[serde(default)]
+    email: String,
+    #[serde(default)]
+    url: String,
+    #[serde(default)]
+    message: String,
+    /// A Unix timestamp in seconds or milliseconds, or an ISO 8601 date.
+    date: Option<serde_yaml::Value>,
+}
+
+/// Entries of every post directory under `dir`, oldest first.
+fn staticman(dir: &Path, prefix: &str) -> Result<Vec<ImportedPost>, String> {
+    let read_dir = |dir: &Path| -> Result<Vec<std::path::PathBuf>, String> {
+        let mut paths: Vec<_> = std::fs::read_dir(dir)
+            .map_err(|e| format!("{}: {e}", dir.display()))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .collect();
+        paths.sort();
+        Ok(paths)
+    };
+
+    let mut posts = Vec::new();
+    for post_dir in read_dir(dir)?.into_iter().filter(|p| p.is_dir()) {
+        let Some(post) = post_dir.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        let mut comments = Vec::new();
+        for file in read_dir(&post_dir)? {
+            let Some(name) = file
+                .file_name()
+                .and_then(|n| n.to_str())
+                .filter(|n| n.ends_with(".yml") || n.ends_with(".yaml"))
+            else {
+                continue;
+            };
+            let yaml =
+                std::fs::read_to_string(&file).map_err(|e| format!("{}: {e}", file.display()))?;
+            let entry: StaticmanEntry =
+                serde_yaml::from_str(&yaml).map_err(|e| format!("{}: {e}", file.display()))?;
+            comments.push(ImportedComment {
+                external_id: entry.id.unwrap_or_else(|| format!("{post}/{name}")),
+                author: Commenter::Guest(Guest {
+                    name: non_empty(entry.name).unwrap_or_else(anonymous),
+                    email: non_empty(entry.email),
+                    website: non_empty(entry.url),
+                }),
+                body: entry.message,
+                created_at: entry.date.as_ref().and_then(staticman_time).ok_or(format!(
+                    "{}: missing or unreadable date",
+                    file.display()
+                ))?,
+            });
+        }
+        comments.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+        posts.push(ImportedPost {
+            page: format!("{}/{post}/", prefix.trim_end_matches('/')),
+            comments,
+        });
+    }
+    Ok(posts)
+}
+
+fn staticman_time(date: &serde_yaml::Value) -> Option<String> {
+    match date {
+        serde_yaml::Value::Number(n) => {
+            let n = n.as_i64()?;
+            // Milliseconds from `{@timestamp}`, seconds from `timestamp-seconds`
+            Some(unix_time(if n > 100_000_000_000 { n / 1000 } else { n }))
+        }
+        serde_yaml::Value::String(s) if s.len() >= 19 => Some(db_time(s)),
+        _ => None,
+    }
+}
+
+/// Seconds since the epoch as stored (`2024-05-01 12:34:56`, UTC).
+fn unix_time(secs: i64) -> String {
+    let (days, secs) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+    // Civil date from days since 1970-01-01, after Howard Hinnant's
+    // `civil_from_days`
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}",
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
@@ -0,0 +1,242 @@
+//! Bans by client address, for abusers who come back with a fresh GitHub
+//! account.
+//!
+//! Addresses are never stored in the clear: comments, threads and replies
+//! record an `ip_hash`, an HMAC of the address keyed by `JWT_SECRET`
+//! (rotating the secret therefore lifts every ban). IPv6 addresses are
+//! hashed by their /64 prefix, which is what a single connection usually
+//! gets. Moderators ban the address some content was posted from; `enforce`
+//! then refuses writes from it, outside the admin API.
+
+use std::net::{IpAddr, SocketAddr};
+
+use axum::{
+    extract::{ConnectInfo, Path, Request, State},
+    http::{HeaderMap, Method, StatusCode},
+    middleware::Next,
+    response::Response,
+    Json,
+};
+use mikaana_shared::{CreateIpBan, IpBan};
+
+use crate::{audit, auth, config::Config, moderation, AppState};
+
+/// Hashed client address, added to every request by `enforce`.
+#[derive(Clone)]
+pub struct IpHash(pub String);
+
+/// The client's address: from `client_ip_header` when configured (its last
+/// entry, the one our proxy appended), otherwise the TCP peer.
+fn client_ip(headers: &HeaderMap, peer: SocketAddr, config: &Config) -> IpAddr {
+    config
+        .client_ip_header
+        .as_deref()
+        .and_then(|name| headers.get(name))
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.rsplit(',').next())
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(peer.ip())
+}
+
+fn hash_ip(ip: IpAddr, secret: &str) -> String {
+    let canonical = match ip.to_canonical() {
+        IpAddr::V4(v4) => v4.to_string(),
+        IpAddr::V6(v6) => {
+            let s = v6.segments();
+            format!("{:x}:{:x}:{:x}:{:x}::/64", s[0], s[1], s[2], s[3])
+        }
+    };
+    let key = ring::hmac::Key::new(ring::hmac::HMAC_SHA256, secret.as_bytes());
+    let tag = ring::hmac::sign(&key, format!("ip:{canonical}").as_bytes());
+    tag.as_ref().iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Middleware: tag the request with its `IpHash` and refuse writes from
+/// banned addresses. Reads stay open, and so does the admin API so staff
+/// can't lock themselves out.
+pub async fn enforce(
+    State(state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let ip = client_ip(req.headers(), peer, &state.config.load());
+    let hash = hash_ip(ip, &state.jwt_secret);
+
+    let write = !matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    if write && !req.uri().path().starts_with("/api/admin/") {
+        let pool = state.reader.clone();
+        let key = hash.clone();
+        let banned = tokio::task::spawn_blocking(move || {
+            let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            conn.query_row(
+                "SELECT EXISTS(SELECT 1 FROM ip_bans WHERE ip_hash = ?1)",
+                [key],
+                |row| row.get::<_, bool>(0),
+            )
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+        })
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+        if banned {
+            return Err(StatusCode::FORBIDDEN);
+        }
+    }
+
+    req.extensions_mut().insert(IpHash(hash));
+    Ok(next.run(req).await)
+}
+
+// ── Queries ──
+
+const BAN_SELECT: &str = "SELECT b.id, b.reason, b.source_type, b.source_id, b.created_at,
+        u.id, u.username, u.avatar_url, u.display_name
+ FROM ip_bans b
+ JOIN users u ON b.created_by = u.id";
+
+fn ban_from_row(row: &rusqlite::Row) -> rusqlite::Result<IpBan> {
+    Ok(IpBan {
+        id: row.get(0)?,
+        reason: row.get(1)?,
+        source_type: row.get(2)?,
+        source_id: row.get(3)?,
+        created_at: row.get(4)?,
+        banned_by: auth::user_from_row(row, 5)?,
+    })
+}
+
+// ── Handlers ──
+
+/// GET /api/admin/ip-bans
+pub async fn list_bans(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<IpBan>>, StatusCode> {
+    let mod_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+
+    let pool = state.reader.clone();
+    let bans = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_moderator(&conn, mod_id)?;
+        let mut stmt = conn
+            .prepare_cached(&format!("{BAN_SELECT} ORDER BY b.id DESC"))
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let rows = stmt
+            .query_map([], ban_from_row)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok::<_, StatusCode>(rows)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(bans))
+}
+
+/// POST /api/admin/ip-bans — ban the address a comment, thread or reply was
+/// posted from; `422` for content that predates IP recording
+pub async fn create_ban(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<CreateIpBan>,
+) -> Result<Json<IpBan>, StatusCode> {
+    let mod_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+    let table = moderation::content_table(&payload.target_type).ok_or(StatusCode::BAD_REQUEST)?;
+    let reason = payload.reason.trim().to_string();
+    if reason.chars().count() > mikaana_shared::MAX_REPORT_REASON_LEN {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let pool = state.db.clone();
+    let ban = tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_moderator(&conn, mod_id)?;
+        let tx = conn
+            .transaction()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        let ip_hash: Option<String> = tx
+            .query_row(
+                &format!("SELECT ip_hash FROM {table} WHERE id = ?1"),
+                [payload.target_id],
+                |row| row.get(0),
+            )
+            .map_err(|_| StatusCode::NOT_FOUND)?;
+        let ip_hash = ip_hash.ok_or(StatusCode::UNPROCESSABLE_ENTITY)?;
+
+        let inserted = tx
+            .execute(
+                "INSERT OR IGNORE INTO ip_bans (ip_hash, reason, source_type, source_id, created_by)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                rusqlite::params![ip_hash, reason, payload.target_type, payload.target_id, mod_id],
+            )
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if inserted == 0 {
+            return Err(StatusCode::CONFLICT);
+        }
+        let id = tx.last_insert_rowid();
+
+        audit::record(
+            &tx,
+            mod_id,
+            "ip.ban",
+            &payload.target_type,
+            payload.target_id,
+            serde_json::json!({ "ban_id": id, "reason": reason }),
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        let ban = tx
+            .query_row(&format!("{BAN_SELECT} WHERE b.id = ?1"), [id], ban_from_row)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        tx.commit().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok::<_, StatusCode>(ban)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(ban))
+}
+
+/// DELETE /api/admin/ip-bans/:id
+pub async fn delete_ban(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, StatusCode> {
+    let mod_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+
+    let pool = state.db.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_moderator(&conn, mod_id)?;
+        let tx = conn
+            .transaction()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        let (source_type, source_id): (String, i64) = tx
+            .query_row(
+                "DELETE FROM ip_bans WHERE id = ?1 RETURNING source_type, source_id",
+                [id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .map_err(|_| StatusCode::NOT_FOUND)?;
+
+        audit::record(
+            &tx,
+            mod_id,
+            "ip.unban",
+            &source_type,
+            source_id,
+            serde_json::json!({ "ban_id": id }),
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        tx.commit().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        Ok(StatusCode::NO_CONTENT)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+}
@@ -0,0 +1,174 @@
+//! Request size limits.
+//!
+//! Every route accepts at most `JSON_BODY_LIMIT` bytes of body unless it sets
+//! its own `DefaultBodyLimit` (uploads do). Going over is a `413`
+//! `payload_too_large`, courtesy of `payload_too_large`. Comments, threads,
+//! replies, messages and drafts are read through `ValidJson`, which also refuses
+//! deeply nested JSON and fields over their length limits with a `422`
+//! (`ApiError::TooDeep`, `ApiError::ValidationFailed`).
+
+use axum::{
+    body::Bytes,
+    extract::{FromRequest, Request},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use mikaana_shared::{
+    ApiError, CreateComment, CreateReply, CreateThread, Draft, SendMessage, MAX_BODY_LEN,
+    MAX_DRAFT_KEY_LEN, MAX_TITLE_LEN,
+};
+use serde::de::DeserializeOwned;
+
+/// Default request body limit, far above any valid JSON payload.
+pub const JSON_BODY_LIMIT: usize = 64 * 1024;
+
+/// None of our payloads nest more than a couple of levels.
+const MAX_JSON_DEPTH: usize = 16;
+
+/// A payload field over its length limit, in characters.
+pub struct TooLong {
+    field: &'static str,
+    max: usize,
+}
+
+pub enum Rejection {
+    TooDeep,
+    TooLong(TooLong),
+    Other(Response),
+}
+
+impl IntoResponse for Rejection {
+    fn into_response(self) -> Response {
+        let error = match self {
+            Rejection::TooDeep => ApiError::TooDeep,
+            Rejection::TooLong(TooLong { field, max }) => ApiError::ValidationFailed {
+                field: field.to_string(),
+                max,
+            },
+            Rejection::Other(resp) => return resp,
+        };
+        (StatusCode::UNPROCESSABLE_ENTITY, Json(error)).into_response()
+    }
+}
+
+/// Response mapper: gives every `413` a `payload_too_large` body, whether from
+/// `DefaultBodyLimit` or a handler.
+pub async fn payload_too_large(resp: Response) -> Response {
+    if resp.status() != StatusCode::PAYLOAD_TOO_LARGE {
+        return resp;
+    }
+    (StatusCode::PAYLOAD_TOO_LARGE, Json(ApiError::PayloadTooLarge)).into_response()
+}
+
+/// Payload length checks, run by `ValidJson` once the JSON has parsed. They
+/// measure the fields as sent, before `content::sanitize`, which is what
+/// `ContentLimits` advertises; stored text may come out longer.
+pub trait Validate {
+    fn validate(&self) -> Result<(), TooLong>;
+}
+
+fn check_len(field: &'static str, value: &str, max: usize) -> Result<(), TooLong> {
+    if value.chars().count() > max {
+        return Err(TooLong { field, max });
+    }
+    Ok(())
+}
+
+impl Validate for CreateComment {
+    fn validate(&self) -> Result<(), TooLong> {
+        check_len("body", &self.body, MAX_BODY_LEN)?;
+        check_len("post_title", self.post_title.as_deref().unwrap_or(""), MAX_TITLE_LEN)
+    }
+}
+
+impl Validate for CreateThread {
+    fn validate(&self) -> Result<(), TooLong> {
+        check_len("title", &self.title, MAX_TITLE_LEN)?;
+        check_len("body", &self.body, MAX_BODY_LEN)
+    }
+}
+
+impl Validate for CreateReply {
+    fn validate(&self) -> Result<(), TooLong> {
+        check_len("body", &self.body, MAX_BODY_LEN)
+    }
+}
+
+impl Validate for SendMessage {
+    fn validate(&self) -> Result<(), TooLong> {
+        check_len("body", &self.body, MAX_BODY_LEN)
+    }
+}
+
+impl Validate for Draft {
+    fn validate(&self) -> Result<(), TooLong> {
+        check_len("key", &self.key, MAX_DRAFT_KEY_LEN)?;
+        check_len("title", &self.title, MAX_TITLE_LEN)?;
+        check_len("body", &self.body, MAX_BODY_LEN)
+    }
+}
+
+/// Whether the JSON text nests arrays and objects deeper than `max`.
+fn nested_deeper_than(json: &[u8], max: usize) -> bool {
+    let (mut depth, mut in_string, mut escaped) = (0usize, false, false);
+    for &b in json {
+        if in_string {
+            match b {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match b {
+            b'"' => in_string = true,
+            b'[' | b'{' => {
+                depth += 1;
+                if depth > max {
+                    return true;
+                }
+            }
+            b']' | b'}' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    false
+}
+
+fn is_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .map(|v| v.trim().to_ascii_lowercase())
+        .is_some_and(|v| v == "application/json" || v.ends_with("+json"))
+}
+
+/// `Json` that also enforces `MAX_JSON_DEPTH` and the payload's `Validate`
+/// limits.
+pub struct ValidJson<T>(pub T);
+
+impl<T, S> FromRequest<S> for ValidJson<T>
+where
+    T: DeserializeOwned + Validate,
+    S: Send + Sync,
+{
+    type Rejection = Rejection;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        if !is_json(req.headers()) {
+            return Err(Rejection::Other(StatusCode::UNSUPPORTED_MEDIA_TYPE.into_response()));
+        }
+        let bytes = Bytes::from_request(req, state)
+            .await
+            .map_err(|e| Rejection::Other(e.into_response()))?;
+        if nested_deeper_than(&bytes, MAX_JSON_DEPTH) {
+            return Err(Rejection::TooDeep);
+        }
+        let Json(value) = Json::<T>::from_bytes(&bytes).map_err(|e| Rejection::Other(e.into_response()))?;
+        value.validate().map_err(Rejection::TooLong)?;
+        Ok(ValidJson(value))
+    }
+}
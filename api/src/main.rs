@@ -1,96 +1,415 @@
+mod account;
+mod activity;
+mod admin;
+mod analytics;
+mod attachments;
+mod audit;
 mod auth;
+mod authors;
+mod avatars;
+mod backup;
+mod badges;
+mod blocks;
+mod bookmarks;
+mod captcha;
 mod comments;
+mod config;
+mod content;
 mod db;
+mod discussions;
+mod digests;
+mod drafts;
+mod emails;
+mod feeds;
+mod flood;
+mod follows;
 mod forum;
 mod github_stats;
+mod graphql;
+mod health;
+mod idempotency;
+mod impersonation;
+mod import;
+mod ip_bans;
+mod limits;
+mod messages;
+mod moderation;
+mod notifications;
+mod post_settings;
+mod request_id;
+mod response_cache;
+mod scheduled;
+mod search;
+mod seed;
+mod site_stats;
+mod seo;
+mod sites;
+mod summaries;
+mod terms;
+mod unfurl;
+mod users;
 mod votes;
+mod wal;
+mod webhooks;
+mod webmentions;
+mod word_filters;
 
 use axum::{
+    extract::DefaultBodyLimit,
+    middleware,
     routing::{delete, get, post},
     Router,
 };
-use tower_http::cors::{AllowHeaders, AllowMethods, CorsLayer};
+use mikaana_shared::routes;
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, CorsLayer};
 
 pub type DbPool = r2d2::Pool<r2d2_sqlite::SqliteConnectionManager>;
 
 #[derive(Clone)]
 pub struct AppState {
+    /// The single writer connection; see `wal.rs`.
     pub db: DbPool,
+    /// Read-only connections, for handlers that never write.
+    pub reader: DbPool,
     pub jwt_secret: String,
     pub github_client_id: String,
     pub github_client_secret: String,
     pub api_url: String,
     pub cors_origin: String,
+    pub config: config::SharedConfig,
+    pub graphql: graphql::Schema,
+    pub features: mikaana_shared::Features,
 }
 
 #[tokio::main]
 async fn main() {
     let database_url =
         std::env::var("DATABASE_URL").unwrap_or_else(|_| "mikaana.db".to_string());
-    let manager = r2d2_sqlite::SqliteConnectionManager::file(&database_url);
-    let pool = r2d2::Pool::new(manager).expect("Failed to create DB pool");
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(result) = backup::run_cli(&args, &database_url) {
+        if let Err(e) = result {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let config: config::SharedConfig = std::sync::Arc::new(arc_swap::ArcSwap::from_pointee(
+        config::Config::load().expect("Failed to load configuration"),
+    ));
+
+    let manager = r2d2_sqlite::SqliteConnectionManager::file(&database_url)
+        .with_init(wal::init_connection(&config.load().database));
+    let pool = r2d2::Pool::builder()
+        .max_size(1)
+        .build(manager)
+        .expect("Failed to create DB pool");
 
     db::run_migrations(&pool).expect("Failed to run migrations");
 
+    sites::seed_all(&pool.get().expect("Failed to get DB connection"), &config.load())
+        .expect("Failed to seed site categories");
+
+    if args == ["seed-dev"] {
+        if let Err(e) = seed::run(&pool) {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(result) = import::run_cli(&args, &pool).await {
+        if let Err(e) = result {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let cors_origin =
         std::env::var("CORS_ORIGIN").unwrap_or_else(|_| "http://localhost:1313".to_string());
     let api_url =
         std::env::var("API_URL").unwrap_or_else(|_| "http://localhost:8080".to_string());
+    avatars::init(&api_url);
+    let features = config::features_from_env();
+
+    // After migrations, which create the file and switch it to WAL
+    let manager = r2d2_sqlite::SqliteConnectionManager::file(&database_url)
+        .with_flags(rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY | rusqlite::OpenFlags::SQLITE_OPEN_NO_MUTEX)
+        .with_init(wal::init_reader(&config.load().database));
+    let reader = r2d2::Pool::builder()
+        .max_size(config.load().database.read_connections)
+        .build(manager)
+        .expect("Failed to create read-only DB pool");
 
     let state = AppState {
+        graphql: graphql::build_schema(reader.clone(), features),
         db: pool,
+        reader,
         jwt_secret: std::env::var("JWT_SECRET")
             .unwrap_or_else(|_| "dev-secret-change-me".to_string()),
         github_client_id: std::env::var("GITHUB_CLIENT_ID").unwrap_or_default(),
         github_client_secret: std::env::var("GITHUB_CLIENT_SECRET").unwrap_or_default(),
         api_url,
         cors_origin: cors_origin.clone(),
+        config: config.clone(),
+        features,
     };
 
+    config::watch_sighup(config.clone(), state.db.clone());
+    webhooks::spawn_worker(state.db.clone());
+    webmentions::spawn_worker(state.db.clone());
+    unfurl::spawn_worker(state.db.clone());
+    digests::spawn_scheduler(&state);
+    notifications::spawn_mailer(&state);
+    wal::spawn_checkpointer(config.clone(), state.db.clone());
+    scheduled::spawn_publisher(config.clone(), state.db.clone());
+    if features.github_stats {
+        github_stats::warm_cache(&state.db).await;
+    }
+
+    // CORS_ORIGIN plus every configured site's origins, as of the latest reload
+    let cors_origin: axum::http::HeaderValue = cors_origin.parse().expect("Invalid CORS_ORIGIN");
     let cors = CorsLayer::new()
-        .allow_origin(
-            cors_origin
-                .parse::<axum::http::HeaderValue>()
-                .expect("Invalid CORS_ORIGIN"),
-        )
+        .allow_origin(AllowOrigin::predicate(move |origin, _| {
+            *origin == cors_origin
+                || origin
+                    .to_str()
+                    .ok()
+                    .and_then(|o| reqwest::Url::parse(o).ok())
+                    .is_some_and(|o| sites::is_site_origin(&config.load(), &o))
+        }))
         .allow_methods(AllowMethods::any())
-        .allow_headers(AllowHeaders::any());
+        .allow_headers(AllowHeaders::any())
+        // Read by the widgets to pace retries after a 429 or 503
+        .expose_headers([axum::http::header::RETRY_AFTER]);
 
-    let app = Router::new()
-        .route("/api/health", get(|| async { "ok" }))
+    let mut app = Router::new()
+        .route(routes::HEALTH, get(health::live))
+        .route(routes::HEALTH_LIVE, get(health::live))
+        .route(routes::HEALTH_READY, get(health::ready))
+        .route(routes::CONFIG, get(config::public_config))
         // Auth
-        .route("/api/auth/github", get(auth::github_login))
-        .route("/api/auth/callback", get(auth::github_callback))
-        .route("/api/auth/me", get(auth::me))
+        .route(routes::auth::GITHUB, get(auth::github_login))
+        .route(routes::auth::CALLBACK, get(auth::github_callback))
+        .route(
+            routes::auth::ME,
+            get(auth::me)
+                .put(account::update_profile)
+                .delete(account::delete_account),
+        )
+        .route(routes::auth::PROFILE, get(account::get_profile))
+        .route(routes::auth::EXPORT, get(account::export_data))
+        .route(routes::AVATAR, get(avatars::get_avatar))
+        .route(routes::TERMS_ACCEPT, post(terms::accept_terms))
         // Comments
         .route(
-            "/api/comments",
+            routes::comments::LIST,
             get(comments::list_comments).post(comments::create_comment),
         )
-        .route("/api/comments/{id}", delete(comments::delete_comment))
-        // Votes
+        .route(routes::comments::HTML, get(comments::list_comments_html))
+        .route(routes::comments::STATE, get(post_settings::get_comment_state))
+        .route(routes::COMMENT_FEED, get(feeds::comment_feed))
+        .route(routes::comments::COMMENT, delete(comments::delete_comment))
+        // Attachments
+        .route(
+            routes::uploads::UPLOADS,
+            post(attachments::upload).layer(DefaultBodyLimit::max(attachments::BODY_LIMIT)),
+        )
+        .route(routes::uploads::UPLOAD, get(attachments::serve_upload))
+        // Link previews
+        .route(routes::UNFURL, get(unfurl::get_preview))
+        // Webmentions
+        .route(routes::WEBMENTION, post(webmentions::receive))
+        .route(routes::WEBMENTIONS, get(webmentions::list))
+        // Analytics
+        .route(routes::EVENTS, post(analytics::record_event))
+        // Admin
+        .route(routes::admin::MERGE_USERS, post(admin::merge_users))
+        .route(routes::admin::AUDIT_EXPORT, get(admin::export_audit))
+        .route(routes::admin::CONFIG_RELOAD, post(admin::reload_config))
+        .route(routes::admin::BACKUP, post(backup::create_backup))
+        .route(routes::admin::CHECKPOINT, post(wal::force_checkpoint))
+        .route(routes::admin::EMAILS, get(emails::list_templates))
+        .route(routes::admin::EMAIL_PREVIEW, get(emails::preview))
+        .route(routes::admin::BULK, post(moderation::bulk_moderate))
+        .route(routes::admin::QUEUE, get(moderation::list_queue))
+        .route(routes::admin::SCHEDULED, get(scheduled::list_scheduled))
+        .route(routes::admin::ANALYTICS, get(analytics::summary))
+        .route(
+            routes::admin::POST_SETTINGS,
+            get(post_settings::list_settings)
+                .put(post_settings::update_settings)
+                .delete(post_settings::delete_settings),
+        )
+        .route(routes::admin::REPORTS, get(moderation::list_reports))
+        .route(
+            routes::admin::RESOLVE_REPORT,
+            post(moderation::resolve_report),
+        )
+        .route(
+            routes::admin::MODERATE,
+            post(moderation::moderate_content),
+        )
+        .route(routes::admin::USERS, get(moderation::list_users))
+        .route(
+            routes::admin::BAN,
+            post(moderation::ban_user).delete(moderation::unban_user),
+        )
         .route(
-            "/api/votes",
-            get(votes::get_votes).post(votes::cast_vote),
+            routes::admin::IMPERSONATE,
+            post(impersonation::impersonate),
         )
-        // GitHub Stats
-        .route("/api/github-stats", get(github_stats::get_github_stats))
-        // Forum
-        .route("/api/forum/categories", get(forum::list_categories))
         .route(
-            "/api/forum/threads",
-            get(forum::list_threads).post(forum::create_thread),
+            routes::admin::WEBHOOKS,
+            get(webhooks::list_webhooks).post(webhooks::create_webhook),
         )
-        .route("/api/forum/threads/{id}", get(forum::get_thread))
+        .route(routes::admin::WEBHOOK, delete(webhooks::delete_webhook))
         .route(
-            "/api/forum/threads/{id}/replies",
-            post(forum::create_reply),
+            routes::admin::WORD_FILTERS,
+            get(word_filters::list_filters).post(word_filters::create_filter),
         )
+        .route(routes::admin::WORD_FILTER, delete(word_filters::delete_filter))
+        .route(
+            routes::admin::IP_BANS,
+            get(ip_bans::list_bans).post(ip_bans::create_ban),
+        )
+        .route(routes::admin::IP_BAN, delete(ip_bans::delete_ban))
+        // Reports
+        .route(routes::REPORTS, post(moderation::create_report))
+        // Bookmarks
+        .route(
+            routes::bookmarks::LIST,
+            get(bookmarks::list_bookmarks).post(bookmarks::add_bookmark),
+        )
+        .route(
+            routes::bookmarks::BOOKMARK,
+            delete(bookmarks::remove_bookmark),
+        )
+        // Drafts
+        .route(
+            routes::drafts::DRAFTS,
+            get(drafts::get_draft)
+                .put(drafts::save_draft)
+                .delete(drafts::delete_draft),
+        )
+        // Users
+        .route(routes::users::SEARCH, get(users::search_users))
+        // Follows
+        .route(
+            routes::users::FOLLOW,
+            post(follows::follow).delete(follows::unfollow),
+        )
+        .route(routes::users::FOLLOWS, get(follows::list_following))
+        .route(
+            routes::users::BLOCK,
+            post(blocks::block).delete(blocks::unblock),
+        )
+        .route(routes::users::BLOCKS, get(blocks::list_blocked))
+        .route(routes::users::FEED, get(follows::feed))
+        // Messages
+        .route(routes::messages::INBOX, get(messages::inbox).post(messages::send_message))
+        .route(routes::messages::CONVERSATION, get(messages::conversation))
+        // Notifications
+        .route(routes::notifications::LIST, get(notifications::list_notifications))
+        .route(routes::notifications::READ_ALL, post(notifications::mark_all_read))
+        .route(routes::notifications::READ, post(notifications::mark_read))
+        .route(
+            routes::notifications::UNSUBSCRIBE,
+            get(emails::unsubscribe).post(emails::unsubscribe),
+        )
+        .route(
+            routes::notifications::PREFERENCES,
+            get(notifications::get_preferences).put(notifications::update_preferences),
+        )
+        // GraphQL
+        .route(
+            routes::GRAPHQL,
+            get(graphql::graphiql).post(graphql::graphql_handler),
+        )
+        // Site Stats
+        .route(routes::STATS, get(site_stats::get_stats))
+        .route(routes::ACTIVITY, get(activity::recent_activity));
+
+    // Optional subsystems, see `config::features_from_env`
+    if features.votes {
+        app = app
+            .route(
+                routes::votes::VOTES,
+                get(votes::get_votes).post(votes::cast_vote),
+            )
+            .route(
+                routes::votes::POST,
+                get(votes::get_post_votes).post(votes::cast_post_vote),
+            );
+    }
+    if features.github_stats {
+        app = app.route(routes::GITHUB_STATS, get(github_stats::get_github_stats));
+    }
+    if features.forum {
+        app = app
+            .route(routes::forum::CATEGORIES, get(forum::list_categories))
+            .route(routes::forum::CATEGORY, get(forum::get_category))
+            .route(
+                routes::forum::SUBSCRIBE,
+                post(forum::subscribe_category).delete(forum::unsubscribe_category),
+            )
+            .route(
+                routes::forum::THREADS,
+                get(forum::list_threads).post(forum::create_thread),
+            )
+            .route(
+                routes::forum::THREAD,
+                get(forum::get_thread).delete(forum::delete_thread),
+            )
+            .route(routes::forum::THREAD_HTML, get(seo::thread_snapshot))
+            .route(routes::forum::PREVIEW, get(seo::thread_preview))
+            .route(
+                routes::forum::REPLIES,
+                post(forum::create_reply),
+            )
+            .route(routes::forum::REPLY, delete(forum::delete_reply))
+            .route(routes::forum::MOVE, post(forum::move_thread))
+            .route(
+                routes::forum::PIN,
+                post(forum::pin_thread).delete(forum::unpin_thread),
+            )
+            .route(
+                routes::forum::SOLUTION,
+                post(forum::set_solution),
+            )
+            .route(
+                routes::forum::SUMMARY,
+                get(summaries::get_summary),
+            )
+            .route(routes::forum::SEARCH, get(search::search_forum))
+            .route(routes::CATEGORY_FEED, get(feeds::category_feed))
+            .route(routes::THREAD_FEED, get(feeds::thread_feed))
+            .route(routes::FORUM_SITEMAP, get(seo::forum_sitemap));
+    }
+
+    let app = app
+        .layer(middleware::from_fn(response_cache::invalidate_on_write))
+        .layer(middleware::from_fn_with_state(state.clone(), idempotency::deduplicate))
+        // Outside deduplicate, so a write refused here can be retried with
+        // the same key once the terms are accepted
+        .layer(middleware::from_fn_with_state(state.clone(), terms::enforce))
+        .layer(middleware::from_fn_with_state(state.clone(), impersonation::enforce))
+        .layer(middleware::from_fn_with_state(state.clone(), ip_bans::enforce))
+        .layer(DefaultBodyLimit::max(limits::JSON_BODY_LIMIT))
+        .layer(middleware::map_response(limits::payload_too_large))
+        .layer(middleware::from_fn(request_id::assign))
         .layer(cors)
         .with_state(state);
 
     let addr = "0.0.0.0:8080";
     println!("API server listening on {addr}");
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+    )
+    .await
+    .unwrap();
 }
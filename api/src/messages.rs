@@ -0,0 +1,227 @@
+//! Private messages between users.
+//!
+//! Messages are instance-wide like accounts, and there is no group chat: a
+//! conversation is everything two users sent each other. Opening a
+//! conversation marks what the other side sent as read.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use mikaana_shared::{Conversation, Inbox, Message, PaginatedCursor, SendMessage, UserId};
+use rusqlite::{Connection, OptionalExtension};
+use serde::Deserialize;
+
+use crate::{auth, content, limits::ValidJson, AppState};
+
+const PAGE_SIZE: i64 = 50;
+
+/// Conversations listed in the inbox.
+const MAX_CONVERSATIONS: i64 = 100;
+
+// ── Queries ──
+
+/// Columns read by `message_from_row`, in order.
+const MESSAGE_SELECT: &str = "SELECT m.id, m.body, m.read_at IS NOT NULL, m.created_at,
+        s.id, s.username, s.avatar_url, s.display_name,
+        r.id, r.username, r.avatar_url, r.display_name
+ FROM messages m
+ JOIN users s ON s.id = m.sender_id
+ JOIN users r ON r.id = m.recipient_id";
+
+fn message_from_row(row: &rusqlite::Row) -> rusqlite::Result<Message> {
+    Ok(Message {
+        id: row.get(0)?,
+        body: row.get(1)?,
+        read: row.get(2)?,
+        created_at: row.get(3)?,
+        sender: auth::user_from_row(row, 4)?,
+        recipient: auth::user_from_row(row, 8)?,
+    })
+}
+
+/// Unread messages addressed to `user_id`.
+pub fn unread_count(conn: &Connection, user_id: UserId) -> rusqlite::Result<i64> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM messages WHERE recipient_id = ?1 AND read_at IS NULL",
+        [user_id],
+        |row| row.get(0),
+    )
+}
+
+fn query_inbox(conn: &Connection, user_id: UserId) -> rusqlite::Result<Inbox> {
+    // Latest message per counterpart
+    let mut stmt = conn.prepare_cached(&format!(
+        "{MESSAGE_SELECT}
+         WHERE m.id IN (
+             SELECT MAX(id) FROM messages WHERE sender_id = ?1 OR recipient_id = ?1
+             GROUP BY CASE WHEN sender_id = ?1 THEN recipient_id ELSE sender_id END)
+         ORDER BY m.id DESC
+         LIMIT ?2"
+    ))?;
+    let latest: Vec<Message> = stmt
+        .query_map(rusqlite::params![user_id, MAX_CONVERSATIONS], message_from_row)?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let mut conversations = Vec::with_capacity(latest.len());
+    for last_message in latest {
+        let with = if last_message.sender.id == user_id {
+            last_message.recipient.clone()
+        } else {
+            last_message.sender.clone()
+        };
+        let unread = conn.query_row(
+            "SELECT COUNT(*) FROM messages
+             WHERE sender_id = ?1 AND recipient_id = ?2 AND read_at IS NULL",
+            [with.id, user_id],
+            |row| row.get(0),
+        )?;
+        conversations.push(Conversation {
+            with,
+            last_message,
+            unread,
+        });
+    }
+
+    Ok(Inbox {
+        conversations,
+        unread: unread_count(conn, user_id)?,
+    })
+}
+
+// ── Handlers ──
+
+/// GET /api/messages — the viewer's conversations
+pub async fn inbox(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Inbox>, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+
+    let pool = state.reader.clone();
+    let inbox = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        query_inbox(&conn, user_id).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(inbox))
+}
+
+#[derive(Deserialize)]
+pub struct ConversationParams {
+    /// `next` from the previous page, for older messages.
+    before: Option<i64>,
+}
+
+/// GET /api/messages/:user_id?before= — messages exchanged with one user,
+/// newest first; marks the ones they sent as read
+pub async fn conversation(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(other_id): Path<UserId>,
+    Query(params): Query<ConversationParams>,
+) -> Result<Json<PaginatedCursor<Message>>, StatusCode> {
+    let claims = auth::extract_claims(&headers, &state.jwt_secret)?;
+    let user_id = claims.sub;
+    let marks_read = claims.records_activity();
+
+    let pool = if marks_read { state.db.clone() } else { state.reader.clone() };
+    let page = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let mut stmt = conn
+            .prepare_cached(&format!(
+                "{MESSAGE_SELECT}
+                 WHERE ((m.sender_id = ?1 AND m.recipient_id = ?2)
+                     OR (m.sender_id = ?2 AND m.recipient_id = ?1))
+                   AND (?3 IS NULL OR m.id < ?3)
+                 ORDER BY m.id DESC
+                 LIMIT ?4"
+            ))
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let mut items: Vec<Message> = stmt
+            .query_map(
+                rusqlite::params![user_id, other_id, params.before, PAGE_SIZE + 1],
+                message_from_row,
+            )
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .filter_map(|r| r.ok())
+            .collect();
+        let next = if items.len() as i64 > PAGE_SIZE {
+            items.truncate(PAGE_SIZE as usize);
+            items.last().map(|m| m.id.to_string())
+        } else {
+            None
+        };
+
+        if marks_read {
+            conn.execute(
+                "UPDATE messages SET read_at = datetime('now')
+                 WHERE sender_id = ?1 AND recipient_id = ?2 AND read_at IS NULL",
+                [other_id, user_id],
+            )
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        }
+
+        Ok::<_, StatusCode>(PaginatedCursor {
+            items,
+            next,
+            per_page: PAGE_SIZE,
+        })
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(page))
+}
+
+/// POST /api/messages
+pub async fn send_message(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    ValidJson(payload): ValidJson<SendMessage>,
+) -> Result<Json<Message>, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+    let body = content::sanitize(&payload.body);
+    if body.trim().is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let pool = state.db.clone();
+    let message = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_active(&conn, user_id)?;
+
+        let recipient_id: UserId = conn
+            .query_row(
+                "SELECT id FROM users
+                 WHERE username = ?1 COLLATE NOCASE
+                   AND deleted_at IS NULL AND merged_into IS NULL AND guest_key IS NULL",
+                [payload.to.trim()],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .ok_or(StatusCode::NOT_FOUND)?;
+        if recipient_id == user_id {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+
+        conn.execute(
+            "INSERT INTO messages (sender_id, recipient_id, body) VALUES (?1, ?2, ?3)",
+            rusqlite::params![user_id, recipient_id, body],
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let id = conn.last_insert_rowid();
+
+        conn.query_row(&format!("{MESSAGE_SELECT} WHERE m.id = ?1"), [id], message_from_row)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(message))
+}
@@ -0,0 +1,669 @@
+//! Reports, the moderation queue and user bans.
+//!
+//! Comments, threads and replies carry a `status`: `published` content is
+//! public, `pending` waits in the queue for a moderator, `removed` is hidden
+//! but kept so a removal can be undone. Reporting is open to any signed-in
+//! user; everything under `/api/admin/moderation` and `/api/admin/users`
+//! needs the moderator or admin role.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use mikaana_shared::{
+    AdminUser, BanUser, BulkAction, BulkModerate, BulkSummary, ContentReport, CreateReport,
+    ModerateContent, ModerationAction, ModerationItem, UserId, MAX_REPORT_REASON_LEN,
+};
+use serde::Deserialize;
+
+use crate::{
+    audit, auth,
+    notifications::{self, Notice},
+    sites, webhooks, AppState,
+};
+
+// ── Queries ──
+
+/// Table holding a moderatable content type.
+pub fn content_table(target_type: &str) -> Option<&'static str> {
+    match target_type {
+        "comment" => Some("comments"),
+        "thread" => Some("threads"),
+        "reply" => Some("replies"),
+        _ => None,
+    }
+}
+
+/// Whether the item exists and is visible to the public.
+pub fn is_published(conn: &rusqlite::Connection, target_type: &str, target_id: i64) -> bool {
+    let Some(table) = content_table(target_type) else {
+        return false;
+    };
+    conn.query_row(
+        &format!("SELECT status = 'published' FROM {table} WHERE id = ?1"),
+        [target_id],
+        |row| row.get(0),
+    )
+    .unwrap_or(false)
+}
+
+/// Whether `viewer` may see the item: published, or theirs, or they
+/// moderate. Scheduled, pending and removed items are hidden from everyone
+/// else; deleted ones from everyone but moderators.
+pub fn is_visible_to(
+    conn: &rusqlite::Connection,
+    target_type: &str,
+    target_id: i64,
+    viewer: Option<UserId>,
+) -> bool {
+    if is_published(conn, target_type, target_id) {
+        return true;
+    }
+    let (Some(uid), Some(table)) = (viewer, content_table(target_type)) else {
+        return false;
+    };
+    let author: Option<UserId> = conn
+        .query_row(
+            &format!("SELECT user_id FROM {table} WHERE id = ?1 AND status != 'deleted'"),
+            [target_id],
+            |row| row.get(0),
+        )
+        .ok();
+    author == Some(uid) || auth::require_moderator(conn, uid).is_ok()
+}
+
+/// All content types as one relation; columns read by `item_from_row`.
+const ITEM_SELECT: &str = "SELECT i.target_type, i.id, i.title, i.body, i.status, i.created_at,
+        i.post_slug, i.thread_id, u.id, u.username, u.avatar_url, u.display_name
+ FROM (
+     SELECT 'comment' AS target_type, id, NULL AS title, body, status, created_at,
+            post_slug, NULL AS thread_id, user_id
+     FROM comments
+     UNION ALL
+     SELECT 'thread', id, title, body, status, created_at, NULL, id, user_id
+     FROM threads
+     UNION ALL
+     SELECT 'reply', id, NULL, body, status, created_at, NULL, thread_id, user_id
+     FROM replies
+ ) i
+ JOIN users u ON i.user_id = u.id";
+
+fn item_from_row(row: &rusqlite::Row) -> rusqlite::Result<ModerationItem> {
+    Ok(ModerationItem {
+        target_type: row.get(0)?,
+        target_id: row.get(1)?,
+        title: row.get(2)?,
+        body: row.get(3)?,
+        status: row.get(4)?,
+        created_at: row.get(5)?,
+        post_slug: row.get(6)?,
+        thread_id: row.get(7)?,
+        author: auth::user_from_row(row, 8)?,
+    })
+}
+
+pub fn query_item(
+    conn: &rusqlite::Connection,
+    target_type: &str,
+    target_id: i64,
+) -> rusqlite::Result<ModerationItem> {
+    conn.query_row(
+        &format!("{ITEM_SELECT} WHERE i.target_type = ?1 AND i.id = ?2"),
+        rusqlite::params![target_type, target_id],
+        item_from_row,
+    )
+}
+
+/// Content awaiting approval, oldest first.
+pub fn query_pending(conn: &rusqlite::Connection) -> rusqlite::Result<Vec<ModerationItem>> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "{ITEM_SELECT} WHERE i.status = 'pending' ORDER BY i.created_at ASC LIMIT 200"
+    ))?;
+    let rows = stmt
+        .query_map([], item_from_row)?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
+
+/// Columns read by `report_from_row`, in order.
+const REPORT_SELECT: &str = "SELECT r.id, r.reason, r.created_at, r.target_type, r.target_id,
+        u.id, u.username, u.avatar_url, u.display_name
+ FROM reports r JOIN users u ON r.reporter_id = u.id";
+
+fn report_from_row(conn: &rusqlite::Connection, row: &rusqlite::Row) -> rusqlite::Result<ContentReport> {
+    let target_type: String = row.get(3)?;
+    let target_id: i64 = row.get(4)?;
+    Ok(ContentReport {
+        id: row.get(0)?,
+        reason: row.get(1)?,
+        created_at: row.get(2)?,
+        reporter: auth::user_from_row(row, 5)?,
+        item: query_item(conn, &target_type, target_id).ok(),
+    })
+}
+
+fn query_report(conn: &rusqlite::Connection, id: i64) -> rusqlite::Result<ContentReport> {
+    conn.query_row(&format!("{REPORT_SELECT} WHERE r.id = ?1"), [id], |row| {
+        report_from_row(conn, row)
+    })
+}
+
+fn query_open_reports(conn: &rusqlite::Connection) -> rusqlite::Result<Vec<ContentReport>> {
+    let mut stmt = conn.prepare_cached(&format!(
+        "{REPORT_SELECT}
+         WHERE r.resolved_at IS NULL
+         ORDER BY r.created_at ASC
+         LIMIT 200"
+    ))?;
+    let rows = stmt
+        .query_map([], |row| report_from_row(conn, row))?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
+
+fn query_recent_users(conn: &rusqlite::Connection, limit: i64) -> rusqlite::Result<Vec<AdminUser>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT u.id, u.username, u.avatar_url, u.display_name, u.role, u.created_at,
+                u.banned_at IS NOT NULL, u.ban_reason,
+                (SELECT COUNT(*) FROM comments WHERE user_id = u.id),
+                (SELECT COUNT(*) FROM threads WHERE user_id = u.id AND moved_to IS NULL),
+                (SELECT COUNT(*) FROM replies WHERE user_id = u.id)
+         FROM users u
+         WHERE u.merged_into IS NULL AND u.deleted_at IS NULL
+         ORDER BY u.created_at DESC, u.id DESC
+         LIMIT ?1",
+    )?;
+    let rows = stmt
+        .query_map([limit], |row| {
+            Ok(AdminUser {
+                user: auth::user_from_row(row, 0)?,
+                role: row.get(4)?,
+                created_at: row.get(5)?,
+                banned: row.get(6)?,
+                ban_reason: row.get(7)?,
+                comments: row.get(8)?,
+                threads: row.get(9)?,
+                replies: row.get(10)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
+
+// ── Reports ──
+
+/// POST /api/reports — flag content for moderators
+pub async fn create_report(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<CreateReport>,
+) -> Result<StatusCode, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+    let site = sites::resolve(&headers, &state.config.load())?;
+
+    let reason = payload.reason.trim().to_string();
+    if reason.chars().count() > MAX_REPORT_REASON_LEN {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if content_table(&payload.target_type).is_none() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let pool = state.db.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_active(&conn, user_id)?;
+
+        let visible = sites::owns(&conn, &site, &payload.target_type, payload.target_id)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            && is_published(&conn, &payload.target_type, payload.target_id);
+        if !visible {
+            return Err(StatusCode::NOT_FOUND);
+        }
+
+        // Reporting the same item twice is a no-op
+        let inserted = conn
+            .execute(
+                "INSERT OR IGNORE INTO reports (reporter_id, target_type, target_id, reason)
+                 VALUES (?1, ?2, ?3, ?4)",
+                rusqlite::params![user_id, payload.target_type, payload.target_id, reason],
+            )
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        if inserted > 0 {
+            let report = query_report(&conn, conn.last_insert_rowid())
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            webhooks::enqueue(&conn, &site, webhooks::REPORT_CREATED, &report)
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        }
+
+        Ok::<_, StatusCode>(())
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// ── Moderation ──
+
+/// GET /api/admin/moderation/queue — pending content
+pub async fn list_queue(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<ModerationItem>>, StatusCode> {
+    let mod_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+
+    let pool = state.reader.clone();
+    let items = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_moderator(&conn, mod_id)?;
+        query_pending(&conn).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(items))
+}
+
+/// GET /api/admin/moderation/reports — unresolved reports
+pub async fn list_reports(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<ContentReport>>, StatusCode> {
+    let mod_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+
+    let pool = state.reader.clone();
+    let reports = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_moderator(&conn, mod_id)?;
+        query_open_reports(&conn).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(reports))
+}
+
+/// POST /api/admin/moderation/reports/:id/resolve — dismiss a report
+pub async fn resolve_report(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, StatusCode> {
+    let mod_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+
+    let pool = state.db.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_moderator(&conn, mod_id)?;
+
+        let updated = conn
+            .execute(
+                "UPDATE reports SET resolved_at = datetime('now'), resolved_by = ?2
+                 WHERE id = ?1 AND resolved_at IS NULL",
+                rusqlite::params![id, mod_id],
+            )
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if updated == 0 {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        Ok(())
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Set an item's status and close its reports, in the caller's transaction.
+pub fn apply_action(
+    conn: &rusqlite::Connection,
+    mod_id: UserId,
+    target_type: &str,
+    target_id: i64,
+    action: ModerationAction,
+) -> Result<(), StatusCode> {
+    let table = content_table(target_type).ok_or(StatusCode::BAD_REQUEST)?;
+    let (status, audit_action) = match action {
+        ModerationAction::Approve => ("published", "content.approve"),
+        ModerationAction::Remove => ("removed", "content.remove"),
+    };
+
+    let (author_id, previous): (UserId, String) = conn
+        .query_row(
+            &format!("SELECT user_id, status FROM {table} WHERE id = ?1"),
+            [target_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    // Its author took it down; there's nothing left to approve or remove
+    if previous == "deleted" {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    conn.execute(
+        &format!("UPDATE {table} SET status = ?2 WHERE id = ?1"),
+        rusqlite::params![target_id, status],
+    )
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Tell the author when their post's fate changes; approving a held post
+    // also sends the notifications its publication would have
+    if previous != status {
+        if previous == "pending" && status == "published" {
+            notifications::announce(conn, target_type, target_id)
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        }
+        if author_id != mod_id {
+            let summary = match action {
+                ModerationAction::Approve => format!("Your {target_type} was approved"),
+                ModerationAction::Remove => format!("Your {target_type} was removed by a moderator"),
+            };
+            notifications::notify(
+                conn,
+                author_id,
+                &Notice {
+                    kind: "moderation",
+                    actor_id: mod_id,
+                    target_type,
+                    target_id,
+                    summary: &summary,
+                },
+            )
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        }
+    }
+
+    conn.execute(
+        "UPDATE reports SET resolved_at = datetime('now'), resolved_by = ?3
+         WHERE target_type = ?1 AND target_id = ?2 AND resolved_at IS NULL",
+        rusqlite::params![target_type, target_id, mod_id],
+    )
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    audit::record(
+        conn,
+        mod_id,
+        audit_action,
+        target_type,
+        target_id,
+        serde_json::json!({}),
+    )
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// POST /api/admin/moderation/content — approve or remove one item
+pub async fn moderate_content(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<ModerateContent>,
+) -> Result<Json<ModerationItem>, StatusCode> {
+    let mod_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+
+    let pool = state.db.clone();
+    let item = tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_moderator(&conn, mod_id)?;
+
+        let tx = conn
+            .transaction()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        apply_action(&tx, mod_id, &payload.target_type, payload.target_id, payload.action)?;
+        let item = query_item(&tx, &payload.target_type, payload.target_id)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        tx.commit().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok::<_, StatusCode>(item)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(item))
+}
+
+/// Most items one bulk request may name; an author's posts don't count.
+const MAX_BULK_ITEMS: usize = 500;
+
+/// Content types and their tables.
+const CONTENT_TYPES: [(&str, &str); 3] =
+    [("comment", "comments"), ("thread", "threads"), ("reply", "replies")];
+
+/// The items a bulk request applies to, as type, table and id: those it
+/// names, then the author's posts the action would change, without repeats.
+fn bulk_targets(
+    conn: &rusqlite::Connection,
+    payload: &BulkModerate,
+) -> Result<Vec<(&'static str, &'static str, i64)>, StatusCode> {
+    let mut targets = Vec::new();
+    for item in &payload.items {
+        let (target_type, table) = CONTENT_TYPES
+            .into_iter()
+            .find(|(t, _)| *t == item.target_type)
+            .ok_or(StatusCode::BAD_REQUEST)?;
+        targets.push((target_type, table, item.target_id));
+    }
+    if let Some(author_id) = payload.author_id {
+        let status = match payload.action {
+            BulkAction::Approve => "status = 'pending'",
+            BulkAction::Remove => "status NOT IN ('removed', 'deleted')",
+            BulkAction::Reassign => "1",
+        };
+        for (target_type, table) in CONTENT_TYPES {
+            let ids: Vec<i64> = conn
+                .prepare_cached(&format!("SELECT id FROM {table} WHERE user_id = ?1 AND {status}"))
+                .and_then(|mut stmt| {
+                    stmt.query_map([author_id], |row| row.get(0))?
+                        .collect::<rusqlite::Result<_>>()
+                })
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            targets.extend(ids.into_iter().map(|id| (target_type, table, id)));
+        }
+    }
+    let mut seen = std::collections::HashSet::new();
+    targets.retain(|target| seen.insert(*target));
+    Ok(targets)
+}
+
+/// Credit an item to another account, in the caller's transaction.
+fn reassign(
+    conn: &rusqlite::Connection,
+    admin_id: UserId,
+    table: &str,
+    target_type: &str,
+    target_id: i64,
+    into: UserId,
+) -> Result<(), StatusCode> {
+    let from: UserId = conn
+        .query_row(
+            &format!("SELECT user_id FROM {table} WHERE id = ?1"),
+            [target_id],
+            |row| row.get(0),
+        )
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    conn.execute(
+        &format!("UPDATE {table} SET user_id = ?2 WHERE id = ?1"),
+        rusqlite::params![target_id, into],
+    )
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    audit::record(
+        conn,
+        admin_id,
+        "content.reassign",
+        target_type,
+        target_id,
+        serde_json::json!({ "from_user_id": from, "into_user_id": into }),
+    )
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// POST /api/admin/bulk — approve, remove or reassign many items at once, in
+/// one transaction. Reassigning needs the admin role.
+pub async fn bulk_moderate(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<BulkModerate>,
+) -> Result<Json<BulkSummary>, StatusCode> {
+    let mod_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+    if payload.items.len() > MAX_BULK_ITEMS
+        || (payload.items.is_empty() && payload.author_id.is_none())
+        || (payload.action == BulkAction::Reassign) != payload.into_user_id.is_some()
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let pool = state.db.clone();
+    let summary = tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        match payload.action {
+            BulkAction::Reassign => auth::require_admin(&conn, mod_id)?,
+            _ => auth::require_moderator(&conn, mod_id)?,
+        }
+        if let Some(into) = payload.into_user_id {
+            let live: bool = conn
+                .query_row(
+                    "SELECT merged_into IS NULL AND deleted_at IS NULL FROM users WHERE id = ?1",
+                    [into],
+                    |row| row.get(0),
+                )
+                .map_err(|_| StatusCode::NOT_FOUND)?;
+            if !live {
+                return Err(StatusCode::CONFLICT);
+            }
+        }
+
+        let tx = conn
+            .transaction()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let mut summary = BulkSummary::default();
+        for (target_type, table, target_id) in bulk_targets(&tx, &payload)? {
+            match (payload.action, payload.into_user_id) {
+                (BulkAction::Approve, _) => {
+                    apply_action(&tx, mod_id, target_type, target_id, ModerationAction::Approve)?
+                }
+                (BulkAction::Remove, _) => {
+                    apply_action(&tx, mod_id, target_type, target_id, ModerationAction::Remove)?
+                }
+                (BulkAction::Reassign, Some(into)) => {
+                    reassign(&tx, mod_id, table, target_type, target_id, into)?
+                }
+                (BulkAction::Reassign, None) => return Err(StatusCode::BAD_REQUEST),
+            }
+            *match target_type {
+                "comment" => &mut summary.comments,
+                "thread" => &mut summary.threads,
+                _ => &mut summary.replies,
+            } += 1;
+        }
+        tx.commit().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok::<_, StatusCode>(summary)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(summary))
+}
+
+// ── Users ──
+
+#[derive(Deserialize)]
+pub struct UserListParams {
+    limit: Option<i64>,
+}
+
+/// GET /api/admin/users?limit=50 — newest accounts first
+pub async fn list_users(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<UserListParams>,
+) -> Result<Json<Vec<AdminUser>>, StatusCode> {
+    let mod_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+    let limit = params.limit.unwrap_or(50).clamp(1, 200);
+
+    let pool = state.reader.clone();
+    let users = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_moderator(&conn, mod_id)?;
+        query_recent_users(&conn, limit).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(users))
+}
+
+/// Ban (`reason` set) or unban; staff accounts can't be banned this way.
+async fn set_ban(
+    state: AppState,
+    headers: HeaderMap,
+    user_id: UserId,
+    reason: Option<String>,
+) -> Result<StatusCode, StatusCode> {
+    let mod_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+
+    let pool = state.db.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_moderator(&conn, mod_id)?;
+
+        let tx = conn
+            .transaction()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        let role: String = tx
+            .query_row("SELECT role FROM users WHERE id = ?1", [user_id], |row| {
+                row.get(0)
+            })
+            .map_err(|_| StatusCode::NOT_FOUND)?;
+        if role != "user" {
+            return Err(StatusCode::FORBIDDEN);
+        }
+
+        let banned = reason.is_some();
+        tx.execute(
+            "UPDATE users
+             SET banned_at = CASE WHEN ?2 THEN datetime('now') END, ban_reason = ?3
+             WHERE id = ?1",
+            rusqlite::params![user_id, banned, reason],
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        audit::record(
+            &tx,
+            mod_id,
+            if banned { "user.ban" } else { "user.unban" },
+            "user",
+            user_id.0,
+            serde_json::json!({ "reason": reason }),
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        tx.commit().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        Ok(StatusCode::NO_CONTENT)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+}
+
+/// POST /api/admin/users/:id/ban — stop a user from posting and voting
+pub async fn ban_user(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(user_id): Path<UserId>,
+    Json(payload): Json<BanUser>,
+) -> Result<StatusCode, StatusCode> {
+    set_ban(state, headers, user_id, Some(payload.reason.trim().to_string())).await
+}
+
+/// DELETE /api/admin/users/:id/ban
+pub async fn unban_user(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(user_id): Path<UserId>,
+) -> Result<StatusCode, StatusCode> {
+    set_ban(state, headers, user_id, None).await
+}
@@ -0,0 +1,595 @@
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use mikaana_shared::{
+    DigestFrequency, Notification, NotificationList, NotificationPrefs, ThreadId, UserId,
+};
+use regex::Regex;
+use rusqlite::Connection;
+use serde::Deserialize;
+
+use crate::{
+    auth,
+    emails::{self, Email, List, Template, Vars},
+    messages, sites, AppState,
+};
+
+/// `@login`, GitHub's rules: letters, digits and inner hyphens, at most 39.
+/// Not preceded by a word character, so e-mail addresses don't count.
+static MENTION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:^|[^\w@/.])@([A-Za-z0-9][A-Za-z0-9-]{0,38})").unwrap());
+
+/// Mentions notified per post, so a list of names can't page everyone.
+const MAX_MENTIONS: usize = 10;
+
+const PAGE_SIZE: i64 = 20;
+
+const EMAIL_POLL_INTERVAL: Duration = Duration::from_secs(30);
+const EMAIL_BATCH_SIZE: i64 = 50;
+
+/// A notification to deliver to one user.
+pub struct Notice<'a> {
+    pub kind: &'a str,
+    pub actor_id: UserId,
+    pub target_type: &'a str,
+    pub target_id: i64,
+    /// Short text shown in the notification list, e.g. a thread title.
+    pub summary: &'a str,
+}
+
+/// Record a notification. Users who opted into email get replies, mentions
+/// and moderation decisions queued for the mailer as well; the rest only
+/// reach email through digests.
+pub fn notify(conn: &Connection, user_id: UserId, notice: &Notice) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO notifications (user_id, kind, actor_id, target_type, target_id, summary, email_state)
+         SELECT id, ?2, ?3, ?4, ?5, ?6,
+                CASE WHEN ?7 AND notify_email = 1 AND email IS NOT NULL THEN 'pending' ELSE 'none' END
+         FROM users WHERE id = ?1",
+        rusqlite::params![
+            user_id,
+            notice.kind,
+            notice.actor_id,
+            notice.target_type,
+            notice.target_id,
+            notice.summary,
+            email_template(notice.kind).is_some()
+        ],
+    )?;
+    Ok(())
+}
+
+/// Notify everyone following `category_id` except the actor; returns who
+/// was notified.
+pub fn notify_category_subscribers(
+    conn: &Connection,
+    category_id: i64,
+    notice: &Notice,
+) -> rusqlite::Result<Vec<UserId>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT user_id FROM category_subscriptions WHERE category_id = ?1 AND user_id != ?2",
+    )?;
+    let subscribers = stmt
+        .query_map(rusqlite::params![category_id, notice.actor_id], |row| {
+            row.get(0)
+        })?
+        .filter_map(|r| r.ok())
+        .collect::<Vec<_>>();
+
+    for &user_id in &subscribers {
+        notify(conn, user_id, notice)?;
+    }
+    Ok(subscribers)
+}
+
+/// Usernames `@mentioned` in `body`, in order, without repeats.
+fn mentions(body: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for cap in MENTION.captures_iter(body) {
+        let name = cap[1].to_ascii_lowercase();
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names.truncate(MAX_MENTIONS);
+    names
+}
+
+/// Notifications for a comment, thread or reply that just became visible,
+/// on posting or on approval from the queue: the thread's author for a
+/// reply, category subscribers for a thread, and whoever it `@mentions`.
+/// Each person hears about it once.
+pub fn announce(conn: &Connection, target_type: &str, target_id: i64) -> rusqlite::Result<()> {
+    let sql = match target_type {
+        "comment" => "SELECT user_id, body, post_slug, NULL FROM comments WHERE id = ?1",
+        "thread" => "SELECT user_id, body, title, category_id FROM threads WHERE id = ?1",
+        "reply" => {
+            "SELECT r.user_id, r.body, t.title, t.user_id
+             FROM replies r JOIN threads t ON t.id = r.thread_id WHERE r.id = ?1"
+        }
+        _ => return Ok(()),
+    };
+    // `related` is the thread's category for threads, its author for replies
+    let (actor_id, body, summary, related): (UserId, String, String, Option<i64>) =
+        conn.query_row(sql, [target_id], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })?;
+    let notice = |kind| Notice {
+        kind,
+        actor_id,
+        target_type,
+        target_id,
+        summary: &summary,
+    };
+
+    let mut told = vec![actor_id];
+    match (target_type, related) {
+        ("thread", Some(category_id)) => {
+            told.extend(notify_category_subscribers(conn, category_id, &notice("new_thread"))?);
+        }
+        ("reply", Some(author_id)) if author_id != actor_id.0 => {
+            notify(conn, UserId(author_id), &notice("reply"))?;
+            told.push(UserId(author_id));
+        }
+        _ => {}
+    }
+
+    for name in mentions(&body) {
+        let user_id: Option<UserId> = conn
+            .query_row(
+                "SELECT id FROM users
+                 WHERE username = ?1 COLLATE NOCASE AND deleted_at IS NULL AND guest_key IS NULL",
+                [&name],
+                |row| row.get(0),
+            )
+            .ok();
+        if let Some(user_id) = user_id.filter(|id| !told.contains(id)) {
+            notify(conn, user_id, &notice("mention"))?;
+            told.push(user_id);
+        }
+    }
+    Ok(())
+}
+
+/// Tell the author of a comment or reply about each milestone its score
+/// passed on the way from `before` to `after`. Each milestone is announced
+/// once per item; the author's own votes don't count as feedback.
+pub fn notify_vote_milestones(
+    conn: &Connection,
+    target_type: &str,
+    target_id: i64,
+    before: i64,
+    after: i64,
+    voter_id: UserId,
+    milestones: &[i64],
+) -> rusqlite::Result<()> {
+    let table = match target_type {
+        "comment" => "comments",
+        "reply" => "replies",
+        _ => return Ok(()),
+    };
+    let author_id: UserId = conn.query_row(
+        &format!("SELECT user_id FROM {table} WHERE id = ?1"),
+        [target_id],
+        |row| row.get(0),
+    )?;
+    if author_id == voter_id {
+        return Ok(());
+    }
+
+    for &milestone in milestones.iter().filter(|&&m| before < m && m <= after) {
+        let fresh = conn.execute(
+            "INSERT OR IGNORE INTO vote_milestones (target_type, target_id, milestone)
+             VALUES (?1, ?2, ?3)",
+            rusqlite::params![target_type, target_id, milestone],
+        )?;
+        if fresh == 0 {
+            continue;
+        }
+        let summary = if milestone == 1 {
+            format!("Your {target_type} got its first upvote")
+        } else {
+            format!("Your {target_type} reached {milestone} votes")
+        };
+        notify(
+            conn,
+            author_id,
+            &Notice {
+                kind: "vote_milestone",
+                actor_id: voter_id,
+                target_type,
+                target_id,
+                summary: &summary,
+            },
+        )?;
+    }
+    Ok(())
+}
+
+// ── Email ──
+
+fn email_template(kind: &str) -> Option<Template> {
+    match kind {
+        "reply" => Some(Template::Reply),
+        "mention" => Some(Template::Mention),
+        "moderation" => Some(Template::Moderation),
+        _ => None,
+    }
+}
+
+struct PendingEmail {
+    id: i64,
+    user_id: UserId,
+    email: String,
+    kind: String,
+    summary: String,
+    actor: Option<String>,
+    target_type: String,
+    target_id: i64,
+    site_id: String,
+    post_slug: Option<String>,
+    thread_id: Option<ThreadId>,
+}
+
+/// Queued emails, after dropping those whose recipient opted out or whose
+/// target was deleted in the meantime.
+fn pending_emails(conn: &Connection) -> rusqlite::Result<Vec<PendingEmail>> {
+    conn.execute(
+        "UPDATE notifications SET email_state = 'none'
+         WHERE email_state = 'pending' AND user_id IN (
+             SELECT id FROM users WHERE notify_email = 0 OR email IS NULL)",
+        [],
+    )?;
+    let mut stmt = conn.prepare_cached(
+        "SELECT n.id, n.user_id, u.email, n.kind, n.summary,
+                COALESCE(a.display_name, a.username),
+                n.target_type, n.target_id, COALESCE(c.site_id, t.site_id, rt.site_id),
+                c.post_slug, COALESCE(t.id, r.thread_id)
+         FROM notifications n
+         JOIN users u ON u.id = n.user_id
+         LEFT JOIN comments c ON n.target_type = 'comment' AND c.id = n.target_id
+         LEFT JOIN threads t ON n.target_type = 'thread' AND t.id = n.target_id
+         LEFT JOIN replies r ON n.target_type = 'reply' AND r.id = n.target_id
+         LEFT JOIN threads rt ON rt.id = r.thread_id
+         LEFT JOIN users a ON a.id = n.actor_id AND a.deleted_at IS NULL
+         WHERE n.email_state = 'pending'
+         ORDER BY n.id
+         LIMIT ?1",
+    )?;
+    let rows: Vec<(PendingEmail, bool)> = stmt
+        .query_map([EMAIL_BATCH_SIZE], |row| {
+            let site_id: Option<String> = row.get(8)?;
+            Ok((
+                PendingEmail {
+                    id: row.get(0)?,
+                    user_id: row.get(1)?,
+                    email: row.get(2)?,
+                    kind: row.get(3)?,
+                    summary: row.get(4)?,
+                    actor: row.get(5)?,
+                    target_type: row.get(6)?,
+                    target_id: row.get(7)?,
+                    site_id: site_id.clone().unwrap_or_default(),
+                    post_slug: row.get(9)?,
+                    thread_id: row.get(10)?,
+                },
+                site_id.is_some(),
+            ))
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    let mut pending = Vec::new();
+    for (email, target_exists) in rows {
+        if target_exists {
+            pending.push(email);
+        } else {
+            set_email_state(conn, email.id, "none")?;
+        }
+    }
+    Ok(pending)
+}
+
+fn set_email_state(conn: &Connection, id: i64, email_state: &str) -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE notifications SET email_state = ?2 WHERE id = ?1",
+        rusqlite::params![id, email_state],
+    )?;
+    Ok(())
+}
+
+fn render_email(state: &AppState, pending: &PendingEmail) -> Option<Email> {
+    let template = email_template(&pending.kind)?;
+    let config = state.config.load();
+    let mut vars = Vars::new();
+    vars.set("actor", pending.actor.as_deref().unwrap_or("Someone"));
+    vars.set("title", pending.summary.as_str());
+    vars.set("decision", pending.summary.as_str());
+    let url = emails::content_url(
+        &config,
+        &state.cors_origin,
+        &pending.site_id,
+        pending.post_slug.as_deref(),
+        pending.thread_id,
+        &pending.target_type,
+        pending.target_id,
+    );
+    if let Some(url) = url {
+        vars.set("url", url);
+    }
+    let unsubscribe =
+        emails::unsubscribe_url(&state.api_url, &state.jwt_secret, List::Notifications, pending.user_id);
+    Some(emails::render(
+        &config,
+        &pending.site_id,
+        template,
+        &pending.email,
+        vars,
+        Some(unsubscribe),
+    ))
+}
+
+async fn send_pending(state: &AppState, client: &reqwest::Client) -> Result<(), String> {
+    let Some(mailer) = state.config.load().mailer.clone() else {
+        return Ok(());
+    };
+
+    let db = state.reader.clone();
+    let pending = tokio::task::spawn_blocking(move || {
+        let conn = db.get().map_err(|e| e.to_string())?;
+        pending_emails(&conn).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    for item in pending {
+        // Email is best effort on top of the in-app notification, so a
+        // failed message isn't retried
+        let email_state = match render_email(state, &item) {
+            Some(email) => match emails::send(client, &mailer, &email).await {
+                Ok(()) => "sent",
+                Err(e) => {
+                    eprintln!("Notification email {} not sent: {e}", item.id);
+                    "failed"
+                }
+            },
+            None => "none",
+        };
+        let db = state.db.clone();
+        tokio::task::spawn_blocking(move || {
+            let conn = db.get().map_err(|e| e.to_string())?;
+            set_email_state(&conn, item.id, email_state).map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+    }
+    Ok(())
+}
+
+/// Email queued notifications for the lifetime of the process. Does
+/// nothing while no `[mailer]` is configured.
+pub fn spawn_mailer(state: &AppState) {
+    let state = state.clone();
+    tokio::spawn(async move {
+        let client = match emails::client() {
+            Ok(client) => client,
+            Err(e) => {
+                eprintln!("Notification emails disabled: {e}");
+                return;
+            }
+        };
+
+        let mut ticks = tokio::time::interval(EMAIL_POLL_INTERVAL);
+        loop {
+            ticks.tick().await;
+            if let Err(e) = send_pending(&state, &client).await {
+                eprintln!("Notification email run failed: {e}");
+            }
+        }
+    });
+}
+
+// ── Handlers ──
+
+/// GET /api/notifications/preferences
+pub async fn get_preferences(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<NotificationPrefs>, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+
+    let pool = state.reader.clone();
+    let prefs = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        conn.query_row(
+            "SELECT notify_email, email IS NOT NULL, digest FROM users WHERE id = ?1",
+            [user_id],
+            |row| {
+                Ok(NotificationPrefs {
+                    email: row.get(0)?,
+                    email_available: row.get(1)?,
+                    digest: DigestFrequency::parse(&row.get::<_, String>(2)?).unwrap_or_default(),
+                })
+            },
+        )
+        .map_err(|_| StatusCode::NOT_FOUND)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(prefs))
+}
+
+/// PUT /api/notifications/preferences
+pub async fn update_preferences(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<NotificationPrefs>,
+) -> Result<StatusCode, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+
+    let pool = state.db.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        // The first digest covers the period after opting in
+        conn.execute(
+            "UPDATE users SET notify_email = ?2, digest = ?3,
+                 digest_sent_at = COALESCE(digest_sent_at, datetime('now'))
+             WHERE id = ?1",
+            rusqlite::params![user_id, payload.email, payload.digest.as_str()],
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        Ok(StatusCode::NO_CONTENT)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+}
+
+/// Notifications with their target's site and location. Rows whose target
+/// was deleted have no site and drop out.
+const NOTIFICATION_FROM: &str = "FROM notifications n
+ LEFT JOIN comments c ON n.target_type = 'comment' AND c.id = n.target_id
+ LEFT JOIN threads t ON n.target_type = 'thread' AND t.id = n.target_id
+ LEFT JOIN replies r ON n.target_type = 'reply' AND r.id = n.target_id
+ LEFT JOIN threads rt ON rt.id = r.thread_id
+ LEFT JOIN users a ON a.id = n.actor_id AND a.deleted_at IS NULL
+ WHERE n.user_id = ?1 AND COALESCE(c.site_id, t.site_id, rt.site_id) = ?2";
+
+fn notification_from_row(row: &rusqlite::Row) -> rusqlite::Result<Notification> {
+    let actor_id: Option<UserId> = row.get(9)?;
+    Ok(Notification {
+        id: row.get(0)?,
+        kind: row.get(1)?,
+        target_type: row.get(2)?,
+        target_id: row.get(3)?,
+        summary: row.get(4)?,
+        read: row.get(5)?,
+        created_at: row.get(6)?,
+        post_slug: row.get(7)?,
+        thread_id: row.get(8)?,
+        actor: match actor_id {
+            Some(_) => Some(auth::user_from_row(row, 9)?),
+            None => None,
+        },
+    })
+}
+
+#[derive(Deserialize)]
+pub struct NotificationParams {
+    before: Option<i64>,
+}
+
+/// GET /api/notifications?before=<id> — this site's notifications, newest
+/// first
+pub async fn list_notifications(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<NotificationParams>,
+) -> Result<Json<NotificationList>, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+    let site = sites::resolve(&headers, &state.config.load())?;
+
+    let pool = state.reader.clone();
+    let list = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let mut stmt = conn
+            .prepare_cached(&format!(
+                "SELECT n.id, n.kind, n.target_type, n.target_id, n.summary,
+                        n.read_at IS NOT NULL, n.created_at, c.post_slug,
+                        COALESCE(t.id, r.thread_id), a.id, a.username, a.avatar_url, a.display_name
+                 {NOTIFICATION_FROM} AND (?3 IS NULL OR n.id < ?3)
+                 ORDER BY n.id DESC LIMIT ?4"
+            ))
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let mut items: Vec<Notification> = stmt
+            .query_map(
+                rusqlite::params![user_id, site, params.before, PAGE_SIZE + 1],
+                notification_from_row,
+            )
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .filter_map(|r| r.ok())
+            .collect();
+        let next = if items.len() as i64 > PAGE_SIZE {
+            items.truncate(PAGE_SIZE as usize);
+            items.last().map(|n| n.id)
+        } else {
+            None
+        };
+
+        let unread: i64 = conn
+            .query_row(
+                &format!("SELECT COUNT(*) {NOTIFICATION_FROM} AND n.read_at IS NULL"),
+                rusqlite::params![user_id, site],
+                |row| row.get(0),
+            )
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        let unread_messages = messages::unread_count(&conn, user_id)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok::<_, StatusCode>(NotificationList {
+            items,
+            unread,
+            unread_messages,
+            next,
+        })
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(list))
+}
+
+/// POST /api/notifications/:id/read
+pub async fn mark_read(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+
+    let pool = state.db.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let updated = conn
+            .execute(
+                "UPDATE notifications SET read_at = COALESCE(read_at, datetime('now'))
+                 WHERE id = ?1 AND user_id = ?2",
+                rusqlite::params![id, user_id],
+            )
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if updated == 0 {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        Ok(StatusCode::NO_CONTENT)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+}
+
+/// POST /api/notifications/read-all — on every site
+pub async fn mark_all_read(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<StatusCode, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+
+    let pool = state.db.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        conn.execute(
+            "UPDATE notifications SET read_at = datetime('now')
+             WHERE user_id = ?1 AND read_at IS NULL",
+            [user_id],
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        Ok(StatusCode::NO_CONTENT)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+}
@@ -0,0 +1,232 @@
+//! Per-post comment settings. Admins close a post's comments outright or
+//! after a number of days (counted from `published_at`, else from the first
+//! comment); `create_comment` then refuses new ones with `403`, and the
+//! widget shows the post as closed. Existing comments stay up.
+
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use mikaana_shared::{CommentState, PostSettings, UpdatePostSettings};
+use rusqlite::{Connection, OptionalExtension};
+use serde::Deserialize;
+
+use crate::{audit, auth, discussions, emails, sites, AppState};
+
+/// Whether `slug` takes new comments, and when it stops.
+pub fn comment_state(conn: &Connection, site: &str, slug: &str) -> rusqlite::Result<CommentState> {
+    let state = conn
+        .query_row(
+            "SELECT comments_closed, closes_at, COALESCE(closes_at <= datetime('now'), 0)
+             FROM (
+                 SELECT s.comments_closed,
+                        datetime(
+                            COALESCE(
+                                s.published_at,
+                                (SELECT MIN(created_at) FROM comments
+                                 WHERE site_id = s.site_id AND post_slug = s.post_slug)
+                            ),
+                            '+' || s.close_after_days || ' days'
+                        ) AS closes_at
+                 FROM post_settings s WHERE s.site_id = ?1 AND s.post_slug = ?2
+             )",
+            [site, slug],
+            |row| {
+                let closed: bool = row.get(0)?;
+                let expired: bool = row.get(2)?;
+                Ok(CommentState {
+                    closed: closed || expired,
+                    closes_at: row.get(1)?,
+                    discussion_url: None,
+                })
+            },
+        )
+        .optional()?;
+    Ok(state.unwrap_or_default())
+}
+
+#[derive(Deserialize)]
+pub struct SlugParams {
+    slug: String,
+}
+
+/// GET /api/comments/state?slug=... — whether the post takes new comments,
+/// and where it's discussed in the forum
+pub async fn get_comment_state(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<SlugParams>,
+) -> Result<Json<CommentState>, StatusCode> {
+    let config = state.config.load_full();
+    let site = sites::resolve(&headers, &config)?;
+    let pool = state.reader.clone();
+
+    let comment_state = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let mut comment_state = comment_state(&conn, &site, &params.slug)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        comment_state.discussion_url = discussions::thread_of(&conn, &site, &params.slug)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .and_then(|id| emails::thread_url(&config, &site, id));
+        Ok::<_, StatusCode>(comment_state)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(comment_state))
+}
+
+fn query_settings(conn: &Connection, site: &str) -> rusqlite::Result<Vec<PostSettings>> {
+    conn.prepare_cached(
+        "SELECT post_slug, comments_closed, close_after_days, published_at, updated_at
+         FROM post_settings WHERE site_id = ?1 ORDER BY post_slug",
+    )?
+    .query_map([site], |row| {
+        Ok(PostSettings {
+            post_slug: row.get(0)?,
+            comments_closed: row.get(1)?,
+            close_after_days: row.get(2)?,
+            published_at: row.get(3)?,
+            updated_at: row.get(4)?,
+        })
+    })?
+    .collect()
+}
+
+/// GET /api/admin/post-settings — the site's posts with settings (admins)
+pub async fn list_settings(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<PostSettings>>, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+    let site = sites::resolve(&headers, &state.config.load())?;
+    let pool = state.reader.clone();
+
+    let settings = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_admin(&conn, user_id)?;
+        query_settings(&conn, &site).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(settings))
+}
+
+/// PUT /api/admin/post-settings — set a post's comment settings (admins).
+/// 400 for an empty slug or a `published_at` SQLite can't read.
+pub async fn update_settings(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<UpdatePostSettings>,
+) -> Result<Json<PostSettings>, StatusCode> {
+    let admin_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+    let site = sites::resolve(&headers, &state.config.load())?;
+    if payload.post_slug.trim().is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let pool = state.db.clone();
+
+    let settings = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_admin(&conn, admin_id)?;
+
+        let published_at = match payload.published_at.as_deref().map(str::trim) {
+            Some(at) if !at.is_empty() => Some(
+                conn.query_row("SELECT datetime(?1)", [at], |row| row.get::<_, Option<String>>(0))
+                    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+                    .ok_or(StatusCode::BAD_REQUEST)?,
+            ),
+            _ => None,
+        };
+        let settings: PostSettings = conn
+            .query_row(
+                "INSERT INTO post_settings
+                     (site_id, post_slug, comments_closed, close_after_days, published_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT (site_id, post_slug) DO UPDATE SET
+                     comments_closed = excluded.comments_closed,
+                     close_after_days = excluded.close_after_days,
+                     published_at = excluded.published_at,
+                     updated_at = datetime('now')
+                 RETURNING post_slug, comments_closed, close_after_days, published_at, updated_at",
+                rusqlite::params![
+                    site,
+                    payload.post_slug,
+                    payload.comments_closed,
+                    payload.close_after_days,
+                    published_at
+                ],
+                |row| {
+                    Ok(PostSettings {
+                        post_slug: row.get(0)?,
+                        comments_closed: row.get(1)?,
+                        close_after_days: row.get(2)?,
+                        published_at: row.get(3)?,
+                        updated_at: row.get(4)?,
+                    })
+                },
+            )
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        audit::record(
+            &conn,
+            admin_id,
+            "post_settings.update",
+            "post",
+            0,
+            serde_json::json!({
+                "site": site,
+                "post_slug": settings.post_slug,
+                "comments_closed": settings.comments_closed,
+                "close_after_days": settings.close_after_days,
+                "published_at": settings.published_at,
+            }),
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok::<_, StatusCode>(settings)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(settings))
+}
+
+/// DELETE /api/admin/post-settings?slug=... — back to open comments (admins)
+pub async fn delete_settings(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<SlugParams>,
+) -> Result<StatusCode, StatusCode> {
+    let admin_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+    let site = sites::resolve(&headers, &state.config.load())?;
+    let pool = state.db.clone();
+
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_admin(&conn, admin_id)?;
+        let deleted = conn
+            .execute(
+                "DELETE FROM post_settings WHERE site_id = ?1 AND post_slug = ?2",
+                [&site, &params.slug],
+            )
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if deleted == 0 {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        audit::record(
+            &conn,
+            admin_id,
+            "post_settings.delete",
+            "post",
+            0,
+            serde_json::json!({ "site": site, "post_slug": params.slug }),
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        Ok(StatusCode::NO_CONTENT)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+}
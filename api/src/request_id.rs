@@ -0,0 +1,109 @@
+//! Request IDs, so a user quoting an error can be matched to the log.
+//!
+//! Each request gets an ID, taken from an incoming `X-Request-Id` (set by a
+//! proxy in front of us) when it looks sane and generated otherwise. It is
+//! returned in the `X-Request-Id` header and in every error body, and
+//! `log` prefixes lines logged while handling the request with it.
+
+use std::fmt;
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::Request,
+    http::{header, HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use mikaana_shared::{ApiError, ErrorBody};
+
+static HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+const MAX_INCOMING_LEN: usize = 64;
+
+/// Error bodies larger than this are passed through without an ID.
+const MAX_ERROR_BODY: usize = 64 * 1024;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// ID of the request being handled, if any. Not visible from inside
+/// `spawn_blocking`.
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(String::clone).ok()
+}
+
+/// `eprintln!`, prefixed with the current request's ID.
+pub fn log(args: fmt::Arguments) {
+    match current() {
+        Some(id) => eprintln!("[{id}] {args}"),
+        None => eprintln!("{args}"),
+    }
+}
+
+fn incoming(req: &Request) -> Option<String> {
+    let id = req.headers().get(&HEADER)?.to_str().ok()?;
+    let sane = !id.is_empty()
+        && id.len() <= MAX_INCOMING_LEN
+        && id.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+    sane.then(|| id.to_string())
+}
+
+/// Middleware: assign the ID, log server errors under it and add it to the
+/// response.
+pub async fn assign(req: Request, next: Next) -> Response {
+    let id = incoming(&req).unwrap_or_else(|| format!("{:016x}", rand::random::<u64>()));
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+
+    let resp = REQUEST_ID.scope(id.clone(), next.run(req)).await;
+    let status = resp.status();
+    if status.is_server_error() {
+        eprintln!("[{id}] {method} {path}: {status}");
+    }
+
+    let mut resp = if status.is_client_error() || status.is_server_error() {
+        with_error_body(resp, &id).await
+    } else {
+        resp
+    };
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        resp.headers_mut().insert(HEADER.clone(), value);
+    }
+    resp
+}
+
+/// Add `request_id` to a JSON error body, or replace any other body with an
+/// `ErrorBody` (keeping plain-text bodies as its `message`).
+async fn with_error_body(resp: Response, id: &str) -> Response {
+    let (mut parts, body) = resp.into_parts();
+    let Ok(bytes) = to_bytes(body, MAX_ERROR_BODY).await else {
+        return Response::from_parts(parts, Body::empty());
+    };
+
+    let is_json = parts
+        .headers
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|v| v.as_bytes().starts_with(b"application/json"));
+    if is_json {
+        if let Ok(serde_json::Value::Object(mut object)) = serde_json::from_slice(&bytes) {
+            object.insert("request_id".to_string(), id.into());
+            parts.headers.remove(header::CONTENT_LENGTH);
+            let body = serde_json::to_vec(&object).unwrap_or_default();
+            return Response::from_parts(parts, Body::from(body));
+        }
+    }
+
+    let message = String::from_utf8_lossy(&bytes).trim().to_string();
+    let error = ErrorBody {
+        error: ApiError::from_status(parts.status.as_u16()),
+        message: (!message.is_empty()).then_some(message),
+        request_id: Some(id.to_string()),
+    };
+    let body = serde_json::to_vec(&error).unwrap_or_default();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts
+        .headers
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    Response::from_parts(parts, Body::from(body))
+}
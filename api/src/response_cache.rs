@@ -0,0 +1,94 @@
+//! In-process cache for hot anonymous reads: category and thread listings
+//! and comment lists, which front-page traffic hits the hardest.
+//!
+//! Only responses that don't depend on the viewer are cached. Entries live
+//! for `cache.ttl_seconds`; `invalidate_on_write` also empties the cache
+//! after any successful write through the API, so the TTL only bounds how
+//! stale changes made elsewhere (edits straight in the database) can be.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::Request,
+    http::Method,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+
+use crate::config::CacheConfig;
+
+struct Entry {
+    value: Arc<dyn Any + Send + Sync>,
+    stored_at: Instant,
+}
+
+static CACHE: LazyLock<Mutex<HashMap<String, Entry>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// The cached value under `key`, if fresh.
+pub fn get<T: Clone + 'static>(settings: &CacheConfig, key: &str) -> Option<T> {
+    let ttl = Duration::from_secs(settings.ttl_seconds);
+    let cache = CACHE.lock().unwrap();
+    let entry = cache.get(key)?;
+    if entry.stored_at.elapsed() >= ttl {
+        return None;
+    }
+    entry.value.downcast_ref::<T>().cloned()
+}
+
+/// Cache `value` under `key`. When full, expired entries are dropped first
+/// and, failing that, everything.
+pub fn put<T: Send + Sync + 'static>(settings: &CacheConfig, key: String, value: T) {
+    if settings.ttl_seconds == 0 || settings.max_entries == 0 {
+        return;
+    }
+    let ttl = Duration::from_secs(settings.ttl_seconds);
+    let mut cache = CACHE.lock().unwrap();
+    if cache.len() >= settings.max_entries && !cache.contains_key(&key) {
+        cache.retain(|_, e| e.stored_at.elapsed() < ttl);
+        if cache.len() >= settings.max_entries {
+            cache.clear();
+        }
+    }
+    cache.insert(
+        key,
+        Entry {
+            value: Arc::new(value),
+            stored_at: Instant::now(),
+        },
+    );
+}
+
+/// `value` as a JSON response, cached under `key` if given.
+pub fn respond<T>(settings: &CacheConfig, key: Option<String>, value: T) -> Response
+where
+    T: Serialize + Clone + Send + Sync + 'static,
+{
+    if let Some(key) = key {
+        put(settings, key, value.clone());
+    }
+    Json(value).into_response()
+}
+
+pub fn clear() {
+    CACHE.lock().unwrap().clear();
+}
+
+/// Middleware: empty the cache after every successful write request.
+/// Coarse, but writes are rare next to reads, and no write path can forget
+/// to invalidate what it touched.
+pub async fn invalidate_on_write(req: Request, next: Next) -> Response {
+    // Analytics events change nothing cached, and come with every view
+    let write = !matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS)
+        && req.uri().path() != "/api/events";
+    let resp = next.run(req).await;
+    if write && resp.status().is_success() {
+        clear();
+    }
+    resp
+}
@@ -0,0 +1,127 @@
+//! Threads scheduled for later, such as release announcements timed with a
+//! blog post. Moderators create them with `publish_at` (see
+//! `forum::create_thread`); they wait as `status = 'scheduled'`, hidden
+//! like any unpublished thread (already pinned, when asked), until the
+//! publisher releases them. Publishing sends what creating a thread would
+//! have sent then: notifications, webhooks and link previews.
+
+use std::time::Duration;
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use mikaana_shared::{Thread, ThreadId};
+use rusqlite::Connection;
+
+use crate::{
+    auth, config::SharedConfig, forum, notifications, response_cache, sites, unfurl, webhooks,
+    AppState, DbPool,
+};
+
+/// How often due threads are looked for, so the most a thread can be late.
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// `at` as stored (UTC `YYYY-MM-DD HH:MM:SS`) when it's still to come;
+/// `None` when it has passed, so the thread goes out right away. 400 for
+/// dates SQLite can't read.
+pub fn future_time(conn: &Connection, at: &str) -> Result<Option<String>, StatusCode> {
+    let (at, future): (Option<String>, bool) = conn
+        .query_row(
+            "SELECT datetime(?1), COALESCE(datetime(?1) > datetime('now'), 0)",
+            [at.trim()],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let at = at.ok_or(StatusCode::BAD_REQUEST)?;
+    Ok(future.then_some(at))
+}
+
+/// Publish the threads whose time has come, each dated when it went out so
+/// it lists as new.
+fn publish_due(conn: &mut Connection, config: &SharedConfig) -> rusqlite::Result<()> {
+    let due: Vec<(ThreadId, String)> = conn
+        .prepare_cached(
+            "SELECT id, site_id FROM threads
+             WHERE status = 'scheduled' AND publish_at <= datetime('now')
+             ORDER BY publish_at, id",
+        )?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    let config = config.load();
+    for (id, site) in due {
+        let tx = conn.transaction()?;
+        tx.execute(
+            "UPDATE threads
+             SET status = 'published', created_at = publish_at, publish_at = NULL
+             WHERE id = ?1",
+            [id],
+        )?;
+        let thread = forum::query_thread(&tx, id)?;
+        notifications::announce(&tx, "thread", id.0)?;
+        webhooks::enqueue(&tx, &site, webhooks::THREAD_CREATED, &thread)?;
+        unfurl::enqueue(&tx, &config, &thread.body)?;
+        tx.commit()?;
+        // Not a request, so `response_cache::invalidate_on_write` misses it
+        response_cache::clear();
+    }
+    Ok(())
+}
+
+/// Publish scheduled threads for the lifetime of the process.
+pub fn spawn_publisher(config: SharedConfig, pool: DbPool) {
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            ticks.tick().await;
+            let pool = pool.clone();
+            let config = config.clone();
+            let result = tokio::task::spawn_blocking(move || {
+                let mut conn = pool.get().map_err(|e| e.to_string())?;
+                publish_due(&mut conn, &config).map_err(|e| e.to_string())
+            })
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|r| r);
+            if let Err(e) = result {
+                eprintln!("Publishing scheduled threads failed: {e}");
+            }
+        }
+    });
+}
+
+/// GET /api/admin/scheduled — the site's threads waiting to go out, soonest
+/// first (moderators)
+pub async fn list_scheduled(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<Thread>>, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+    let site = sites::resolve(&headers, &state.config.load())?;
+    let pool = state.reader.clone();
+
+    let threads = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_moderator(&conn, user_id)?;
+        let ids: Vec<ThreadId> = conn
+            .prepare_cached(
+                "SELECT id FROM threads WHERE site_id = ?1 AND status = 'scheduled'
+                 ORDER BY publish_at, id",
+            )
+            .and_then(|mut stmt| {
+                stmt.query_map([&site], |row| row.get(0))?
+                    .collect::<rusqlite::Result<_>>()
+            })
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        ids.into_iter()
+            .map(|id| forum::query_thread(&conn, id))
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(threads))
+}
@@ -0,0 +1,139 @@
+//! Forum search over the full-text indexes of thread and reply text (see
+//! `db::create_search_index`), for the widget's search-as-you-type box.
+
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use mikaana_shared::{ExcerptPart, SearchResult};
+use serde::Deserialize;
+
+use crate::{sites, AppState};
+
+/// Results returned when the request doesn't ask for a number.
+const DEFAULT_LIMIT: i64 = 10;
+const MAX_LIMIT: i64 = 25;
+
+/// Words of the query used; the rest are ignored.
+const MAX_TERMS: usize = 8;
+
+/// Words of context in an excerpt.
+const EXCERPT_TOKENS: i64 = 16;
+
+/// Marks around matched terms in FTS snippets, split out into
+/// `ExcerptPart`s before sending.
+const MATCH_START: char = '\u{1}';
+const MATCH_END: char = '\u{2}';
+
+#[derive(Deserialize)]
+pub struct SearchParams {
+    #[serde(default)]
+    q: String,
+    limit: Option<i64>,
+}
+
+/// `q` as an FTS query: each word quoted, so punctuation and operators in it
+/// are taken literally, and matched as a prefix, since the last one is
+/// usually still being typed. Every word has to match.
+fn match_query(q: &str) -> Option<String> {
+    let terms: Vec<String> = q
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .take(MAX_TERMS)
+        .map(|t| format!("\"{t}\"*"))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+/// A snippet with `MATCH_START`/`MATCH_END` marks as plain and matched runs.
+fn excerpt(snippet: &str) -> Vec<ExcerptPart> {
+    let mut parts = Vec::new();
+    let mut push = |text: &str, matched: bool| {
+        if !text.is_empty() {
+            parts.push(ExcerptPart {
+                text: text.to_string(),
+                matched,
+            });
+        }
+    };
+    let mut chunks = snippet.split(MATCH_START);
+    push(chunks.next().unwrap_or_default(), false);
+    for chunk in chunks {
+        let (hit, rest) = chunk.split_once(MATCH_END).unwrap_or((chunk, ""));
+        push(hit, true);
+        push(rest, false);
+    }
+    parts
+}
+
+/// GET /api/forum/search?q=&limit= — published threads and replies on the
+/// site matching every word of `q`, best match first, with an excerpt
+/// around the match. Moved threads' stubs are left out.
+pub async fn search_forum(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<SearchParams>,
+) -> Result<Json<Vec<SearchResult>>, StatusCode> {
+    let site = sites::resolve(&headers, &state.config.load())?;
+    let Some(query) = match_query(&params.q) else {
+        return Ok(Json(Vec::new()));
+    };
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let pool = state.reader.clone();
+
+    let results = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT t.id, NULL, t.title,
+                        snippet(threads_fts, 1, ?3, ?4, '\u{2026}', ?5), t.created_at,
+                        bm25(threads_fts, 2.0, 1.0) AS rank
+                 FROM threads_fts
+                 JOIN threads t ON t.id = threads_fts.rowid
+                 WHERE threads_fts MATCH ?1 AND t.site_id = ?2
+                   AND t.status = 'published' AND t.moved_to IS NULL
+                 UNION ALL
+                 SELECT t.id, r.id, t.title,
+                        snippet(replies_fts, 0, ?3, ?4, '\u{2026}', ?5), r.created_at,
+                        bm25(replies_fts) AS rank
+                 FROM replies_fts
+                 JOIN replies r ON r.id = replies_fts.rowid
+                 JOIN threads t ON t.id = r.thread_id
+                 WHERE replies_fts MATCH ?1 AND t.site_id = ?2
+                   AND r.status = 'published' AND t.status = 'published' AND t.moved_to IS NULL
+                 ORDER BY rank
+                 LIMIT ?6",
+            )
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let results = stmt
+            .query_map(
+                rusqlite::params![
+                    query,
+                    site,
+                    MATCH_START.to_string(),
+                    MATCH_END.to_string(),
+                    EXCERPT_TOKENS,
+                    limit
+                ],
+                |row| {
+                    let snippet: String = row.get(3)?;
+                    Ok(SearchResult {
+                        thread_id: row.get(0)?,
+                        reply_id: row.get(1)?,
+                        title: row.get(2)?,
+                        excerpt: excerpt(&snippet),
+                        created_at: row.get(4)?,
+                    })
+                },
+            )
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok::<_, StatusCode>(results)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(results))
+}
@@ -0,0 +1,182 @@
+//! `mikaana-api seed-dev`: fill an empty database with fake users,
+//! comments, threads, replies and votes, so frontend work doesn't start
+//! from nothing. Content is spread over the past few days so sorting and
+//! relative dates look realistic. Refuses to run on a database that already
+//! has users.
+
+use crate::{votes, DbPool};
+
+/// The first one is made an admin.
+const USERS: [&str; 6] = ["alice", "bob", "carol", "dave", "erin", "frank"];
+
+const SLUGS: [&str; 3] = [
+    "/blog/hello-world/",
+    "/blog/rust-in-production/",
+    "/blog/notes-on-sqlite/",
+];
+
+const COMMENTS: [&str; 8] = [
+    "Great write-up, thanks for sharing!",
+    "I ran into the same problem last month. The fix in the second section worked for me too.",
+    "Have you benchmarked this against the previous approach? Curious about the numbers.",
+    "Small typo in the third paragraph: \"recieve\" should be \"receive\".",
+    "This is the clearest explanation of the topic I've read so far.",
+    "I'd push back a little on the conclusion. It depends a lot on the workload.",
+    "Bookmarked. Looking forward to the follow-up post.",
+    "Does this still apply to the latest release?",
+];
+
+/// `(category slug, title, body)`
+const THREADS: [(&str, &str, &str); 5] = [
+    (
+        "general",
+        "Welcome! Introduce yourself",
+        "Say hi and tell us what you're working on.",
+    ),
+    (
+        "general",
+        "What are you reading this week?",
+        "Books, papers, blog posts, anything goes.",
+    ),
+    (
+        "projects",
+        "Show and tell: weekend projects",
+        "Share something you built recently, however small.",
+    ),
+    (
+        "help",
+        "SQLite locking errors under load",
+        "I keep getting `database is locked` when two requests write at once. Any ideas?",
+    ),
+    (
+        "help",
+        "How do I get started with the forum API?",
+        "Is there an example of listing threads from a script?",
+    ),
+];
+
+const REPLIES: [&str; 8] = [
+    "Hello everyone!",
+    "Turning on WAL mode and setting a busy timeout fixed this for me.",
+    "Nice, thanks for posting this.",
+    "+1, I had the same question.",
+    "There's a small client crate in the repo that wraps the endpoints.",
+    "Currently reading a book on distributed systems, slowly.",
+    "I built a tiny static site generator, mostly to learn.",
+    "Welcome aboard!",
+];
+
+pub fn run(pool: &DbPool) -> Result<(), String> {
+    let mut conn = pool.get().map_err(|e| e.to_string())?;
+    let users: i64 = conn
+        .query_row("SELECT COUNT(*) FROM users", [], |row| row.get(0))
+        .map_err(|e| e.to_string())?;
+    if users > 0 {
+        return Err("The database already has users; seed-dev only fills an empty one".to_string());
+    }
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    seed(&tx).map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+
+    println!(
+        "Seeded {} users, {} comments, {} threads with replies, and votes",
+        USERS.len(),
+        COMMENTS.len() * SLUGS.len(),
+        THREADS.len()
+    );
+    Ok(())
+}
+
+fn seed(conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+    let mut user_ids = Vec::new();
+    for (i, name) in USERS.iter().enumerate() {
+        conn.execute(
+            "INSERT INTO users (github_id, github_login, username, avatar_url, created_at)
+             VALUES (?1, ?2, ?2, ?3, datetime('now', ?4))",
+            rusqlite::params![
+                900_001 + i as i64,
+                name,
+                format!(
+                    "https://avatars.githubusercontent.com/u/{}?v=4",
+                    583_231 + i
+                ),
+                format!("-{} days", 30 - i),
+            ],
+        )?;
+        user_ids.push(conn.last_insert_rowid());
+    }
+    conn.execute(
+        "UPDATE users SET role = 'admin' WHERE id = ?1",
+        [user_ids[0]],
+    )?;
+    let author = |n: usize| user_ids[n % user_ids.len()];
+
+    // Minutes ago, counting down so later items are newer
+    let mut age = 5 * 24 * 60;
+    let mut ago = || {
+        age -= 53;
+        format!("-{age} minutes")
+    };
+
+    let mut targets = Vec::new();
+    for (s, slug) in SLUGS.iter().enumerate() {
+        for (c, body) in COMMENTS.iter().enumerate() {
+            conn.execute(
+                "INSERT INTO comments (post_slug, user_id, body, created_at)
+                 VALUES (?1, ?2, ?3, datetime('now', ?4))",
+                rusqlite::params![slug, author(c + s), body, ago()],
+            )?;
+            targets.push(("comment", conn.last_insert_rowid()));
+        }
+    }
+
+    for (t, (category, title, body)) in THREADS.iter().enumerate() {
+        let category_id: i64 = conn.query_row(
+            "SELECT id FROM categories WHERE site_id = 'default' AND slug = ?1",
+            [category],
+            |row| row.get(0),
+        )?;
+        conn.execute(
+            "INSERT INTO threads (category_id, user_id, title, body, created_at)
+             VALUES (?1, ?2, ?3, ?4, datetime('now', ?5))",
+            rusqlite::params![category_id, author(t), title, body, ago()],
+        )?;
+        let thread_id = conn.last_insert_rowid();
+        targets.push(("thread", thread_id));
+
+        for r in 0..(t * 2 + 1).min(REPLIES.len()) {
+            conn.execute(
+                "INSERT INTO replies (thread_id, user_id, body, created_at)
+                 VALUES (?1, ?2, ?3, datetime('now', ?4))",
+                rusqlite::params![
+                    thread_id,
+                    author(t + r + 1),
+                    REPLIES[(t + r) % REPLIES.len()],
+                    ago()
+                ],
+            )?;
+            targets.push(("reply", conn.last_insert_rowid()));
+        }
+    }
+
+    // Mostly upvotes, some downvotes, some abstentions; nobody votes for themselves
+    for (i, (target_type, target_id)) in targets.iter().enumerate() {
+        for (u, user_id) in user_ids.iter().enumerate() {
+            let value = match (i * 7 + u * 3) % 5 {
+                0..=2 => 1,
+                3 => -1,
+                _ => continue,
+            };
+            conn.execute(
+                "INSERT INTO votes (user_id, target_type, target_id, value)
+                 SELECT ?1, ?2, ?3, ?4
+                 WHERE NOT EXISTS (SELECT 1 FROM comments WHERE ?2 = 'comment' AND id = ?3 AND user_id = ?1)
+                   AND NOT EXISTS (SELECT 1 FROM threads WHERE ?2 = 'thread' AND id = ?3 AND user_id = ?1)
+                   AND NOT EXISTS (SELECT 1 FROM replies WHERE ?2 = 'reply' AND id = ?3 AND user_id = ?1)",
+                rusqlite::params![user_id, target_type, target_id, value],
+            )?;
+        }
+    }
+    votes::recount_all(conn)
+}
@@ -0,0 +1,268 @@
+//! The forum for crawlers and link unfurlers, which don't run the WASM
+//! widget: a sitemap of thread addresses, and each thread as a plain HTML
+//! page with meta and OpenGraph tags, whose fields are also served as JSON.
+//!
+//! The addresses listed are the forum's own (`{forum_url}/thread/{id}`), so
+//! the snapshot is only reached when the site's host sends crawlers there,
+//! e.g. by proxying bot user agents on `/discuss/thread/{id}` to
+//! `/api/forum/threads/{id}/html`.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse},
+    Json,
+};
+use mikaana_shared::{LinkPreview, Reply, ReplySort, Thread, ThreadId};
+use serde::Deserialize;
+
+use crate::{config::Config, emails, forum, sites, AppState};
+
+/// The most URLs one sitemap file may hold.
+const SITEMAP_LIMIT: i64 = 50_000;
+
+/// Characters of the body kept in the page description.
+const DESCRIPTION_LEN: usize = 160;
+
+#[derive(Deserialize)]
+pub struct SitemapParams {
+    /// Site to list, for crawlers that can't send an `Origin`; defaults to
+    /// the requesting site.
+    site: Option<String>,
+}
+
+/// GET /sitemap-forum.xml — every published thread's address with the time
+/// of its latest activity. 404 when the site has no `forum_url`.
+pub async fn forum_sitemap(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<SitemapParams>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let config = state.config.load_full();
+    let site = sites::resolve_named(params.site, &headers, &config)?;
+    if config.forum_url(&site).is_none() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let pool = state.reader.clone();
+
+    let threads = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT t.id, COALESCE(MAX(r.created_at), t.created_at) AS lastmod
+                 FROM threads t
+                 LEFT JOIN replies r ON r.thread_id = t.id AND r.status = 'published'
+                 WHERE t.site_id = ?1 AND t.status = 'published' AND t.moved_to IS NULL
+                 GROUP BY t.id
+                 ORDER BY lastmod DESC
+                 LIMIT ?2",
+            )
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let rows: Vec<(ThreadId, String)> = stmt
+            .query_map(rusqlite::params![site, SITEMAP_LIMIT], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok::<_, StatusCode>((site, rows))
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    let (site, threads) = threads;
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+    for (id, lastmod) in threads {
+        let Some(loc) = emails::thread_url(&config, &site, id) else {
+            continue;
+        };
+        xml.push_str(&format!(
+            "<url><loc>{}</loc><lastmod>{}</lastmod></url>\n",
+            emails::escape(&loc),
+            emails::escape(lastmod.get(..10).unwrap_or(&lastmod)),
+        ));
+    }
+    xml.push_str("</urlset>\n");
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/xml; charset=utf-8"),
+            (header::CACHE_CONTROL, "public, max-age=3600"),
+            (header::VARY, sites::VARY),
+        ],
+        xml,
+    ))
+}
+
+/// A published thread and the site it belongs to. Threads carry their site,
+/// so crawlers needn't send an `Origin` for it.
+pub fn published_thread(conn: &rusqlite::Connection, id: ThreadId) -> Result<(String, Thread), StatusCode> {
+    let site: String = conn
+        .query_row(
+            "SELECT site_id FROM threads WHERE id = ?1 AND status = 'published'",
+            [id],
+            |row| row.get(0),
+        )
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+    let thread = forum::query_thread(conn, id).map_err(|_| StatusCode::NOT_FOUND)?;
+    Ok((site, thread))
+}
+
+/// GET /api/forum/threads/:id/preview — the OpenGraph fields of the thread's
+/// page, for embedding sites that build their own link cards.
+pub async fn thread_preview(
+    State(state): State<AppState>,
+    Path(id): Path<ThreadId>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let config = state.config.load_full();
+    let pool = state.reader.clone();
+
+    let (site, thread) = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        published_thread(&conn, id)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok((
+        [(header::CACHE_CONTROL, "public, max-age=300")],
+        Json(preview(&config, &site, &thread)),
+    ))
+}
+
+/// GET /api/forum/threads/:id/html — the thread and its replies as a
+/// standalone page, with a description, OpenGraph and Twitter tags and a
+/// canonical link to the forum.
+pub async fn thread_snapshot(
+    State(state): State<AppState>,
+    Path(id): Path<ThreadId>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let config = state.config.load_full();
+    let pool = state.reader.clone();
+
+    let (site, thread, replies) = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let (site, thread) = published_thread(&conn, id)?;
+        let replies = forum::query_replies(&conn, id, ReplySort::Oldest)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        Ok::<_, StatusCode>((site, thread, replies))
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok((
+        [(header::CACHE_CONTROL, "public, max-age=300")],
+        Html(render_thread(&preview(&config, &site, &thread), &thread, &replies)),
+    ))
+}
+
+/// The first `DESCRIPTION_LEN` characters of `body` on one line.
+fn description(body: &str) -> String {
+    let text = body.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut chars = text.chars();
+    let head: String = chars.by_ref().take(DESCRIPTION_LEN).collect();
+    if chars.next().is_some() {
+        format!("{}\u{2026}", head.trim_end())
+    } else {
+        head
+    }
+}
+
+/// A moved thread's stub previews as the thread it moved to.
+fn preview(config: &Config, site: &str, thread: &Thread) -> LinkPreview {
+    let branding = config.branding(site);
+    LinkPreview {
+        title: thread.title.clone(),
+        description: description(&thread.body),
+        image: thread
+            .attachments
+            .iter()
+            .find(|a| a.is_image())
+            .map(|a| a.url.clone())
+            .or_else(|| branding.card_image_url.clone()),
+        url: emails::thread_url(config, site, thread.moved_to.unwrap_or(thread.id)),
+        site_name: branding.site_name.clone(),
+    }
+}
+
+/// Bodies are escaped and shown as typed, as the widget does (see
+/// `Markup::Plain`).
+fn render_thread(preview: &LinkPreview, thread: &Thread, replies: &[Reply]) -> String {
+    let title = emails::escape(&preview.title);
+    let description = emails::escape(&preview.description);
+
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{title}</title>\n\
+         <meta name=\"description\" content=\"{description}\">\n\
+         <meta property=\"og:type\" content=\"article\">\n\
+         <meta property=\"og:title\" content=\"{title}\">\n\
+         <meta property=\"og:description\" content=\"{description}\">\n\
+         <meta property=\"article:published_time\" content=\"{published}\">\n\
+         <meta property=\"article:author\" content=\"{author}\">\n\
+         <meta name=\"twitter:card\" content=\"{card}\">\n\
+         <meta name=\"twitter:title\" content=\"{title}\">\n\
+         <meta name=\"twitter:description\" content=\"{description}\">\n",
+        published = emails::escape(&thread.created_at.replacen(' ', "T", 1)),
+        author = emails::escape(thread.user.name()),
+        card = if preview.image.is_some() { "summary_large_image" } else { "summary" },
+    );
+    if let Some(url) = &preview.url {
+        let url = emails::escape(url);
+        html.push_str(&format!(
+            "<link rel=\"canonical\" href=\"{url}\">\n<meta property=\"og:url\" content=\"{url}\">\n"
+        ));
+    }
+    if let Some(image) = &preview.image {
+        let image = emails::escape(image);
+        html.push_str(&format!(
+            "<meta property=\"og:image\" content=\"{image}\">\n<meta name=\"twitter:image\" content=\"{image}\">\n"
+        ));
+    }
+    if let Some(name) = &preview.site_name {
+        html.push_str(&format!(
+            "<meta property=\"og:site_name\" content=\"{}\">\n",
+            emails::escape(name)
+        ));
+    }
+    html.push_str("</head>\n<body>\n<main class=\"mikaana-thread-static\">\n");
+
+    html.push_str(&format!(
+        "<article class=\"mikaana-thread-detail\" id=\"thread-{id}\">\n<h1>{title}</h1>\n\
+         <p class=\"mikaana-thread-meta\"><strong>{author}</strong> <time datetime=\"{at}\">{date}</time></p>\n\
+         <div class=\"mikaana-thread-body\">{body}</div>\n</article>\n",
+        id = thread.id,
+        author = emails::escape(thread.user.name()),
+        at = emails::escape(&thread.created_at),
+        date = emails::escape(thread.created_at.get(..10).unwrap_or(&thread.created_at)),
+        body = emails::escape(&thread.body),
+    ));
+
+    html.push_str(&format!("<h2>Replies ({})</h2>\n", replies.len()));
+    for r in replies {
+        let solution = if thread.solution_reply_id == Some(r.id) { " mikaana-solution" } else { "" };
+        html.push_str(&format!(
+            "<article class=\"mikaana-comment{solution}\" id=\"reply-{id}\">\n\
+             <p class=\"mikaana-comment-header\"><strong>{author}</strong> <time datetime=\"{at}\">{date}</time></p>\n\
+             <div class=\"mikaana-comment-body\">{body}</div>\n</article>\n",
+            id = r.id,
+            author = emails::escape(r.user.name()),
+            at = emails::escape(&r.created_at),
+            date = emails::escape(r.created_at.get(..10).unwrap_or(&r.created_at)),
+            body = emails::escape(&r.body),
+        ));
+    }
+
+    if let Some(url) = &preview.url {
+        html.push_str(&format!(
+            "<p><a href=\"{}\">View this discussion</a></p>\n",
+            emails::escape(url)
+        ));
+    }
+    html.push_str("</main>\n</body>\n</html>\n");
+    html
+}
@@ -0,0 +1,82 @@
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use mikaana_shared::{PostActivity, SiteStats};
+
+use crate::{sites, AppState};
+
+/// How many posts `most_active_posts` lists.
+const TOP_POSTS: i64 = 5;
+
+fn query_stats(conn: &rusqlite::Connection, site: &str) -> rusqlite::Result<SiteStats> {
+    let count = |sql: &str| conn.query_row(sql, [site], |row| row.get::<_, i64>(0));
+
+    let mut stmt = conn.prepare_cached(
+        "SELECT post_slug, COUNT(*), MAX(created_at) FROM comments
+         WHERE site_id = ?1 AND status = 'published'
+         GROUP BY post_slug
+         ORDER BY COUNT(*) DESC, MAX(created_at) DESC
+         LIMIT ?2",
+    )?;
+    let most_active_posts = stmt
+        .query_map(rusqlite::params![site, TOP_POSTS], |row| {
+            Ok(PostActivity {
+                post_slug: row.get(0)?,
+                comments: row.get(1)?,
+                last_comment_at: row.get(2)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(SiteStats {
+        users: count(
+            "SELECT COUNT(*) FROM users u
+             WHERE u.merged_into IS NULL AND u.deleted_at IS NULL AND (
+                 EXISTS(SELECT 1 FROM comments WHERE user_id = u.id AND site_id = ?1)
+                 OR EXISTS(SELECT 1 FROM threads WHERE user_id = u.id AND site_id = ?1)
+                 OR EXISTS(SELECT 1 FROM replies r JOIN threads t ON r.thread_id = t.id
+                           WHERE r.user_id = u.id AND t.site_id = ?1)
+                 OR EXISTS(SELECT 1 FROM votes WHERE user_id = u.id AND site_id = ?1))",
+        )?,
+        comments: count(
+            "SELECT COUNT(*) FROM comments WHERE site_id = ?1 AND status = 'published'",
+        )?,
+        threads: count(
+            "SELECT COUNT(*) FROM threads
+             WHERE site_id = ?1 AND status = 'published' AND moved_to IS NULL",
+        )?,
+        replies: count(
+            "SELECT COUNT(*) FROM replies r JOIN threads t ON r.thread_id = t.id
+             WHERE t.site_id = ?1 AND r.status = 'published'",
+        )?,
+        votes: count("SELECT COUNT(*) FROM votes WHERE site_id = ?1")?,
+        most_active_posts,
+    })
+}
+
+/// GET /api/stats — community totals and the busiest posts
+pub async fn get_stats(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, StatusCode> {
+    let site = sites::resolve(&headers, &state.config.load())?;
+    let pool = state.reader.clone();
+    let stats = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        query_stats(&conn, &site).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok((
+        [
+            (header::CACHE_CONTROL, "public, max-age=300"),
+            (header::VARY, sites::VARY),
+        ],
+        Json(stats),
+    ))
+}
@@ -0,0 +1,110 @@
+//! Several sites (blogs) can share one instance.
+//!
+//! Comments, threads, categories and votes carry a `site_id`; replies belong
+//! to their thread's site. A request's site comes from, in order:
+//!
+//! 1. an `X-Mikaana-Site-Key` header matching a site's `api_key` (an unknown
+//!    key is rejected rather than falling through),
+//! 2. an `Origin` header listed in a site's `origins`,
+//! 3. otherwise the `default` site, which is all there is on a single-site
+//!    instance.
+//!
+//! Accounts, notifications and moderation stay instance-wide.
+
+use axum::http::{HeaderMap, StatusCode};
+use rusqlite::OptionalExtension;
+
+use crate::{config::Config, db};
+
+pub const DEFAULT_SITE: &str = "default";
+
+const SITE_KEY_HEADER: &str = "x-mikaana-site-key";
+
+/// `Vary` value for cacheable responses that differ per site.
+pub const VARY: &str = "Origin, X-Mikaana-Site-Key";
+
+/// The site a request is for.
+pub fn resolve(headers: &HeaderMap, config: &Config) -> Result<String, StatusCode> {
+    if let Some(key) = headers.get(SITE_KEY_HEADER) {
+        let key = key.to_str().map_err(|_| StatusCode::UNAUTHORIZED)?;
+        return config
+            .sites
+            .iter()
+            .find(|s| s.api_key.as_deref() == Some(key))
+            .map(|s| s.id.clone())
+            .ok_or(StatusCode::UNAUTHORIZED);
+    }
+
+    let origin = headers
+        .get(axum::http::header::ORIGIN)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| reqwest::Url::parse(v).ok());
+    if let Some(origin) = origin {
+        let origin = origin.origin();
+        let site = config.sites.iter().find(|s| {
+            s.origins
+                .iter()
+                .any(|o| reqwest::Url::parse(o).is_ok_and(|o| o.origin() == origin))
+        });
+        if let Some(site) = site {
+            return Ok(site.id.clone());
+        }
+    }
+
+    Ok(DEFAULT_SITE.to_string())
+}
+
+/// The site named by a `?site=` parameter, for clients that send no
+/// `Origin` such as crawlers and feed readers; without one, the requesting
+/// site. 404 for sites that aren't configured.
+pub fn resolve_named(
+    name: Option<String>,
+    headers: &HeaderMap,
+    config: &Config,
+) -> Result<String, StatusCode> {
+    match name {
+        Some(site) if site == DEFAULT_SITE || config.site(&site).is_some() => Ok(site),
+        Some(_) => Err(StatusCode::NOT_FOUND),
+        None => resolve(headers, config),
+    }
+}
+
+/// Whether `origin` belongs to any configured site.
+pub fn is_site_origin(config: &Config, origin: &reqwest::Url) -> bool {
+    let origin = origin.origin();
+    config
+        .sites
+        .iter()
+        .flat_map(|s| &s.origins)
+        .any(|o| reqwest::Url::parse(o).is_ok_and(|o| o.origin() == origin))
+}
+
+/// Whether a vote or report target belongs to `site`.
+pub fn owns(
+    conn: &rusqlite::Connection,
+    site: &str,
+    target_type: &str,
+    target_id: i64,
+) -> rusqlite::Result<bool> {
+    let sql = match target_type {
+        "post" => "SELECT site_id FROM post_targets WHERE id = ?1",
+        "comment" => "SELECT site_id FROM comments WHERE id = ?1",
+        "thread" => "SELECT site_id FROM threads WHERE id = ?1",
+        "reply" => {
+            "SELECT t.site_id FROM replies r JOIN threads t ON r.thread_id = t.id WHERE r.id = ?1"
+        }
+        _ => return Ok(false),
+    };
+    let owner: Option<String> = conn
+        .query_row(sql, [target_id], |row| row.get(0))
+        .optional()?;
+    Ok(owner.as_deref() == Some(site))
+}
+
+/// Give every configured site the default forum categories.
+pub fn seed_all(conn: &rusqlite::Connection, config: &Config) -> rusqlite::Result<()> {
+    for site in &config.sites {
+        db::seed_categories(conn, &site.id)?;
+    }
+    Ok(())
+}
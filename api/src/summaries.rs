@@ -0,0 +1,221 @@
+//! "Summary so far" boxes for long threads.
+//!
+//! The API does no summarizing itself: it posts the thread to an
+//! operator-configured endpoint (`[summarizer]` in the config file) and
+//! caches the answer per revision of the reply set. Request body:
+//!
+//! ```json
+//! {"thread_id": 1, "title": "...", "body": "<p>...</p>",
+//!  "replies": [{"author": "octocat", "body": "<p>...</p>"}]}
+//! ```
+//!
+//! The endpoint answers `{"summary": "plain text"}`.
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use mikaana_shared::{ReplySort, ThreadId, ThreadSummary};
+use serde::{Deserialize, Serialize};
+
+use crate::{config::SummarizerConfig, forum, request_id, sites, AppState};
+
+#[derive(Serialize)]
+struct SummaryRequest {
+    thread_id: ThreadId,
+    title: String,
+    body: String,
+    replies: Vec<SummaryReply>,
+}
+
+#[derive(Serialize)]
+struct SummaryReply {
+    author: String,
+    body: String,
+}
+
+#[derive(Deserialize)]
+struct SummaryResponse {
+    summary: String,
+}
+
+/// What the database knows before the summarizer is consulted.
+enum Cached {
+    Ineligible,
+    Fresh(ThreadSummary),
+    Stale {
+        request: SummaryRequest,
+        revision: String,
+        reply_count: i64,
+        previous: Option<ThreadSummary>,
+    },
+}
+
+fn load(
+    conn: &rusqlite::Connection,
+    site: &str,
+    thread_id: ThreadId,
+    min_replies: i64,
+) -> Result<Cached, StatusCode> {
+    if !sites::owns(conn, site, "thread", thread_id.0)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let thread = forum::query_thread(conn, thread_id).map_err(|_| StatusCode::NOT_FOUND)?;
+    let replies = forum::query_replies(conn, thread_id, ReplySort::Oldest)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let reply_count = replies.len() as i64;
+    if reply_count < min_replies {
+        return Ok(Cached::Ineligible);
+    }
+
+    // Count plus newest id changes whenever a reply is added or removed
+    let revision = format!(
+        "{reply_count}:{}",
+        replies.iter().map(|r| r.id.0).max().unwrap_or(0)
+    );
+
+    let cached = conn
+        .query_row(
+            "SELECT revision, summary, reply_count, generated_at
+             FROM thread_summaries WHERE thread_id = ?1",
+            [thread_id],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    ThreadSummary {
+                        summary: row.get(1)?,
+                        reply_count: row.get(2)?,
+                        generated_at: row.get(3)?,
+                    },
+                ))
+            },
+        )
+        .ok();
+
+    if let Some((cached_revision, summary)) = &cached {
+        if *cached_revision == revision {
+            return Ok(Cached::Fresh(summary.clone()));
+        }
+    }
+
+    Ok(Cached::Stale {
+        request: SummaryRequest {
+            thread_id,
+            title: thread.title,
+            body: thread.body,
+            replies: replies
+                .into_iter()
+                .map(|r| SummaryReply {
+                    author: r.user.name().to_string(),
+                    body: r.body,
+                })
+                .collect(),
+        },
+        revision,
+        reply_count,
+        previous: cached.map(|(_, summary)| summary),
+    })
+}
+
+async fn generate(config: &SummarizerConfig, request: &SummaryRequest) -> Result<String, String> {
+    let client = reqwest::Client::builder()
+        .user_agent("mikaana-api")
+        .timeout(std::time::Duration::from_secs(30))
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let mut req = client.post(&config.url).json(request);
+    if let Some(token) = &config.token {
+        req = req.bearer_auth(token);
+    }
+
+    let resp: SummaryResponse = req
+        .send()
+        .await
+        .map_err(|e| e.to_string())?
+        .error_for_status()
+        .map_err(|e| e.to_string())?
+        .json()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let summary = resp.summary.trim().to_string();
+    if summary.is_empty() {
+        return Err("empty summary".to_string());
+    }
+    Ok(summary)
+}
+
+/// GET /api/forum/threads/:id/summary — `null` when summaries are disabled
+/// or the thread is too short
+pub async fn get_summary(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(thread_id): Path<ThreadId>,
+) -> Result<Json<Option<ThreadSummary>>, StatusCode> {
+    let site = sites::resolve(&headers, &state.config.load())?;
+    let Some(config) = state.config.load().summarizer.clone() else {
+        return Ok(Json(None));
+    };
+
+    let pool = state.db.clone();
+    let min_replies = config.min_replies;
+    let cached = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        load(&conn, &site, thread_id, min_replies)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    let (request, revision, reply_count, previous) = match cached {
+        Cached::Ineligible => return Ok(Json(None)),
+        Cached::Fresh(summary) => return Ok(Json(Some(summary))),
+        Cached::Stale {
+            request,
+            revision,
+            reply_count,
+            previous,
+        } => (request, revision, reply_count, previous),
+    };
+
+    let summary = match generate(&config, &request).await {
+        Ok(summary) => summary,
+        Err(e) => {
+            // An older summary beats none; its reply_count tells readers how far it goes
+            request_id::log(format_args!("Summarizer error for thread {thread_id}: {e}"));
+            return match previous {
+                Some(previous) => Ok(Json(Some(previous))),
+                None => Err(StatusCode::BAD_GATEWAY),
+            };
+        }
+    };
+
+    let pool = state.db.clone();
+    let stored = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        conn.query_row(
+            "INSERT INTO thread_summaries (thread_id, revision, reply_count, summary)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(thread_id) DO UPDATE
+             SET revision = ?2, reply_count = ?3, summary = ?4, generated_at = datetime('now')
+             RETURNING summary, reply_count, generated_at",
+            rusqlite::params![thread_id, revision, reply_count, summary],
+            |row| {
+                Ok(ThreadSummary {
+                    summary: row.get(0)?,
+                    reply_count: row.get(1)?,
+                    generated_at: row.get(2)?,
+                })
+            },
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(Some(stored)))
+}
@@ -0,0 +1,122 @@
+//! Terms of service users accept before posting.
+//!
+//! With `[terms]` configured, `enforce` answers writes from signed-in users
+//! who haven't accepted the current version with `428 Precondition
+//! Required` and `ApiError::TermsNotAccepted`; the widgets then show the terms and send
+//! `POST /api/terms/accept`. Every acceptance is kept (version and time), so
+//! bumping the version asks everyone again without losing the record.
+
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use mikaana_shared::{AcceptTerms, ApiError, TermsAcceptance, TermsRequired, UserId};
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::{auth, AppState};
+
+/// Writes that don't need the terms: accepting them, account management
+/// (including deleting the account), and staff work.
+fn exempt(path: &str) -> bool {
+    path == "/api/terms/accept"
+        || path.starts_with("/api/auth/")
+        || path.starts_with("/api/admin/")
+        // GraphQL has no mutations
+        || path == "/api/graphql"
+}
+
+fn accepted(conn: &Connection, user_id: UserId, version: &str) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "SELECT 1 FROM terms_acceptances WHERE user_id = ?1 AND version = ?2",
+        rusqlite::params![user_id, version],
+        |_| Ok(()),
+    )
+    .optional()
+    .map(|row| row.is_some())
+}
+
+/// Middleware: refuse writes from users who haven't accepted the current
+/// terms. Requests without a valid token pass; their handlers answer 401.
+pub async fn enforce(State(state): State<AppState>, req: Request, next: Next) -> Response {
+    let Some(terms) = state.config.load().terms.clone() else {
+        return next.run(req).await;
+    };
+    let write = !matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    if !write || exempt(req.uri().path()) {
+        return next.run(req).await;
+    }
+    let Ok(user_id) = auth::extract_user_id(req.headers(), &state.jwt_secret) else {
+        return next.run(req).await;
+    };
+
+    let pool = state.reader.clone();
+    let version = terms.version.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        accepted(&conn, user_id, &version).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    .and_then(|r| r);
+
+    match result {
+        Ok(true) => next.run(req).await,
+        Ok(false) => (
+            StatusCode::PRECONDITION_REQUIRED,
+            Json(ApiError::TermsNotAccepted(TermsRequired {
+                version: terms.version,
+                url: terms.url,
+            })),
+        )
+            .into_response(),
+        Err(status) => status.into_response(),
+    }
+}
+
+/// POST /api/terms/accept — accept the current terms. 409 when `version`
+/// isn't current (the terms changed while the dialog was open); 404 when no
+/// terms are configured.
+pub async fn accept_terms(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<AcceptTerms>,
+) -> Result<StatusCode, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+    let terms = state.config.load().terms.clone().ok_or(StatusCode::NOT_FOUND)?;
+    if payload.version != terms.version {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    let pool = state.db.clone();
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_active(&conn, user_id)?;
+        conn.execute(
+            "INSERT OR IGNORE INTO terms_acceptances (user_id, version) VALUES (?1, ?2)",
+            rusqlite::params![user_id, terms.version],
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Every version the user accepted, oldest first, for their data export.
+pub fn query_acceptances(conn: &Connection, user_id: UserId) -> rusqlite::Result<Vec<TermsAcceptance>> {
+    conn.prepare_cached(
+        "SELECT version, accepted_at FROM terms_acceptances WHERE user_id = ?1
+         ORDER BY accepted_at, version",
+    )?
+    .query_map([user_id], |row| {
+        Ok(TermsAcceptance {
+            version: row.get(0)?,
+            accepted_at: row.get(1)?,
+        })
+    })?
+    .collect()
+}
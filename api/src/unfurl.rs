@@ -0,0 +1,491 @@
+//! Previews of pages linked from comments, threads and replies.
+//!
+//! Bare URLs in newly published bodies are queued (`enqueue`), and a
+//! background worker fetches each page once and keeps its title,
+//! description and image, read from OpenGraph and Twitter meta tags, the
+//! `<title>`, or the page's oEmbed endpoint. The widgets look them up with
+//! `GET /api/unfurl?url=`, which only answers from that cache, so visitors
+//! can't make the API fetch arbitrary addresses.
+//!
+//! Every fetch here and in `webmentions.rs` goes through `client`, which
+//! won't connect to loopback, private or link-local addresses, even when a
+//! public name resolves to one or a redirect points there.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use mikaana_shared::{bare_urls, LinkPreview};
+use regex::Regex;
+use reqwest::Url;
+use serde::Deserialize;
+
+use crate::{config::Config, AppState, DbPool};
+
+const MAX_ATTEMPTS: i64 = 3;
+/// First retry delay; doubles with each attempt.
+const RETRY_BASE_SECS: i64 = 300;
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+const BATCH_SIZE: i64 = 20;
+/// Linking a page again after this long fetches it anew.
+const REFRESH_AFTER_DAYS: i64 = 7;
+
+const MAX_URL_LEN: usize = 2048;
+/// Pages are read up to this many bytes; `<head>` is nearly always within.
+const MAX_PAGE_BYTES: usize = 1024 * 1024;
+const MAX_REDIRECTS: usize = 5;
+/// Characters kept of a page's title.
+const MAX_TITLE_LEN: usize = 200;
+/// Characters kept of a page's description.
+const MAX_DESCRIPTION_LEN: usize = 300;
+
+static ATTR: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)([a-z][a-z0-9_:.-]*)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#).unwrap()
+});
+static META_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)<meta\b[^>]*>").unwrap());
+static LINK_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)<link\b[^>]*>").unwrap());
+static TITLE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?is)<title[^>]*>(.*?)</title>").unwrap());
+
+// ── Fetching ──
+
+/// Whether `ip` is reachable on the public internet.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                // Carrier-grade NAT, 100.64.0.0/10
+                || (a == 100 && b & 0xc0 == 64))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(v4) => is_public(v4.into()),
+            None => {
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local())
+            }
+        },
+    }
+}
+
+/// `text` as an http(s) URL the API may fetch. Host names are checked when
+/// they are resolved (`PublicResolver`), literal addresses here.
+pub fn parse_url(text: &str) -> Option<Url> {
+    if text.len() > MAX_URL_LEN {
+        return None;
+    }
+    let url = Url::parse(text).ok()?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return None;
+    }
+    let host = url.host_str()?;
+    match host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+        Ok(ip) if !is_public(ip) => None,
+        _ => Some(url),
+    }
+}
+
+/// Resolves host names to their public addresses only.
+struct PublicResolver;
+
+impl reqwest::dns::Resolve for PublicResolver {
+    fn resolve(&self, name: reqwest::dns::Name) -> reqwest::dns::Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| is_public(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err("no public address".into());
+            }
+            Ok(Box::new(addrs.into_iter()) as reqwest::dns::Addrs)
+        })
+    }
+}
+
+/// A client for fetching other sites' pages; see the module docs.
+pub fn client(user_agent: &str) -> reqwest::Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .user_agent(user_agent)
+        .timeout(Duration::from_secs(10))
+        .dns_resolver(Arc::new(PublicResolver))
+        .redirect(reqwest::redirect::Policy::custom(|attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                attempt.error("too many redirects")
+            } else if parse_url(attempt.url().as_str()).is_none() {
+                attempt.error("redirect to a disallowed address")
+            } else {
+                attempt.follow()
+            }
+        }))
+        .build()
+}
+
+pub struct Page {
+    /// Where the page ended up after redirects.
+    pub url: Url,
+    pub status: reqwest::StatusCode,
+    /// `Link` header values.
+    pub links: Vec<String>,
+    /// The start of the body, up to `MAX_PAGE_BYTES`.
+    pub html: String,
+}
+
+pub async fn fetch(client: &reqwest::Client, url: &str) -> Result<Page, String> {
+    let url = parse_url(url).ok_or("disallowed address")?;
+    let mut response = client.get(url).send().await.map_err(|e| e.to_string())?;
+    let links = response
+        .headers()
+        .get_all(header::LINK)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .map(str::to_string)
+        .collect();
+    let (url, status) = (response.url().clone(), response.status());
+
+    let mut body = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(|e| e.to_string())? {
+        body.extend_from_slice(&chunk);
+        if body.len() >= MAX_PAGE_BYTES {
+            body.truncate(MAX_PAGE_BYTES);
+            break;
+        }
+    }
+    Ok(Page { url, status, links, html: String::from_utf8_lossy(&body).into_owned() })
+}
+
+// ── Parsing ──
+
+/// Undo the entity escapes attribute values and titles commonly use.
+pub fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&amp;", "&")
+}
+
+/// The value of attribute `name` in the start tag `tag`.
+pub fn attribute(tag: &str, name: &str) -> Option<String> {
+    ATTR.captures_iter(tag).find_map(|caps| {
+        if !caps[1].eq_ignore_ascii_case(name) {
+            return None;
+        }
+        let value = caps.get(2).or(caps.get(3)).or(caps.get(4))?;
+        Some(unescape(value.as_str()))
+    })
+}
+
+/// `text` unescaped, on one line and at most `max` characters.
+fn clean(text: &str, max: usize) -> String {
+    let text = unescape(text).split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut chars = text.chars();
+    let head: String = chars.by_ref().take(max).collect();
+    if chars.next().is_some() {
+        format!("{}\u{2026}", head.trim_end())
+    } else {
+        head
+    }
+}
+
+/// The page's `<title>`; empty when it has none.
+pub fn title(html: &str) -> String {
+    TITLE
+        .captures(html)
+        .map(|caps| clean(&caps[1], MAX_TITLE_LEN))
+        .unwrap_or_default()
+}
+
+/// `<meta>` contents keyed by lowercased `property` or `name`; the first
+/// of each wins.
+fn meta_tags(html: &str) -> HashMap<String, String> {
+    let mut meta = HashMap::new();
+    for tag in META_TAG.find_iter(html) {
+        let tag = tag.as_str();
+        let key = attribute(tag, "property").or_else(|| attribute(tag, "name"));
+        if let (Some(key), Some(content)) = (key, attribute(tag, "content")) {
+            meta.entry(key.to_ascii_lowercase()).or_insert(content);
+        }
+    }
+    meta
+}
+
+/// `href` made absolute against `base`, if it is a usable http(s) address.
+fn absolute(base: &Url, href: &str) -> Option<String> {
+    let url = base.join(href.trim()).ok()?;
+    Some(parse_url(url.as_str())?.to_string())
+}
+
+/// The preview a page's meta tags describe; `None` without any title.
+fn read_preview(page: &Page) -> Option<LinkPreview> {
+    let meta = meta_tags(&page.html);
+    let get = |keys: &[&str]| keys.iter().find_map(|k| meta.get(*k)).filter(|v| !v.trim().is_empty());
+
+    let title = get(&["og:title", "twitter:title"])
+        .map(|t| clean(t, MAX_TITLE_LEN))
+        .unwrap_or_else(|| title(&page.html));
+    if title.is_empty() {
+        return None;
+    }
+    Some(LinkPreview {
+        title,
+        description: get(&["og:description", "twitter:description", "description"])
+            .map(|d| clean(d, MAX_DESCRIPTION_LEN))
+            .unwrap_or_default(),
+        image: get(&["og:image", "og:image:url", "twitter:image"])
+            .and_then(|src| absolute(&page.url, src)),
+        url: get(&["og:url"])
+            .and_then(|href| absolute(&page.url, href))
+            .or_else(|| Some(page.url.to_string())),
+        site_name: get(&["og:site_name"]).map(|s| clean(s, MAX_TITLE_LEN)),
+    })
+}
+
+/// The page's oEmbed endpoint, from `<link rel="alternate"
+/// type="application/json+oembed">`.
+fn oembed_url(page: &Page) -> Option<String> {
+    LINK_TAG.find_iter(&page.html).find_map(|tag| {
+        let tag = tag.as_str();
+        let kind = attribute(tag, "type")?;
+        if !kind.eq_ignore_ascii_case("application/json+oembed") {
+            return None;
+        }
+        absolute(&page.url, &attribute(tag, "href")?)
+    })
+}
+
+#[derive(Deserialize)]
+struct OEmbed {
+    title: Option<String>,
+    author_name: Option<String>,
+    provider_name: Option<String>,
+    thumbnail_url: Option<String>,
+}
+
+/// Fill gaps in `preview` (or make one) from the page's oEmbed response,
+/// which video and social sites often have in place of meta tags.
+async fn with_oembed(client: &reqwest::Client, page: &Page, preview: Option<LinkPreview>) -> Option<LinkPreview> {
+    if preview.as_ref().is_some_and(|p| p.image.is_some()) {
+        return preview;
+    }
+    let Some(endpoint) = oembed_url(page) else {
+        return preview;
+    };
+    let oembed = match fetch(client, &endpoint).await {
+        Ok(response) if response.status.is_success() => {
+            serde_json::from_str::<OEmbed>(&response.html).ok()
+        }
+        _ => None,
+    };
+    let Some(oembed) = oembed else {
+        return preview;
+    };
+
+    let image = oembed.thumbnail_url.and_then(|src| absolute(&page.url, &src));
+    match preview {
+        Some(mut preview) => {
+            preview.image = image;
+            preview.site_name = preview.site_name.or(oembed.provider_name);
+            Some(preview)
+        }
+        None => Some(LinkPreview {
+            title: clean(&oembed.title?, MAX_TITLE_LEN),
+            description: oembed.author_name.unwrap_or_default(),
+            image,
+            url: Some(page.url.to_string()),
+            site_name: oembed.provider_name,
+        }),
+    }
+}
+
+async fn unfurl(client: &reqwest::Client, url: &str) -> Result<Option<LinkPreview>, String> {
+    let page = fetch(client, url).await?;
+    match page.status.as_u16() {
+        // Nothing to show, and no point asking again
+        404 | 410 => return Ok(None),
+        _ if !page.status.is_success() => return Err(format!("HTTP {}", page.status)),
+        _ => {}
+    }
+    let preview = read_preview(&page);
+    Ok(with_oembed(client, &page, preview).await)
+}
+
+// ── Queue ──
+
+/// Distinct URLs in `body` that get previews, at most `max`.
+pub fn preview_urls(body: &str, max: usize) -> Vec<Url> {
+    let mut urls: Vec<Url> = Vec::new();
+    for url in bare_urls(body).iter().filter_map(|u| parse_url(u)) {
+        if urls.len() == max {
+            break;
+        }
+        if !urls.contains(&url) {
+            urls.push(url);
+        }
+    }
+    urls
+}
+
+/// Queue the pages a just-published body links to, when previews are on.
+pub fn enqueue(conn: &rusqlite::Connection, config: &Config, body: &str) -> rusqlite::Result<()> {
+    let Some(unfurl) = &config.unfurl else {
+        return Ok(());
+    };
+    for url in preview_urls(body, unfurl.max_links) {
+        conn.execute(
+            "INSERT INTO link_previews (url) VALUES (?1)
+             ON CONFLICT(url) DO UPDATE
+             SET fetched_at = NULL, attempts = 0, next_attempt_at = datetime('now')
+             WHERE fetched_at < datetime('now', '-' || ?2 || ' days')",
+            rusqlite::params![url.as_str(), REFRESH_AFTER_DAYS],
+        )?;
+    }
+    Ok(())
+}
+
+struct Due {
+    url: String,
+    attempts: i64,
+}
+
+fn due_pages(conn: &rusqlite::Connection) -> rusqlite::Result<Vec<Due>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT url, attempts FROM link_previews
+         WHERE fetched_at IS NULL AND attempts < ?1 AND next_attempt_at <= datetime('now')
+         ORDER BY created_at
+         LIMIT ?2",
+    )?;
+    let rows = stmt
+        .query_map([MAX_ATTEMPTS, BATCH_SIZE], |row| {
+            Ok(Due { url: row.get(0)?, attempts: row.get(1)? })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
+
+fn record_fetch(
+    conn: &rusqlite::Connection,
+    due: &Due,
+    result: Result<Option<LinkPreview>, String>,
+) -> rusqlite::Result<()> {
+    match result {
+        // A refresh that finds nothing keeps the preview it had
+        Ok(preview) => conn.execute(
+            "UPDATE link_previews
+             SET data = COALESCE(?2, data), fetched_at = datetime('now'),
+                 attempts = attempts + 1, last_error = NULL
+             WHERE url = ?1",
+            rusqlite::params![
+                due.url,
+                preview.and_then(|p| serde_json::to_string(&p).ok())
+            ],
+        )?,
+        Err(error) => {
+            let delay = RETRY_BASE_SECS << due.attempts;
+            conn.execute(
+                "UPDATE link_previews
+                 SET attempts = attempts + 1, last_error = ?2,
+                     next_attempt_at = datetime('now', '+' || ?3 || ' seconds')
+                 WHERE url = ?1",
+                rusqlite::params![due.url, error, delay],
+            )?
+        }
+    };
+    Ok(())
+}
+
+async fn fetch_due(pool: &DbPool, client: &reqwest::Client) -> Result<(), String> {
+    let db = pool.clone();
+    let due = tokio::task::spawn_blocking(move || {
+        let conn = db.get().map_err(|e| e.to_string())?;
+        due_pages(&conn).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    for page in due {
+        let result = unfurl(client, &page.url).await;
+        let db = pool.clone();
+        tokio::task::spawn_blocking(move || {
+            let conn = db.get().map_err(|e| e.to_string())?;
+            record_fetch(&conn, &page, result).map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+    }
+    Ok(())
+}
+
+/// Fetch queued pages for the lifetime of the process.
+pub fn spawn_worker(pool: DbPool) {
+    tokio::spawn(async move {
+        let client = match client("mikaana-api (link preview)") {
+            Ok(client) => client,
+            Err(e) => {
+                eprintln!("Link previews disabled: {e}");
+                return;
+            }
+        };
+
+        let mut ticks = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            ticks.tick().await;
+            if let Err(e) = fetch_due(&pool, &client).await {
+                eprintln!("Fetching link previews failed: {e}");
+            }
+        }
+    });
+}
+
+// ── Handlers ──
+
+#[derive(Deserialize)]
+pub struct UnfurlParams {
+    url: String,
+}
+
+/// GET /api/unfurl?url= — the preview of a page linked from a post; 404
+/// until it has been fetched, or when it had nothing to show
+pub async fn get_preview(
+    State(state): State<AppState>,
+    Query(params): Query<UnfurlParams>,
+) -> Result<impl IntoResponse, StatusCode> {
+    if state.config.load().unfurl.is_none() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let url = parse_url(&params.url).ok_or(StatusCode::BAD_REQUEST)?;
+    let pool = state.reader.clone();
+
+    let data: String = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        conn.query_row(
+            "SELECT data FROM link_previews WHERE url = ?1 AND data IS NOT NULL",
+            [url.as_str()],
+            |row| row.get(0),
+        )
+        .map_err(|_| StatusCode::NOT_FOUND)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+    let preview: LinkPreview =
+        serde_json::from_str(&data).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(([(header::CACHE_CONTROL, "public, max-age=3600")], Json(preview)))
+}
@@ -0,0 +1,69 @@
+//! Looking up other users by name, for `@mention` autocomplete.
+
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use mikaana_shared::User;
+use serde::Deserialize;
+
+use crate::{auth, AppState};
+
+const SEARCH_LIMIT: i64 = 8;
+
+/// Longest username GitHub allows, and so the longest useful query.
+const MAX_QUERY_LEN: usize = 39;
+
+#[derive(Deserialize)]
+pub struct SearchParams {
+    #[serde(default)]
+    q: String,
+}
+
+/// GET /api/users/search?q= — active users whose username or display name
+/// starts with `q` (a leading `@` is ignored), username matches first
+pub async fn search_users(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<SearchParams>,
+) -> Result<Json<Vec<User>>, StatusCode> {
+    auth::extract_user_id(&headers, &state.jwt_secret)?;
+    let q = params.q.trim().trim_start_matches('@');
+    if q.is_empty() {
+        return Ok(Json(Vec::new()));
+    }
+    if q.chars().count() > MAX_QUERY_LEN {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let pattern = format!(
+        "{}%",
+        q.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+    );
+
+    let pool = state.reader.clone();
+    let users = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT id, username, avatar_url, display_name FROM users
+                 WHERE deleted_at IS NULL AND merged_into IS NULL AND banned_at IS NULL AND guest_key IS NULL
+                   AND (username LIKE ?1 ESCAPE '\\' OR display_name LIKE ?1 ESCAPE '\\')
+                 ORDER BY username LIKE ?1 ESCAPE '\\' DESC, username COLLATE NOCASE
+                 LIMIT ?2",
+            )
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let users = stmt
+            .query_map(rusqlite::params![pattern, SEARCH_LIMIT], |row| {
+                auth::user_from_row(row, 0)
+            })
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok::<_, StatusCode>(users)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(users))
+}
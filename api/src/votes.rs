@@ -3,10 +3,10 @@ use axum::{
     http::{HeaderMap, StatusCode},
     Json,
 };
-use mikaana_shared::{CreateVote, VoteResponse};
+use mikaana_shared::{CreatePostVote, CreateVote, ExportedVote, UserId, VoteMode, VoteResponse};
 use serde::Deserialize;
 
-use crate::{auth, AppState};
+use crate::{auth, forum::ThreadFlags, moderation, notifications, sites, AppState};
 
 #[derive(Deserialize)]
 pub struct VoteQuery {
@@ -14,6 +14,204 @@ pub struct VoteQuery {
     id: i64,
 }
 
+#[derive(Deserialize)]
+pub struct PostVoteQuery {
+    slug: String,
+}
+
+/// Longest slug accepted for a post vote.
+const MAX_SLUG_LEN: usize = 500;
+
+// ── Queries ──
+
+/// Target types and the tables holding their running `vote_count`.
+const COUNTERS: [(&str, &str); 4] = [
+    ("post", "post_targets"),
+    ("comment", "comments"),
+    ("thread", "threads"),
+    ("reply", "replies"),
+];
+
+fn counter_table(target_type: &str) -> Option<&'static str> {
+    COUNTERS
+        .iter()
+        .find(|(t, _)| *t == target_type)
+        .map(|(_, table)| *table)
+}
+
+pub fn vote_count(
+    conn: &rusqlite::Connection,
+    site: &str,
+    target_type: &str,
+    target_id: i64,
+) -> i64 {
+    let Some(table) = counter_table(target_type) else {
+        return 0;
+    };
+    if !sites::owns(conn, site, target_type, target_id).unwrap_or(false) {
+        return 0;
+    }
+    conn.query_row(
+        &format!("SELECT vote_count FROM {table} WHERE id = ?1"),
+        [target_id],
+        |row| row.get(0),
+    )
+    .unwrap_or(0)
+}
+
+fn adjust_count(
+    conn: &rusqlite::Connection,
+    target_type: &str,
+    target_id: i64,
+    delta: i32,
+) -> rusqlite::Result<()> {
+    if let Some(table) = counter_table(target_type) {
+        conn.execute(
+            &format!("UPDATE {table} SET vote_count = vote_count + ?2 WHERE id = ?1"),
+            rusqlite::params![target_id, delta],
+        )?;
+    }
+    Ok(())
+}
+
+/// Take the votes matched by `filter` (a condition on `votes v`) out of
+/// their targets' counts, ahead of deleting them.
+pub fn discount(
+    conn: &rusqlite::Connection,
+    filter: &str,
+    params: impl rusqlite::Params + Clone,
+) -> rusqlite::Result<()> {
+    for (target_type, table) in COUNTERS {
+        conn.execute(
+            &format!(
+                "UPDATE {table} SET vote_count = vote_count - (
+                     SELECT COALESCE(SUM(v.value), 0) FROM votes v
+                     WHERE {filter} AND v.target_type = '{target_type}' AND v.target_id = {table}.id)
+                 WHERE id IN (SELECT v.target_id FROM votes v
+                              WHERE {filter} AND v.target_type = '{target_type}')"
+            ),
+            params.clone(),
+        )?;
+    }
+    Ok(())
+}
+
+/// Recompute every `vote_count` from the votes themselves.
+pub fn recount_all(conn: &rusqlite::Connection) -> rusqlite::Result<()> {
+    for (target_type, table) in COUNTERS {
+        conn.execute(
+            &format!(
+                "UPDATE {table} SET vote_count = (
+                     SELECT COALESCE(SUM(value), 0) FROM votes
+                     WHERE target_type = ?1 AND target_id = {table}.id)"
+            ),
+            [target_type],
+        )?;
+    }
+    Ok(())
+}
+
+pub fn user_vote(
+    conn: &rusqlite::Connection,
+    site: &str,
+    user_id: UserId,
+    target_type: &str,
+    target_id: i64,
+) -> Option<i32> {
+    conn.query_row(
+        "SELECT value FROM votes
+         WHERE site_id = ?1 AND user_id = ?2 AND target_type = ?3 AND target_id = ?4",
+        rusqlite::params![site, user_id, target_type, target_id],
+        |row| row.get(0),
+    )
+    .ok()
+}
+
+pub fn query_user_votes(
+    conn: &rusqlite::Connection,
+    user_id: UserId,
+) -> rusqlite::Result<Vec<ExportedVote>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT target_type, target_id, value, created_at FROM votes
+         WHERE user_id = ?1 ORDER BY created_at ASC",
+    )?;
+    let rows = stmt
+        .query_map([user_id], |row| {
+            Ok(ExportedVote {
+                target_type: row.get(0)?,
+                target_id: row.get(1)?,
+                value: row.get(2)?,
+                created_at: row.get(3)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
+
+/// Record `value` for the user, toggling it off when they cast the same vote
+/// again, and return the new tally. Run inside a transaction, so the vote
+/// and its target's count change together.
+fn apply_vote(
+    conn: &rusqlite::Connection,
+    site: &str,
+    user_id: UserId,
+    target_type: &str,
+    target_id: i64,
+    value: i32,
+    mode: VoteMode,
+) -> Result<VoteResponse, StatusCode> {
+    // Check if user already voted
+    let existing = user_vote(conn, site, user_id, target_type, target_id);
+
+    // Downvotes cast before a switch to up-only can still be taken back
+    if mode == VoteMode::Disabled || (!mode.allows(value) && existing != Some(value)) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let (user_vote, delta) = match existing {
+        Some(v) if v == value => {
+            // Same vote → remove (toggle off)
+            conn.execute(
+                "DELETE FROM votes
+                 WHERE site_id = ?1 AND user_id = ?2 AND target_type = ?3 AND target_id = ?4",
+                rusqlite::params![site, user_id, target_type, target_id],
+            )
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            (None, -v)
+        }
+        Some(v) => {
+            // Different vote → update
+            conn.execute(
+                "UPDATE votes SET value = ?5
+                 WHERE site_id = ?1 AND user_id = ?2 AND target_type = ?3 AND target_id = ?4",
+                rusqlite::params![site, user_id, target_type, target_id, value],
+            )
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            (Some(value), value - v)
+        }
+        None => {
+            // New vote → insert
+            conn.execute(
+                "INSERT INTO votes (site_id, user_id, target_type, target_id, value)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                rusqlite::params![site, user_id, target_type, target_id, value],
+            )
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            (Some(value), value)
+        }
+    };
+    adjust_count(conn, target_type, target_id, delta)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(VoteResponse {
+        vote_count: vote_count(conn, site, target_type, target_id),
+        user_vote,
+    })
+}
+
+// ── Handlers ──
+
 /// GET /api/votes?type=comment&id=123
 pub async fn get_votes(
     State(state): State<AppState>,
@@ -21,36 +219,19 @@ pub async fn get_votes(
     Query(params): Query<VoteQuery>,
 ) -> Result<Json<VoteResponse>, StatusCode> {
     let user_id = auth::extract_user_id(&headers, &state.jwt_secret).ok();
+    let site = sites::resolve(&headers, &state.config.load())?;
 
-    let pool = state.db.clone();
+    let pool = state.reader.clone();
     let target_type = params.r#type;
     let target_id = params.id;
 
     let resp = tokio::task::spawn_blocking(move || {
         let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-        let vote_count: i64 = conn
-            .query_row(
-                "SELECT COALESCE(SUM(value), 0) FROM votes
-                 WHERE target_type = ?1 AND target_id = ?2",
-                rusqlite::params![target_type, target_id],
-                |row| row.get(0),
-            )
-            .unwrap_or(0);
-
-        let user_vote = user_id.and_then(|uid| {
-            conn.query_row(
-                "SELECT value FROM votes
-                 WHERE user_id = ?1 AND target_type = ?2 AND target_id = ?3",
-                rusqlite::params![uid, target_type, target_id],
-                |row| row.get::<_, i32>(0),
-            )
-            .ok()
-        });
-
         Ok::<_, StatusCode>(VoteResponse {
-            vote_count,
-            user_vote,
+            vote_count: vote_count(&conn, &site, &target_type, target_id),
+            user_vote: user_id
+                .and_then(|uid| user_vote(&conn, &site, uid, &target_type, target_id)),
         })
     })
     .await
@@ -66,6 +247,7 @@ pub async fn cast_vote(
     Json(payload): Json<CreateVote>,
 ) -> Result<Json<VoteResponse>, StatusCode> {
     let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+    let site = sites::resolve(&headers, &state.config.load())?;
 
     if payload.value != 1 && payload.value != -1 {
         return Err(StatusCode::BAD_REQUEST);
@@ -75,64 +257,108 @@ pub async fn cast_vote(
     let target_type = payload.target_type.clone();
     let target_id = payload.target_id;
     let value = payload.value;
+    let milestones = state.config.load().vote_milestones.clone();
+    let mode = state.config.load().vote_mode(&site);
 
     let resp = tokio::task::spawn_blocking(move || {
-        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let mut conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_active(&conn, user_id)?;
+        let tx = conn
+            .transaction()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-        // Check if user already voted
-        let existing: Option<i32> = conn
-            .query_row(
-                "SELECT value FROM votes
-                 WHERE user_id = ?1 AND target_type = ?2 AND target_id = ?3",
-                rusqlite::params![user_id, target_type, target_id],
-                |row| row.get(0),
-            )
-            .ok();
-
-        let user_vote = match existing {
-            Some(v) if v == value => {
-                // Same vote → remove (toggle off)
-                conn.execute(
-                    "DELETE FROM votes WHERE user_id = ?1 AND target_type = ?2 AND target_id = ?3",
-                    rusqlite::params![user_id, target_type, target_id],
-                )
-                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-                None
-            }
-            Some(_) => {
-                // Different vote → update
-                conn.execute(
-                    "UPDATE votes SET value = ?4
-                     WHERE user_id = ?1 AND target_type = ?2 AND target_id = ?3",
-                    rusqlite::params![user_id, target_type, target_id, value],
-                )
-                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-                Some(value)
-            }
-            None => {
-                // New vote → insert
-                conn.execute(
-                    "INSERT INTO votes (user_id, target_type, target_id, value)
-                     VALUES (?1, ?2, ?3, ?4)",
-                    rusqlite::params![user_id, target_type, target_id, value],
-                )
-                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-                Some(value)
-            }
-        };
+        // Content of other sites is invisible here, so can't be voted on either
+        if !sites::owns(&tx, &site, &target_type, target_id)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        // Nor can anything held, scheduled, removed or deleted
+        if moderation::content_table(&target_type).is_some()
+            && !moderation::is_published(&tx, &target_type, target_id)
+        {
+            return Err(StatusCode::NOT_FOUND);
+        }
 
-        let vote_count: i64 = conn
-            .query_row(
-                "SELECT COALESCE(SUM(value), 0) FROM votes
-                 WHERE target_type = ?1 AND target_id = ?2",
-                rusqlite::params![target_type, target_id],
-                |row| row.get(0),
-            )
-            .unwrap_or(0);
+        // Archived threads and their replies are frozen
+        let flags = ThreadFlags::for_target(&tx, &target_type, target_id)
+            .map_err(|_| StatusCode::NOT_FOUND)?;
+        if flags.is_some_and(|f| !f.can_vote()) {
+            return Err(StatusCode::FORBIDDEN);
+        }
 
+        let before = vote_count(&tx, &site, &target_type, target_id);
+        let resp = apply_vote(&tx, &site, user_id, &target_type, target_id, value, mode)?;
+        notifications::notify_vote_milestones(
+            &tx,
+            &target_type,
+            target_id,
+            before,
+            resp.vote_count,
+            user_id,
+            &milestones,
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        tx.commit().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok(resp)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(resp))
+}
+
+// ── Post votes ──
+
+/// The id post votes of `slug` are stored under, if anyone voted on it yet.
+pub fn post_target(conn: &rusqlite::Connection, site: &str, slug: &str) -> Option<i64> {
+    conn.query_row(
+        "SELECT id FROM post_targets WHERE site_id = ?1 AND slug = ?2",
+        [site, slug],
+        |row| row.get(0),
+    )
+    .ok()
+}
+
+fn ensure_post_target(
+    conn: &rusqlite::Connection,
+    site: &str,
+    slug: &str,
+) -> rusqlite::Result<i64> {
+    conn.execute(
+        "INSERT OR IGNORE INTO post_targets (site_id, slug) VALUES (?1, ?2)",
+        [site, slug],
+    )?;
+    conn.query_row(
+        "SELECT id FROM post_targets WHERE site_id = ?1 AND slug = ?2",
+        [site, slug],
+        |row| row.get(0),
+    )
+}
+
+/// GET /api/votes/post?slug=/blog/hello-world/
+pub async fn get_post_votes(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<PostVoteQuery>,
+) -> Result<Json<VoteResponse>, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret).ok();
+    let site = sites::resolve(&headers, &state.config.load())?;
+
+    let pool = state.reader.clone();
+    let resp = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        let Some(id) = post_target(&conn, &site, &params.slug) else {
+            return Ok(VoteResponse {
+                vote_count: 0,
+                user_vote: None,
+            });
+        };
         Ok::<_, StatusCode>(VoteResponse {
-            vote_count,
-            user_vote,
+            vote_count: vote_count(&conn, &site, "post", id),
+            user_vote: user_id.and_then(|uid| user_vote(&conn, &site, uid, "post", id)),
         })
     })
     .await
@@ -140,3 +366,41 @@ pub async fn cast_vote(
 
     Ok(Json(resp))
 }
+
+/// POST /api/votes/post — like `/api/votes`, addressed by slug
+pub async fn cast_post_vote(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<CreatePostVote>,
+) -> Result<Json<VoteResponse>, StatusCode> {
+    let user_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+    let site = sites::resolve(&headers, &state.config.load())?;
+
+    let slug = payload.post_slug.trim().to_string();
+    if payload.value != 1 && payload.value != -1 {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if slug.is_empty() || slug.len() > MAX_SLUG_LEN {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let pool = state.db.clone();
+    let mode = state.config.load().vote_mode(&site);
+    let resp = tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_active(&conn, user_id)?;
+        let tx = conn
+            .transaction()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        let id = ensure_post_target(&tx, &site, &slug)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let resp = apply_vote(&tx, &site, user_id, "post", id, payload.value, mode)?;
+        tx.commit().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        Ok::<_, StatusCode>(resp)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(resp))
+}
@@ -0,0 +1,143 @@
+//! Write-ahead log settings and checkpoints, for running under continuous
+//! replication such as Litestream.
+//!
+//! Litestream ships WAL frames to a replica as they are written, so the
+//! database must be in WAL mode, and it prefers to run checkpoints itself:
+//! set `database.autocheckpoint_pages = 0` when it's in charge. Without a
+//! replicator, leave SQLite's automatic checkpoints on or set
+//! `checkpoint_interval_seconds`, or the WAL grows without bound.
+//! `POST /api/admin/checkpoint` forces one, e.g. before snapshotting the
+//! volume.
+//!
+//! Writes go through one connection (`AppState::db`); requests wait their
+//! turn for it in the pool instead of racing each other into `SQLITE_BUSY`.
+//! Reads that never write use a separate pool of read-only connections
+//! (`AppState::reader`), which in WAL mode neither wait for the writer nor
+//! hold it up.
+
+use std::time::Duration;
+
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use mikaana_shared::CheckpointResult;
+use serde::Deserialize;
+
+use crate::{audit, auth, config::DatabaseConfig, config::SharedConfig, AppState, DbPool};
+
+/// Per-connection pragmas and statement cache size. Read once at startup;
+/// changing them needs a restart.
+pub fn init_connection(
+    settings: &DatabaseConfig,
+) -> impl Fn(&mut rusqlite::Connection) -> rusqlite::Result<()> + Send + Sync + 'static {
+    let settings = settings.clone();
+    move |conn| {
+        conn.busy_timeout(Duration::from_millis(settings.busy_timeout_ms))?;
+        if settings.wal {
+            conn.pragma_update(None, "journal_mode", "WAL")?;
+            // Durable as of the last checkpoint; commits still never corrupt
+            conn.pragma_update(None, "synchronous", "NORMAL")?;
+        }
+        conn.pragma_update(None, "wal_autocheckpoint", settings.autocheckpoint_pages)?;
+        conn.set_prepared_statement_cache_capacity(settings.statement_cache_capacity);
+        Ok(())
+    }
+}
+
+/// Like `init_connection`, for the read-only pool: the journal settings
+/// belong to the writer.
+pub fn init_reader(
+    settings: &DatabaseConfig,
+) -> impl Fn(&mut rusqlite::Connection) -> rusqlite::Result<()> + Send + Sync + 'static {
+    let settings = settings.clone();
+    move |conn| {
+        conn.busy_timeout(Duration::from_millis(settings.busy_timeout_ms))?;
+        conn.pragma_update(None, "query_only", true)?;
+        conn.set_prepared_statement_cache_capacity(settings.statement_cache_capacity);
+        Ok(())
+    }
+}
+
+fn checkpoint(conn: &rusqlite::Connection, mode: &str) -> rusqlite::Result<CheckpointResult> {
+    conn.query_row(&format!("PRAGMA wal_checkpoint({mode})"), [], |row| {
+        Ok(CheckpointResult {
+            busy: row.get(0)?,
+            log_frames: row.get(1)?,
+            checkpointed_frames: row.get(2)?,
+        })
+    })
+}
+
+/// Run a `PASSIVE` checkpoint every `database.checkpoint_interval_seconds`
+/// (re-read after each one, so reloads apply); 0 leaves it to SQLite or the
+/// replicator.
+pub fn spawn_checkpointer(config: SharedConfig, pool: DbPool) {
+    tokio::spawn(async move {
+        loop {
+            let interval = config.load().database.checkpoint_interval_seconds;
+            if interval == 0 {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                continue;
+            }
+            tokio::time::sleep(Duration::from_secs(interval)).await;
+
+            let pool = pool.clone();
+            let result = tokio::task::spawn_blocking(move || {
+                let conn = pool.get().map_err(|e| e.to_string())?;
+                checkpoint(&conn, "PASSIVE").map_err(|e| e.to_string())
+            })
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|r| r);
+            if let Err(e) = result {
+                eprintln!("Scheduled WAL checkpoint failed: {e}");
+            }
+        }
+    });
+}
+
+#[derive(Deserialize)]
+pub struct CheckpointParams {
+    mode: Option<String>,
+}
+
+/// POST /api/admin/checkpoint?mode=passive|full|restart|truncate
+pub async fn force_checkpoint(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<CheckpointParams>,
+) -> Result<Json<CheckpointResult>, StatusCode> {
+    let admin_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+    let mode = match params.mode.as_deref().unwrap_or("passive") {
+        "passive" => "PASSIVE",
+        "full" => "FULL",
+        "restart" => "RESTART",
+        "truncate" => "TRUNCATE",
+        _ => return Err(StatusCode::BAD_REQUEST),
+    };
+
+    let pool = state.db.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_admin(&conn, admin_id)?;
+
+        let result = checkpoint(&conn, mode).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        audit::record(
+            &conn,
+            admin_id,
+            "database.checkpoint",
+            "database",
+            0,
+            serde_json::json!({ "mode": mode, "busy": result.busy }),
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok::<_, StatusCode>(result)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(result))
+}
@@ -0,0 +1,369 @@
+//! Outgoing webhooks for content events.
+//!
+//! Handlers call `enqueue` next to the write they announce; that stores one
+//! delivery per matching webhook, and a background worker POSTs them:
+//!
+//! ```text
+//! POST <url>
+//! Content-Type: application/json
+//! X-Mikaana-Event: comment.created
+//! X-Mikaana-Delivery: 42
+//! X-Mikaana-Signature: sha256=<hex HMAC-SHA256 of the body, keyed by the secret>
+//!
+//! {"event": "comment.created", "site_id": "default", "created_at": "...", "data": {...}}
+//! ```
+//!
+//! `data` is the created `Comment`, `Thread` or `Reply` as the REST API
+//! returns it, or the report for `report.created`. Failed deliveries are
+//! retried with exponential backoff, `MAX_ATTEMPTS` times in total.
+
+use std::time::Duration;
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use mikaana_shared::{CreateWebhook, NewWebhook, Webhook};
+use serde::Serialize;
+
+use crate::{audit, auth, sites, AppState, DbPool};
+
+pub const COMMENT_CREATED: &str = "comment.created";
+pub const THREAD_CREATED: &str = "thread.created";
+pub const REPLY_CREATED: &str = "reply.created";
+pub const REPORT_CREATED: &str = "report.created";
+
+const EVENTS: [&str; 4] = [COMMENT_CREATED, THREAD_CREATED, REPLY_CREATED, REPORT_CREATED];
+
+const MAX_ATTEMPTS: i64 = 8;
+/// First retry delay; doubles with each attempt (30s, 1m, 2m, ... ~1h).
+const RETRY_BASE_SECS: i64 = 30;
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+const BATCH_SIZE: i64 = 50;
+
+// ── Queue ──
+
+/// Queue `event` for every webhook of `site` subscribed to it.
+pub fn enqueue(
+    conn: &rusqlite::Connection,
+    site: &str,
+    event: &str,
+    data: &impl Serialize,
+) -> rusqlite::Result<()> {
+    let payload = serde_json::json!({
+        "event": event,
+        "site_id": site,
+        "created_at": conn.query_row("SELECT datetime('now')", [], |row| row.get::<_, String>(0))?,
+        "data": data,
+    });
+    conn.execute(
+        "INSERT INTO webhook_deliveries (webhook_id, event, payload)
+         SELECT id, ?2, ?3 FROM webhooks
+         WHERE site_id = ?1 AND (events = '' OR ',' || events || ',' LIKE '%,' || ?2 || ',%')",
+        rusqlite::params![site, event, payload.to_string()],
+    )?;
+    Ok(())
+}
+
+struct Delivery {
+    id: i64,
+    event: String,
+    payload: String,
+    attempts: i64,
+    url: String,
+    secret: String,
+}
+
+fn due_deliveries(conn: &rusqlite::Connection) -> rusqlite::Result<Vec<Delivery>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT d.id, d.event, d.payload, d.attempts, w.url, w.secret
+         FROM webhook_deliveries d JOIN webhooks w ON d.webhook_id = w.id
+         WHERE d.delivered_at IS NULL AND d.attempts < ?1
+           AND d.next_attempt_at <= datetime('now')
+         ORDER BY d.id
+         LIMIT ?2",
+    )?;
+    let rows = stmt
+        .query_map([MAX_ATTEMPTS, BATCH_SIZE], |row| {
+            Ok(Delivery {
+                id: row.get(0)?,
+                event: row.get(1)?,
+                payload: row.get(2)?,
+                attempts: row.get(3)?,
+                url: row.get(4)?,
+                secret: row.get(5)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
+
+fn record_attempt(
+    conn: &rusqlite::Connection,
+    delivery: &Delivery,
+    result: Result<(), String>,
+) -> rusqlite::Result<()> {
+    match result {
+        Ok(()) => conn.execute(
+            "UPDATE webhook_deliveries
+             SET attempts = attempts + 1, delivered_at = datetime('now'), last_error = NULL
+             WHERE id = ?1",
+            [delivery.id],
+        )?,
+        Err(error) => {
+            let delay = RETRY_BASE_SECS << delivery.attempts;
+            conn.execute(
+                "UPDATE webhook_deliveries
+                 SET attempts = attempts + 1, last_error = ?2,
+                     next_attempt_at = datetime('now', '+' || ?3 || ' seconds')
+                 WHERE id = ?1",
+                rusqlite::params![delivery.id, error, delay],
+            )?
+        }
+    };
+    Ok(())
+}
+
+// ── Worker ──
+
+fn sign(secret: &str, body: &str) -> String {
+    let key = ring::hmac::Key::new(ring::hmac::HMAC_SHA256, secret.as_bytes());
+    let tag = ring::hmac::sign(&key, body.as_bytes());
+    let hex: String = tag.as_ref().iter().map(|b| format!("{b:02x}")).collect();
+    format!("sha256={hex}")
+}
+
+async fn send(client: &reqwest::Client, delivery: &Delivery) -> Result<(), String> {
+    client
+        .post(&delivery.url)
+        .header("Content-Type", "application/json")
+        .header("X-Mikaana-Event", &delivery.event)
+        .header("X-Mikaana-Delivery", delivery.id.to_string())
+        .header("X-Mikaana-Signature", sign(&delivery.secret, &delivery.payload))
+        .body(delivery.payload.clone())
+        .send()
+        .await
+        .map_err(|e| e.to_string())?
+        .error_for_status()
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+async fn deliver_due(pool: &DbPool, client: &reqwest::Client) -> Result<(), String> {
+    let db = pool.clone();
+    let due = tokio::task::spawn_blocking(move || {
+        let conn = db.get().map_err(|e| e.to_string())?;
+        due_deliveries(&conn).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    for delivery in due {
+        let result = send(client, &delivery).await;
+        let db = pool.clone();
+        tokio::task::spawn_blocking(move || {
+            let conn = db.get().map_err(|e| e.to_string())?;
+            record_attempt(&conn, &delivery, result).map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+    }
+    Ok(())
+}
+
+/// Deliver queued webhooks for the lifetime of the process.
+pub fn spawn_worker(pool: DbPool) {
+    tokio::spawn(async move {
+        let client = match reqwest::Client::builder()
+            .user_agent("mikaana-api")
+            .timeout(Duration::from_secs(10))
+            .build()
+        {
+            Ok(client) => client,
+            Err(e) => {
+                eprintln!("Webhook delivery disabled: {e}");
+                return;
+            }
+        };
+
+        let mut ticks = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            ticks.tick().await;
+            if let Err(e) = deliver_due(&pool, &client).await {
+                eprintln!("Webhook delivery failed: {e}");
+            }
+        }
+    });
+}
+
+// ── Queries ──
+
+/// Columns read by `webhook_from_row`, in order.
+const WEBHOOK_SELECT: &str = "SELECT w.id, w.site_id, w.url, w.events, w.created_at,
+        (SELECT COUNT(*) FROM webhook_deliveries
+         WHERE webhook_id = w.id AND delivered_at IS NULL AND attempts < ?1),
+        (SELECT last_error FROM webhook_deliveries
+         WHERE webhook_id = w.id AND last_error IS NOT NULL ORDER BY id DESC LIMIT 1)
+ FROM webhooks w";
+
+fn webhook_from_row(row: &rusqlite::Row) -> rusqlite::Result<Webhook> {
+    let events: String = row.get(3)?;
+    Ok(Webhook {
+        id: row.get(0)?,
+        site_id: row.get(1)?,
+        url: row.get(2)?,
+        events: events
+            .split(',')
+            .filter(|e| !e.is_empty())
+            .map(String::from)
+            .collect(),
+        created_at: row.get(4)?,
+        pending_deliveries: row.get(5)?,
+        last_error: row.get(6)?,
+    })
+}
+
+fn query_webhooks(conn: &rusqlite::Connection) -> rusqlite::Result<Vec<Webhook>> {
+    let mut stmt = conn.prepare_cached(&format!("{WEBHOOK_SELECT} ORDER BY w.id"))?;
+    let rows = stmt
+        .query_map([MAX_ATTEMPTS], webhook_from_row)?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
+
+fn new_secret() -> String {
+    use rand::Rng;
+    let bytes: [u8; 32] = rand::thread_rng().gen();
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+// ── Handlers ──
+
+/// GET /api/admin/webhooks
+pub async fn list_webhooks(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<Webhook>>, StatusCode> {
+    let admin_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+
+    let pool = state.reader.clone();
+    let hooks = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_admin(&conn, admin_id)?;
+        query_webhooks(&conn).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(hooks))
+}
+
+/// POST /api/admin/webhooks — the response carries the signing secret
+pub async fn create_webhook(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<CreateWebhook>,
+) -> Result<Json<NewWebhook>, StatusCode> {
+    let admin_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+
+    let url = reqwest::Url::parse(&payload.url).map_err(|_| StatusCode::BAD_REQUEST)?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if !payload.events.iter().all(|e| EVENTS.contains(&e.as_str())) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let site = payload
+        .site_id
+        .unwrap_or_else(|| sites::DEFAULT_SITE.to_string());
+    if site != sites::DEFAULT_SITE && state.config.load().site(&site).is_none() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let pool = state.db.clone();
+    let secret = new_secret();
+    let events = payload.events.join(",");
+    let created = tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_admin(&conn, admin_id)?;
+        let tx = conn
+            .transaction()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        tx.execute(
+            "INSERT INTO webhooks (site_id, url, secret, events) VALUES (?1, ?2, ?3, ?4)",
+            rusqlite::params![site, url.as_str(), secret, events],
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let id = tx.last_insert_rowid();
+
+        audit::record(
+            &tx,
+            admin_id,
+            "webhook.create",
+            "webhook",
+            id,
+            serde_json::json!({ "site_id": site, "url": url.as_str(), "events": events }),
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        let webhook = tx
+            .query_row(
+                &format!("{WEBHOOK_SELECT} WHERE w.id = ?2"),
+                [MAX_ATTEMPTS, id],
+                webhook_from_row,
+            )
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        tx.commit().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok::<_, StatusCode>(NewWebhook { webhook, secret })
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(created))
+}
+
+/// DELETE /api/admin/webhooks/:id — drops undelivered events too
+pub async fn delete_webhook(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, StatusCode> {
+    let admin_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+
+    let pool = state.db.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_admin(&conn, admin_id)?;
+        let tx = conn
+            .transaction()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        tx.execute("DELETE FROM webhook_deliveries WHERE webhook_id = ?1", [id])
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let url: String = tx
+            .query_row("DELETE FROM webhooks WHERE id = ?1 RETURNING url", [id], |row| {
+                row.get(0)
+            })
+            .map_err(|_| StatusCode::NOT_FOUND)?;
+
+        audit::record(
+            &tx,
+            admin_id,
+            "webhook.delete",
+            "webhook",
+            id,
+            serde_json::json!({ "url": url }),
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        tx.commit().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        Ok(StatusCode::NO_CONTENT)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+}
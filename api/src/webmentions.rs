@@ -0,0 +1,462 @@
+//! Webmentions (<https://www.w3.org/TR/webmention/>) for blog posts.
+//!
+//! Receiving: `POST /api/webmention` with the form fields `source` and
+//! `target`, where `target` is a post on one of the instance's sites. The
+//! mention is stored as pending and a background worker fetches `source`;
+//! it is listed (`GET /api/webmentions?slug=`) once that page is found to
+//! link to `target`, and dropped when it no longer does. Sending the same
+//! mention again re-checks it.
+//!
+//! Sending (`send = true`): links in newly published comments are queued,
+//! and the worker posts the comment's address to each linked page's
+//! endpoint, when it advertises one.
+//!
+//! Pages are fetched with `unfurl::client`, so mentions can't be used to
+//! probe the network the API runs in.
+
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use axum::{
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Form, Json,
+};
+use mikaana_shared::{bare_urls, Comment, Webmention};
+use regex::Regex;
+use reqwest::Url;
+use serde::Deserialize;
+
+use crate::unfurl::{self, attribute, fetch, parse_url, unescape, Page};
+use crate::{config::Config, emails, sites, AppState, DbPool};
+
+const MAX_ATTEMPTS: i64 = 5;
+/// First retry delay; doubles with each attempt.
+const RETRY_BASE_SECS: i64 = 60;
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+const BATCH_SIZE: i64 = 20;
+
+/// Links of one comment that are sent webmentions.
+const MAX_LINKS_PER_COMMENT: usize = 10;
+const LIST_LIMIT: i64 = 100;
+
+static HREF: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?i)\bhref\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'>]+))"#).unwrap()
+});
+static LINK_TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)<(?:link|a)\b[^>]*>").unwrap());
+
+// ── Addresses ──
+
+/// The site whose posts live at `url`'s origin.
+fn target_site(config: &Config, cors_origin: &str, url: &Url) -> Option<String> {
+    let origin = url.origin();
+    let site = config.sites.iter().find(|s| {
+        s.origins
+            .iter()
+            .any(|o| Url::parse(o).is_ok_and(|o| o.origin() == origin))
+    });
+    match site {
+        Some(site) => Some(site.id.clone()),
+        None => Url::parse(cors_origin)
+            .is_ok_and(|o| o.origin() == origin)
+            .then(|| sites::DEFAULT_SITE.to_string()),
+    }
+}
+
+fn links_to(html: &str, target: &str) -> bool {
+    HREF.captures_iter(html).any(|caps| {
+        caps.get(1)
+            .or(caps.get(2))
+            .or(caps.get(3))
+            .is_some_and(|href| unescape(href.as_str()) == target)
+    })
+}
+
+/// The page's webmention endpoint: from a `Link` header, else the first
+/// `<link>` or `<a>` with `rel="webmention"`.
+fn endpoint(page: &Page) -> Option<Url> {
+    let from_header = page.links.iter().flat_map(|v| v.split(',')).find_map(|link| {
+        let (href, params) = link.trim().strip_prefix('<')?.split_once('>')?;
+        let is_webmention = params.split(';').any(|param| {
+            param.trim().strip_prefix("rel=").is_some_and(|rel| {
+                rel.trim_matches('"')
+                    .split_whitespace()
+                    .any(|r| r.eq_ignore_ascii_case("webmention"))
+            })
+        });
+        is_webmention.then(|| href.to_string())
+    });
+    let href = from_header.or_else(|| {
+        LINK_TAG.find_iter(&page.html).find_map(|tag| {
+            let rel = attribute(tag.as_str(), "rel")?;
+            if !rel.split_whitespace().any(|r| r.eq_ignore_ascii_case("webmention")) {
+                return None;
+            }
+            attribute(tag.as_str(), "href")
+        })
+    })?;
+    // An empty href means the page itself
+    let url = page.url.join(&href).ok()?;
+    parse_url(url.as_str())
+}
+
+// ── Receiving ──
+
+enum Check {
+    /// The source links to the target; carries the source's title.
+    Links(String),
+    /// The source is gone or no longer links to the target.
+    Gone,
+    Failed(String),
+}
+
+async fn check(client: &reqwest::Client, source: &str, target: &str) -> Check {
+    let page = match fetch(client, source).await {
+        Ok(page) => page,
+        Err(e) => return Check::Failed(e),
+    };
+    match page.status.as_u16() {
+        404 | 410 => Check::Gone,
+        _ if !page.status.is_success() => Check::Failed(format!("HTTP {}", page.status)),
+        _ if links_to(&page.html, target) => Check::Links(unfurl::title(&page.html)),
+        _ => Check::Gone,
+    }
+}
+
+struct Pending {
+    id: i64,
+    source: String,
+    target: String,
+    attempts: i64,
+}
+
+fn due_checks(conn: &rusqlite::Connection) -> rusqlite::Result<Vec<Pending>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, source, target, attempts FROM webmentions
+         WHERE pending = 1 AND next_attempt_at <= datetime('now')
+         ORDER BY id
+         LIMIT ?1",
+    )?;
+    let rows = stmt
+        .query_map([BATCH_SIZE], |row| {
+            Ok(Pending {
+                id: row.get(0)?,
+                source: row.get(1)?,
+                target: row.get(2)?,
+                attempts: row.get(3)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
+
+fn record_check(conn: &rusqlite::Connection, pending: &Pending, check: Check) -> rusqlite::Result<()> {
+    match check {
+        Check::Links(title) => conn.execute(
+            "UPDATE webmentions
+             SET pending = 0, title = ?2, verified_at = datetime('now'),
+                 attempts = attempts + 1, last_error = NULL
+             WHERE id = ?1",
+            rusqlite::params![pending.id, title],
+        )?,
+        Check::Gone => conn.execute("DELETE FROM webmentions WHERE id = ?1", [pending.id])?,
+        // After the last attempt a previously verified mention stays listed
+        Check::Failed(error) => {
+            let delay = RETRY_BASE_SECS << pending.attempts;
+            conn.execute(
+                "UPDATE webmentions
+                 SET attempts = attempts + 1, last_error = ?2,
+                     pending = attempts + 1 < ?4,
+                     next_attempt_at = datetime('now', '+' || ?3 || ' seconds')
+                 WHERE id = ?1",
+                rusqlite::params![pending.id, error, delay, MAX_ATTEMPTS],
+            )?
+        }
+    };
+    Ok(())
+}
+
+async fn check_due(pool: &DbPool, client: &reqwest::Client) -> Result<(), String> {
+    let db = pool.clone();
+    let due = tokio::task::spawn_blocking(move || {
+        let conn = db.get().map_err(|e| e.to_string())?;
+        due_checks(&conn).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    for pending in due {
+        let result = check(client, &pending.source, &pending.target).await;
+        let db = pool.clone();
+        tokio::task::spawn_blocking(move || {
+            let conn = db.get().map_err(|e| e.to_string())?;
+            record_check(&conn, &pending, result).map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+    }
+    Ok(())
+}
+
+// ── Sending ──
+
+/// Queue webmentions from a just-published comment to the pages it links,
+/// when sending is on and the comment's own address is known. Links to the
+/// instance's sites are skipped.
+pub fn enqueue_sends(
+    conn: &rusqlite::Connection,
+    config: &Config,
+    cors_origin: &str,
+    site: &str,
+    comment: &Comment,
+) -> rusqlite::Result<()> {
+    if !config.webmentions.as_ref().is_some_and(|w| w.send) {
+        return Ok(());
+    }
+    let Some(source) = emails::content_url(
+        config,
+        cors_origin,
+        site,
+        Some(&comment.post_slug),
+        None,
+        "comment",
+        comment.id.0,
+    ) else {
+        return Ok(());
+    };
+
+    let targets = bare_urls(&comment.body)
+        .into_iter()
+        .filter_map(|link| parse_url(&link))
+        .filter(|url| target_site(config, cors_origin, url).is_none())
+        .take(MAX_LINKS_PER_COMMENT);
+    for target in targets {
+        conn.execute(
+            "INSERT OR IGNORE INTO webmention_sends (source, target) VALUES (?1, ?2)",
+            rusqlite::params![source, target.as_str()],
+        )?;
+    }
+    Ok(())
+}
+
+/// Notify `target`'s endpoint that `source` links to it. `Ok(false)` when
+/// the page has no endpoint.
+async fn send(client: &reqwest::Client, source: &str, target: &str) -> Result<bool, String> {
+    let page = fetch(client, target).await?;
+    if !page.status.is_success() {
+        return Err(format!("HTTP {}", page.status));
+    }
+    let Some(endpoint) = endpoint(&page) else {
+        return Ok(false);
+    };
+    client
+        .post(endpoint)
+        .form(&[("source", source), ("target", target)])
+        .send()
+        .await
+        .map_err(|e| e.to_string())?
+        .error_for_status()
+        .map_err(|e| e.to_string())?;
+    Ok(true)
+}
+
+struct Outgoing {
+    id: i64,
+    source: String,
+    target: String,
+    attempts: i64,
+}
+
+fn due_sends(conn: &rusqlite::Connection) -> rusqlite::Result<Vec<Outgoing>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT id, source, target, attempts FROM webmention_sends
+         WHERE done_at IS NULL AND attempts < ?1 AND next_attempt_at <= datetime('now')
+         ORDER BY id
+         LIMIT ?2",
+    )?;
+    let rows = stmt
+        .query_map([MAX_ATTEMPTS, BATCH_SIZE], |row| {
+            Ok(Outgoing {
+                id: row.get(0)?,
+                source: row.get(1)?,
+                target: row.get(2)?,
+                attempts: row.get(3)?,
+            })
+        })?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(rows)
+}
+
+fn record_send(
+    conn: &rusqlite::Connection,
+    outgoing: &Outgoing,
+    result: Result<bool, String>,
+) -> rusqlite::Result<()> {
+    match result {
+        Ok(sent) => conn.execute(
+            "UPDATE webmention_sends
+             SET attempts = attempts + 1, done_at = datetime('now'), last_error = ?2
+             WHERE id = ?1",
+            rusqlite::params![outgoing.id, (!sent).then_some("no webmention endpoint")],
+        )?,
+        Err(error) => {
+            let delay = RETRY_BASE_SECS << outgoing.attempts;
+            conn.execute(
+                "UPDATE webmention_sends
+                 SET attempts = attempts + 1, last_error = ?2,
+                     next_attempt_at = datetime('now', '+' || ?3 || ' seconds')
+                 WHERE id = ?1",
+                rusqlite::params![outgoing.id, error, delay],
+            )?
+        }
+    };
+    Ok(())
+}
+
+async fn send_due(pool: &DbPool, client: &reqwest::Client) -> Result<(), String> {
+    let db = pool.clone();
+    let due = tokio::task::spawn_blocking(move || {
+        let conn = db.get().map_err(|e| e.to_string())?;
+        due_sends(&conn).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    for outgoing in due {
+        let result = send(client, &outgoing.source, &outgoing.target).await;
+        let db = pool.clone();
+        tokio::task::spawn_blocking(move || {
+            let conn = db.get().map_err(|e| e.to_string())?;
+            record_send(&conn, &outgoing, result).map_err(|e| e.to_string())
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+    }
+    Ok(())
+}
+
+// ── Worker ──
+
+/// Check received and send queued webmentions for the lifetime of the
+/// process.
+pub fn spawn_worker(pool: DbPool) {
+    tokio::spawn(async move {
+        let client = match unfurl::client("mikaana-api (webmention)") {
+            Ok(client) => client,
+            Err(e) => {
+                eprintln!("Webmentions disabled: {e}");
+                return;
+            }
+        };
+
+        let mut ticks = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            ticks.tick().await;
+            if let Err(e) = check_due(&pool, &client).await {
+                eprintln!("Webmention verification failed: {e}");
+            }
+            if let Err(e) = send_due(&pool, &client).await {
+                eprintln!("Sending webmentions failed: {e}");
+            }
+        }
+    });
+}
+
+// ── Handlers ──
+
+#[derive(Deserialize)]
+pub struct MentionForm {
+    source: String,
+    target: String,
+}
+
+/// POST /api/webmention — accept a mention for checking; 202 Accepted.
+pub async fn receive(
+    State(state): State<AppState>,
+    Form(form): Form<MentionForm>,
+) -> Result<StatusCode, StatusCode> {
+    let config = state.config.load();
+    if config.webmentions.is_none() {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let (Some(source), Some(target)) = (parse_url(&form.source), parse_url(&form.target)) else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+    if source == target {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let site = target_site(&config, &state.cors_origin, &target).ok_or(StatusCode::BAD_REQUEST)?;
+    let slug = target.path().to_string();
+    let pool = state.db.clone();
+
+    tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        conn.execute(
+            "INSERT INTO webmentions (site_id, post_slug, source, target)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(source, target) DO UPDATE
+             SET pending = 1, attempts = 0, last_error = NULL,
+                 next_attempt_at = datetime('now')",
+            rusqlite::params![site, slug, form.source, form.target],
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        Ok::<_, StatusCode>(())
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+#[derive(Deserialize)]
+pub struct ListParams {
+    slug: String,
+}
+
+/// GET /api/webmentions?slug= — a post's verified mentions, newest first
+pub async fn list(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<ListParams>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let site = sites::resolve(&headers, &state.config.load())?;
+    let pool = state.reader.clone();
+
+    let mentions = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT id, source, title, verified_at FROM webmentions
+                 WHERE site_id = ?1 AND post_slug = ?2 AND verified_at IS NOT NULL
+                 ORDER BY verified_at DESC, id DESC
+                 LIMIT ?3",
+            )
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let mentions: Vec<Webmention> = stmt
+            .query_map(rusqlite::params![site, params.slug, LIST_LIMIT], |row| {
+                Ok(Webmention {
+                    id: row.get(0)?,
+                    source: row.get(1)?,
+                    title: row.get(2)?,
+                    verified_at: row.get(3)?,
+                })
+            })
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok::<_, StatusCode>(mentions)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok((
+        [
+            (header::CACHE_CONTROL, "public, max-age=300"),
+            (header::VARY, sites::VARY),
+        ],
+        Json(mentions),
+    ))
+}
@@ -0,0 +1,209 @@
+//! Word filters checked against new comments, threads and replies.
+//!
+//! A filter is a word or phrase (matched whole, ignoring case) or a regular
+//! expression. A post matching a `reject` filter is refused with `422`; one
+//! matching only `queue` filters is stored as `pending` and waits in the
+//! moderation queue. The list is instance-wide and managed by moderators
+//! under `/api/admin/word-filters`.
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use mikaana_shared::{CreateWordFilter, FilterAction, WordFilter};
+use regex::{Regex, RegexBuilder};
+
+use crate::{audit, auth, AppState};
+
+const MAX_PATTERN_LEN: usize = 200;
+
+/// Compiled size cap, so a pathological pattern can't slow down posting.
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+fn compile(pattern: &str, is_regex: bool) -> Result<Regex, regex::Error> {
+    let source = if is_regex {
+        pattern.to_string()
+    } else {
+        // Not `\b`: that never matches next to a leading or trailing symbol
+        format!(r"(?:^|\W){}(?:\W|$)", regex::escape(pattern))
+    };
+    RegexBuilder::new(&source)
+        .case_insensitive(true)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+}
+
+/// The status new content should get: `published`, or `pending` if a queue
+/// filter matched. A reject filter match is `422 Unprocessable Entity`.
+pub fn check(conn: &rusqlite::Connection, texts: &[&str]) -> Result<&'static str, StatusCode> {
+    let mut stmt = conn
+        .prepare_cached("SELECT pattern, is_regex, action FROM word_filters")
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let filters = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, bool>(1)?, row.get::<_, String>(2)?))
+        })
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .filter_map(|r| r.ok());
+
+    let mut status = "published";
+    for (pattern, is_regex, action) in filters {
+        // Patterns are validated on creation
+        let Ok(re) = compile(&pattern, is_regex) else {
+            continue;
+        };
+        if texts.iter().any(|t| re.is_match(t)) {
+            if action == FilterAction::Reject.as_str() {
+                return Err(StatusCode::UNPROCESSABLE_ENTITY);
+            }
+            status = "pending";
+        }
+    }
+    Ok(status)
+}
+
+// ── Queries ──
+
+const FILTER_SELECT: &str = "SELECT id, pattern, is_regex, action, created_at FROM word_filters";
+
+fn filter_from_row(row: &rusqlite::Row) -> rusqlite::Result<WordFilter> {
+    let action: String = row.get(3)?;
+    Ok(WordFilter {
+        id: row.get(0)?,
+        pattern: row.get(1)?,
+        is_regex: row.get(2)?,
+        action: if action == FilterAction::Queue.as_str() {
+            FilterAction::Queue
+        } else {
+            FilterAction::Reject
+        },
+        created_at: row.get(4)?,
+    })
+}
+
+// ── Handlers ──
+
+/// GET /api/admin/word-filters
+pub async fn list_filters(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<Vec<WordFilter>>, StatusCode> {
+    let mod_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+
+    let pool = state.reader.clone();
+    let filters = tokio::task::spawn_blocking(move || {
+        let conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_moderator(&conn, mod_id)?;
+        let mut stmt = conn
+            .prepare_cached(&format!("{FILTER_SELECT} ORDER BY id"))
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let rows = stmt
+            .query_map([], filter_from_row)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .filter_map(|r| r.ok())
+            .collect();
+        Ok::<_, StatusCode>(rows)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(filters))
+}
+
+/// POST /api/admin/word-filters
+pub async fn create_filter(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(payload): Json<CreateWordFilter>,
+) -> Result<Json<WordFilter>, StatusCode> {
+    let mod_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+
+    let pattern = payload.pattern.trim().to_string();
+    if pattern.is_empty() || pattern.chars().count() > MAX_PATTERN_LEN {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    compile(&pattern, payload.is_regex).map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    let pool = state.db.clone();
+    let filter = tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_moderator(&conn, mod_id)?;
+        let tx = conn
+            .transaction()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        tx.execute(
+            "INSERT INTO word_filters (pattern, is_regex, action) VALUES (?1, ?2, ?3)",
+            rusqlite::params![pattern, payload.is_regex, payload.action.as_str()],
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let id = tx.last_insert_rowid();
+
+        audit::record(
+            &tx,
+            mod_id,
+            "word_filter.create",
+            "word_filter",
+            id,
+            serde_json::json!({
+                "pattern": pattern,
+                "is_regex": payload.is_regex,
+                "action": payload.action.as_str(),
+            }),
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        let filter = tx
+            .query_row(&format!("{FILTER_SELECT} WHERE id = ?1"), [id], filter_from_row)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        tx.commit().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        Ok::<_, StatusCode>(filter)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)??;
+
+    Ok(Json(filter))
+}
+
+/// DELETE /api/admin/word-filters/:id — content it queued stays queued
+pub async fn delete_filter(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Path(id): Path<i64>,
+) -> Result<StatusCode, StatusCode> {
+    let mod_id = auth::extract_user_id(&headers, &state.jwt_secret)?;
+
+    let pool = state.db.clone();
+    tokio::task::spawn_blocking(move || {
+        let mut conn = pool.get().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        auth::require_moderator(&conn, mod_id)?;
+        let tx = conn
+            .transaction()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        let pattern: String = tx
+            .query_row(
+                "DELETE FROM word_filters WHERE id = ?1 RETURNING pattern",
+                [id],
+                |row| row.get(0),
+            )
+            .map_err(|_| StatusCode::NOT_FOUND)?;
+
+        audit::record(
+            &tx,
+            mod_id,
+            "word_filter.delete",
+            "word_filter",
+            id,
+            serde_json::json!({ "pattern": pattern }),
+        )
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        tx.commit().map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        Ok(StatusCode::NO_CONTENT)
+    })
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+}
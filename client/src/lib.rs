@@ -0,0 +1,682 @@
+//! Typed HTTP client for the mikaana API, for bots, import tools and
+//! server-side integrations.
+//!
+//! ```no_run
+//! # async fn demo() -> Result<(), mikaana_client::Error> {
+//! let client = mikaana_client::Client::new("https://mikaana-api.fly.dev")
+//!     .with_token(std::env::var("MIKAANA_TOKEN").unwrap());
+//! for c in client.list_comments("/blog/hello-world/").await? {
+//!     println!("{}: {}", c.user.username, c.body);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use mikaana_shared::*;
+use reqwest::{Method, RequestBuilder, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+// ── Errors ──
+
+#[derive(Debug)]
+pub enum Error {
+    /// Transport failure or undecodable response body.
+    Http(reqwest::Error),
+    /// The API answered with a non-success status; `body.error` says what
+    /// went wrong.
+    Status { status: StatusCode, body: ErrorBody },
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Http(e) => write!(f, "request failed: {e}"),
+            Error::Status { status, body } => {
+                write!(f, "API error: {status}")?;
+                if let Some(message) = &body.message {
+                    write!(f, ": {message}")?;
+                }
+                if let Some(id) = &body.request_id {
+                    write!(f, " (request {id})")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Http(e) => Some(e),
+            Error::Status { .. } => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Http(e)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// ── Client ──
+
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+    token: Option<String>,
+    site_key: Option<String>,
+}
+
+impl Client {
+    /// `base_url` is the API origin, e.g. `http://localhost:8080`.
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self::with_http_client(reqwest::Client::new(), base_url)
+    }
+
+    /// Use a preconfigured `reqwest::Client` (timeouts, proxies, ...).
+    pub fn with_http_client(http: reqwest::Client, base_url: impl Into<String>) -> Self {
+        Self {
+            http,
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            token: None,
+            site_key: None,
+        }
+    }
+
+    /// Authenticate requests with a JWT issued by the OAuth callback.
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    pub fn set_token(&mut self, token: Option<String>) {
+        self.token = token;
+    }
+
+    /// Act on a site other than the instance's default one, using the
+    /// `api_key` from its `[[sites]]` config entry.
+    pub fn with_site_key(mut self, key: impl Into<String>) -> Self {
+        self.site_key = Some(key.into());
+        self
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let mut req = self.http.request(method, format!("{}{}", self.base_url, path));
+        if let Some(key) = &self.site_key {
+            req = req.header("X-Mikaana-Site-Key", key);
+        }
+        match &self.token {
+            Some(token) => req.bearer_auth(token),
+            None => req,
+        }
+    }
+
+    async fn send(req: RequestBuilder) -> Result<reqwest::Response> {
+        let resp = req.send().await?;
+        let status = resp.status();
+        if status.is_success() {
+            Ok(resp)
+        } else {
+            let text = resp.text().await.unwrap_or_default();
+            let body = serde_json::from_str(&text).unwrap_or_else(|_| ErrorBody {
+                error: ApiError::from_status(status.as_u16()),
+                message: (!text.is_empty()).then_some(text),
+                request_id: None,
+            });
+            Err(Error::Status { status, body })
+        }
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        Ok(Self::send(self.request(Method::GET, path)).await?.json().await?)
+    }
+
+    async fn post<T: DeserializeOwned, B: Serialize + ?Sized>(&self, path: &str, body: &B) -> Result<T> {
+        Ok(Self::send(self.request(Method::POST, path).json(body)).await?.json().await?)
+    }
+
+    /// POST for endpoints that answer `204 No Content`.
+    async fn post_empty<B: Serialize + ?Sized>(&self, path: &str, body: &B) -> Result<()> {
+        Self::send(self.request(Method::POST, path).json(body)).await?;
+        Ok(())
+    }
+
+    async fn send_empty(&self, method: Method, path: &str) -> Result<()> {
+        Self::send(self.request(method, path)).await?;
+        Ok(())
+    }
+
+    // ── Health ──
+
+    pub async fn health(&self) -> Result<()> {
+        self.send_empty(Method::GET, routes::HEALTH).await
+    }
+
+    pub async fn public_config(&self) -> Result<PublicConfig> {
+        self.get(routes::CONFIG).await
+    }
+
+    pub async fn site_stats(&self) -> Result<SiteStats> {
+        self.get(routes::STATS).await
+    }
+
+    /// Count one widget interaction, when the server has analytics on.
+    pub async fn record_event(&self, event: AnalyticsEvent) -> Result<()> {
+        self.post_empty(routes::EVENTS, &RecordEvent { event }).await
+    }
+
+    // ── Auth ──
+
+    /// URL that starts the GitHub OAuth flow and returns to `redirect`.
+    pub fn github_login_url(&self, redirect: &str) -> String {
+        format!("{}{}", self.base_url, routes::auth::github(redirect, false))
+    }
+
+    pub async fn me(&self) -> Result<User> {
+        self.get(routes::auth::ME).await
+    }
+
+    pub async fn profile(&self) -> Result<Profile> {
+        self.get(routes::auth::PROFILE).await
+    }
+
+    pub async fn update_profile(&self, profile: &UpdateProfile) -> Result<Profile> {
+        Ok(Self::send(self.request(Method::PUT, routes::auth::ME).json(profile))
+            .await?
+            .json()
+            .await?)
+    }
+
+    pub async fn export_data(&self) -> Result<UserExport> {
+        self.get(routes::auth::EXPORT).await
+    }
+
+    /// Delete the signed-in account. Whether its content is anonymized or
+    /// removed depends on the server's `account_deletion` setting.
+    pub async fn delete_account(&self) -> Result<()> {
+        self.send_empty(Method::DELETE, routes::auth::ME).await
+    }
+
+    /// Accept the terms of service `version`, which must be the current one
+    /// (`public_config().terms`). Until then writes fail with `428`.
+    pub async fn accept_terms(&self, version: &str) -> Result<()> {
+        let body = AcceptTerms {
+            version: version.to_string(),
+        };
+        self.post_empty(routes::TERMS_ACCEPT, &body).await
+    }
+
+    // ── Comments ──
+
+    pub async fn list_comments(&self, slug: &str) -> Result<Vec<Comment>> {
+        self.get(&routes::comments::list(slug)).await
+    }
+
+    /// Whether the post takes new comments.
+    pub async fn comment_state(&self, slug: &str) -> Result<CommentState> {
+        self.get(&routes::comments::state(slug)).await
+    }
+
+    pub async fn create_comment(&self, comment: &CreateComment) -> Result<Comment> {
+        self.post(routes::comments::LIST, comment).await
+    }
+
+    pub async fn delete_comment(&self, id: CommentId) -> Result<()> {
+        self.send_empty(Method::DELETE, &routes::comments::comment(id))
+            .await
+    }
+
+    /// Verified webmentions of a post, newest first.
+    pub async fn list_webmentions(&self, slug: &str) -> Result<Vec<Webmention>> {
+        self.get(&routes::webmentions(slug)).await
+    }
+
+    // ── Uploads ──
+
+    /// Upload a file; pass the returned id in a create request's
+    /// `attachment_ids` to attach it.
+    pub async fn upload(&self, filename: &str, content_type: &str, bytes: Vec<u8>) -> Result<Attachment> {
+        let path = routes::uploads::upload(filename);
+        let req = self
+            .request(Method::POST, &path)
+            .header(reqwest::header::CONTENT_TYPE, content_type)
+            .body(bytes);
+        Ok(Self::send(req).await?.json().await?)
+    }
+
+    // ── Votes ──
+
+    pub async fn get_votes(&self, target_type: &str, target_id: i64) -> Result<VoteResponse> {
+        self.get(&routes::votes::tally(target_type, target_id)).await
+    }
+
+    pub async fn cast_vote(&self, vote: &CreateVote) -> Result<VoteResponse> {
+        self.post(routes::votes::VOTES, vote).await
+    }
+
+    pub async fn get_post_votes(&self, slug: &str) -> Result<VoteResponse> {
+        self.get(&routes::votes::post(slug)).await
+    }
+
+    pub async fn cast_post_vote(&self, vote: &CreatePostVote) -> Result<VoteResponse> {
+        self.post(routes::votes::POST, vote).await
+    }
+
+    // ── Forum ──
+
+    pub async fn list_categories(&self) -> Result<Vec<ForumCategory>> {
+        self.get(routes::forum::CATEGORIES).await
+    }
+
+    pub async fn get_category(&self, slug: &str) -> Result<ForumCategory> {
+        self.get(&routes::forum::category(slug)).await
+    }
+
+    pub async fn subscribe_category(&self, slug: &str) -> Result<()> {
+        self.send_empty(Method::POST, &routes::forum::subscribe(slug))
+            .await
+    }
+
+    pub async fn unsubscribe_category(&self, slug: &str) -> Result<()> {
+        self.send_empty(Method::DELETE, &routes::forum::subscribe(slug))
+            .await
+    }
+
+    pub async fn list_threads(
+        &self,
+        category: &str,
+        page: i64,
+        sort: ThreadSort,
+    ) -> Result<Paginated<Thread>> {
+        self.get(&routes::forum::threads(category, page, sort)).await
+    }
+
+    /// Threads by cursor: `after` is `None` for the first page, then the
+    /// previous page's `next`.
+    pub async fn list_threads_after(
+        &self,
+        category: &str,
+        after: Option<&str>,
+        sort: ThreadSort,
+    ) -> Result<PaginatedCursor<Thread>> {
+        self.get(&routes::forum::threads_after(category, after, sort))
+            .await
+    }
+
+    pub async fn create_thread(&self, thread: &CreateThread) -> Result<Thread> {
+        self.post(routes::forum::THREADS, thread).await
+    }
+
+    pub async fn get_thread(&self, id: ThreadId, sort: ReplySort) -> Result<ThreadDetail> {
+        self.get(&routes::forum::thread(id, sort)).await
+    }
+
+    pub async fn create_reply(&self, thread_id: ThreadId, reply: &CreateReply) -> Result<Reply> {
+        self.post(&routes::forum::replies(thread_id), reply)
+            .await
+    }
+
+    /// Move a thread to another category (moderators only).
+    pub async fn move_thread(&self, thread_id: ThreadId, request: &MoveThread) -> Result<Thread> {
+        self.post(&routes::forum::move_to(thread_id), request)
+            .await
+    }
+
+    /// Pin a thread to the top of its category, or unpin it (moderators only).
+    pub async fn set_pinned(&self, thread_id: ThreadId, pinned: bool) -> Result<Thread> {
+        let method = if pinned { Method::POST } else { Method::DELETE };
+        let path = routes::forum::pin(thread_id);
+        Ok(Self::send(self.request(method, &path)).await?.json().await?)
+    }
+
+    /// Accept `reply_id` as the thread's answer, or clear it with `None`.
+    pub async fn set_solution(&self, thread_id: ThreadId, reply_id: Option<ReplyId>) -> Result<Thread> {
+        self.post(&routes::forum::solution(thread_id), &SetSolution { reply_id })
+            .await
+    }
+
+    /// `None` when the server has no summarizer or the thread is too short.
+    pub async fn thread_summary(&self, thread_id: ThreadId) -> Result<Option<ThreadSummary>> {
+        self.get(&routes::forum::summary(thread_id)).await
+    }
+
+    /// Title, description and image for previews of a shared thread link.
+    pub async fn thread_preview(&self, thread_id: ThreadId) -> Result<LinkPreview> {
+        self.get(&routes::forum::preview(thread_id)).await
+    }
+
+    /// Published threads and replies matching every word of `query`, best
+    /// match first.
+    pub async fn search_forum(&self, query: &str) -> Result<Vec<SearchResult>> {
+        self.get(&routes::forum::search(query)).await
+    }
+
+    // ── Notifications ──
+
+    /// Newest first; pass the previous page's `next` as `before` to go back.
+    pub async fn notifications(&self, before: Option<i64>) -> Result<NotificationList> {
+        self.get(&routes::notifications::list(before)).await
+    }
+
+    pub async fn mark_notification_read(&self, id: i64) -> Result<()> {
+        self.send_empty(Method::POST, &routes::notifications::read(id))
+            .await
+    }
+
+    /// Marks notifications read on every site, not just this client's.
+    pub async fn mark_all_notifications_read(&self) -> Result<()> {
+        self.send_empty(Method::POST, routes::notifications::READ_ALL)
+            .await
+    }
+
+    pub async fn notification_preferences(&self) -> Result<NotificationPrefs> {
+        self.get(routes::notifications::PREFERENCES).await
+    }
+
+    pub async fn update_notification_preferences(&self, prefs: &NotificationPrefs) -> Result<()> {
+        Self::send(
+            self.request(Method::PUT, routes::notifications::PREFERENCES)
+                .json(prefs),
+        )
+        .await?;
+        Ok(())
+    }
+
+    // ── Activity ──
+
+    /// The newest comments, threads and replies on the site (at most 50).
+    pub async fn recent_activity(&self, limit: i64) -> Result<Vec<ActivityItem>> {
+        self.get(&routes::activity(limit)).await
+    }
+
+    // ── Drafts ──
+
+    pub async fn draft(&self, key: &str) -> Result<Draft> {
+        self.get(&routes::drafts::draft(key)).await
+    }
+
+    /// Saving an empty title and body deletes the draft.
+    pub async fn save_draft(&self, draft: &Draft) -> Result<()> {
+        Self::send(self.request(Method::PUT, routes::drafts::DRAFTS).json(draft)).await?;
+        Ok(())
+    }
+
+    pub async fn delete_draft(&self, key: &str) -> Result<()> {
+        self.send_empty(Method::DELETE, &routes::drafts::draft(key))
+            .await
+    }
+
+    // ── Bookmarks ──
+
+    /// Bookmarks on this client's site, newest first.
+    pub async fn bookmarks(&self) -> Result<Vec<Bookmark>> {
+        self.get(routes::bookmarks::LIST).await
+    }
+
+    /// `target_type` is `thread`, `reply` or `comment`.
+    pub async fn add_bookmark(&self, target_type: &str, target_id: i64) -> Result<()> {
+        Self::send(self.request(Method::POST, routes::bookmarks::LIST).json(&CreateBookmark {
+            target_type: target_type.to_string(),
+            target_id,
+        }))
+        .await?;
+        Ok(())
+    }
+
+    pub async fn remove_bookmark(&self, target_type: &str, target_id: i64) -> Result<()> {
+        self.send_empty(Method::DELETE, &routes::bookmarks::bookmark(target_type, target_id))
+            .await
+    }
+
+    // ── Users ──
+
+    /// Users whose username or display name starts with `q`, for mentions.
+    pub async fn search_users(&self, q: &str) -> Result<Vec<User>> {
+        self.get(&routes::users::search(q)).await
+    }
+
+    // ── Follows ──
+
+    pub async fn follow_user(&self, user_id: UserId) -> Result<()> {
+        self.send_empty(Method::POST, &routes::users::follow(user_id))
+            .await
+    }
+
+    pub async fn unfollow_user(&self, user_id: UserId) -> Result<()> {
+        self.send_empty(Method::DELETE, &routes::users::follow(user_id))
+            .await
+    }
+
+    pub async fn following(&self) -> Result<Vec<User>> {
+        self.get(routes::users::FOLLOWS).await
+    }
+
+    /// Their comments and replies come back blanked out, flagged `blocked`.
+    pub async fn block_user(&self, user_id: UserId) -> Result<()> {
+        self.send_empty(Method::POST, &routes::users::block(user_id))
+            .await
+    }
+
+    pub async fn unblock_user(&self, user_id: UserId) -> Result<()> {
+        self.send_empty(Method::DELETE, &routes::users::block(user_id))
+            .await
+    }
+
+    pub async fn blocked_users(&self) -> Result<Vec<User>> {
+        self.get(routes::users::BLOCKS).await
+    }
+
+    /// Newest first; pass the previous page's `next` as `before` to go back.
+    pub async fn feed(&self, before: Option<&str>) -> Result<PaginatedCursor<ActivityItem>> {
+        self.get(&routes::users::feed(before)).await
+    }
+
+    // ── Messages ──
+
+    pub async fn inbox(&self) -> Result<Inbox> {
+        self.get(routes::messages::INBOX).await
+    }
+
+    /// Newest first; pass the previous page's `next` as `before` to go back.
+    /// Marks the other user's messages as read.
+    pub async fn conversation(&self, user_id: UserId, before: Option<&str>) -> Result<PaginatedCursor<Message>> {
+        self.get(&routes::messages::conversation(user_id, before))
+            .await
+    }
+
+    pub async fn send_message(&self, message: &SendMessage) -> Result<Message> {
+        self.post(routes::messages::INBOX, message).await
+    }
+
+    // ── GitHub Stats ──
+
+    pub async fn github_stats(&self, repo: &str) -> Result<GitHubStats> {
+        self.get(&routes::github_stats(repo)).await
+    }
+
+    // ── GraphQL ──
+
+    /// Run a GraphQL query; returns the raw response (`data` / `errors`).
+    pub async fn graphql(
+        &self,
+        query: &str,
+        variables: serde_json::Value,
+    ) -> Result<serde_json::Value> {
+        self.post(
+            routes::GRAPHQL,
+            &serde_json::json!({ "query": query, "variables": variables }),
+        )
+        .await
+    }
+
+    // ── Moderation ──
+
+    pub async fn report(&self, report: &CreateReport) -> Result<()> {
+        self.post_empty(routes::REPORTS, report).await
+    }
+
+    pub async fn moderation_queue(&self) -> Result<Vec<ModerationItem>> {
+        self.get(routes::admin::QUEUE).await
+    }
+
+    /// Threads created with a future `publish_at`, soonest first.
+    pub async fn scheduled_threads(&self) -> Result<Vec<Thread>> {
+        self.get(routes::admin::SCHEDULED).await
+    }
+
+    /// Interaction counts over the last `days` days (admins).
+    pub async fn analytics(&self, days: u32) -> Result<AnalyticsSummary> {
+        self.get(&routes::admin::analytics(days)).await
+    }
+
+    /// Posts with comment settings (admins).
+    pub async fn post_settings(&self) -> Result<Vec<PostSettings>> {
+        self.get(routes::admin::POST_SETTINGS).await
+    }
+
+    /// Close a post's comments, now or after some days (admins).
+    pub async fn update_post_settings(&self, settings: &UpdatePostSettings) -> Result<PostSettings> {
+        Ok(Self::send(self.request(Method::PUT, routes::admin::POST_SETTINGS).json(settings))
+            .await?
+            .json()
+            .await?)
+    }
+
+    /// Reopen a post's comments, dropping its settings (admins).
+    pub async fn delete_post_settings(&self, slug: &str) -> Result<()> {
+        let path = routes::admin::post_settings(slug);
+        self.send_empty(Method::DELETE, &path).await
+    }
+
+    pub async fn open_reports(&self) -> Result<Vec<ContentReport>> {
+        self.get(routes::admin::REPORTS).await
+    }
+
+    pub async fn resolve_report(&self, id: i64) -> Result<()> {
+        self.send_empty(Method::POST, &routes::admin::resolve_report(id))
+            .await
+    }
+
+    pub async fn moderate(&self, action: &ModerateContent) -> Result<ModerationItem> {
+        self.post(routes::admin::MODERATE, action).await
+    }
+
+    pub async fn recent_users(&self, limit: i64) -> Result<Vec<AdminUser>> {
+        self.get(&routes::admin::users(limit)).await
+    }
+
+    pub async fn ban_user(&self, user_id: UserId, reason: &str) -> Result<()> {
+        let ban = BanUser {
+            reason: reason.to_string(),
+        };
+        self.post_empty(&routes::admin::ban(user_id), &ban).await
+    }
+
+    pub async fn unban_user(&self, user_id: UserId) -> Result<()> {
+        self.send_empty(Method::DELETE, &routes::admin::ban(user_id))
+            .await
+    }
+
+    /// Read-only token for viewing the site as `user_id`; every request made
+    /// with it is audit-logged under the admin.
+    pub async fn impersonate(&self, user_id: UserId) -> Result<Impersonation> {
+        self.post(&routes::admin::impersonate(user_id), &()).await
+    }
+
+    pub async fn word_filters(&self) -> Result<Vec<WordFilter>> {
+        self.get(routes::admin::WORD_FILTERS).await
+    }
+
+    pub async fn create_word_filter(&self, filter: &CreateWordFilter) -> Result<WordFilter> {
+        self.post(routes::admin::WORD_FILTERS, filter).await
+    }
+
+    pub async fn delete_word_filter(&self, id: i64) -> Result<()> {
+        self.send_empty(Method::DELETE, &routes::admin::word_filter(id))
+            .await
+    }
+
+    pub async fn ip_bans(&self) -> Result<Vec<IpBan>> {
+        self.get(routes::admin::IP_BANS).await
+    }
+
+    /// Ban the address a comment, thread or reply was posted from.
+    pub async fn ban_ip(&self, ban: &CreateIpBan) -> Result<IpBan> {
+        self.post(routes::admin::IP_BANS, ban).await
+    }
+
+    pub async fn delete_ip_ban(&self, id: i64) -> Result<()> {
+        self.send_empty(Method::DELETE, &routes::admin::ip_ban(id))
+            .await
+    }
+
+    // ── Admin ──
+
+    pub async fn merge_users(&self, merge: &MergeUsers) -> Result<MergeSummary> {
+        self.post(routes::admin::MERGE_USERS, merge).await
+    }
+
+    pub async fn reload_config(&self) -> Result<()> {
+        self.send_empty(Method::POST, routes::admin::CONFIG_RELOAD).await
+    }
+
+    /// Snapshot the database to the server's configured backup target.
+    pub async fn backup(&self) -> Result<BackupInfo> {
+        self.post(routes::admin::BACKUP, &()).await
+    }
+
+    /// Force a WAL checkpoint; `mode` is `passive`, `full`, `restart` or
+    /// `truncate`.
+    pub async fn checkpoint(&self, mode: &str) -> Result<CheckpointResult> {
+        self.post(&routes::admin::checkpoint(mode), &()).await
+    }
+
+    /// Names of the email templates `email_preview` accepts.
+    pub async fn email_templates(&self) -> Result<Vec<String>> {
+        self.get(routes::admin::EMAILS).await
+    }
+
+    /// `template` rendered with sample data and this client's site branding.
+    pub async fn email_preview(&self, template: &str) -> Result<EmailPreview> {
+        self.get(&routes::admin::email_preview(template)).await
+    }
+
+    pub async fn webhooks(&self) -> Result<Vec<Webhook>> {
+        self.get(routes::admin::WEBHOOKS).await
+    }
+
+    /// The returned secret is shown only once; store it with the receiver.
+    pub async fn create_webhook(&self, webhook: &CreateWebhook) -> Result<NewWebhook> {
+        self.post(routes::admin::WEBHOOKS, webhook).await
+    }
+
+    pub async fn delete_webhook(&self, id: i64) -> Result<()> {
+        self.send_empty(Method::DELETE, &routes::admin::webhook(id))
+            .await
+    }
+
+    /// Audit records between `from` (inclusive) and `to` (exclusive), one JSON
+    /// object per entry.
+    pub async fn export_audit(
+        &self,
+        from: Option<&str>,
+        to: Option<&str>,
+    ) -> Result<Vec<serde_json::Value>> {
+        let path = routes::admin::audit_export(from, to);
+        let text = Self::send(self.request(Method::GET, &path))
+            .await?
+        .text()
+        .await?;
+
+        Ok(text
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+}
@@ -0,0 +1,62 @@
+//! Recent comments, threads and replies, as a sidebar widget showing the
+//! community is alive, and the shared wording for the forum's feed.
+
+use leptos::prelude::*;
+use mikaana_shared::{routes, ActivityItem};
+use wasm_bindgen_futures::spawn_local;
+
+use crate::{api, notifications::forum_base, time};
+
+pub fn describe(item: &ActivityItem) -> String {
+    match item.kind.as_str() {
+        "thread" => format!("{} started {}", item.user.name(), item.title),
+        "reply" => format!("{} replied to {}", item.user.name(), item.title),
+        _ => format!("{} commented on {}", item.user.name(), item.title),
+    }
+}
+
+/// Where an item lives: the comment on its post, or the thread in the forum.
+pub fn link(item: &ActivityItem) -> String {
+    match (&item.post_slug, item.thread_id) {
+        (Some(slug), _) => format!("{slug}#comment-{}", item.id),
+        (None, Some(thread)) if item.kind == "reply" => {
+            format!("{}thread/{thread}#reply-{}", forum_base(), item.id)
+        }
+        (None, Some(thread)) => format!("{}thread/{thread}", forum_base()),
+        (None, None) => forum_base(),
+    }
+}
+
+/// The `limit` newest posts on the site.
+#[component]
+pub fn RecentActivity(limit: u32) -> impl IntoView {
+    let items: RwSignal<Option<Vec<ActivityItem>>> = RwSignal::new(None);
+
+    spawn_local(async move {
+        if let Ok(list) = api::get::<Vec<ActivityItem>>(&routes::activity(limit.into())).await {
+            items.set(Some(list));
+        }
+    });
+
+    move || {
+        items.get().map(|list| {
+            if list.is_empty() {
+                return view! { <p class="mikaana-hint">"No activity yet."</p> }.into_any();
+            }
+            view! {
+                <ul class="mikaana-activity-list">
+                    {list.into_iter().map(|item| view! {
+                        <li>
+                            <img src={item.user.avatar_url.clone()} alt="" class="mikaana-avatar" width="20" height="20" />
+                            " "
+                            <a href=link(&item)>{describe(&item)}</a>
+                            " "
+                            <time datetime=item.created_at.clone()>{time::ago(&item.created_at)}</time>
+                        </li>
+                    }).collect_view()}
+                </ul>
+            }
+            .into_any()
+        })
+    }
+}
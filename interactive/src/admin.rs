@@ -0,0 +1,819 @@
+use leptos::prelude::*;
+use mikaana_shared::*;
+use wasm_bindgen_futures::spawn_local;
+
+use crate::api;
+use crate::auth::{AuthState, LoginButton};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum AdminTab {
+    Queue,
+    Reports,
+    Users,
+    Scheduled,
+}
+
+impl AdminTab {
+    const ALL: [AdminTab; 4] = [
+        AdminTab::Queue,
+        AdminTab::Reports,
+        AdminTab::Users,
+        AdminTab::Scheduled,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            AdminTab::Queue => "Pending",
+            AdminTab::Reports => "Reports",
+            AdminTab::Users => "Users",
+            AdminTab::Scheduled => "Scheduled",
+        }
+    }
+}
+
+/// Moderation dashboard — mounted on `#mikaana-admin`.
+#[component]
+pub fn AdminApp() -> impl IntoView {
+    let auth = expect_context::<AuthState>();
+    let tab = RwSignal::new(AdminTab::Queue);
+
+    view! {
+        <div class="mikaana-admin">
+            <div class="mikaana-admin-header">
+                <h2>"Moderation"</h2>
+                <LoginButton />
+            </div>
+            <Show
+                when=move || auth.user.get().is_some()
+                fallback=|| view! { <p class="mikaana-hint">"Log in with a moderator account."</p> }
+            >
+                <nav class="mikaana-sort-toggle mikaana-admin-tabs">
+                    {AdminTab::ALL
+                        .into_iter()
+                        .map(|t| {
+                            view! {
+                                <button
+                                    class="mikaana-btn mikaana-btn-sm"
+                                    class:active=move || tab.get() == t
+                                    on:click=move |_| tab.set(t)
+                                >
+                                    {t.label()}
+                                </button>
+                            }
+                        })
+                        .collect_view()}
+                </nav>
+                {move || match tab.get() {
+                    AdminTab::Queue => view! { <QueuePanel /> }.into_any(),
+                    AdminTab::Reports => view! { <ReportsPanel /> }.into_any(),
+                    AdminTab::Users => view! { <UsersPanel /> }.into_any(),
+                    AdminTab::Scheduled => view! { <ScheduledPanel /> }.into_any(),
+                }}
+            </Show>
+        </div>
+    }
+}
+
+fn describe_error(e: api::Error) -> String {
+    match e.api() {
+        Some(ApiError::Forbidden) => "Your account doesn't have moderator access.".to_string(),
+        _ => e.to_string(),
+    }
+}
+
+/// Plain-text preview of a sanitized HTML body.
+fn excerpt(html: &str) -> String {
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    if text.chars().count() > 300 {
+        text = text.chars().take(300).collect::<String>() + "…";
+    }
+    text
+}
+
+async fn moderate(item: &ModerationItem, action: ModerationAction) -> Result<ModerationItem, api::Error> {
+    let payload = ModerateContent {
+        target_type: item.target_type.clone(),
+        target_id: item.target_id,
+        action,
+    };
+    api::post(routes::admin::MODERATE, &payload).await
+}
+
+async fn bulk(payload: BulkModerate) -> Result<BulkSummary, api::Error> {
+    api::post(routes::admin::BULK, &payload).await
+}
+
+/// "Removed 3 comments, 1 thread and 0 replies."
+fn describe_bulk(verb: &str, summary: &BulkSummary) -> String {
+    let count = |n: usize, one: &str, many: &str| format!("{n} {}", if n == 1 { one } else { many });
+    format!(
+        "{verb} {}, {} and {}.",
+        count(summary.comments, "comment", "comments"),
+        count(summary.threads, "thread", "threads"),
+        count(summary.replies, "reply", "replies"),
+    )
+}
+
+fn confirm(message: &str) -> bool {
+    web_sys::window()
+        .and_then(|w| w.confirm_with_message(message).ok())
+        .unwrap_or(false)
+}
+
+fn content_ref(item: &ModerationItem) -> ContentRef {
+    ContentRef {
+        target_type: item.target_type.clone(),
+        target_id: item.target_id,
+    }
+}
+
+/// Who posted what, where.
+#[component]
+fn ItemSummary(item: ModerationItem) -> impl IntoView {
+    let location = match (&item.post_slug, item.thread_id) {
+        (Some(slug), _) => Some(view! { <a href={slug.clone()}>{slug.clone()}</a> }.into_any()),
+        (None, Some(id)) => Some(view! { <span>{format!("thread #{id}")}</span> }.into_any()),
+        _ => None,
+    };
+
+    view! {
+        <div class="mikaana-admin-item">
+            <div class="mikaana-comment-header">
+                <img src={item.author.avatar_url.clone()} alt="" class="mikaana-avatar" width="20" height="20" />
+                <strong>{item.author.name().to_string()}</strong>
+                <span class="mikaana-admin-tag">{item.target_type.clone()}</span>
+                <time>{item.created_at.clone()}</time>
+                {location}
+            </div>
+            {item.title.clone().map(|t| view! { <div class="mikaana-thread-title">{t}</div> })}
+            <p class="mikaana-admin-excerpt">{excerpt(&item.body)}</p>
+        </div>
+    }
+}
+
+// ── Pending ──
+
+/// Pending content, approved or removed one at a time or, ticked, several
+/// at once.
+#[component]
+fn QueuePanel() -> impl IntoView {
+    let items: RwSignal<Vec<ModerationItem>> = RwSignal::new(Vec::new());
+    let loading = RwSignal::new(true);
+    let error: RwSignal<Option<String>> = RwSignal::new(None);
+    let notice: RwSignal<Option<String>> = RwSignal::new(None);
+    let selected: RwSignal<Vec<ContentRef>> = RwSignal::new(Vec::new());
+
+    let load = move || {
+        spawn_local(async move {
+            match api::get::<Vec<ModerationItem>>(routes::admin::QUEUE).await {
+                Ok(list) => {
+                    selected.update(|s| s.retain(|r| list.iter().any(|i| content_ref(i) == *r)));
+                    items.set(list);
+                }
+                Err(e) => error.set(Some(describe_error(e))),
+            }
+            loading.set(false);
+        });
+    };
+    load();
+
+    // Bulk changes can reach past the listed items, so reload afterwards
+    let act_bulk = move |payload: BulkModerate, verb: &'static str| {
+        spawn_local(async move {
+            match bulk(payload).await {
+                Ok(summary) => {
+                    error.set(None);
+                    notice.set(Some(describe_bulk(verb, &summary)));
+                    load();
+                }
+                Err(e) => error.set(Some(describe_error(e))),
+            }
+        });
+    };
+    let act_selected = move |action: BulkAction| {
+        let into_user_id = if action == BulkAction::Reassign {
+            let answer = web_sys::window()
+                .and_then(|w| w.prompt_with_message("Id of the account to credit them to").ok())
+                .flatten();
+            match answer.and_then(|id| id.trim().parse().ok()) {
+                Some(id) => Some(id),
+                None => return,
+            }
+        } else {
+            None
+        };
+        let verb = match action {
+            BulkAction::Approve => "Approved",
+            BulkAction::Remove => "Removed",
+            BulkAction::Reassign => "Reassigned",
+        };
+        let payload = BulkModerate {
+            action,
+            items: selected.get_untracked(),
+            author_id: None,
+            into_user_id,
+        };
+        act_bulk(payload, verb);
+    };
+    let remove_by_author = move |author: User| {
+        if !confirm(&format!("Remove everything {} has posted?", author.name())) {
+            return;
+        }
+        let payload = BulkModerate {
+            action: BulkAction::Remove,
+            items: Vec::new(),
+            author_id: Some(author.id),
+            into_user_id: None,
+        };
+        act_bulk(payload, "Removed");
+    };
+    let all_selected = move || {
+        items.with(|list| !list.is_empty() && selected.with(|s| s.len() == list.len()))
+    };
+    let none_selected = move || selected.with(Vec::is_empty);
+
+    let act = move |item: ModerationItem, action: ModerationAction| {
+        spawn_local(async move {
+            match moderate(&item, action).await {
+                Ok(_) => items.update(|list| {
+                    list.retain(|i| {
+                        (i.target_type.as_str(), i.target_id)
+                            != (item.target_type.as_str(), item.target_id)
+                    })
+                }),
+                Err(e) => error.set(Some(describe_error(e))),
+            }
+        });
+    };
+
+    view! {
+        <section class="mikaana-admin-panel">
+            <Show when=move || loading.get()>
+                <p class="mikaana-loading">"Loading..."</p>
+            </Show>
+            {move || error.get().map(|e| view! { <p class="mikaana-error">{e}</p> })}
+            {move || notice.get().map(|n| view! { <p class="mikaana-hint">{n}</p> })}
+            <Show when=move || !loading.get() && items.with(|i| i.is_empty())>
+                <p class="mikaana-hint">"Nothing waiting for review."</p>
+            </Show>
+            <Show when=move || items.with(|i| !i.is_empty())>
+                <div class="mikaana-form-actions mikaana-admin-bulk">
+                    <label>
+                        <input
+                            type="checkbox"
+                            prop:checked=all_selected
+                            on:change=move |_| {
+                                let all = if all_selected() {
+                                    Vec::new()
+                                } else {
+                                    items.with_untracked(|list| list.iter().map(content_ref).collect())
+                                };
+                                selected.set(all);
+                            }
+                        />
+                        " Select all"
+                    </label>
+                    <button
+                        class="mikaana-btn mikaana-btn-sm"
+                        disabled=none_selected
+                        on:click=move |_| act_selected(BulkAction::Approve)
+                    >
+                        "Approve selected"
+                    </button>
+                    <button
+                        class="mikaana-btn mikaana-btn-sm mikaana-btn-danger"
+                        disabled=none_selected
+                        on:click=move |_| act_selected(BulkAction::Remove)
+                    >
+                        "Remove selected"
+                    </button>
+                    <button
+                        class="mikaana-btn mikaana-btn-sm"
+                        disabled=none_selected
+                        title="Admins only"
+                        on:click=move |_| act_selected(BulkAction::Reassign)
+                    >
+                        "Reassign selected\u{2026}"
+                    </button>
+                </div>
+            </Show>
+            <For
+                each=move || items.get()
+                key=|i| (i.target_type.clone(), i.target_id)
+                let:item
+            >
+                {
+                    let approve = item.clone();
+                    let remove = item.clone();
+                    let author = item.author.clone();
+                    let target = content_ref(&item);
+                    let checked = {
+                        let target = target.clone();
+                        move || selected.with(|s| s.contains(&target))
+                    };
+                    view! {
+                        <div class="mikaana-admin-card">
+                            <label class="mikaana-admin-select">
+                                <input
+                                    type="checkbox"
+                                    prop:checked=checked
+                                    on:change=move |ev| {
+                                        let on = event_target_checked(&ev);
+                                        selected.update(|s| {
+                                            s.retain(|r| *r != target);
+                                            if on {
+                                                s.push(target.clone());
+                                            }
+                                        });
+                                    }
+                                />
+                            </label>
+                            <ItemSummary item=item />
+                            <div class="mikaana-form-actions">
+                                <button
+                                    class="mikaana-btn mikaana-btn-sm"
+                                    on:click=move |_| act(approve.clone(), ModerationAction::Approve)
+                                >
+                                    "Approve"
+                                </button>
+                                <button
+                                    class="mikaana-btn mikaana-btn-sm mikaana-btn-danger"
+                                    on:click=move |_| act(remove.clone(), ModerationAction::Remove)
+                                >
+                                    "Remove"
+                                </button>
+                                <button
+                                    class="mikaana-btn mikaana-btn-sm mikaana-btn-danger"
+                                    title="Remove every comment, thread and reply by this user"
+                                    on:click=move |_| remove_by_author(author.clone())
+                                >
+                                    "Remove all by author"
+                                </button>
+                            </div>
+                        </div>
+                    }
+                }
+            </For>
+        </section>
+    }
+}
+
+// ── Reports ──
+
+#[component]
+fn ReportsPanel() -> impl IntoView {
+    let reports: RwSignal<Vec<ContentReport>> = RwSignal::new(Vec::new());
+    let loading = RwSignal::new(true);
+    let error: RwSignal<Option<String>> = RwSignal::new(None);
+
+    spawn_local(async move {
+        match api::get::<Vec<ContentReport>>(routes::admin::REPORTS).await {
+            Ok(list) => reports.set(list),
+            Err(e) => error.set(Some(describe_error(e))),
+        }
+        loading.set(false);
+    });
+
+    // Removing content resolves every report against it
+    let remove = move |item: ModerationItem| {
+        spawn_local(async move {
+            match moderate(&item, ModerationAction::Remove).await {
+                Ok(_) => reports.update(|list| {
+                    list.retain(|r| {
+                        r.item.as_ref().is_none_or(|i| {
+                            (i.target_type.as_str(), i.target_id)
+                                != (item.target_type.as_str(), item.target_id)
+                        })
+                    })
+                }),
+                Err(e) => error.set(Some(describe_error(e))),
+            }
+        });
+    };
+
+    let dismiss = move |id: i64| {
+        spawn_local(async move {
+            let url = routes::admin::resolve_report(id);
+            match api::post_empty(&url, &()).await {
+                Ok(()) => reports.update(|list| list.retain(|r| r.id != id)),
+                Err(e) => error.set(Some(describe_error(e))),
+            }
+        });
+    };
+
+    view! {
+        <section class="mikaana-admin-panel">
+            <Show when=move || loading.get()>
+                <p class="mikaana-loading">"Loading..."</p>
+            </Show>
+            {move || error.get().map(|e| view! { <p class="mikaana-error">{e}</p> })}
+            <Show when=move || !loading.get() && reports.with(|r| r.is_empty())>
+                <p class="mikaana-hint">"No open reports."</p>
+            </Show>
+            <For
+                each=move || reports.get()
+                key=|r| r.id
+                let:report
+            >
+                {
+                    let id = report.id;
+                    let item = report.item.clone();
+                    view! {
+                        <div class="mikaana-admin-card">
+                            <p class="mikaana-admin-report">
+                                <strong>{report.reporter.name().to_string()}</strong>
+                                " reported: "
+                                {if report.reason.is_empty() { "(no reason given)".to_string() } else { report.reason.clone() }}
+                            </p>
+                            {match item.clone() {
+                                Some(item) => view! { <ItemSummary item=item /> }.into_any(),
+                                None => view! { <p class="mikaana-hint">"The content no longer exists."</p> }.into_any(),
+                            }}
+                            <div class="mikaana-form-actions">
+                                {item.filter(|i| i.status != "removed").map(|item| view! {
+                                    <button
+                                        class="mikaana-btn mikaana-btn-sm mikaana-btn-danger"
+                                        on:click=move |_| remove(item.clone())
+                                    >
+                                        "Remove content"
+                                    </button>
+                                })}
+                                <button class="mikaana-btn mikaana-btn-sm" on:click=move |_| dismiss(id)>
+                                    "Dismiss"
+                                </button>
+                            </div>
+                        </div>
+                    }
+                }
+            </For>
+        </section>
+    }
+}
+
+// ── Users ──
+
+#[component]
+fn UsersPanel() -> impl IntoView {
+    let users: RwSignal<Vec<AdminUser>> = RwSignal::new(Vec::new());
+    let loading = RwSignal::new(true);
+    let error: RwSignal<Option<String>> = RwSignal::new(None);
+    let notice: RwSignal<Option<String>> = RwSignal::new(None);
+
+    spawn_local(async move {
+        match api::get::<Vec<AdminUser>>(&routes::admin::users(100)).await {
+            Ok(list) => users.set(list),
+            Err(e) => error.set(Some(describe_error(e))),
+        }
+        loading.set(false);
+    });
+
+    let set_banned = move |user_id: UserId, ban: bool| {
+        let reason = if ban {
+            // Cancelling the prompt cancels the ban
+            match web_sys::window().and_then(|w| w.prompt_with_message("Reason for the ban (optional)").ok()) {
+                Some(Some(reason)) => Some(reason),
+                _ => return,
+            }
+        } else {
+            None
+        };
+        spawn_local(async move {
+            let url = routes::admin::ban(user_id);
+            let result = match &reason {
+                Some(reason) => api::post_empty(&url, &BanUser { reason: reason.clone() }).await,
+                None => api::delete(&url).await,
+            };
+            match result {
+                Ok(()) => users.update(|list| {
+                    if let Some(u) = list.iter_mut().find(|u| u.user.id == user_id) {
+                        u.banned = ban;
+                        u.ban_reason = reason.filter(|r| !r.trim().is_empty());
+                    }
+                }),
+                Err(e) => error.set(Some(describe_error(e))),
+            }
+        });
+    };
+
+    let remove_posts = move |user: User| {
+        if !confirm(&format!("Remove everything {} has posted?", user.name())) {
+            return;
+        }
+        let payload = BulkModerate {
+            action: BulkAction::Remove,
+            items: Vec::new(),
+            author_id: Some(user.id),
+            into_user_id: None,
+        };
+        spawn_local(async move {
+            match bulk(payload).await {
+                Ok(summary) => notice.set(Some(describe_bulk("Removed", &summary))),
+                Err(e) => error.set(Some(describe_error(e))),
+            }
+        });
+    };
+
+    // Read-only session as the user, logged under the admin; the widgets
+    // show a banner to get back
+    let view_as = move |user_id: UserId| {
+        spawn_local(async move {
+            let url = routes::admin::impersonate(user_id);
+            match api::post::<Impersonation, _>(&url, &()).await {
+                Ok(session) => {
+                    api::start_impersonation(&session.token, session.user.name());
+                    if let Some(win) = web_sys::window() {
+                        let _ = win.location().set_href("/");
+                    }
+                }
+                Err(e) => error.set(Some(describe_error(e))),
+            }
+        });
+    };
+
+    view! {
+        <section class="mikaana-admin-panel">
+            <Show when=move || loading.get()>
+                <p class="mikaana-loading">"Loading..."</p>
+            </Show>
+            {move || error.get().map(|e| view! { <p class="mikaana-error">{e}</p> })}
+            {move || notice.get().map(|n| view! { <p class="mikaana-hint">{n}</p> })}
+            <table class="mikaana-admin-users">
+                <thead>
+                    <tr>
+                        <th>"User"</th>
+                        <th>"Joined"</th>
+                        <th>"Posts"</th>
+                        <th></th>
+                    </tr>
+                </thead>
+                <tbody>
+                    {move || {
+                        users
+                            .get()
+                            .into_iter()
+                            .map(|u| {
+                                let id = u.user.id;
+                                let user = u.user.clone();
+                                let staff = u.role != "user";
+                                view! {
+                                    <tr class:mikaana-banned=u.banned>
+                                        <td>
+                                            <img src={u.user.avatar_url.clone()} alt="" class="mikaana-avatar" width="20" height="20" />
+                                            " "
+                                            <strong>{u.user.name().to_string()}</strong>
+                                            {staff.then(|| view! { <span class="mikaana-admin-tag">{u.role.clone()}</span> })}
+                                            {u.ban_reason.clone().map(|r| view! { <div class="mikaana-hint">{format!("Banned: {r}")}</div> })}
+                                        </td>
+                                        <td>{u.created_at.clone()}</td>
+                                        <td>{format!("{} / {} / {}", u.comments, u.threads, u.replies)}</td>
+                                        <td>
+                                            {(!staff).then(|| view! {
+                                                <button class="mikaana-btn mikaana-btn-sm" on:click=move |_| view_as(id)>
+                                                    "View as"
+                                                </button>
+                                                " "
+                                                <button class="mikaana-btn mikaana-btn-sm mikaana-btn-danger" on:click=move |_| remove_posts(user.clone())>
+                                                    "Remove posts"
+                                                </button>
+                                                " "
+                                            })}
+                                            {(!staff).then(|| if u.banned {
+                                                view! {
+                                                    <button class="mikaana-btn mikaana-btn-sm" on:click=move |_| set_banned(id, false)>
+                                                        "Unban"
+                                                    </button>
+                                                }.into_any()
+                                            } else {
+                                                view! {
+                                                    <button class="mikaana-btn mikaana-btn-sm mikaana-btn-danger" on:click=move |_| set_banned(id, true)>
+                                                        "Ban"
+                                                    </button>
+                                                }.into_any()
+                                            })}
+                                        </td>
+                                    </tr>
+                                }
+                            })
+                            .collect_view()
+                    }}
+                </tbody>
+            </table>
+            <p class="mikaana-hint">"Posts: comments / threads / replies"</p>
+        </section>
+    }
+}
+
+// ── Scheduled ──
+
+/// A `datetime-local` value, in the browser's time zone, as a UTC ISO 8601
+/// date for `CreateThread::publish_at`.
+fn local_to_utc(value: &str) -> Option<String> {
+    let date = web_sys::js_sys::Date::new(&value.into());
+    (!date.get_time().is_nan()).then(|| date.to_iso_string().into())
+}
+
+/// A stored UTC time in the browser's time zone.
+fn utc_to_local(at: &str) -> String {
+    let date = web_sys::js_sys::Date::new(&format!("{}Z", at.replacen(' ', "T", 1)).into());
+    date.to_locale_string("default", &wasm_bindgen::JsValue::UNDEFINED).into()
+}
+
+/// Announcement threads waiting for their publish time, and a form to
+/// schedule one.
+#[component]
+fn ScheduledPanel() -> impl IntoView {
+    let threads: RwSignal<Vec<Thread>> = RwSignal::new(Vec::new());
+    let categories: RwSignal<Vec<ForumCategory>> = RwSignal::new(Vec::new());
+    let loading = RwSignal::new(true);
+    let error: RwSignal<Option<String>> = RwSignal::new(None);
+    let notice: RwSignal<Option<String>> = RwSignal::new(None);
+
+    let category = RwSignal::new(String::new());
+    let title = RwSignal::new(String::new());
+    let body = RwSignal::new(String::new());
+    let publish_at = RwSignal::new(String::new());
+    let pin = RwSignal::new(true);
+    let submitting = RwSignal::new(false);
+
+    let load = move || {
+        spawn_local(async move {
+            match api::get::<Vec<Thread>>(routes::admin::SCHEDULED).await {
+                Ok(list) => threads.set(list),
+                Err(e) => error.set(Some(describe_error(e))),
+            }
+            loading.set(false);
+        });
+    };
+    load();
+    spawn_local(async move {
+        if let Ok(list) = api::get::<Vec<ForumCategory>>(routes::forum::CATEGORIES).await {
+            if let Some(first) = list.first() {
+                category.set(first.slug.clone());
+            }
+            categories.set(list);
+        }
+    });
+
+    let on_submit = move |ev: leptos::ev::SubmitEvent| {
+        ev.prevent_default();
+        let Some(at) = local_to_utc(&publish_at.get_untracked()) else {
+            error.set(Some("Pick a date and time to publish at.".to_string()));
+            return;
+        };
+        let payload = CreateThread {
+            category_slug: category.get_untracked(),
+            title: title.get_untracked(),
+            body: body.get_untracked(),
+            attachment_ids: Vec::new(),
+            captcha_token: None,
+            publish_at: Some(at),
+            pin: pin.get_untracked(),
+        };
+        submitting.set(true);
+        spawn_local(async move {
+            match api::post::<Thread, _>(routes::forum::THREADS, &payload).await {
+                Ok(thread) => {
+                    error.set(None);
+                    notice.set(Some(match thread.publish_at {
+                        Some(_) => format!("Scheduled \u{201c}{}\u{201d}.", thread.title),
+                        None => format!("Published \u{201c}{}\u{201d}: its time has passed.", thread.title),
+                    }));
+                    title.set(String::new());
+                    body.set(String::new());
+                    publish_at.set(String::new());
+                    load();
+                }
+                Err(e) => error.set(Some(describe_error(e))),
+            }
+            submitting.set(false);
+        });
+    };
+
+    let cancel = move |thread: Thread| {
+        if !confirm(&format!("Cancel \u{201c}{}\u{201d}? It won't be published.", thread.title)) {
+            return;
+        }
+        let payload = BulkModerate {
+            action: BulkAction::Remove,
+            items: vec![ContentRef {
+                target_type: "thread".to_string(),
+                target_id: thread.id.0,
+            }],
+            author_id: None,
+            into_user_id: None,
+        };
+        spawn_local(async move {
+            match bulk(payload).await {
+                Ok(_) => threads.update(|list| list.retain(|t| t.id != thread.id)),
+                Err(e) => error.set(Some(describe_error(e))),
+            }
+        });
+    };
+
+    view! {
+        <section class="mikaana-admin-panel">
+            {move || error.get().map(|e| view! { <p class="mikaana-error">{e}</p> })}
+            {move || notice.get().map(|n| view! { <p class="mikaana-hint">{n}</p> })}
+            <form class="mikaana-admin-card mikaana-admin-schedule" on:submit=on_submit>
+                <select
+                    class="mikaana-input"
+                    prop:value=move || category.get()
+                    on:change=move |ev| category.set(event_target_value(&ev))
+                >
+                    {move || {
+                        categories
+                            .get()
+                            .into_iter()
+                            .map(|c| view! { <option value=c.slug.clone()>{c.name.clone()}</option> })
+                            .collect_view()
+                    }}
+                </select>
+                <input
+                    class="mikaana-input"
+                    type="text"
+                    placeholder="Announcement title"
+                    prop:value=move || title.get()
+                    on:input=move |ev| title.set(event_target_value(&ev))
+                />
+                <textarea
+                    class="mikaana-input"
+                    rows="6"
+                    placeholder="Write the announcement..."
+                    prop:value=move || body.get()
+                    on:input=move |ev| body.set(event_target_value(&ev))
+                ></textarea>
+                <div class="mikaana-form-actions">
+                    <label>
+                        "Publish at "
+                        <input
+                            type="datetime-local"
+                            prop:value=move || publish_at.get()
+                            on:input=move |ev| publish_at.set(event_target_value(&ev))
+                        />
+                    </label>
+                    <label>
+                        <input
+                            type="checkbox"
+                            prop:checked=move || pin.get()
+                            on:change=move |ev| pin.set(event_target_checked(&ev))
+                        />
+                        " Pin when published"
+                    </label>
+                    <button
+                        class="mikaana-btn mikaana-btn-sm"
+                        type="submit"
+                        disabled=move || {
+                            submitting.get() || title.with(|t| t.trim().is_empty())
+                                || body.with(|b| b.trim().is_empty())
+                        }
+                    >
+                        "Schedule"
+                    </button>
+                </div>
+            </form>
+            <Show when=move || loading.get()>
+                <p class="mikaana-loading">"Loading..."</p>
+            </Show>
+            <Show when=move || !loading.get() && threads.with(|t| t.is_empty())>
+                <p class="mikaana-hint">"Nothing scheduled."</p>
+            </Show>
+            <For each=move || threads.get() key=|t| t.id let:thread>
+                {
+                    let when = thread.publish_at.as_deref().map(utc_to_local).unwrap_or_default();
+                    let category = categories.with_untracked(|list| {
+                        list.iter()
+                            .find(|c| c.id == thread.category_id)
+                            .map(|c| c.name.clone())
+                            .unwrap_or_default()
+                    });
+                    let pinned = thread.pinned;
+                    let title = thread.title.clone();
+                    let body = excerpt(&thread.body);
+                    view! {
+                        <div class="mikaana-admin-card">
+                            <strong>{title}</strong>
+                            {pinned.then(|| view! { " " <span class="mikaana-admin-tag">"pinned"</span> })}
+                            <div class="mikaana-hint">{format!("{category} \u{b7} {when}")}</div>
+                            <p class="mikaana-admin-excerpt">{body}</p>
+                            <button
+                                class="mikaana-btn mikaana-btn-sm mikaana-btn-danger"
+                                on:click=move |_| cancel(thread.clone())
+                            >
+                                "Cancel"
+                            </button>
+                        </div>
+                    }
+                }
+            </For>
+        </section>
+    }
+}
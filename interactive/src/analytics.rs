@@ -0,0 +1,34 @@
+//! Reports widget interactions to `POST /api/events` when the server counts
+//! them. Reports carry only the event: no token, no cookie. Browsers set to
+//! Do Not Track or Global Privacy Control send nothing at all.
+
+use mikaana_shared::{routes, AnalyticsEvent, RecordEvent};
+use wasm_bindgen::JsValue;
+use wasm_bindgen_futures::spawn_local;
+
+use crate::{api, config};
+
+/// Whether the visitor asked not to be tracked.
+fn opted_out() -> bool {
+    let Some(navigator) = web_sys::window().map(|w| w.navigator()) else {
+        return true;
+    };
+    let gpc = web_sys::js_sys::Reflect::get(&navigator, &JsValue::from_str("globalPrivacyControl"))
+        .ok()
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    gpc || navigator.do_not_track() == "1"
+}
+
+/// Count `event`, in the background. Failures are ignored: a lost count
+/// isn't worth bothering anyone about.
+pub fn track(event: AnalyticsEvent) {
+    config::with_config(move |config| {
+        if !config.analytics || opted_out() {
+            return;
+        }
+        spawn_local(async move {
+            let _ = api::post_anonymous(routes::EVENTS, &RecordEvent { event }).await;
+        });
+    });
+}
@@ -1,6 +1,13 @@
-use gloo_net::http::Request;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use futures_channel::oneshot;
+use gloo_net::http::{Method, Request, RequestBuilder, Response};
+use mikaana_shared::{routes, ApiError, ErrorBody, TermsRequired};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
+use wasm_bindgen_futures::spawn_local;
 use web_sys::window;
 
 fn api_base() -> String {
@@ -42,75 +49,494 @@ pub fn clear_token() {
     }
 }
 
-pub fn has_token() -> bool {
-    get_token().is_some()
+/// Dispatched on `window` by `sign_out`, so every widget's `AuthProvider`
+/// forgets the user, not just the one that noticed.
+pub const SIGNED_OUT_EVENT: &str = "mikaana-signed-out";
+
+/// Forget the session everywhere on the page.
+pub fn sign_out() {
+    clear_token();
+    let Some(win) = window() else {
+        return;
+    };
+    if let Ok(event) = web_sys::Event::new(SIGNED_OUT_EVENT) {
+        let _ = win.dispatch_event(&event);
+    }
 }
 
-pub async fn get<T: DeserializeOwned>(path: &str) -> Result<T, String> {
-    let url = format!("{}{}", api_base(), path);
-    let mut req = Request::get(&url);
+/// A request made with `token` was answered `401`: the token expired or its
+/// account is gone. An expired impersonation falls back to the admin's own
+/// session; anything else signs out. Ignored if the token has changed since
+/// (signed in again while the request was out).
+fn session_expired(token: &str) {
+    if get_token().as_deref() != Some(token) {
+        return;
+    }
+    if impersonating().is_some() {
+        stop_impersonation();
+        if let Some(win) = window() {
+            let _ = win.location().reload();
+        }
+    } else {
+        sign_out();
+    }
+}
 
-    if let Some(token) = get_token() {
-        req = req.header("Authorization", &format!("Bearer {}", token));
+const ADMIN_TOKEN_KEY: &str = "mikaana_admin_token";
+const IMPERSONATING_KEY: &str = "mikaana_impersonating";
+
+/// Switch to an impersonation token (see `POST
+/// /api/admin/users/{id}/impersonate`), setting the admin's own aside.
+pub fn start_impersonation(token: &str, username: &str) {
+    let Some(storage) = window().and_then(|w| w.local_storage().ok()).flatten() else {
+        return;
+    };
+    if let (Some(own), Ok(None)) = (get_token(), storage.get_item(ADMIN_TOKEN_KEY)) {
+        let _ = storage.set_item(ADMIN_TOKEN_KEY, &own);
     }
+    let _ = storage.set_item(IMPERSONATING_KEY, username);
+    set_token(token);
+}
 
-    let resp = req.send().await.map_err(|e| e.to_string())?;
+/// Who the admin is viewing the site as, if impersonating.
+pub fn impersonating() -> Option<String> {
+    window()?
+        .local_storage()
+        .ok()??
+        .get_item(IMPERSONATING_KEY)
+        .ok()?
+}
 
-    if !resp.ok() {
-        return Err(format!("API error: {}", resp.status()));
+/// Back to the admin's own token.
+pub fn stop_impersonation() {
+    let Some(storage) = window().and_then(|w| w.local_storage().ok()).flatten() else {
+        return;
+    };
+    match storage.get_item(ADMIN_TOKEN_KEY).ok().flatten() {
+        Some(own) => set_token(&own),
+        None => clear_token(),
     }
+    let _ = storage.remove_item(ADMIN_TOKEN_KEY);
+    let _ = storage.remove_item(IMPERSONATING_KEY);
+}
 
-    resp.json().await.map_err(|e| e.to_string())
+/// Why a request through this module failed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// The server answered with an error `status`; `body.error` says what.
+    Api { status: u16, body: ErrorBody },
+    /// No answer (offline, blocked), or one that didn't parse.
+    Network(String),
 }
 
-pub async fn post<T: DeserializeOwned, B: Serialize>(path: &str, body: &B) -> Result<T, String> {
-    let url = format!("{}{}", api_base(), path);
-    let mut req = Request::post(&url).header("Content-Type", "application/json");
+impl Error {
+    fn network(e: impl std::fmt::Display) -> Self {
+        Error::Network(e.to_string())
+    }
+
+    /// What the server said went wrong.
+    pub fn api(&self) -> Option<&ApiError> {
+        match self {
+            Error::Api { body, .. } => Some(&body.error),
+            Error::Network(_) => None,
+        }
+    }
 
-    if let Some(token) = get_token() {
-        req = req.header("Authorization", &format!("Bearer {}", token));
+    /// The request ID from the error body, if the server sent one.
+    pub fn request_id(&self) -> Option<&str> {
+        match self {
+            Error::Api { body, .. } => body.request_id.as_deref(),
+            Error::Network(_) => None,
+        }
     }
+}
 
-    let req = req.body(serde_json::to_string(body).map_err(|e| e.to_string())?).map_err(|e| e.to_string())?;
+/// "API error: 404", plus the request ID so users can quote it when
+/// reporting the problem.
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self, self.request_id()) {
+            (Error::Api { status, .. }, Some(id)) => write!(f, "API error: {status} (request {id})"),
+            (Error::Api { status, .. }, None) => write!(f, "API error: {status}"),
+            (Error::Network(e), _) => f.write_str(e),
+        }
+    }
+}
+
+/// The `Error` for an error response. A `428` for unaccepted terms of
+/// service also brings up the acceptance dialog (see `TERMS_REQUIRED_EVENT`).
+async fn error_from(resp: Response) -> Error {
+    let status = resp.status();
+    let text = resp.text().await.unwrap_or_default();
+    let body = serde_json::from_str::<ErrorBody>(&text).unwrap_or_else(|_| ErrorBody {
+        error: ApiError::from_status(status),
+        message: None,
+        request_id: None,
+    });
+    if let ApiError::TermsNotAccepted(terms) = &body.error {
+        terms_required(terms.clone());
+    }
+    Error::Api { status, body }
+}
+
+/// Window event fired when a write is refused until the user accepts the
+/// current terms of service; `take_terms_required` has the details.
+pub const TERMS_REQUIRED_EVENT: &str = "mikaana-terms-required";
+
+thread_local! {
+    static TERMS_REQUIRED: RefCell<Option<TermsRequired>> = const { RefCell::new(None) };
+}
+
+fn terms_required(terms: TermsRequired) {
+    TERMS_REQUIRED.set(Some(terms));
+    let Some(win) = window() else {
+        return;
+    };
+    if let Ok(event) = web_sys::Event::new(TERMS_REQUIRED_EVENT) {
+        let _ = win.dispatch_event(&event);
+    }
+}
+
+/// The terms the last `TERMS_REQUIRED_EVENT` asked for.
+pub fn take_terms_required() -> Option<TermsRequired> {
+    TERMS_REQUIRED.take()
+}
+
+// ── GET cache ──
+//
+// Widgets on one page often ask for the same thing (`/api/auth/me` per
+// `AuthProvider`, the same vote counts, bookmarks). Identical GETs in flight
+// share one request, and successful responses are reused for `CACHE_TTL_MS`.
+// Any write through this module empties the cache once it's answered, so a
+// widget never reads something older than its own changes.
+
+/// How long a response is reused, in milliseconds.
+const CACHE_TTL_MS: f64 = 30_000.0;
+
+type Body = Result<Rc<str>, Error>;
+
+enum CacheEntry {
+    Ready { body: Rc<str>, at: f64 },
+    /// Callers waiting on the request already in flight.
+    Pending(Vec<oneshot::Sender<Body>>),
+}
+
+thread_local! {
+    static CACHE: RefCell<HashMap<String, CacheEntry>> = RefCell::new(HashMap::new());
+    /// Bumped by `invalidate`, so a request that started before a write
+    /// doesn't cache what it read.
+    static GENERATION: Cell<u32> = const { Cell::new(0) };
+}
+
+fn now() -> f64 {
+    web_sys::js_sys::Date::now()
+}
+
+/// Forget every cached response. Requests in flight still answer their
+/// callers but aren't kept.
+fn invalidate() {
+    GENERATION.set(GENERATION.get().wrapping_add(1));
+    CACHE.with_borrow_mut(|cache| cache.retain(|_, e| matches!(e, CacheEntry::Pending(_))));
+}
+
+pub async fn get<T: DeserializeOwned>(path: &str) -> Result<T, Error> {
+    let token = get_token();
+    // Responses depend on who is asking
+    let key = format!("{}|{path}", token.as_deref().unwrap_or_default());
+    let (tx, rx) = oneshot::channel();
+
+    let cached = CACHE.with_borrow_mut(|cache| match cache.get_mut(&key) {
+        Some(CacheEntry::Ready { body, at }) if now() - *at < CACHE_TTL_MS => Some(Some(body.clone())),
+        Some(CacheEntry::Pending(waiters)) => {
+            waiters.push(tx);
+            Some(None)
+        }
+        _ => {
+            cache.retain(|_, e| !matches!(e, CacheEntry::Ready { at, .. } if now() - *at >= CACHE_TTL_MS));
+            cache.insert(key.clone(), CacheEntry::Pending(vec![tx]));
+            None
+        }
+    });
+
+    let body = match cached {
+        Some(Some(body)) => body,
+        Some(None) => rx.await.map_err(Error::network)??,
+        None => {
+            // Fetched on its own task so it completes (and wakes the other
+            // waiters) even if this caller goes away
+            let path = path.to_string();
+            let generation = GENERATION.get();
+            spawn_local(async move {
+                let body = fetch_text(&path, token).await.map(Rc::<str>::from);
+                let waiters = CACHE.with_borrow_mut(|cache| {
+                    let waiters = match cache.remove(&key) {
+                        Some(CacheEntry::Pending(waiters)) => waiters,
+                        _ => Vec::new(),
+                    };
+                    if let (Ok(body), true) = (&body, GENERATION.get() == generation) {
+                        cache.insert(key, CacheEntry::Ready { body: body.clone(), at: now() });
+                    }
+                    waiters
+                });
+                for waiter in waiters {
+                    let _ = waiter.send(body.clone());
+                }
+            });
+            rx.await.map_err(Error::network)??
+        }
+    };
+
+    serde_json::from_str(&body).map_err(Error::network)
+}
 
-    let resp = req.send().await.map_err(|e| e.to_string())?;
+async fn fetch_text(path: &str, token: Option<String>) -> Result<String, Error> {
+    let url = format!("{}{}", api_base(), path);
+    let resp = send(Retry::Idempotent, token.as_deref(), || {
+        let mut req = Request::get(&url);
+        if let Some(token) = &token {
+            req = req.header("Authorization", &format!("Bearer {}", token));
+        }
+        req.build().map_err(Error::network)
+    })
+    .await?;
 
     if !resp.ok() {
-        return Err(format!("API error: {}", resp.status()));
+        return Err(error_from(resp).await);
     }
 
-    resp.json().await.map_err(|e| e.to_string())
+    resp.text().await.map_err(Error::network)
+}
+
+// ── Retries ──
+
+/// Attempts after the first.
+const MAX_RETRIES: u32 = 3;
+/// First backoff, doubled per attempt and jittered.
+const RETRY_BASE_MS: f64 = 500.0;
+/// Longest wait between attempts. A `Retry-After` asking for more (a posting
+/// cooldown) isn't waited out; the caller gets the response instead.
+const RETRY_MAX_MS: f64 = 10_000.0;
+
+/// Which failures a request may be repeated after.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Retry {
+    /// GETs: dropped connections and overloaded or unreachable upstreams.
+    Idempotent,
+    /// Writes: only `429` and `503`, which the server answers before acting
+    /// on the request.
+    Refused,
 }
 
-pub async fn delete(path: &str) -> Result<(), String> {
+impl Retry {
+    fn status(self, status: u16) -> bool {
+        match self {
+            Retry::Idempotent => matches!(status, 429 | 502 | 503 | 504),
+            Retry::Refused => matches!(status, 429 | 503),
+        }
+    }
+}
+
+/// Random wait before retry `attempt` (from 0): "full jitter", so widgets
+/// that failed together don't come back together.
+fn backoff_ms(attempt: u32) -> f64 {
+    let ceiling = (RETRY_BASE_MS * 2f64.powi(attempt as i32)).min(RETRY_MAX_MS);
+    web_sys::js_sys::Math::random() * ceiling
+}
+
+/// `Retry-After` in milliseconds; only the delay-seconds form.
+fn retry_after_ms(resp: &Response) -> Option<f64> {
+    let secs: f64 = resp.headers().get("Retry-After")?.trim().parse().ok()?;
+    Some((secs * 1000.0).max(0.0))
+}
+
+async fn sleep(ms: f64) {
+    let (tx, rx) = oneshot::channel::<()>();
+    leptos::prelude::set_timeout(
+        move || {
+            let _ = tx.send(());
+        },
+        std::time::Duration::from_millis(ms as u64),
+    );
+    let _ = rx.await;
+}
+
+/// Send the request `make` builds, again after a pause if it fails in a
+/// way `retry` allows, up to `MAX_RETRIES` times. `token` is the session
+/// it was made with, if any, for `session_expired`.
+async fn send(
+    retry: Retry,
+    token: Option<&str>,
+    make: impl Fn() -> Result<Request, Error>,
+) -> Result<Response, Error> {
+    let mut attempt = 0;
+    loop {
+        let wait = match make()?.send().await {
+            Ok(resp)
+                if attempt < MAX_RETRIES
+                    && retry.status(resp.status())
+                    && retry_after_ms(&resp).is_none_or(|ms| ms <= RETRY_MAX_MS) =>
+            {
+                retry_after_ms(&resp).unwrap_or_else(|| backoff_ms(attempt))
+            }
+            Ok(resp) => {
+                if let (401, Some(token)) = (resp.status(), token) {
+                    session_expired(token);
+                }
+                return Ok(resp);
+            }
+            Err(_) if attempt < MAX_RETRIES && retry == Retry::Idempotent => backoff_ms(attempt),
+            Err(e) => return Err(Error::network(e)),
+        };
+        sleep(wait).await;
+        attempt += 1;
+    }
+}
+
+/// Send a write: JSON `body` if any, with the session token and `headers`.
+/// Empties the GET cache once answered.
+async fn send_write(
+    method: Method,
+    path: &str,
+    body: Option<String>,
+    headers: &[(&str, &str)],
+) -> Result<Response, Error> {
     let url = format!("{}{}", api_base(), path);
-    let mut req = Request::delete(&url);
+    let token = get_token();
+    let resp = send(Retry::Refused, token.as_deref(), || {
+        let mut req = RequestBuilder::new(&url).method(method.clone());
+        if let Some(token) = &token {
+            req = req.header("Authorization", &format!("Bearer {}", token));
+        }
+        for (name, value) in headers {
+            req = req.header(name, value);
+        }
+        match &body {
+            Some(json) => req
+                .header("Content-Type", "application/json")
+                .body(json.clone())
+                .map_err(Error::network),
+            None => req.build().map_err(Error::network),
+        }
+    })
+    .await;
+    // Even a failed write may have gone through
+    invalidate();
+    let resp = resp?;
 
-    if let Some(token) = get_token() {
-        req = req.header("Authorization", &format!("Bearer {}", token));
+    if !resp.ok() {
+        return Err(error_from(resp).await);
     }
 
-    let resp = req.send().await.map_err(|e| e.to_string())?;
+    Ok(resp)
+}
 
+fn to_json<B: Serialize>(body: &B) -> Result<Option<String>, Error> {
+    serde_json::to_string(body).map(Some).map_err(Error::network)
+}
+
+pub async fn post<T: DeserializeOwned, B: Serialize>(path: &str, body: &B) -> Result<T, Error> {
+    let resp = send_write(Method::POST, path, to_json(body)?, &[]).await?;
+    resp.json().await.map_err(Error::network)
+}
+
+/// `post` with an `Idempotency-Key`: sending it again with the same key
+/// (double submit, retry after a dropped connection) creates nothing new
+/// and returns the first response.
+pub async fn post_once<T: DeserializeOwned, B: Serialize>(
+    path: &str,
+    body: &B,
+    key: &str,
+) -> Result<T, Error> {
+    let resp = send_write(Method::POST, path, to_json(body)?, &[("Idempotency-Key", key)]).await?;
+    resp.json().await.map_err(Error::network)
+}
+
+/// Fresh key for `post_once`. Forms keep one per draft and replace it after
+/// a successful post.
+pub fn idempotency_key() -> String {
+    let part = || (web_sys::js_sys::Math::random() * (1u64 << 53) as f64) as u64;
+    format!("{:014x}{:014x}", part(), part())
+}
+
+/// POST for endpoints that answer `204 No Content`.
+pub async fn post_empty<B: Serialize>(path: &str, body: &B) -> Result<(), Error> {
+    send_write(Method::POST, path, to_json(body)?, &[]).await?;
+    Ok(())
+}
+
+/// POST without the session token, retries or emptying the GET cache, for
+/// reports that mustn't be tied to the user (see `analytics`).
+pub async fn post_anonymous<B: Serialize>(path: &str, body: &B) -> Result<(), Error> {
+    let url = format!("{}{}", api_base(), path);
+    let json = serde_json::to_string(body).map_err(Error::network)?;
+    let resp = Request::post(&url)
+        .header("Content-Type", "application/json")
+        .body(json)
+        .map_err(Error::network)?
+        .send()
+        .await
+        .map_err(Error::network)?;
     if !resp.ok() {
-        return Err(format!("API error: {}", resp.status()));
+        return Err(error_from(resp).await);
     }
+    Ok(())
+}
+
+pub async fn put<T: DeserializeOwned, B: Serialize>(path: &str, body: &B) -> Result<T, Error> {
+    let resp = send_write(Method::PUT, path, to_json(body)?, &[]).await?;
+    resp.json().await.map_err(Error::network)
+}
+
+/// PUT for endpoints that answer `204 No Content`.
+pub async fn put_empty<B: Serialize>(path: &str, body: &B) -> Result<(), Error> {
+    send_write(Method::PUT, path, to_json(body)?, &[]).await?;
+    Ok(())
+}
 
+pub async fn delete(path: &str) -> Result<(), Error> {
+    send_write(Method::DELETE, path, None, &[]).await?;
     Ok(())
 }
 
+/// Upload a file as the raw request body, typed by its own MIME type.
+pub async fn upload<T: DeserializeOwned>(file: &web_sys::File) -> Result<T, Error> {
+    let url = format!("{}{}", api_base(), routes::uploads::upload(&file.name()));
+    let token = get_token();
+    let resp = send(Retry::Refused, token.as_deref(), || {
+        let mut req = Request::post(&url).header("Content-Type", &file.type_());
+        if let Some(token) = &token {
+            req = req.header("Authorization", &format!("Bearer {}", token));
+        }
+        req.body(file.clone()).map_err(Error::network)
+    })
+    .await;
+    invalidate();
+    let resp = resp?;
+
+    if !resp.ok() {
+        return Err(error_from(resp).await);
+    }
+
+    resp.json().await.map_err(Error::network)
+}
+
+/// Origin of the API server, for checking where `postMessage` events come from.
+pub fn api_origin() -> Option<String> {
+    web_sys::Url::new(&api_base()).ok().map(|u| u.origin())
+}
+
 /// Build the GitHub login URL, passing the current page as the redirect target.
 pub fn github_login_url() -> String {
     let current_url = window()
         .and_then(|w| w.location().href().ok())
         .unwrap_or_default();
-    format!(
-        "{}/api/auth/github?redirect={}",
-        api_base(),
-        urlencoding(&current_url)
-    )
+    format!("{}{}", api_base(), routes::auth::github(&current_url, false))
 }
 
-fn urlencoding(s: &str) -> String {
-    web_sys::js_sys::encode_uri_component(s).as_string().unwrap_or_default()
+/// Login URL for the popup flow: the callback answers with `postMessage`.
+pub fn github_popup_login_url() -> String {
+    let current_url = window()
+        .and_then(|w| w.location().href().ok())
+        .unwrap_or_default();
+    format!("{}{}", api_base(), routes::auth::github(&current_url, true))
 }
@@ -1,9 +1,9 @@
 use leptos::prelude::*;
-use mikaana_shared::User;
+use mikaana_shared::{routes, ApiError, Profile, UpdateProfile, User};
 use wasm_bindgen_futures::spawn_local;
 use web_sys::window;
 
-use crate::api;
+use crate::{api, config, notifications::NotificationBell};
 
 /// Reactive auth state shared via context.
 #[derive(Clone, Debug)]
@@ -31,7 +31,7 @@ fn consume_url_token() -> Option<String> {
         api::set_token(t);
         // Remove ?token= from the visible URL
         params.delete("token");
-        let clean = if params.to_string().as_string().map_or(true, |s| s.is_empty()) {
+        let clean = if params.to_string().as_string().is_none_or(|s| s.is_empty()) {
             url.pathname()
         } else {
             format!("{}?{}", url.pathname(), params.to_string())
@@ -59,24 +59,46 @@ pub fn AuthProvider(children: Children) -> impl IntoView {
     let token = RwSignal::new(initial_token);
     let user: RwSignal<Option<User>> = RwSignal::new(None);
 
-    let auth = AuthState {
-        user,
-        token: token,
-    };
+    let auth = AuthState { user, token };
     provide_context(auth.clone());
 
+    // Token from a popup login (see LoginButton), posted by the API's callback page
+    let handle = window_event_listener(leptos::ev::message, move |ev| {
+        if Some(ev.origin()) != api::api_origin() {
+            return;
+        }
+        let data = ev.data();
+        let field = |name: &str| {
+            web_sys::js_sys::Reflect::get(&data, &name.into())
+                .ok()
+                .and_then(|v| v.as_string())
+        };
+        if field("type").as_deref() == Some("mikaana-token") {
+            if let Some(t) = field("token") {
+                api::set_token(&t);
+                token.set(Some(t));
+            }
+        }
+    });
+    on_cleanup(move || handle.remove());
+
+    // Logout, or a 401 anywhere on the page (see `api::session_expired`)
+    let handle = window_event_listener_untyped(api::SIGNED_OUT_EVENT, move |_| {
+        token.set(None);
+        user.set(None);
+    });
+    on_cleanup(move || handle.remove());
+
     // Fetch user profile when we have a token
     Effect::new(move |_| {
         if let Some(_t) = token.get() {
             spawn_local(async move {
-                match api::get::<User>("/api/auth/me").await {
+                match api::get::<User>(routes::auth::ME).await {
                     Ok(u) => user.set(Some(u)),
-                    Err(_) => {
-                        // Token invalid — clear it
-                        api::clear_token();
-                        token.set(None);
-                        user.set(None);
-                    }
+                    // The account is gone; a 401 is already handled by `api`
+                    Err(e) if e.api() == Some(&ApiError::NotFound) => api::sign_out(),
+                    // Transient; stay signed in
+                    Err(_) => {}
                 }
             });
         } else {
@@ -84,18 +106,40 @@ pub fn AuthProvider(children: Children) -> impl IntoView {
         }
     });
 
-    children()
+    let banner = api::impersonating().map(|username| {
+        let stop = move |_| {
+            api::stop_impersonation();
+            reload();
+        };
+        view! {
+            <div class="mikaana-impersonation">
+                <span>"Viewing as " <strong>{username}</strong> " (read-only)"</span>
+                <button class="mikaana-btn mikaana-btn-sm" on:click=stop>"Stop"</button>
+            </div>
+        }
+    });
+
+    view! {
+        {banner}
+        {children()}
+    }
+}
+
+fn reload() {
+    if let Some(win) = window() {
+        let _ = win.location().reload();
+    }
 }
 
-/// Login / logout button.
+/// Login / logout button. Signed-in users get a dropdown with the profile editor.
 #[component]
 pub fn LoginButton() -> impl IntoView {
     let auth = expect_context::<AuthState>();
+    let open = RwSignal::new(false);
 
     let on_logout = move |_| {
-        api::clear_token();
-        auth.token.set(None);
-        auth.user.set(None);
+        api::sign_out();
+        open.set(false);
     };
 
     move || {
@@ -103,17 +147,194 @@ pub fn LoginButton() -> impl IntoView {
             view! {
                 <div class="mikaana-auth">
                     <img src={user.avatar_url.clone()} alt="" class="mikaana-avatar" width="24" height="24" />
-                    <span class="mikaana-username">{user.username.clone()}</span>
+                    <button
+                        class="mikaana-username mikaana-dropdown-toggle"
+                        aria-expanded=move || open.get().to_string()
+                        on:click=move |_| open.update(|o| *o = !*o)
+                    >
+                        {user.name().to_string()}
+                    </button>
+                    <NotificationBell />
                     <button class="mikaana-btn mikaana-btn-sm" on:click=on_logout>"Logout"</button>
+                    <Show when=move || open.get()>
+                        <div class="mikaana-dropdown">
+                            <ProfileEditor on_close=move || open.set(false) />
+                            <DeleteAccount username=user.username.clone() />
+                        </div>
+                    </Show>
                 </div>
             }
             .into_any()
         } else {
             let url = api::github_login_url();
             view! {
-                <a class="mikaana-btn" href={url}>"Login with GitHub"</a>
+                <a class="mikaana-btn" href={url} on:click=open_login_popup>"Login with GitHub"</a>
             }
             .into_any()
         }
     }
 }
+
+/// Log in through a small popup so the page (scroll position, drafts) stays
+/// put. If the popup is blocked the link navigates as usual.
+fn open_login_popup(ev: leptos::ev::MouseEvent) {
+    let Some(win) = window() else {
+        return;
+    };
+    let popup = win.open_with_url_and_target_and_features(
+        &api::github_popup_login_url(),
+        "mikaana-login",
+        "popup,width=600,height=720",
+    );
+    if matches!(popup, Ok(Some(_))) {
+        ev.prevent_default();
+    }
+}
+
+/// Edit display name, bio and website.
+#[component]
+fn ProfileEditor(on_close: impl Fn() + Copy + Send + Sync + 'static) -> impl IntoView {
+    let user = expect_context::<AuthState>().user;
+    let display_name = RwSignal::new(String::new());
+    let bio = RwSignal::new(String::new());
+    let website = RwSignal::new(String::new());
+    let limits = config::limits();
+    let (status, set_status) = signal(Option::<String>::None);
+    let (saving, set_saving) = signal(false);
+
+    let fill = move |profile: Profile| {
+        display_name.set(profile.display_name.unwrap_or_default());
+        bio.set(profile.bio);
+        website.set(profile.website.unwrap_or_default());
+    };
+
+    spawn_local(async move {
+        match api::get::<Profile>(routes::auth::PROFILE).await {
+            Ok(profile) => fill(profile),
+            Err(e) => set_status.set(Some(e.to_string())),
+        }
+    });
+
+    let on_submit = move |ev: leptos::ev::SubmitEvent| {
+        ev.prevent_default();
+        set_saving.set(true);
+        let payload = UpdateProfile {
+            display_name: display_name.get(),
+            bio: bio.get(),
+            website: website.get(),
+        };
+        spawn_local(async move {
+            match api::put::<Profile, _>(routes::auth::ME, &payload).await {
+                Ok(profile) => {
+                    // The header shows the display name too
+                    user.set(Some(profile.user.clone()));
+                    fill(profile);
+                    set_status.set(Some("Saved".to_string()));
+                }
+                Err(e) => set_status.set(Some(e.to_string())),
+            }
+            set_saving.set(false);
+        });
+    };
+
+    view! {
+        <form class="mikaana-profile-form" on:submit=on_submit>
+            <label>
+                "Display name"
+                <input
+                    class="mikaana-input"
+                    maxlength=move || limits.get().display_name.to_string()
+                    prop:value=move || display_name.get()
+                    on:input=move |ev| display_name.set(event_target_value(&ev))
+                />
+            </label>
+            <label>
+                "Bio"
+                <textarea
+                    class="mikaana-textarea"
+                    rows="3"
+                    maxlength=move || limits.get().bio.to_string()
+                    prop:value=move || bio.get()
+                    on:input=move |ev| bio.set(event_target_value(&ev))
+                />
+            </label>
+            <label>
+                "Website"
+                <input
+                    class="mikaana-input"
+                    type="url"
+                    placeholder="https://"
+                    prop:value=move || website.get()
+                    on:input=move |ev| website.set(event_target_value(&ev))
+                />
+            </label>
+            <div class="mikaana-form-actions">
+                <button type="submit" class="mikaana-btn mikaana-btn-sm" disabled=move || saving.get()>
+                    "Save"
+                </button>
+                <button type="button" class="mikaana-btn mikaana-btn-sm" on:click=move |_| on_close()>
+                    "Close"
+                </button>
+                {move || status.get().map(|s| view! { <span class="mikaana-form-status">{s}</span> })}
+            </div>
+        </form>
+    }
+}
+
+/// Two-step account deletion: reveal the form, then type the username to confirm.
+#[component]
+fn DeleteAccount(username: String) -> impl IntoView {
+    let confirming = RwSignal::new(false);
+    let typed = RwSignal::new(String::new());
+    let (error, set_error) = signal(Option::<String>::None);
+    let (deleting, set_deleting) = signal(false);
+
+    let username = StoredValue::new(username);
+    let matches = move || username.with_value(|name| typed.get().trim() == name);
+
+    let on_delete = move |_| {
+        set_deleting.set(true);
+        spawn_local(async move {
+            match api::delete(routes::auth::ME).await {
+                Ok(()) => api::sign_out(),
+                Err(e) => {
+                    set_error.set(Some(e.to_string()));
+                    set_deleting.set(false);
+                }
+            }
+        });
+    };
+
+    view! {
+        <div class="mikaana-delete-account">
+            <Show
+                when=move || confirming.get()
+                fallback=move || view! {
+                    <button class="mikaana-btn mikaana-btn-sm mikaana-btn-danger" on:click=move |_| confirming.set(true)>
+                        "Delete account"
+                    </button>
+                }
+            >
+                <p>"This permanently deletes your account. Type your username to confirm."</p>
+                <input
+                    class="mikaana-input"
+                    prop:value=move || typed.get()
+                    on:input=move |ev| typed.set(event_target_value(&ev))
+                />
+                <div class="mikaana-form-actions">
+                    <button
+                        class="mikaana-btn mikaana-btn-sm mikaana-btn-danger"
+                        disabled=move || !matches() || deleting.get()
+                        on:click=on_delete
+                    >
+                        "Delete permanently"
+                    </button>
+                    <button class="mikaana-btn mikaana-btn-sm" on:click=move |_| confirming.set(false)>
+                        "Cancel"
+                    </button>
+                </div>
+                {move || error.get().map(|e| view! { <p class="mikaana-error">{e}</p> })}
+            </Show>
+        </div>
+    }
+}
@@ -0,0 +1,76 @@
+//! Blocking users from comments and replies. The server blanks out what
+//! blocked users wrote (`Comment::blocked`, `Reply::blocked`); the widgets
+//! show a placeholder in its place and reload their list after a change.
+
+use leptos::prelude::*;
+use mikaana_shared::{routes, UserId};
+use wasm_bindgen_futures::spawn_local;
+
+use crate::api;
+use crate::auth::AuthState;
+
+/// Block toggle for the author of a comment or reply. Hidden when logged
+/// out and on the viewer's own posts. Bumps `reload` once blocked.
+#[component]
+pub fn BlockButton(user_id: UserId, reload: RwSignal<u32>) -> impl IntoView {
+    let auth = expect_context::<AuthState>();
+    let pending = RwSignal::new(false);
+
+    let on_click = move |_| {
+        pending.set(true);
+        spawn_local(async move {
+            if api::post_empty(&routes::users::block(user_id), &())
+                .await
+                .is_ok()
+            {
+                reload.update(|n| *n += 1);
+            }
+            pending.set(false);
+        });
+    };
+
+    view! {
+        <Show when=move || auth.user.get().is_some_and(|me| me.id != user_id)>
+            <button
+                class="mikaana-btn mikaana-btn-sm mikaana-block-btn"
+                disabled=move || pending.get()
+                title="Hide what this user posts"
+                on:click=on_click
+            >
+                "Block"
+            </button>
+        </Show>
+    }
+}
+
+/// Stands in for a blocked user's comment or reply.
+#[component]
+pub fn BlockedPlaceholder(user_id: UserId, reload: RwSignal<u32>) -> impl IntoView {
+    let pending = RwSignal::new(false);
+
+    let on_unblock = move |_| {
+        pending.set(true);
+        spawn_local(async move {
+            if api::delete(&routes::users::block(user_id))
+                .await
+                .is_ok()
+            {
+                reload.update(|n| *n += 1);
+            }
+            pending.set(false);
+        });
+    };
+
+    view! {
+        <div class="mikaana-blocked">
+            <span class="mikaana-hint">"Blocked user"</span>
+            <button
+                class="mikaana-btn mikaana-btn-sm"
+                disabled=move || pending.get()
+                on:click=on_unblock
+            >
+                "Unblock"
+            </button>
+        </div>
+    }
+}
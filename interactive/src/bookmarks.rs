@@ -0,0 +1,86 @@
+//! Bookmark toggle for threads, replies and comments. The viewer's
+//! bookmarks are loaded once per widget and shared by its buttons.
+
+use leptos::prelude::*;
+use mikaana_shared::{routes, Bookmark, CreateBookmark};
+use wasm_bindgen_futures::spawn_local;
+
+use crate::api;
+use crate::auth::AuthState;
+
+/// `(target_type, target_id)` of everything the viewer bookmarked.
+#[derive(Clone, Copy)]
+pub struct Bookmarks(RwSignal<Vec<(String, i64)>>);
+
+/// Load the viewer's bookmarks whenever they log in, for the
+/// `BookmarkButton`s below.
+pub fn provide_bookmarks() {
+    let auth = expect_context::<AuthState>();
+    let bookmarks = Bookmarks(RwSignal::new(Vec::new()));
+    provide_context(bookmarks);
+
+    Effect::new(move |_| {
+        if auth.user.get().is_none() {
+            bookmarks.0.set(Vec::new());
+            return;
+        }
+        spawn_local(async move {
+            if let Ok(list) = api::get::<Vec<Bookmark>>(routes::bookmarks::LIST).await {
+                bookmarks.0.set(
+                    list.into_iter()
+                        .map(|b| (b.item.kind, b.item.id))
+                        .collect(),
+                );
+            }
+        });
+    });
+}
+
+/// Star toggle. Hidden when logged out.
+#[component]
+pub fn BookmarkButton(target_type: &'static str, id: i64) -> impl IntoView {
+    let auth = expect_context::<AuthState>();
+    let Bookmarks(bookmarks) = expect_context::<Bookmarks>();
+    let pending = RwSignal::new(false);
+    let saved = move || bookmarks.with(|list| list.iter().any(|(t, i)| t == target_type && *i == id));
+
+    let on_click = move |_| {
+        let save = !saved();
+        pending.set(true);
+        spawn_local(async move {
+            let result = if save {
+                let payload = CreateBookmark {
+                    target_type: target_type.to_string(),
+                    target_id: id,
+                };
+                api::post_empty(routes::bookmarks::LIST, &payload).await
+            } else {
+                api::delete(&routes::bookmarks::bookmark(target_type, id)).await
+            };
+            if result.is_ok() {
+                bookmarks.update(|list| {
+                    list.retain(|(t, i)| !(t == target_type && *i == id));
+                    if save {
+                        list.push((target_type.to_string(), id));
+                    }
+                });
+            }
+            pending.set(false);
+        });
+    };
+
+    view! {
+        <Show when=move || auth.user.get().is_some()>
+            <button
+                class="mikaana-btn mikaana-btn-sm mikaana-bookmark-btn"
+                class:active=saved
+                disabled=move || pending.get()
+                aria-pressed=move || saved().to_string()
+                title=move || if saved() { "Remove bookmark" } else { "Bookmark" }
+                on:click=on_click
+            >
+                {move || if saved() { "\u{2605}" } else { "\u{2606}" }}
+            </button>
+        </Show>
+    }
+}
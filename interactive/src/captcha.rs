@@ -0,0 +1,108 @@
+//! Turnstile or hCaptcha challenge for posts from new accounts. Forms show
+//! it only after the server answers a post with `428 Precondition Required`,
+//! then send the solved token with the next attempt.
+
+use leptos::html;
+use leptos::prelude::*;
+use mikaana_shared::{ApiError, CaptchaInfo, CaptchaProvider};
+use wasm_bindgen::prelude::*;
+
+use crate::{api, config};
+
+#[wasm_bindgen(inline_js = r#"
+export function render_captcha(global, src, el, sitekey, callback) {
+    const render = () => window[global].render(el, { sitekey, callback });
+    if (window[global]) {
+        render();
+        return;
+    }
+    let script = document.querySelector("script[data-mikaana-captcha]");
+    if (!script) {
+        script = document.createElement("script");
+        script.src = src;
+        script.async = true;
+        script.dataset.mikaanaCaptcha = "";
+        document.head.appendChild(script);
+    }
+    script.addEventListener("load", render);
+}
+"#)]
+extern "C" {
+    fn render_captcha(
+        global: &str,
+        src: &str,
+        el: &web_sys::HtmlElement,
+        sitekey: &str,
+        callback: &JsValue,
+    );
+}
+
+/// Challenge state shared by a form and its `CaptchaChallenge`.
+#[derive(Clone, Copy, Default)]
+pub struct Captcha {
+    required: RwSignal<bool>,
+    token: RwSignal<Option<String>>,
+    /// Bumped to render a fresh challenge; tokens are single-use.
+    generation: RwSignal<u32>,
+}
+
+impl Captcha {
+    /// Token to send with the next post.
+    pub fn token(self) -> Option<String> {
+        self.token.get_untracked()
+    }
+
+    /// Update after a post attempt: a `428` brings up the challenge, and any
+    /// answer spends the token.
+    pub fn after_post<T>(self, result: &Result<T, api::Error>) {
+        match result {
+            Ok(_) => self.required.set(false),
+            Err(e) if e.api() == Some(&ApiError::PreconditionRequired) => self.required.set(true),
+            Err(_) => {}
+        }
+        if self.token.get_untracked().is_some() {
+            self.token.set(None);
+            self.generation.update(|g| *g += 1);
+        }
+    }
+}
+
+fn script(provider: CaptchaProvider) -> (&'static str, &'static str) {
+    match provider {
+        CaptchaProvider::Turnstile => (
+            "turnstile",
+            "https://challenges.cloudflare.com/turnstile/v0/api.js?render=explicit",
+        ),
+        CaptchaProvider::Hcaptcha => (
+            "hcaptcha",
+            "https://js.hcaptcha.com/1/api.js?render=explicit",
+        ),
+    }
+}
+
+/// The provider's widget, while `captcha` says one is needed.
+#[component]
+pub fn CaptchaChallenge(captcha: Captcha) -> impl IntoView {
+    let info: RwSignal<Option<CaptchaInfo>> = RwSignal::new(None);
+    config::with_config(move |config| {
+        let _ = info.try_set(config.captcha.clone());
+    });
+
+    move || {
+        let info = info.get()?;
+        if !captcha.required.get() {
+            return None;
+        }
+        captcha.generation.track();
+
+        let node = NodeRef::<html::Div>::new();
+        node.on_load(move |el| {
+            let (global, src) = script(info.provider);
+            let callback = Closure::<dyn Fn(String)>::new(move |token: String| {
+                let _ = captcha.token.try_set(Some(token));
+            });
+            render_captcha(global, src, &el, &info.site_key, &callback.into_js_value());
+        });
+        Some(view! { <div class="mikaana-captcha" node_ref=node></div> })
+    }
+}
@@ -1,50 +1,115 @@
 use leptos::prelude::*;
-use mikaana_shared::{Comment, CreateComment};
+use mikaana_shared::{routes, AnalyticsEvent, Attachment, Comment, CommentState, CreateComment};
 use wasm_bindgen_futures::spawn_local;
 
-use crate::api;
 use crate::auth::{AuthState, LoginButton};
-use crate::votes::VoteButton;
+use crate::blocks::{BlockButton, BlockedPlaceholder};
+use crate::bookmarks::{provide_bookmarks, BookmarkButton};
+use crate::captcha::{Captcha, CaptchaChallenge};
+use crate::{analytics, api, config};
+use crate::editor::{
+    post_error, AttachmentList, AttachmentPicker, AutosizeTextarea, ClampedBody, CollapseToggle, Cooldown, DraftSaver,
+    UserBadges, HELD_NOTICE, within_limit,
+};
+use crate::lazy::{self, LoadMore, Paging};
+use crate::link_previews::LinkPreviews;
+use crate::skeleton::CommentSkeleton;
+use crate::votes::{VoteButton, VoteTarget};
+use crate::webmentions::Webmentions;
 
-/// Top-level comment section for a blog post.
+/// Comments revealed at a time with `Paging::Infinite`.
+const COMMENT_BATCH: usize = 20;
+
+/// Top-level comment section for a blog post. All comments arrive in one
+/// response; with `Paging::Infinite` they are revealed in batches as the
+/// reader scrolls.
 #[component]
 pub fn CommentSection(slug: String) -> impl IntoView {
     let comments: RwSignal<Vec<Comment>> = RwSignal::new(Vec::new());
     let loading = RwSignal::new(true);
     let error: RwSignal<Option<String>> = RwSignal::new(None);
+    let closed = RwSignal::new(false);
+    let discussion_url: RwSignal<Option<String>> = RwSignal::new(None);
+    // Bumped to fetch the list again, after (un)blocking someone
+    let reload = RwSignal::new(0u32);
+    provide_bookmarks();
+
+    let shown = RwSignal::new(match lazy::paging() {
+        Paging::Pages => usize::MAX,
+        Paging::Infinite => COMMENT_BATCH,
+    });
+    // Comments posted here show up straight away, however far the list goes
+    Effect::new(move |prev: Option<usize>| {
+        let len = comments.with(Vec::len);
+        if let Some(prev) = prev.filter(|&prev| len > prev && !loading.get_untracked()) {
+            shown.update(|s| *s = s.saturating_add(len - prev));
+        }
+        len
+    });
 
-    // Fetch comments on mount
+    // Fetch comments on mount, and again on `reload`
     {
         let slug = slug.clone();
+        Effect::new(move |_| {
+            reload.track();
+            let slug = slug.clone();
+            spawn_local(async move {
+                match api::get::<Vec<Comment>>(&routes::comments::list(&slug)).await {
+                    Ok(c) => comments.set(c),
+                    Err(e) => error.set(Some(e.to_string())),
+                }
+                loading.set(false);
+            });
+        });
+    }
+    {
+        let url = routes::comments::state(&slug);
         spawn_local(async move {
-            match api::get::<Vec<Comment>>(&format!("/api/comments?slug={}", slug)).await {
-                Ok(c) => comments.set(c),
-                Err(e) => error.set(Some(e)),
+            // Open unless the server says otherwise; it refuses posts anyway
+            if let Ok(state) = api::get::<CommentState>(&url).await {
+                closed.set(state.closed);
+                discussion_url.set(state.discussion_url);
             }
-            loading.set(false);
         });
     }
 
+    let form_slug = slug.clone();
     view! {
         <section class="mikaana-comments">
             <h3>"Comments"</h3>
-            <LoginButton />
-            <CommentForm slug=slug.clone() comments=comments />
+            {move || discussion_url.get().map(|url| view! {
+                <p class="mikaana-hint mikaana-discussion-link">
+                    <a href=url>"Continue the discussion in the forum"</a>
+                </p>
+            })}
+            <Show
+                when=move || !closed.get()
+                fallback=|| view! { <p class="mikaana-hint mikaana-comments-closed">"Comments are closed."</p> }
+            >
+                <LoginButton />
+                <CommentForm slug=form_slug.clone() comments=comments />
+            </Show>
             <Show when=move || loading.get()>
-                <p class="mikaana-loading">"Loading comments..."</p>
+                <CommentSkeleton count=3 />
             </Show>
             <Show when=move || error.get().is_some()>
                 <p class="mikaana-error">{move || error.get().unwrap_or_default()}</p>
             </Show>
             <div class="mikaana-comment-list">
                 <For
-                    each=move || comments.get()
-                    key=|c| c.id
+                    each=move || comments.get().into_iter().take(shown.get())
+                    key=|c| (c.id, c.blocked)
                     let:comment
                 >
-                    <CommentItem comment=comment comments=comments />
+                    <CommentItem comment=comment comments=comments reload=reload />
                 </For>
             </div>
+            <LoadMore
+                more=Signal::derive(move || comments.with(Vec::len) > shown.get())
+                loading=loading
+                load=move || shown.update(|s| *s = s.saturating_add(COMMENT_BATCH))
+            />
+            <Webmentions slug=slug />
         </section>
     }
 }
@@ -54,7 +119,15 @@ pub fn CommentSection(slug: String) -> impl IntoView {
 fn CommentForm(slug: String, comments: RwSignal<Vec<Comment>>) -> impl IntoView {
     let auth = expect_context::<AuthState>();
     let body = RwSignal::new(String::new());
+    let attachments: RwSignal<Vec<Attachment>> = RwSignal::new(Vec::new());
     let submitting = RwSignal::new(false);
+    let notice: RwSignal<Option<String>> = RwSignal::new(None);
+    let captcha = Captcha::default();
+    let cooldown = Cooldown::new();
+    let key = RwSignal::new(api::idempotency_key());
+    let draft = DraftSaver::new(format!("comment:{slug}"), None, body);
+    let limits = config::limits();
+    let too_long = move || !body.with(|b| within_limit(b, limits.get().body));
 
     let on_submit = {
         let slug = slug.clone();
@@ -65,18 +138,35 @@ fn CommentForm(slug: String, comments: RwSignal<Vec<Comment>>) -> impl IntoView
                 return;
             }
             submitting.set(true);
+            notice.set(None);
             let slug = slug.clone();
             spawn_local(async move {
                 let payload = CreateComment {
                     post_slug: slug,
                     body: text,
+                    attachment_ids: attachments.get_untracked().iter().map(|a| a.id).collect(),
+                    captcha_token: captcha.token(),
+                    post_title: Some(document().title()).filter(|t| !t.trim().is_empty()),
                 };
-                match api::post::<Comment, _>("/api/comments", &payload).await {
+                let result =
+                    api::post_once::<Comment, _>(routes::comments::LIST, &payload, &key.get_untracked())
+                        .await;
+                captcha.after_post(&result);
+                match result {
                     Ok(c) => {
-                        comments.update(|list| list.push(c));
+                        analytics::track(AnalyticsEvent::CommentPosted);
+                        if c.pending {
+                            notice.set(Some(HELD_NOTICE.to_string()));
+                        } else {
+                            comments.update(|list| list.push(c));
+                        }
+                        draft.clear();
                         body.set(String::new());
+                        attachments.set(Vec::new());
+                        key.set(api::idempotency_key());
                     }
-                    Err(_e) => { /* TODO: show error */ }
+                    Err(e) if cooldown.start(&e) => {}
+                    Err(e) => notice.set(Some(post_error(&e))),
                 }
                 submitting.set(false);
             });
@@ -87,21 +177,18 @@ fn CommentForm(slug: String, comments: RwSignal<Vec<Comment>>) -> impl IntoView
         if auth.user.get().is_some() {
             view! {
                 <form class="mikaana-comment-form" on:submit=on_submit.clone()>
-                    <textarea
-                        class="mikaana-textarea"
-                        placeholder="Write a comment..."
-                        prop:value=move || body.get()
-                        on:input=move |ev| {
-                            body.set(event_target_value(&ev));
-                        }
-                    />
+                    <AutosizeTextarea value=body placeholder="Write a comment..." />
+                    <AttachmentPicker attachments=attachments />
+                    <CaptchaChallenge captcha=captcha />
                     <button
                         class="mikaana-btn"
                         type="submit"
-                        disabled=move || submitting.get()
+                        disabled=move || submitting.get() || too_long() || cooldown.active()
                     >
                         {move || if submitting.get() { "Posting..." } else { "Post Comment" }}
                     </button>
+                    {move || notice.get().map(|n| view! { <p class="mikaana-hint">{n}</p> })}
+                    {move || cooldown.message().map(|m| view! { <p class="mikaana-hint mikaana-cooldown">{m}</p> })}
                 </form>
             }
             .into_any()
@@ -113,7 +200,15 @@ fn CommentForm(slug: String, comments: RwSignal<Vec<Comment>>) -> impl IntoView
 
 /// Single comment display.
 #[component]
-fn CommentItem(comment: Comment, comments: RwSignal<Vec<Comment>>) -> impl IntoView {
+fn CommentItem(comment: Comment, comments: RwSignal<Vec<Comment>>, reload: RwSignal<u32>) -> impl IntoView {
+    if comment.blocked {
+        return view! {
+            <div class="mikaana-comment">
+                <BlockedPlaceholder user_id=comment.user.id reload=reload />
+            </div>
+        }
+        .into_any();
+    }
     let auth = expect_context::<AuthState>();
     let comment_id = comment.id;
     let is_own = move || {
@@ -125,7 +220,7 @@ fn CommentItem(comment: Comment, comments: RwSignal<Vec<Comment>>) -> impl IntoV
 
     let on_delete = move |_| {
         spawn_local(async move {
-            if api::delete(&format!("/api/comments/{}", comment_id))
+            if api::delete(&routes::comments::comment(comment_id))
                 .await
                 .is_ok()
             {
@@ -134,18 +229,36 @@ fn CommentItem(comment: Comment, comments: RwSignal<Vec<Comment>>) -> impl IntoV
         });
     };
 
+    let collapsed = RwSignal::new(false);
+
     view! {
-        <div class="mikaana-comment">
+        <div class="mikaana-comment" class:mikaana-collapsed=move || collapsed.get()>
             <div class="mikaana-comment-header">
+                <CollapseToggle collapsed=collapsed />
                 <img src={comment.user.avatar_url.clone()} alt="" class="mikaana-avatar" width="24" height="24" />
-                <strong>{comment.user.username.clone()}</strong>
+                <strong>{comment.user.name().to_string()}</strong>
+                <UserBadges badges=comment.user.badges.clone() />
                 <time>{comment.created_at.clone()}</time>
                 <Show when=is_own>
                     <button class="mikaana-btn mikaana-btn-sm mikaana-btn-danger" on:click=on_delete>"Delete"</button>
                 </Show>
+                <BlockButton user_id=comment.user.id reload=reload />
+            </div>
+            <ClampedBody
+                body=comment.body.clone()
+                length=comment.body_length
+                class="mikaana-comment-body"
+            />
+            <LinkPreviews body=comment.body.clone() />
+            <AttachmentList attachments=comment.attachments.clone() />
+            <div class="mikaana-comment-actions">
+                <VoteButton
+                    target=VoteTarget::comment(comment.id)
+                    initial_count=comment.vote_count
+                />
+                <BookmarkButton target_type="comment" id=comment.id.0 />
             </div>
-            <p class="mikaana-comment-body">{comment.body.clone()}</p>
-            <VoteButton target_type="comment".to_string() target_id=comment.id initial_count=comment.vote_count />
         </div>
     }
+    .into_any()
 }
@@ -0,0 +1,149 @@
+//! Server-driven settings (`GET /api/config`), fetched once per page and
+//! shared by every widget on it.
+
+use std::cell::RefCell;
+
+use leptos::prelude::*;
+use mikaana_shared::{routes, ContentLimits, PublicConfig};
+use wasm_bindgen_futures::spawn_local;
+use web_sys::HtmlElement;
+
+use crate::api;
+
+type Waiter = Box<dyn FnOnce(&PublicConfig)>;
+
+enum State {
+    Idle,
+    Loading(Vec<Waiter>),
+    Ready(Box<PublicConfig>),
+}
+
+thread_local! {
+    static STATE: RefCell<State> = const { RefCell::new(State::Idle) };
+}
+
+/// Run `f` with the server config, fetching it on first use. Widgets
+/// mounting while the request is in flight queue behind it; a failed fetch
+/// falls back to defaults.
+pub fn with_config(f: impl FnOnce(&PublicConfig) + 'static) {
+    let mut f: Option<Waiter> = Some(Box::new(f));
+    let (ready, fetch) = STATE.with_borrow_mut(|state| match state {
+        State::Ready(config) => (Some(config.as_ref().clone()), false),
+        State::Loading(waiters) => {
+            waiters.extend(f.take());
+            (None, false)
+        }
+        State::Idle => {
+            *state = State::Loading(f.take().into_iter().collect());
+            (None, true)
+        }
+    });
+
+    // Called outside the borrow so `f` may itself use the config
+    if let (Some(config), Some(f)) = (ready, f) {
+        f(&config);
+    }
+    if fetch {
+        spawn_local(fetch_config());
+    }
+}
+
+async fn fetch_config() {
+    let config = api::get::<PublicConfig>(routes::CONFIG)
+        .await
+        .unwrap_or_default();
+    let waiters = STATE.with_borrow_mut(|state| {
+        match std::mem::replace(state, State::Ready(Box::new(config.clone()))) {
+            State::Loading(waiters) => waiters,
+            _ => Vec::new(),
+        }
+    });
+    for waiter in waiters {
+        waiter(&config);
+    }
+}
+
+/// Length limits for form fields: the built-in ones until the server's
+/// arrive.
+pub fn limits() -> RwSignal<ContentLimits> {
+    let limits = RwSignal::new(ContentLimits::default());
+    with_config(move |config| {
+        let _ = limits.try_set(config.limits);
+    });
+    limits
+}
+
+/// The server's locale, once the config has arrived and if it sets one.
+pub fn locale() -> Option<String> {
+    STATE.with_borrow(|state| match state {
+        State::Ready(config) => config.locale.clone(),
+        _ => None,
+    })
+}
+
+/// Apply branding to a widget's mount element: accent color, the optional
+/// custom stylesheet, and (for full-size widgets) the footer line.
+pub fn apply_branding(el: HtmlElement, footer: bool) {
+    with_config(move |config| {
+        let branding = &config.branding;
+
+        if let Some(color) = &branding.accent_color {
+            let _ = HtmlElement::style(&el).set_property("--mikaana-accent", color);
+        }
+
+        if let Some(url) = &branding.custom_css_url {
+            add_stylesheet(url);
+        }
+
+        if footer && (branding.powered_by || branding.site_name.is_some()) {
+            add_footer(&el, branding.site_name.as_deref(), branding.powered_by);
+        }
+    });
+}
+
+fn add_stylesheet(url: &str) {
+    let Some(document) = web_sys::window().and_then(|w| w.document()) else {
+        return;
+    };
+    // Several widgets share one page; link the stylesheet once
+    if document
+        .query_selector("link[data-mikaana-branding]")
+        .ok()
+        .flatten()
+        .is_some()
+    {
+        return;
+    }
+    let (Some(head), Ok(link)) = (document.head(), document.create_element("link")) else {
+        return;
+    };
+    let _ = link.set_attribute("rel", "stylesheet");
+    let _ = link.set_attribute("href", url);
+    let _ = link.set_attribute("data-mikaana-branding", "");
+    let _ = head.append_child(&link);
+}
+
+fn add_footer(el: &HtmlElement, site_name: Option<&str>, powered_by: bool) {
+    let Some(document) = el.owner_document() else {
+        return;
+    };
+    let Ok(footer) = document.create_element("footer") else {
+        return;
+    };
+    footer.set_class_name("mikaana-powered-by");
+
+    if let Some(name) = site_name {
+        if let Ok(span) = document.create_element("span") {
+            span.set_text_content(Some(name));
+            let _ = footer.append_child(&span);
+        }
+    }
+    if powered_by {
+        if let Ok(link) = document.create_element("a") {
+            let _ = link.set_attribute("href", "https://github.com/girivs82/mikaana");
+            link.set_text_content(Some("Powered by mikaana"));
+            let _ = footer.append_child(&link);
+        }
+    }
+    let _ = el.append_child(&footer);
+}
@@ -0,0 +1,652 @@
+use std::time::Duration;
+
+use leptos::html;
+use leptos::prelude::*;
+use mikaana_shared::{routes, ApiError, Attachment, Draft, UploadLimits, UserBadge, EMOJI};
+use wasm_bindgen_futures::spawn_local;
+use web_sys::window;
+
+use crate::auth::AuthState;
+use crate::mentions::{MentionList, Mentions};
+use crate::{api, config};
+
+/// Bodies longer than this many characters start clamped behind "Show more".
+const CLAMP_THRESHOLD: usize = 1200;
+
+/// Bodies with more lines than this start clamped too, however short.
+const CLAMP_LINES: usize = 20;
+
+/// Textarea that grows with its content; past the CSS `max-height` it
+/// scrolls instead. Counts characters against the body limit (forms disable
+/// submitting while `within_limit` says it's over) and completes `@mentions`.
+#[component]
+pub fn AutosizeTextarea(value: RwSignal<String>, #[prop(into)] placeholder: String) -> impl IntoView {
+    let node = NodeRef::<html::Textarea>::new();
+    let limits = config::limits();
+    let mentions = Mentions::new();
+
+    // Also runs when the value is set programmatically, e.g. cleared after posting
+    Effect::new(move |_| {
+        value.track();
+        if let Some(el) = node.get() {
+            let style = web_sys::HtmlElement::style(&el);
+            let _ = style.set_property("height", "auto");
+            let _ = style.set_property("height", &format!("{}px", el.scroll_height()));
+        }
+    });
+
+    view! {
+        <textarea
+            class="mikaana-textarea mikaana-autosize"
+            node_ref=node
+            placeholder=placeholder
+            prop:value=move || value.get()
+            on:input=move |ev| {
+                value.set(event_target_value(&ev));
+                if let Some(el) = node.get_untracked() {
+                    mentions.on_input(&el);
+                }
+            }
+            on:keydown=move |ev| mentions.on_keydown(&ev, value, node)
+            on:blur=move |_| mentions.close()
+        />
+        <MentionList mentions=mentions value=value node=node />
+        <div class="mikaana-editor-tools">
+            <EmojiPicker value=value node=node />
+            <CharCounter value=value max=Signal::derive(move || limits.get().body) />
+        </div>
+    }
+}
+
+/// Byte offset in `text` of a UTF-16 offset, as the DOM counts selections.
+pub(crate) fn byte_offset(text: &str, utf16: u32) -> usize {
+    let mut units = 0;
+    for (i, c) in text.char_indices() {
+        if units >= utf16 as usize {
+            return i;
+        }
+        units += c.len_utf16();
+    }
+    text.len()
+}
+
+/// Toggle for a grid of the `EMOJI` the server knows; picking one inserts
+/// it at the cursor in `node`. Typing `:shortcode:` works too, as the server
+/// expands those.
+#[component]
+fn EmojiPicker(value: RwSignal<String>, node: NodeRef<html::Textarea>) -> impl IntoView {
+    let open = RwSignal::new(false);
+
+    let insert = move |emoji: &'static str| {
+        let mut text = value.get_untracked();
+        let el = node.get_untracked();
+        let (start, end) = el
+            .as_ref()
+            .and_then(|el| Some((el.selection_start().ok()??, el.selection_end().ok()??)))
+            .unwrap_or_else(|| {
+                let len = text.encode_utf16().count() as u32;
+                (len, len)
+            });
+        text.replace_range(byte_offset(&text, start)..byte_offset(&text, end), emoji);
+        if let Some(el) = el {
+            // Set the DOM value first so the cursor can go right after the emoji
+            el.set_value(&text);
+            let cursor = start + emoji.encode_utf16().count() as u32;
+            let _ = el.set_selection_range(cursor, cursor);
+            let _ = el.focus();
+        }
+        value.set(text);
+        open.set(false);
+    };
+
+    view! {
+        <div class="mikaana-emoji-picker">
+            <button
+                type="button"
+                class="mikaana-link-btn"
+                title="Insert emoji"
+                aria-expanded=move || open.get().to_string()
+                on:click=move |_| open.update(|o| *o = !*o)
+            >
+                "\u{1F642}"
+            </button>
+            <Show when=move || open.get()>
+                <div class="mikaana-emoji-grid">
+                    {EMOJI
+                        .iter()
+                        .map(|&(code, emoji)| view! {
+                            <button type="button" title=format!(":{code}:") on:click=move |_| insert(emoji)>
+                                {emoji}
+                            </button>
+                        })
+                        .collect_view()}
+                </div>
+            </Show>
+        </div>
+    }
+}
+
+/// Whether `value` fits in `max` characters, counted as the server counts
+/// them (not UTF-16 units, as `maxlength` would).
+pub fn within_limit(value: &str, max: usize) -> bool {
+    value.chars().count() <= max
+}
+
+/// "120 / 10000" under a field, flagged once `value` goes over `max`.
+#[component]
+pub fn CharCounter(value: RwSignal<String>, #[prop(into)] max: Signal<usize>) -> impl IntoView {
+    let count = Memo::new(move |_| value.with(|v| v.chars().count()));
+
+    view! {
+        <span class="mikaana-char-count" class:mikaana-char-count-over=move || { count.get() > max.get() }>
+            {move || format!("{} / {}", count.get(), max.get())}
+        </span>
+    }
+}
+
+/// Rendered comment/reply body. Long bodies (per the server's length hint,
+/// or by line count) are clamped to a fixed height with a "Show more" toggle.
+#[component]
+pub fn ClampedBody(body: String, length: usize, #[prop(into)] class: String) -> impl IntoView {
+    let long = length > CLAMP_THRESHOLD || body.lines().count() > CLAMP_LINES;
+    let expanded = RwSignal::new(false);
+
+    view! {
+        <div class=class class:mikaana-clamped=move || long && !expanded.get()>
+            {body}
+        </div>
+        <Show when=move || long>
+            <button class="mikaana-link-btn" on:click=move |_| expanded.update(|e| *e = !*e)>
+                {move || if expanded.get() { "Show less" } else { "Show more" }}
+            </button>
+        </Show>
+    }
+}
+
+/// Header button folding a comment or reply down to its header line. The
+/// item hides the rest itself under the `mikaana-collapsed` class.
+#[component]
+pub fn CollapseToggle(collapsed: RwSignal<bool>) -> impl IntoView {
+    view! {
+        <button
+            type="button"
+            class="mikaana-collapse-btn"
+            aria-expanded=move || (!collapsed.get()).to_string()
+            aria-label=move || if collapsed.get() { "Expand" } else { "Collapse" }
+            on:click=move |_| collapsed.update(|c| *c = !*c)
+        >
+            {move || if collapsed.get() { "[+]" } else { "[\u{2212}]" }}
+        </button>
+    }
+}
+
+/// Chips after an author's name ("Moderator", "Author", "New member").
+#[component]
+pub fn UserBadges(badges: Vec<UserBadge>) -> impl IntoView {
+    badges
+        .into_iter()
+        .map(|badge| {
+            let class = match badge {
+                UserBadge::Moderator => "mikaana-user-badge mikaana-user-badge-moderator",
+                UserBadge::Author => "mikaana-user-badge mikaana-user-badge-author",
+                UserBadge::NewMember => "mikaana-user-badge mikaana-user-badge-new",
+            };
+            view! { <span class=class>{badge.label()}</span> }
+        })
+        .collect_view()
+}
+
+// ── Drafts ──
+
+const DRAFT_STORAGE_PREFIX: &str = "mikaana_draft:";
+
+/// How long typing has to pause before a draft is sent to the server.
+const DRAFT_SYNC_DELAY: Duration = Duration::from_secs(2);
+
+fn draft_path(key: &str) -> String {
+    routes::drafts::draft(key)
+}
+
+fn local_draft(key: &str) -> Option<Draft> {
+    let storage = window()?.local_storage().ok()??;
+    let json = storage.get_item(&format!("{DRAFT_STORAGE_PREFIX}{key}")).ok()??;
+    serde_json::from_str(&json).ok()
+}
+
+fn store_local_draft(draft: &Draft) {
+    let Some(storage) = window().and_then(|w| w.local_storage().ok()).flatten() else {
+        return;
+    };
+    let item = format!("{DRAFT_STORAGE_PREFIX}{}", draft.key);
+    if draft.title.trim().is_empty() && draft.body.trim().is_empty() {
+        let _ = storage.remove_item(&item);
+    } else if let Ok(json) = serde_json::to_string(draft) {
+        let _ = storage.set_item(&item, &json);
+    }
+}
+
+/// Autosave for an editor. Restores the draft saved under `key` (see
+/// `Draft::key`) into `title` and `body`, then saves every change to
+/// localStorage and, for signed-in users, to the server once typing pauses.
+/// Call `clear` after posting.
+#[derive(Clone, Copy)]
+pub struct DraftSaver {
+    key: StoredValue<String>,
+    /// Bumped per change, so only the last of a burst of edits is synced.
+    generation: StoredValue<u32>,
+    token: RwSignal<Option<String>>,
+}
+
+impl DraftSaver {
+    pub fn new(key: String, title: Option<RwSignal<String>>, body: RwSignal<String>) -> Self {
+        let auth = expect_context::<AuthState>();
+        let saver = DraftSaver {
+            key: StoredValue::new(key.clone()),
+            generation: StoredValue::new(0),
+            token: auth.token,
+        };
+
+        let restore = move |draft: Draft| {
+            let untouched = body.with_untracked(String::is_empty)
+                && title.is_none_or(|t| t.with_untracked(String::is_empty));
+            if untouched {
+                if let Some(title) = title {
+                    title.set(draft.title);
+                }
+                body.set(draft.body);
+            }
+        };
+        match local_draft(&key) {
+            Some(draft) => restore(draft),
+            None if saver.signed_in() => spawn_local(async move {
+                if let Ok(draft) = api::get::<Draft>(&draft_path(&key)).await {
+                    restore(draft);
+                }
+            }),
+            None => {}
+        }
+
+        // The first run only subscribes; there's nothing new to save yet
+        Effect::new(move |prev: Option<()>| {
+            let draft = Draft {
+                key: saver.key.get_value(),
+                title: title.map(|t| t.get()).unwrap_or_default(),
+                body: body.get(),
+                updated_at: String::new(),
+            };
+            if prev.is_none() {
+                return;
+            }
+            store_local_draft(&draft);
+            if !saver.signed_in() {
+                return;
+            }
+            saver.generation.update_value(|g| *g += 1);
+            let generation = saver.generation.get_value();
+            set_timeout(
+                move || {
+                    if saver.generation.try_get_value() != Some(generation) {
+                        return;
+                    }
+                    spawn_local(async move {
+                        let _ = api::put_empty(routes::drafts::DRAFTS, &draft).await;
+                    });
+                },
+                DRAFT_SYNC_DELAY,
+            );
+        });
+
+        saver
+    }
+
+    fn signed_in(&self) -> bool {
+        self.token.with_untracked(Option::is_some)
+    }
+
+    /// Forget the draft, locally and on the server.
+    pub fn clear(&self) {
+        let key = self.key.get_value();
+        // Drops any sync still waiting to run
+        self.generation.update_value(|g| *g += 1);
+        store_local_draft(&Draft {
+            key: key.clone(),
+            ..Draft::default()
+        });
+        if self.signed_in() {
+            spawn_local(async move {
+                let _ = api::delete(&draft_path(&key)).await;
+            });
+        }
+    }
+}
+
+/// Countdown after a post refused for coming too soon after the user's
+/// previous ones (`ApiError::PostingTooFast`). Forms keep submitting disabled
+/// while it's `active` and show its `message`.
+#[derive(Clone, Copy)]
+pub struct Cooldown {
+    remaining: RwSignal<u64>,
+    /// Bumped per `start`, so an older countdown stops ticking.
+    generation: StoredValue<u32>,
+}
+
+impl Cooldown {
+    pub fn new() -> Self {
+        Cooldown {
+            remaining: RwSignal::new(0),
+            generation: StoredValue::new(0),
+        }
+    }
+
+    /// Count down the wait `err` asks for; false when it isn't a cooldown.
+    pub fn start(self, err: &api::Error) -> bool {
+        let Some(&ApiError::PostingTooFast { retry_after: secs }) = err.api() else {
+            return false;
+        };
+        self.generation.update_value(|g| *g += 1);
+        self.remaining.set(secs);
+        self.tick(self.generation.get_value());
+        true
+    }
+
+    fn tick(self, generation: u32) {
+        set_timeout(
+            move || {
+                if self.generation.try_get_value() != Some(generation) {
+                    return;
+                }
+                let left = self.remaining.get_untracked().saturating_sub(1);
+                self.remaining.set(left);
+                if left > 0 {
+                    self.tick(generation);
+                }
+            },
+            Duration::from_secs(1),
+        );
+    }
+
+    pub fn active(self) -> bool {
+        self.remaining.get() > 0
+    }
+
+    pub fn message(self) -> Option<String> {
+        match self.remaining.get() {
+            0 => None,
+            secs => Some(format!(
+                "You're posting too fast. You can post again in {}.",
+                wait_label(secs)
+            )),
+        }
+    }
+}
+
+/// `45 s`, `2 min 5 s`.
+fn wait_label(secs: u64) -> String {
+    match (secs / 60, secs % 60) {
+        (0, s) => format!("{s} s"),
+        (m, 0) => format!("{m} min"),
+        (m, s) => format!("{m} min {s} s"),
+    }
+}
+
+/// Shown instead of a new post that a word filter held for moderation.
+pub const HELD_NOTICE: &str = "Thanks! Your post will appear once a moderator approves it.";
+
+/// User-facing text for a failed post, from an `api` error.
+pub fn post_error(err: &api::Error) -> String {
+    match (err.api(), err.request_id()) {
+        (Some(ApiError::UnprocessableEntity), _) => {
+            "Your post contains words that aren't allowed here.".to_string()
+        }
+        (Some(ApiError::ValidationFailed { .. } | ApiError::TooDeep), _) => {
+            "Your post is too long.".to_string()
+        }
+        (Some(ApiError::PayloadTooLarge), _) => "Your post is too large.".to_string(),
+        (Some(ApiError::DuplicatePost), _) => "You just posted that.".to_string(),
+        (Some(ApiError::IdempotencyKeyInUse), _) => "Still posting, hold on.".to_string(),
+        (Some(ApiError::TermsNotAccepted(_)), _) => {
+            "Please accept the terms of service, then post again.".to_string()
+        }
+        (Some(ApiError::PreconditionRequired), _) => {
+            "Please complete the check below, then post again.".to_string()
+        }
+        (_, Some(id)) => {
+            format!("Couldn't post, please try again. If this keeps happening, mention request {id}.")
+        }
+        (_, None) => "Couldn't post, please try again.".to_string(),
+    }
+}
+
+/// Upload button for an editor, with previews of what's been uploaded so
+/// far. The form sends `attachments`' ids with the post and clears the list
+/// afterwards. Hidden when the server has uploads turned off.
+#[component]
+pub fn AttachmentPicker(attachments: RwSignal<Vec<Attachment>>) -> impl IntoView {
+    let limits: RwSignal<Option<UploadLimits>> = RwSignal::new(None);
+    let uploading = RwSignal::new(0usize);
+    let error: RwSignal<Option<String>> = RwSignal::new(None);
+    let input = NodeRef::<html::Input>::new();
+    let content_limits = config::limits();
+
+    config::with_config(move |config| {
+        let _ = limits.try_set(config.uploads.clone());
+    });
+
+    let on_change = move |_| {
+        let (Some(el), Some(limits)) = (input.get_untracked(), limits.get_untracked()) else {
+            return;
+        };
+        let Some(files) = el.files() else { return };
+        error.set(None);
+        for i in 0..files.length() {
+            let Some(file) = files.get(i) else { continue };
+            let max = content_limits.get_untracked().attachments;
+            if attachments.with_untracked(Vec::len) + uploading.get_untracked() >= max {
+                error.set(Some(format!("At most {max} files per post.")));
+                break;
+            }
+            if let Err(e) = check_file(&file, &limits) {
+                error.set(Some(e));
+                continue;
+            }
+            uploading.update(|n| *n += 1);
+            spawn_local(async move {
+                match api::upload::<Attachment>(&file).await {
+                    Ok(a) => attachments.update(|list| list.push(a)),
+                    Err(e) => error.set(Some(format!("Couldn't upload {}: {e}", file.name()))),
+                }
+                uploading.update(|n| *n -= 1);
+            });
+        }
+        // Lets the same file be picked again after removing it
+        el.set_value("");
+    };
+
+    move || {
+        limits.get().map(|limits| {
+            let accept = limits.allowed_types.join(",");
+            view! {
+                <div class="mikaana-attachment-picker">
+                    <div class="mikaana-attachment-previews">
+                        <For
+                            each=move || attachments.get()
+                            key=|a| a.id
+                            let:attachment
+                        >
+                            <AttachmentPreview attachment=attachment attachments=attachments />
+                        </For>
+                    </div>
+                    <label class="mikaana-btn mikaana-btn-sm mikaana-upload-btn">
+                        {move || if uploading.get() > 0 { "Uploading..." } else { "Attach files" }}
+                        <input
+                            type="file"
+                            multiple
+                            accept=accept
+                            node_ref=input
+                            on:change=on_change
+                        />
+                    </label>
+                    <Show when=move || error.get().is_some()>
+                        <p class="mikaana-error">{move || error.get().unwrap_or_default()}</p>
+                    </Show>
+                </div>
+            }
+        })
+    }
+}
+
+/// Catch files the server would refuse before uploading them.
+fn check_file(file: &web_sys::File, limits: &UploadLimits) -> Result<(), String> {
+    if !limits.allowed_types.iter().any(|t| *t == file.type_()) {
+        return Err(format!("{} isn't a supported file type.", file.name()));
+    }
+    if file.size() > limits.max_bytes as f64 {
+        return Err(format!(
+            "{} is too large (limit {}).",
+            file.name(),
+            format_size(limits.max_bytes as i64)
+        ));
+    }
+    Ok(())
+}
+
+#[component]
+fn AttachmentPreview(attachment: Attachment, attachments: RwSignal<Vec<Attachment>>) -> impl IntoView {
+    let id = attachment.id;
+    let thumb = if attachment.is_image() {
+        view! { <img src=attachment.url.clone() alt=attachment.filename.clone() /> }.into_any()
+    } else {
+        view! { <span class="mikaana-attachment-icon">"\u{1F4C4}"</span> }.into_any()
+    };
+    view! {
+        <div class="mikaana-attachment-preview" title=attachment.filename.clone()>
+            {thumb}
+            <span class="mikaana-attachment-name">{attachment.filename.clone()}</span>
+            <button
+                type="button"
+                class="mikaana-link-btn"
+                aria-label="Remove"
+                on:click=move |_| attachments.update(|list| list.retain(|a| a.id != id))
+            >
+                "\u{2715}"
+            </button>
+        </div>
+    }
+}
+
+/// Attachments under a posted comment, thread or reply: images as
+/// thumbnails that open in a [`Lightbox`] (or, with a modifier key, the
+/// full file in a new tab), anything else as a download link.
+#[component]
+pub fn AttachmentList(attachments: Vec<Attachment>) -> impl IntoView {
+    (!attachments.is_empty()).then(|| {
+        let (images, files): (Vec<_>, Vec<_>) = attachments.into_iter().partition(Attachment::is_image);
+        let open = RwSignal::new(None::<usize>);
+        view! {
+            <div class="mikaana-attachments">
+                <div class="mikaana-attachment-images">
+                    {images
+                        .iter()
+                        .cloned()
+                        .enumerate()
+                        .map(|(i, a)| {
+                            let href = a.url.clone();
+                            view! {
+                                <a
+                                    href=href
+                                    target="_blank"
+                                    rel="noopener"
+                                    on:click=move |ev| {
+                                        if !(ev.ctrl_key() || ev.meta_key() || ev.shift_key()) {
+                                            ev.prevent_default();
+                                            open.set(Some(i));
+                                        }
+                                    }
+                                >
+                                    <img src=a.url alt=a.filename loading="lazy" decoding="async" />
+                                </a>
+                            }
+                        })
+                        .collect_view()}
+                </div>
+                <ul class="mikaana-attachment-files">
+                    {files
+                        .into_iter()
+                        .map(|a| view! {
+                            <li>
+                                <a href=a.url target="_blank" rel="noopener">{a.filename}</a>
+                                " (" {format_size(a.size)} ")"
+                            </li>
+                        })
+                        .collect_view()}
+                </ul>
+                <Lightbox images open />
+            </div>
+        }
+    })
+}
+
+/// One of `images` at full size over the page while `open` holds its index.
+/// Escape, the close button or a click outside the image closes it; the
+/// arrow keys step through the others.
+#[component]
+fn Lightbox(images: Vec<Attachment>, open: RwSignal<Option<usize>>) -> impl IntoView {
+    let count = images.len();
+    let images = StoredValue::new(images);
+    let node = NodeRef::<html::Div>::new();
+
+    // Take focus so the keys reach the overlay rather than the page
+    Effect::new(move |_| {
+        if open.get().is_some() {
+            if let Some(el) = node.get() {
+                let _ = el.focus();
+            }
+        }
+    });
+
+    let on_keydown = move |ev: leptos::ev::KeyboardEvent| {
+        let Some(i) = open.get_untracked() else {
+            return;
+        };
+        match ev.key().as_str() {
+            "Escape" => open.set(None),
+            "ArrowRight" => open.set(Some((i + 1) % count)),
+            "ArrowLeft" => open.set(Some((i + count - 1) % count)),
+            _ => return,
+        }
+        ev.prevent_default();
+    };
+
+    move || {
+        let i = open.get()?;
+        let image = images.with_value(|list| list.get(i).cloned())?;
+        Some(view! {
+            <div
+                class="mikaana-lightbox"
+                role="dialog"
+                aria-modal="true"
+                aria-label=image.filename.clone()
+                tabindex="-1"
+                node_ref=node
+                on:keydown=on_keydown
+                on:click=move |_| open.set(None)
+            >
+                <img src=image.url.clone() alt=image.filename.clone() on:click=|ev| ev.stop_propagation() />
+                {(count > 1).then(|| view! {
+                    <span class="mikaana-lightbox-count">{format!("{} / {count}", i + 1)}</span>
+                })}
+                <button type="button" class="mikaana-lightbox-close" aria-label="Close" on:click=move |_| open.set(None)>
+                    "\u{2715}"
+                </button>
+            </div>
+        })
+    }
+}
+
+fn format_size(bytes: i64) -> String {
+    match bytes {
+        b if b < 1024 => format!("{b} B"),
+        b if b < 1024 * 1024 => format!("{:.0} KB", b as f64 / 1024.0),
+        b => format!("{:.1} MB", b as f64 / (1024.0 * 1024.0)),
+    }
+}
@@ -0,0 +1,63 @@
+//! Custom elements wrapping the widgets, for sites that can't place the
+//! `#mikaana-*` mount points (other static site generators, CMS embeds):
+//!
+//! ```html
+//! <mikaana-votes slug="/blog/hello-world/"></mikaana-votes>
+//! <mikaana-comments slug="/blog/hello-world/"></mikaana-comments>
+//! <mikaana-forum></mikaana-forum>
+//! <mikaana-site-stats></mikaana-site-stats>
+//! <mikaana-recent-activity limit="5"></mikaana-recent-activity>
+//! ```
+//!
+//! `slug` defaults to the page path when omitted. `vote-mode` (`up-only` or
+//! `disabled`) restricts voting beyond what the server allows, and
+//! `paging="infinite"` makes comment and thread lists load more as they
+//! scroll instead of showing Prev/Next. Each element mounts once,
+//! the first time it is connected to the document; comments wait until they
+//! scroll into view unless the element has `data-eager`.
+
+use wasm_bindgen::prelude::*;
+use web_sys::HtmlElement;
+
+#[wasm_bindgen(inline_js = r#"
+export function define_element(name, mount) {
+    if (customElements.get(name)) return;
+    customElements.define(name, class extends HTMLElement {
+        connectedCallback() {
+            if (this.mikaanaMounted) return;
+            this.mikaanaMounted = true;
+            mount(this);
+        }
+    });
+}
+"#)]
+extern "C" {
+    fn define_element(name: &str, mount: &Closure<dyn Fn(HtmlElement)>);
+}
+
+fn define(name: &str, mount: fn(HtmlElement)) {
+    let callback = Closure::<dyn Fn(HtmlElement)>::new(mount);
+    define_element(name, &callback);
+    // Elements can connect at any time, so the callback lives for the page
+    callback.forget();
+}
+
+/// Register the `<mikaana-*>` elements for the widgets in this bundle.
+pub fn define_all() {
+    #[cfg(feature = "comments")]
+    define("mikaana-comments", |el| {
+        crate::lazy::when_visible(el, crate::mount_comments)
+    });
+    #[cfg(feature = "votes")]
+    define("mikaana-votes", crate::mount_votes);
+    #[cfg(feature = "forum")]
+    define("mikaana-forum", crate::mount_forum);
+    #[cfg(feature = "site-stats")]
+    define("mikaana-site-stats", |el| {
+        crate::lazy::when_visible(el, crate::mount_site_stats)
+    });
+    #[cfg(feature = "recent-activity")]
+    define("mikaana-recent-activity", |el| {
+        crate::lazy::when_visible(el, crate::mount_recent_activity)
+    });
+}
@@ -1,25 +1,208 @@
+use std::collections::HashMap;
+
 use leptos::prelude::*;
 use mikaana_shared::*;
 use wasm_bindgen_futures::spawn_local;
 
-use crate::api;
+use crate::{activity, analytics, api, config};
 use crate::auth::{AuthState, LoginButton};
-use crate::votes::VoteButton;
+use crate::blocks::{BlockButton, BlockedPlaceholder};
+use crate::bookmarks::{provide_bookmarks, BookmarkButton};
+use crate::captcha::{Captcha, CaptchaChallenge};
+use crate::editor::{
+    post_error, AttachmentList, AttachmentPicker, AutosizeTextarea, ClampedBody, CharCounter, CollapseToggle,
+    Cooldown, DraftSaver, UserBadges, HELD_NOTICE, within_limit,
+};
+use crate::lazy::{self, LoadMore, Paging};
+use crate::link_previews::LinkPreviews;
+use crate::skeleton::{ThreadDetailSkeleton, ThreadListSkeleton};
+use crate::time;
+use crate::votes::{VoteButton, VoteTarget, VoteVariant};
 
 #[derive(Clone, Debug)]
 enum ForumPage {
     Categories,
     Threads { cat_slug: String },
-    Thread { id: i64 },
+    Thread { id: ThreadId },
+    Feed,
+    Bookmarks,
+    Inbox,
+    Conversation { user_id: UserId },
+}
+
+impl ForumPage {
+    /// Page for the part of the path below the forum's base.
+    fn parse(rest: &str) -> Self {
+        let rest = rest.trim_end_matches('/');
+        if let Some(id) = rest.strip_prefix("thread/").and_then(|id| id.parse().ok()) {
+            ForumPage::Thread { id }
+        } else if rest == "feed" {
+            ForumPage::Feed
+        } else if rest == "bookmarks" {
+            ForumPage::Bookmarks
+        } else if rest == "messages" {
+            ForumPage::Inbox
+        } else if let Some(user_id) = rest.strip_prefix("messages/").and_then(|id| id.parse().ok()) {
+            ForumPage::Conversation { user_id }
+        } else if let Some(slug) = rest.strip_prefix("category/").filter(|s| !s.is_empty()) {
+            ForumPage::Threads {
+                cat_slug: slug.to_string(),
+            }
+        } else {
+            ForumPage::Categories
+        }
+    }
+
+    fn path(&self) -> String {
+        match self {
+            ForumPage::Categories => String::new(),
+            ForumPage::Threads { cat_slug } => format!("category/{cat_slug}"),
+            ForumPage::Thread { id } => format!("thread/{id}"),
+            ForumPage::Feed => "feed/".to_string(),
+            ForumPage::Bookmarks => "bookmarks/".to_string(),
+            ForumPage::Inbox => "messages/".to_string(),
+            ForumPage::Conversation { user_id } => format!("messages/{user_id}"),
+        }
+    }
+}
+
+/// Path the forum is mounted at (`/discuss/`), shared with the pages for
+/// building permalinks.
+#[derive(Clone)]
+struct ForumBase(String);
+
+impl ForumBase {
+    fn from_path(pathname: &str) -> Self {
+        for marker in ["/thread/", "/category/", "/feed/", "/bookmarks/", "/messages/"] {
+            if let Some(i) = pathname.find(marker) {
+                return ForumBase(pathname[..=i].to_string());
+            }
+        }
+        let base = if pathname.ends_with('/') {
+            pathname.to_string()
+        } else {
+            format!("{pathname}/")
+        };
+        ForumBase(base)
+    }
+
+    fn page(&self, pathname: &str) -> ForumPage {
+        ForumPage::parse(pathname.get(self.0.len()..).unwrap_or_default())
+    }
+
+    fn url(&self, page: &ForumPage) -> String {
+        format!("{}{}", self.0, page.path())
+    }
+}
+
+fn current_path() -> String {
+    window().location().pathname().unwrap_or_default()
+}
+
+/// Times to check whether a page going back has loaded enough to scroll to
+/// where it was left, and the pause between checks.
+const RESTORE_ATTEMPTS: u32 = 30;
+const RESTORE_RETRY: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// Scroll to `y` once the page is tall enough to, checking again while its
+/// content loads; gives up and scrolls as far as it can after `attempts`.
+fn restore_scroll(y: f64, attempts: u32) {
+    let win = window();
+    let height = document()
+        .document_element()
+        .map(|el| el.scroll_height() as f64)
+        .unwrap_or_default();
+    let viewport = win.inner_height().ok().and_then(|h| h.as_f64()).unwrap_or_default();
+    if height - viewport >= y || attempts == 0 {
+        win.scroll_to_with_x_and_y(0.0, y);
+    } else {
+        set_timeout(move || restore_scroll(y, attempts - 1), RESTORE_RETRY);
+    }
 }
 
 /// Top-level forum SPA — mounted on /discuss/*.
+///
+/// Pages live at `/discuss/category/{slug}`, `/discuss/thread/{id}`,
+/// `/discuss/feed/`, `/discuss/bookmarks/` and `/discuss/messages/{user_id}`,
+/// so deep links need the host to serve the forum page for every path under
+/// `/discuss/`.
 #[component]
 pub fn ForumApp() -> impl IntoView {
-    let page = RwSignal::new(ForumPage::Categories);
+    let base = ForumBase::from_path(&current_path());
+    let page = RwSignal::new(base.page(&current_path()));
+    provide_context(base.clone());
+    provide_bookmarks();
+    let following = Following(RwSignal::new(Vec::new()));
+    provide_context(following);
+    let auth = expect_context::<AuthState>();
+    Effect::new(move |_| {
+        if auth.user.get().is_none() {
+            following.0.set(Vec::new());
+            return;
+        }
+        spawn_local(async move {
+            if let Ok(users) = api::get::<Vec<User>>(routes::users::FOLLOWS).await {
+                following.0.set(users);
+            }
+        });
+    });
+
+    // Where each page visited this session was scrolled to, by path, so
+    // going back returns there. The forum does this itself, since the
+    // browser would restore before the page's content has loaded.
+    let scroll_positions = StoredValue::new(HashMap::<String, f64>::new());
+    if let Ok(history) = window().history() {
+        let _ = history.set_scroll_restoration(web_sys::ScrollRestoration::Manual);
+    }
+    let scroll = window_event_listener(leptos::ev::scroll, move |_| {
+        let y = window().scroll_y().unwrap_or_default();
+        scroll_positions.update_value(|positions| {
+            positions.insert(current_path(), y);
+        });
+    });
+    on_cleanup(move || scroll.remove());
+    let root = NodeRef::<leptos::html::Div>::new();
+
+    // Keep the address bar in step with the page so it can be shared
+    Effect::new({
+        let base = base.clone();
+        move |prev: Option<()>| {
+            let url = base.url(&page.get());
+            if url == current_path() {
+                return;
+            }
+            if let Ok(history) = window().history() {
+                let state = wasm_bindgen::JsValue::NULL;
+                // The first run only tidies the URL the forum was opened at
+                let _ = if prev.is_some() {
+                    history.push_state_with_url(&state, "", Some(&url))
+                } else {
+                    history.replace_state_with_url(&state, "", Some(&url))
+                };
+            }
+            // A new page starts at the top of the forum
+            if prev.is_some() {
+                if let Some(el) = root.get_untracked() {
+                    if el.get_bounding_client_rect().top() < 0.0 {
+                        el.scroll_into_view();
+                    }
+                }
+            }
+        }
+    });
+    let popstate = window_event_listener(leptos::ev::popstate, move |_| {
+        let path = current_path();
+        let y = scroll_positions.with_value(|positions| positions.get(&path).copied());
+        page.set(base.page(&path));
+        if let Some(y) = y {
+            // After the page has rendered
+            request_animation_frame(move || restore_scroll(y, RESTORE_ATTEMPTS));
+        }
+    });
+    on_cleanup(move || popstate.remove());
 
     view! {
-        <div class="mikaana-forum">
+        <div class="mikaana-forum" node_ref=root>
             <div style="display:flex;align-items:center;gap:1rem;margin-bottom:1rem">
                 <h2 style="margin:0">
                     <a href="javascript:void(0)"
@@ -27,17 +210,204 @@ pub fn ForumApp() -> impl IntoView {
                         style="text-decoration:none;color:inherit"
                     >"Discuss"</a>
                 </h2>
+                <SearchBox nav=page />
+                <MemberLinks nav=page />
                 <LoginButton />
             </div>
             {move || match page.get() {
                 ForumPage::Categories => view! { <CategoryList nav=page /> }.into_any(),
                 ForumPage::Threads { cat_slug } => view! { <ThreadList cat_slug=cat_slug nav=page /> }.into_any(),
                 ForumPage::Thread { id } => view! { <ThreadView thread_id=id nav=page /> }.into_any(),
+                ForumPage::Feed => view! { <FeedView nav=page /> }.into_any(),
+                ForumPage::Bookmarks => view! { <BookmarkList nav=page /> }.into_any(),
+                ForumPage::Inbox => view! { <InboxView nav=page /> }.into_any(),
+                ForumPage::Conversation { user_id } => {
+                    view! { <ConversationView user_id=user_id nav=page /> }.into_any()
+                }
             }}
         </div>
     }
 }
 
+/// "Feed", "Bookmarks" and "Messages" links in the forum header, for
+/// signed-in users.
+#[component]
+fn MemberLinks(nav: RwSignal<ForumPage>) -> impl IntoView {
+    let auth = expect_context::<AuthState>();
+
+    view! {
+        <Show when=move || auth.user.get().is_some()>
+            <a class="mikaana-btn mikaana-btn-sm" href="javascript:void(0)" on:click=move |_| nav.set(ForumPage::Feed)>
+                "Feed"
+            </a>
+            <a class="mikaana-btn mikaana-btn-sm" href="javascript:void(0)" on:click=move |_| nav.set(ForumPage::Bookmarks)>
+                "Bookmarks"
+            </a>
+            <a class="mikaana-btn mikaana-btn-sm" href="javascript:void(0)" on:click=move |_| nav.set(ForumPage::Inbox)>
+                "Messages"
+            </a>
+        </Show>
+    }
+}
+
+// ── Search ──
+
+/// Pause in typing before searching.
+const SEARCH_DELAY: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// Shortest query searched for.
+const MIN_QUERY_LEN: usize = 2;
+
+fn searchable(query: &str) -> bool {
+    query.trim().chars().count() >= MIN_QUERY_LEN
+}
+
+/// Search field in the forum header. Results update as the query is typed,
+/// with the matching words highlighted; picking one opens the thread at the
+/// matching reply.
+#[component]
+fn SearchBox(nav: RwSignal<ForumPage>) -> impl IntoView {
+    let base = StoredValue::new(expect_context::<ForumBase>());
+    let query = RwSignal::new(String::new());
+    let results: RwSignal<Vec<SearchResult>> = RwSignal::new(Vec::new());
+    let pending = RwSignal::new(false);
+    let open = RwSignal::new(false);
+    let selected = RwSignal::new(0usize);
+    // Bumped per keystroke, so only the latest search is shown
+    let generation = StoredValue::new(0u32);
+
+    let on_input = move |ev| {
+        let q = event_target_value(&ev);
+        query.set(q.clone());
+        open.set(true);
+        generation.update_value(|g| *g += 1);
+        if !searchable(&q) {
+            pending.set(false);
+            results.set(Vec::new());
+            return;
+        }
+        pending.set(true);
+        let current = generation.get_value();
+        set_timeout(
+            move || {
+                if generation.try_get_value() != Some(current) {
+                    return;
+                }
+                spawn_local(async move {
+                    let path = routes::forum::search(&q);
+                    let found = api::get::<Vec<SearchResult>>(&path).await.unwrap_or_default();
+                    if generation.try_get_value() != Some(current) {
+                        return;
+                    }
+                    selected.set(0);
+                    results.set(found);
+                    pending.set(false);
+                });
+            },
+            SEARCH_DELAY,
+        );
+    };
+
+    let pick = move |result: &SearchResult| {
+        let page = ForumPage::Thread { id: result.thread_id };
+        if let Some(reply_id) = result.reply_id {
+            // With the fragment in the address first, the thread view finds
+            // the reply as it would from a permalink
+            let url = format!("{}#reply-{reply_id}", base.with_value(|b| b.url(&page)));
+            if let Ok(history) = window().history() {
+                let _ = history.push_state_with_url(&wasm_bindgen::JsValue::NULL, "", Some(&url));
+            }
+        }
+        open.set(false);
+        nav.set(page);
+    };
+
+    let on_keydown = move |ev: leptos::ev::KeyboardEvent| {
+        let count = results.with_untracked(Vec::len);
+        match ev.key().as_str() {
+            "ArrowDown" if count > 0 => selected.update(|i| *i = (*i + 1) % count),
+            "ArrowUp" if count > 0 => selected.update(|i| *i = (*i + count - 1) % count),
+            "Enter" => {
+                let result = results.with_untracked(|r| r.get(selected.get_untracked()).cloned());
+                if let Some(result) = result {
+                    pick(&result);
+                }
+            }
+            "Escape" => open.set(false),
+            _ => return,
+        }
+        ev.prevent_default();
+    };
+
+    view! {
+        <div class="mikaana-search">
+            <input
+                type="search"
+                class="mikaana-input mikaana-search-input"
+                placeholder="Search the forum"
+                aria-label="Search the forum"
+                role="combobox"
+                aria-expanded=move || open.get().to_string()
+                prop:value=move || query.get()
+                on:input=on_input
+                on:keydown=on_keydown
+                on:focus=move |_| open.set(true)
+                on:blur=move |_| open.set(false)
+            />
+            <Show when=move || open.get() && query.with(|q| searchable(q))>
+                <ul class="mikaana-search-results" role="listbox">
+                    {move || {
+                        if results.with(Vec::is_empty) {
+                            let text = if pending.get() { "Searching..." } else { "No matches" };
+                            return view! { <li class="mikaana-hint">{text}</li> }.into_any();
+                        }
+                        results
+                            .get()
+                            .into_iter()
+                            .enumerate()
+                            .map(|(i, result)| {
+                                let excerpt = result
+                                    .excerpt
+                                    .iter()
+                                    .map(|part| {
+                                        if part.matched {
+                                            view! { <mark>{part.text.clone()}</mark> }.into_any()
+                                        } else {
+                                            part.text.clone().into_any()
+                                        }
+                                    })
+                                    .collect_view();
+                                let is_reply = result.reply_id.is_some();
+                                let title = result.title.clone();
+                                // mousedown, so the field's blur doesn't close the list first
+                                let on_pick = move |ev: leptos::ev::MouseEvent| {
+                                    ev.prevent_default();
+                                    pick(&result);
+                                };
+                                view! {
+                                    <li
+                                        role="option"
+                                        aria-selected=move || (selected.get() == i).to_string()
+                                        class:mikaana-search-selected=move || selected.get() == i
+                                        on:mousedown=on_pick
+                                    >
+                                        <span class="mikaana-search-title">
+                                            {title}
+                                            {is_reply.then(|| view! { <span class="mikaana-search-kind">"reply"</span> })}
+                                        </span>
+                                        <span class="mikaana-search-excerpt">{excerpt}</span>
+                                    </li>
+                                }
+                            })
+                            .collect_view()
+                            .into_any()
+                    }}
+                </ul>
+            </Show>
+        </div>
+    }
+}
+
 // ── Categories ──
 
 #[component]
@@ -46,7 +416,7 @@ fn CategoryList(nav: RwSignal<ForumPage>) -> impl IntoView {
     let loading = RwSignal::new(true);
 
     spawn_local(async move {
-        if let Ok(c) = api::get::<Vec<ForumCategory>>("/api/forum/categories").await {
+        if let Ok(c) = api::get::<Vec<ForumCategory>>(routes::forum::CATEGORIES).await {
             cats.set(c);
         }
         loading.set(false);
@@ -73,6 +443,7 @@ fn CategoryList(nav: RwSignal<ForumPage>) -> impl IntoView {
                             >
                                 <h4>{cat.name.clone()}</h4>
                                 <p>{cat.description.clone()}</p>
+                                <FollowButton slug=cat.slug.clone() subscribed=cat.subscribed />
                             </a>
                         }
                     }
@@ -82,6 +453,48 @@ fn CategoryList(nav: RwSignal<ForumPage>) -> impl IntoView {
     }
 }
 
+/// Follow / unfollow toggle for a category card. Hidden when logged out.
+#[component]
+fn FollowButton(slug: String, subscribed: bool) -> impl IntoView {
+    let auth = expect_context::<AuthState>();
+    let following = RwSignal::new(subscribed);
+    let pending = RwSignal::new(false);
+
+    let on_click = move |ev: leptos::ev::MouseEvent| {
+        // The button sits inside the card link
+        ev.stop_propagation();
+        ev.prevent_default();
+        let follow = !following.get_untracked();
+        let path = routes::forum::subscribe(&slug);
+        pending.set(true);
+        spawn_local(async move {
+            let result = if follow {
+                api::post_empty(&path, &()).await
+            } else {
+                api::delete(&path).await
+            };
+            if result.is_ok() {
+                following.set(follow);
+            }
+            pending.set(false);
+        });
+    };
+
+    view! {
+        <Show when=move || auth.user.get().is_some()>
+            <button
+                class="mikaana-btn mikaana-btn-sm mikaana-follow-btn"
+                class:active=move || following.get()
+                disabled=move || pending.get()
+                title="Get notified about new threads in this category"
+                on:click=on_click.clone()
+            >
+                {move || if following.get() { "Following" } else { "Follow" }}
+            </button>
+        </Show>
+    }
+}
+
 // ── Threads in a category ──
 
 #[component]
@@ -90,17 +503,44 @@ fn ThreadList(cat_slug: String, nav: RwSignal<ForumPage>) -> impl IntoView {
     let loading = RwSignal::new(true);
     let page = RwSignal::new(1i64);
     let total = RwSignal::new(0i64);
+    let sort = RwSignal::new(ThreadSort::default());
     let show_form = RwSignal::new(false);
+    let category: RwSignal<Option<ForumCategory>> = RwSignal::new(None);
     let cat_slug_signal = RwSignal::new(cat_slug);
+    let infinite = lazy::paging() == Paging::Infinite;
+
+    {
+        let url = routes::forum::category(&cat_slug_signal.get_untracked());
+        spawn_local(async move {
+            if let Ok(c) = api::get::<ForumCategory>(&url).await {
+                category.set(Some(c));
+            }
+        });
+    }
+    let read_only = move || category.get().is_some_and(|c| c.read_only);
+    let reloading = move || loading.get() && !(infinite && page.get() > 1);
 
     Effect::new(move |_| {
         let slug = cat_slug_signal.get();
         let p = page.get();
+        let s = sort.get();
         loading.set(true);
         spawn_local(async move {
-            let url = format!("/api/forum/threads?category={}&page={}", slug, p);
+            let url = routes::forum::threads(&slug, p, s);
             if let Ok(result) = api::get::<Paginated<Thread>>(&url).await {
-                threads.set(result.items);
+                if infinite && p > 1 {
+                    // Skip threads pushed down onto this page by new ones
+                    threads.update(|list| {
+                        let fresh: Vec<Thread> = result
+                            .items
+                            .into_iter()
+                            .filter(|t| !list.iter().any(|seen| seen.id == t.id))
+                            .collect();
+                        list.extend(fresh);
+                    });
+                } else {
+                    threads.set(result.items);
+                }
                 total.set(result.total);
             }
             loading.set(false);
@@ -109,57 +549,128 @@ fn ThreadList(cat_slug: String, nav: RwSignal<ForumPage>) -> impl IntoView {
 
     view! {
         <section class="mikaana-threads">
-            <h3>{move || format!("Threads in {}", cat_slug_signal.get())}</h3>
-            <button class="mikaana-btn" on:click=move |_| show_form.update(|v| *v = !*v)>
-                {move || if show_form.get() { "Cancel" } else { "New Thread" }}
-            </button>
+            <nav class="mikaana-breadcrumbs">
+                <a href="javascript:void(0)" on:click=move |_| nav.set(ForumPage::Categories)>"Discuss"</a>
+                " \u{203A} "
+                <span>{move || category.get().map(|c| c.name).unwrap_or_else(|| cat_slug_signal.get())}</span>
+            </nav>
+            {move || category.get().map(|c| view! {
+                <header class="mikaana-category-header">
+                    <div>
+                        <h3>{c.name.clone()}</h3>
+                        <p>{c.description.clone()}</p>
+                    </div>
+                    <FollowButton slug=c.slug.clone() subscribed=c.subscribed />
+                </header>
+            })}
+            <div class="mikaana-reply-toolbar">
+                <Show when=move || !read_only()>
+                    <button class="mikaana-btn" on:click=move |_| show_form.update(|v| *v = !*v)>
+                        {move || if show_form.get() { "Cancel" } else { "New Thread" }}
+                    </button>
+                </Show>
+                <div class="mikaana-sort-toggle">
+                    {ThreadSort::ALL
+                        .into_iter()
+                        .map(|s| {
+                            view! {
+                                <button
+                                    class="mikaana-btn mikaana-btn-sm"
+                                    class:active=move || sort.get() == s
+                                    on:click=move |_| {
+                                        page.set(1);
+                                        sort.set(s);
+                                    }
+                                >
+                                    {thread_sort_label(s)}
+                                </button>
+                            }
+                        })
+                        .collect_view()}
+                </div>
+            </div>
             <Show when=move || show_form.get()>
                 <NewThreadForm cat_slug=cat_slug_signal.get_untracked() threads=threads show_form=show_form />
             </Show>
-            <Show when=move || loading.get()>
-                <p class="mikaana-loading">"Loading..."</p>
+            // Stands in for the list while a page loads; appended pages keep it
+            <Show when=reloading>
+                <ThreadListSkeleton count=5 />
             </Show>
-            <div class="mikaana-thread-list">
+            <div class="mikaana-thread-list" prop:hidden=reloading>
                 <For
                     each=move || threads.get()
                     key=|t| t.id
                     let:thread
                 >
                     {
-                        let id = thread.id;
+                        // Stubs left by a move open the thread where it went
+                        let id = thread.moved_to.unwrap_or(thread.id);
+                        let moved = thread.moved_to.is_some();
                         view! {
                             <a class="mikaana-thread-card"
+                                class:mikaana-thread-moved=moved
                                 href="javascript:void(0)"
                                 on:click=move |_| nav.set(ForumPage::Thread { id })
                             >
-                                <div class="mikaana-thread-title">{thread.title.clone()}</div>
-                                <div class="mikaana-thread-meta">
-                                    <span>{thread.user.username.clone()}</span>
-                                    <time>{thread.created_at.clone()}</time>
-                                    <span>{format!("{} replies", thread.reply_count)}</span>
+                                <div class="mikaana-thread-title">
+                                    {moved.then(|| view! { <span class="mikaana-moved-badge">"Moved: "</span> })}
+                                    {thread.pinned.then(|| view! { <span class="mikaana-pinned-badge">"Pinned: "</span> })}
+                                    {thread.title.clone()}
+                                    {thread.has_unread.then(|| view! {
+                                        " " <span class="mikaana-new-badge">"new"</span>
+                                    })}
+                                    {thread.solution_reply_id.is_some().then(|| view! {
+                                        " " <span class="mikaana-solved-badge">"\u{2713} Solved"</span>
+                                    })}
                                 </div>
+                                {(!moved).then(|| view! {
+                                    <div class="mikaana-thread-meta">
+                                        <span>{thread.user.name().to_string()}</span>
+                                        <time datetime=thread.created_at.clone()>{time::ago(&thread.created_at)}</time>
+                                        <span>{format!("{} replies", thread.reply_count)}</span>
+                                        {thread.last_reply_at.clone().zip(thread.last_reply_user.clone()).map(|(at, user)| view! {
+                                            <span class="mikaana-last-reply">
+                                                {format!("last reply by {}, {}", user.name(), time::ago(&at))}
+                                            </span>
+                                        })}
+                                    </div>
+                                })}
                             </a>
                         }
                     }
                 </For>
             </div>
-            <div class="mikaana-pagination">
-                <button
-                    class="mikaana-btn mikaana-btn-sm"
-                    disabled=move || page.get() <= 1
-                    on:click=move |_| page.update(|p| *p -= 1)
-                >
-                    "Prev"
-                </button>
-                <span>{move || format!("Page {}", page.get())}</span>
-                <button
-                    class="mikaana-btn mikaana-btn-sm"
-                    disabled=move || { page.get() * 20 >= total.get() }
-                    on:click=move |_| page.update(|p| *p += 1)
-                >
-                    "Next"
-                </button>
-            </div>
+            {if infinite {
+                view! {
+                    <LoadMore
+                        more=Signal::derive(move || page.get() * 20 < total.get())
+                        loading=loading
+                        load=move || page.update(|p| *p += 1)
+                    />
+                }
+                .into_any()
+            } else {
+                view! {
+                    <div class="mikaana-pagination">
+                        <button
+                            class="mikaana-btn mikaana-btn-sm"
+                            disabled=move || page.get() <= 1
+                            on:click=move |_| page.update(|p| *p -= 1)
+                        >
+                            "Prev"
+                        </button>
+                        <span>{move || format!("Page {}", page.get())}</span>
+                        <button
+                            class="mikaana-btn mikaana-btn-sm"
+                            disabled=move || { page.get() * 20 >= total.get() }
+                            on:click=move |_| page.update(|p| *p += 1)
+                        >
+                            "Next"
+                        </button>
+                    </div>
+                }
+                .into_any()
+            }}
         </section>
     }
 }
@@ -174,7 +685,18 @@ fn NewThreadForm(
     let auth = expect_context::<AuthState>();
     let title = RwSignal::new(String::new());
     let body = RwSignal::new(String::new());
+    let limits = config::limits();
+    let attachments: RwSignal<Vec<Attachment>> = RwSignal::new(Vec::new());
     let submitting = RwSignal::new(false);
+    let notice: RwSignal<Option<String>> = RwSignal::new(None);
+    let captcha = Captcha::default();
+    let cooldown = Cooldown::new();
+    let key = RwSignal::new(api::idempotency_key());
+    let draft = DraftSaver::new(format!("thread:{cat_slug}"), Some(title), body);
+    let too_long = move || {
+        let limits = limits.get();
+        !title.with(|t| within_limit(t, limits.title)) || !body.with(|b| within_limit(b, limits.body))
+    };
 
     let on_submit = {
         let cat_slug = cat_slug.clone();
@@ -188,16 +710,35 @@ fn NewThreadForm(
                 category_slug: cat_slug.clone(),
                 title: title.get_untracked(),
                 body: body.get_untracked(),
+                attachment_ids: attachments.get_untracked().iter().map(|a| a.id).collect(),
+                captcha_token: captcha.token(),
+                publish_at: None,
+                pin: false,
             };
+            notice.set(None);
             spawn_local(async move {
-                match api::post::<Thread, _>("/api/forum/threads", &payload).await {
+                let result =
+                    api::post_once::<Thread, _>(routes::forum::THREADS, &payload, &key.get_untracked())
+                        .await;
+                captcha.after_post(&result);
+                match result {
                     Ok(t) => {
-                        threads.update(|list| list.insert(0, t));
+                        analytics::track(AnalyticsEvent::ThreadCreated);
+                        // Before `show_form` unmounts the form and its draft
+                        draft.clear();
+                        if t.pending {
+                            notice.set(Some(HELD_NOTICE.to_string()));
+                        } else {
+                            threads.update(|list| list.insert(0, t));
+                            show_form.set(false);
+                        }
                         title.set(String::new());
                         body.set(String::new());
-                        show_form.set(false);
+                        attachments.set(Vec::new());
+                        key.set(api::idempotency_key());
                     }
-                    Err(_) => {}
+                    Err(e) if cooldown.start(&e) => {}
+                    Err(e) => notice.set(Some(post_error(&e))),
                 }
                 submitting.set(false);
             });
@@ -213,15 +754,19 @@ fn NewThreadForm(
                 prop:value=move || title.get()
                 on:input=move |ev| title.set(event_target_value(&ev))
             />
-            <textarea
-                class="mikaana-textarea"
-                placeholder="Write your post..."
-                prop:value=move || body.get()
-                on:input=move |ev| body.set(event_target_value(&ev))
-            />
-            <button class="mikaana-btn" type="submit" disabled=move || submitting.get()>
+            <CharCounter value=title max=Signal::derive(move || limits.get().title) />
+            <AutosizeTextarea value=body placeholder="Write your post..." />
+            <AttachmentPicker attachments=attachments />
+            <CaptchaChallenge captcha=captcha />
+            <button
+                class="mikaana-btn"
+                type="submit"
+                disabled=move || submitting.get() || too_long() || cooldown.active()
+            >
                 {move || if submitting.get() { "Posting..." } else { "Create Thread" }}
             </button>
+            {move || notice.get().map(|n| view! { <p class="mikaana-hint">{n}</p> })}
+            {move || cooldown.message().map(|m| view! { <p class="mikaana-hint mikaana-cooldown">{m}</p> })}
         </form>
     }
 }
@@ -229,74 +774,364 @@ fn NewThreadForm(
 // ── Thread detail + replies ──
 
 #[component]
-fn ThreadView(thread_id: i64, #[allow(unused)] nav: RwSignal<ForumPage>) -> impl IntoView {
+fn ThreadView(thread_id: ThreadId, nav: RwSignal<ForumPage>) -> impl IntoView {
     let thread: RwSignal<Option<Thread>> = RwSignal::new(None);
     let replies: RwSignal<Vec<Reply>> = RwSignal::new(Vec::new());
     let loading = RwSignal::new(true);
+    let can_reply = RwSignal::new(true);
+    let vote_disabled: RwSignal<Option<String>> = RwSignal::new(None);
+    let disabled_reason: RwSignal<Option<String>> = RwSignal::new(None);
+    let solution: RwSignal<Option<ReplyId>> = RwSignal::new(None);
+    let can_mark_solution = RwSignal::new(false);
+    let sort = RwSignal::new(load_reply_sort());
+    // Bumped to fetch the thread again, after (un)blocking someone
+    let reload = RwSignal::new(0u32);
+    let last_read_at: RwSignal<Option<String>> = RwSignal::new(None);
+    // Reply named by a `#reply-{id}` permalink
+    let linked_reply = linked_reply();
+    let scrolled = StoredValue::new(false);
+
+    let is_unread = move |reply: &Reply| {
+        last_read_at.with(|seen| seen.as_ref().is_some_and(|seen| reply.created_at > *seen))
+    };
+    // Oldest reply the viewer hasn't seen, whatever the sort order
+    let first_unread = Memo::new(move |_| {
+        replies.with(|list| {
+            list.iter()
+                .filter(|r| is_unread(r))
+                .min_by(|a, b| a.created_at.cmp(&b.created_at).then(a.id.cmp(&b.id)))
+                .map(|r| r.id)
+        })
+    });
 
     let tid = thread_id;
-    spawn_local(async move {
-        #[derive(serde::Deserialize)]
-        struct ThreadDetail {
-            thread: Thread,
-            replies: Vec<Reply>,
-        }
-        if let Ok(detail) = api::get::<ThreadDetail>(&format!("/api/forum/threads/{}", tid)).await {
-            thread.set(Some(detail.thread));
-            replies.set(detail.replies);
-        }
-        loading.set(false);
+    Effect::new(move |_| {
+        let s = sort.get();
+        reload.track();
+        spawn_local(async move {
+            let url = routes::forum::thread(tid, s);
+            if let Ok(detail) = api::get::<ThreadDetail>(&url).await {
+                // Old links to a moved thread's stub
+                if let Some(id) = detail.thread.moved_to {
+                    nav.set(ForumPage::Thread { id });
+                    return;
+                }
+                can_reply.set(detail.can_reply);
+                if !detail.can_vote {
+                    vote_disabled.set(detail.disabled_reason.clone());
+                }
+                disabled_reason.set(detail.disabled_reason);
+                can_mark_solution.set(detail.can_mark_solution);
+                // Only the first load; re-sorting has already marked it read
+                if !scrolled.get_value() {
+                    last_read_at.set(detail.last_read_at);
+                }
+                solution.set(detail.thread.solution_reply_id);
+                thread.set(Some(detail.thread));
+                replies.set(detail.replies);
+                if !scrolled.get_value() {
+                    scrolled.set_value(true);
+                    analytics::track(AnalyticsEvent::ThreadViewed);
+                    // A permalink's reply, else where a returning reader left off
+                    if let Some(id) = linked_reply.or_else(|| first_unread.get_untracked()) {
+                        // After the reply list has rendered
+                        request_animation_frame(move || scroll_to_reply(id));
+                    }
+                }
+            }
+            loading.set(false);
+        });
     });
 
+    let set_sort = move |s: ReplySort| {
+        save_reply_sort(s);
+        sort.set(s);
+    };
+
     view! {
         <section class="mikaana-thread-view">
-            <Show when=move || loading.get()>
-                <p class="mikaana-loading">"Loading..."</p>
+            <Show when=move || loading.get() && thread.with(Option::is_none)>
+                <ThreadDetailSkeleton />
             </Show>
+            <ThreadSummaryBox thread_id=thread_id />
             {move || {
                 thread.get().map(|t| {
                     view! {
                         <article class="mikaana-thread-detail">
-                            <h3>{t.title.clone()}</h3>
+                            <div class="mikaana-thread-heading">
+                                <VoteButton
+                                    target=VoteTarget::thread(t.id)
+                                    initial_count=0
+                                    disabled_reason=vote_disabled.get_untracked()
+                                    variant=VoteVariant::Stacked
+                                />
+                                <h3>{t.title.clone()}</h3>
+                            </div>
                             <div class="mikaana-thread-meta">
                                 <img src={t.user.avatar_url.clone()} alt="" class="mikaana-avatar" width="24" height="24" />
-                                <strong>{t.user.username.clone()}</strong>
+                                <strong>{t.user.name().to_string()}</strong>
+                                <UserBadges badges=t.user.badges.clone() />
                                 <time>{t.created_at.clone()}</time>
+                                <FollowUserButton user=t.user.clone() />
+                                <BookmarkButton target_type="thread" id=t.id.0 />
                             </div>
                             <div class="mikaana-thread-body">{t.body.clone()}</div>
+                            <LinkPreviews body=t.body.clone() />
+                            <AttachmentList attachments=t.attachments.clone() />
                         </article>
                     }
                 })
             }}
-            <h4>{move || format!("Replies ({})", replies.get().len())}</h4>
+            <div class="mikaana-reply-toolbar">
+                <h4>{move || format!("Replies ({})", replies.get().len())}</h4>
+                {move || first_unread.get().map(|id| view! {
+                    <button class="mikaana-btn mikaana-btn-sm" on:click=move |_| scroll_to_reply(id)>
+                        "Jump to first unread"
+                    </button>
+                })}
+                <div class="mikaana-sort-toggle">
+                    {ReplySort::ALL
+                        .into_iter()
+                        .map(|s| {
+                            view! {
+                                <button
+                                    class="mikaana-btn mikaana-btn-sm"
+                                    class:active=move || sort.get() == s
+                                    on:click=move |_| set_sort(s)
+                                >
+                                    {sort_label(s)}
+                                </button>
+                            }
+                        })
+                        .collect_view()}
+                </div>
+            </div>
             <div class="mikaana-reply-list">
                 <For
                     each=move || replies.get()
-                    key=|r| r.id
+                    key=|r| (r.id, r.blocked)
                     let:reply
                 >
-                    <div class="mikaana-reply">
-                        <div class="mikaana-reply-header">
-                            <img src={reply.user.avatar_url.clone()} alt="" class="mikaana-avatar" width="24" height="24" />
-                            <strong>{reply.user.username.clone()}</strong>
-                            <time>{reply.created_at.clone()}</time>
-                        </div>
-                        <p>{reply.body.clone()}</p>
-                        <VoteButton target_type="reply".to_string() target_id=reply.id initial_count=reply.vote_count />
-                    </div>
+                    {
+                        if reply.blocked {
+                            return view! {
+                                <div id=format!("reply-{}", reply.id) class="mikaana-reply">
+                                    <BlockedPlaceholder user_id=reply.user.id reload=reload />
+                                </div>
+                            }
+                            .into_any();
+                        }
+                        let collapsed = RwSignal::new(false);
+                        view! {
+                            <div
+                                id=format!("reply-{}", reply.id)
+                                class="mikaana-reply"
+                                class:mikaana-collapsed=move || collapsed.get()
+                                class:mikaana-reply-solution=move || solution.get() == Some(reply.id)
+                                class:mikaana-reply-linked=linked_reply == Some(reply.id)
+                            >
+                                <div class="mikaana-reply-header">
+                                    <CollapseToggle collapsed=collapsed />
+                                    <img src={reply.user.avatar_url.clone()} alt="" class="mikaana-avatar" width="24" height="24" />
+                                    <strong>{reply.user.name().to_string()}</strong>
+                                    <UserBadges badges=reply.user.badges.clone() />
+                                    <time>{reply.created_at.clone()}</time>
+                                    {is_unread(&reply).then(|| view! { <span class="mikaana-new-badge">"new"</span> })}
+                                    <Show when=move || solution.get() == Some(reply.id)>
+                                        <span class="mikaana-solved-badge" title="Accepted answer">"\u{2713} Solution"</span>
+                                    </Show>
+                                    <BlockButton user_id=reply.user.id reload=reload />
+                                </div>
+                                <ClampedBody
+                                    body=reply.body.clone()
+                                    length=reply.body_length
+                                    class="mikaana-reply-body"
+                                />
+                                <LinkPreviews body=reply.body.clone() />
+                                <AttachmentList attachments=reply.attachments.clone() />
+                                <div class="mikaana-reply-actions">
+                                    <VoteButton
+                                        target=VoteTarget::reply(reply.id)
+                                        initial_count=reply.vote_count
+                                        disabled_reason=vote_disabled.get_untracked()
+                                    />
+                                    <PermalinkButton thread_id=thread_id reply_id=reply.id />
+                                    <BookmarkButton target_type="reply" id=reply.id.0 />
+                                    <Show when=move || can_mark_solution.get()>
+                                        <SolutionButton thread_id=thread_id reply_id=reply.id solution=solution />
+                                    </Show>
+                                </div>
+                            </div>
+                        }
+                        .into_any()
+                    }
                 </For>
             </div>
-            <ReplyForm thread_id=thread_id replies=replies />
+            <Show
+                when=move || can_reply.get()
+                fallback=move || view! {
+                    <p class="mikaana-hint mikaana-disabled-notice">
+                        {move || disabled_reason.get().unwrap_or_default()}
+                    </p>
+                }
+            >
+                <ReplyForm thread_id=thread_id replies=replies />
+            </Show>
         </section>
     }
 }
 
+/// Reply id from a `#reply-{id}` fragment in the page URL.
+fn linked_reply() -> Option<ReplyId> {
+    window()
+        .location()
+        .hash()
+        .ok()?
+        .strip_prefix("#reply-")?
+        .parse()
+        .ok()
+}
+
+fn scroll_to_reply(id: ReplyId) {
+    if let Some(el) = document().get_element_by_id(&format!("reply-{id}")) {
+        el.scroll_into_view();
+    }
+}
+
+/// Copies the reply's permalink (`/discuss/thread/42#reply-137`).
+#[component]
+fn PermalinkButton(thread_id: ThreadId, reply_id: ReplyId) -> impl IntoView {
+    let base = expect_context::<ForumBase>();
+    let copied = RwSignal::new(false);
+
+    let on_click = move |_| {
+        let origin = window().location().origin().unwrap_or_default();
+        let url = format!(
+            "{origin}{}#reply-{reply_id}",
+            base.url(&ForumPage::Thread { id: thread_id })
+        );
+        let _ = window().navigator().clipboard().write_text(&url);
+        copied.set(true);
+        set_timeout(
+            move || {
+                let _ = copied.try_set(false);
+            },
+            std::time::Duration::from_secs(2),
+        );
+    };
+
+    view! {
+        <button
+            class="mikaana-btn mikaana-btn-sm mikaana-permalink"
+            title="Copy a link to this reply"
+            on:click=on_click
+        >
+            {move || if copied.get() { "Copied" } else { "Link" }}
+        </button>
+    }
+}
+
+/// Accept a reply as the thread's answer, or withdraw the acceptance.
+#[component]
+fn SolutionButton(thread_id: ThreadId, reply_id: ReplyId, solution: RwSignal<Option<ReplyId>>) -> impl IntoView {
+    let pending = RwSignal::new(false);
+    let accepted = move || solution.get() == Some(reply_id);
+
+    let on_click = move |_| {
+        let payload = SetSolution {
+            reply_id: (solution.get_untracked() != Some(reply_id)).then_some(reply_id),
+        };
+        pending.set(true);
+        spawn_local(async move {
+            let path = routes::forum::solution(thread_id);
+            if let Ok(t) = api::post::<Thread, _>(&path, &payload).await {
+                solution.set(t.solution_reply_id);
+            }
+            pending.set(false);
+        });
+    };
+
+    view! {
+        <button
+            class="mikaana-btn mikaana-btn-sm"
+            disabled=move || pending.get()
+            on:click=on_click
+        >
+            {move || if accepted() { "Unmark solution" } else { "Mark as solution" }}
+        </button>
+    }
+}
+
+/// Collapsible "summary so far", shown only when the server provides one.
+#[component]
+fn ThreadSummaryBox(thread_id: ThreadId) -> impl IntoView {
+    let summary: RwSignal<Option<ThreadSummary>> = RwSignal::new(None);
+
+    spawn_local(async move {
+        let url = routes::forum::summary(thread_id);
+        if let Ok(s) = api::get::<Option<ThreadSummary>>(&url).await {
+            summary.set(s);
+        }
+    });
+
+    move || {
+        summary.get().map(|s| {
+            view! {
+                <details class="mikaana-thread-summary">
+                    <summary>{format!("Summary of the first {} replies", s.reply_count)}</summary>
+                    <p>{s.summary}</p>
+                </details>
+            }
+        })
+    }
+}
+
+fn thread_sort_label(sort: ThreadSort) -> &'static str {
+    match sort {
+        ThreadSort::Activity => "Active",
+        ThreadSort::Newest => "Newest",
+    }
+}
+
+fn sort_label(sort: ReplySort) -> &'static str {
+    match sort {
+        ReplySort::Oldest => "Oldest",
+        ReplySort::Newest => "Newest",
+        ReplySort::Top => "Top",
+    }
+}
+
+const REPLY_SORT_KEY: &str = "mikaana_reply_sort";
+
+/// The reader's preferred reply order, remembered across visits.
+fn load_reply_sort() -> ReplySort {
+    web_sys::window()
+        .and_then(|w| w.local_storage().ok().flatten())
+        .and_then(|s| s.get_item(REPLY_SORT_KEY).ok().flatten())
+        .and_then(|v| ReplySort::parse(&v))
+        .unwrap_or_default()
+}
+
+fn save_reply_sort(sort: ReplySort) {
+    if let Some(storage) = web_sys::window().and_then(|w| w.local_storage().ok().flatten()) {
+        let _ = storage.set_item(REPLY_SORT_KEY, sort.as_str());
+    }
+}
+
 /// Reply form.
 #[component]
-fn ReplyForm(thread_id: i64, replies: RwSignal<Vec<Reply>>) -> impl IntoView {
+fn ReplyForm(thread_id: ThreadId, replies: RwSignal<Vec<Reply>>) -> impl IntoView {
     let auth = expect_context::<AuthState>();
     let body = RwSignal::new(String::new());
+    let attachments: RwSignal<Vec<Attachment>> = RwSignal::new(Vec::new());
     let submitting = RwSignal::new(false);
+    let notice: RwSignal<Option<String>> = RwSignal::new(None);
+    let captcha = Captcha::default();
+    let cooldown = Cooldown::new();
+    let key = RwSignal::new(api::idempotency_key());
+    let draft = DraftSaver::new(format!("reply:{thread_id}"), None, body);
+    let limits = config::limits();
+    let too_long = move || !body.with(|b| within_limit(b, limits.get().body));
 
     let on_submit = move |ev: leptos::ev::SubmitEvent| {
         ev.prevent_default();
@@ -306,20 +1141,30 @@ fn ReplyForm(thread_id: i64, replies: RwSignal<Vec<Reply>>) -> impl IntoView {
         submitting.set(true);
         let payload = CreateReply {
             body: body.get_untracked(),
+            attachment_ids: attachments.get_untracked().iter().map(|a| a.id).collect(),
+            captcha_token: captcha.token(),
         };
         let tid = thread_id;
+        notice.set(None);
         spawn_local(async move {
-            match api::post::<Reply, _>(
-                &format!("/api/forum/threads/{}/replies", tid),
-                &payload,
-            )
-            .await
-            {
+            let path = routes::forum::replies(tid);
+            let result = api::post_once::<Reply, _>(&path, &payload, &key.get_untracked()).await;
+            captcha.after_post(&result);
+            match result {
                 Ok(r) => {
-                    replies.update(|list| list.push(r));
+                    analytics::track(AnalyticsEvent::ReplyPosted);
+                    if r.pending {
+                        notice.set(Some(HELD_NOTICE.to_string()));
+                    } else {
+                        replies.update(|list| list.push(r));
+                    }
+                    draft.clear();
                     body.set(String::new());
+                    attachments.set(Vec::new());
+                    key.set(api::idempotency_key());
                 }
-                Err(_) => {}
+                Err(e) if cooldown.start(&e) => {}
+                Err(e) => notice.set(Some(post_error(&e))),
             }
             submitting.set(false);
         });
@@ -328,16 +1173,19 @@ fn ReplyForm(thread_id: i64, replies: RwSignal<Vec<Reply>>) -> impl IntoView {
     move || {
         if auth.user.get().is_some() {
             view! {
-                <form class="mikaana-reply-form" on:submit=on_submit.clone()>
-                    <textarea
-                        class="mikaana-textarea"
-                        placeholder="Write a reply..."
-                        prop:value=move || body.get()
-                        on:input=move |ev| body.set(event_target_value(&ev))
-                    />
-                    <button class="mikaana-btn" type="submit" disabled=move || submitting.get()>
+                <form class="mikaana-reply-form" on:submit=on_submit>
+                    <AutosizeTextarea value=body placeholder="Write a reply..." />
+                    <AttachmentPicker attachments=attachments />
+                    <CaptchaChallenge captcha=captcha />
+                    <button
+                        class="mikaana-btn"
+                        type="submit"
+                        disabled=move || submitting.get() || too_long() || cooldown.active()
+                    >
                         {move || if submitting.get() { "Replying..." } else { "Reply" }}
                     </button>
+                    {move || notice.get().map(|n| view! { <p class="mikaana-hint">{n}</p> })}
+                    {move || cooldown.message().map(|m| view! { <p class="mikaana-hint mikaana-cooldown">{m}</p> })}
                 </form>
             }
             .into_any()
@@ -346,3 +1194,465 @@ fn ReplyForm(thread_id: i64, replies: RwSignal<Vec<Reply>>) -> impl IntoView {
         }
     }
 }
+
+// ── Follows ──
+
+/// Users the viewer follows, shared by every follow button on the page.
+#[derive(Clone, Copy)]
+struct Following(RwSignal<Vec<User>>);
+
+/// Follow / unfollow toggle for a user. Hidden when logged out and on the
+/// viewer's own posts.
+#[component]
+fn FollowUserButton(user: User) -> impl IntoView {
+    let auth = expect_context::<AuthState>();
+    let Following(following) = expect_context::<Following>();
+    let pending = RwSignal::new(false);
+    let user_id = user.id;
+    let is_following = move || following.with(|list| list.iter().any(|u| u.id == user_id));
+
+    let on_click = move |_| {
+        let follow = !is_following();
+        let path = routes::users::follow(user_id);
+        let user = user.clone();
+        pending.set(true);
+        spawn_local(async move {
+            let result = if follow {
+                api::post_empty(&path, &()).await
+            } else {
+                api::delete(&path).await
+            };
+            if result.is_ok() {
+                following.update(|list| {
+                    list.retain(|u| u.id != user_id);
+                    if follow {
+                        list.push(user);
+                    }
+                });
+            }
+            pending.set(false);
+        });
+    };
+
+    view! {
+        <Show when=move || auth.user.get().is_some_and(|me| me.id != user_id)>
+            <button
+                class="mikaana-btn mikaana-btn-sm mikaana-follow-btn"
+                class:active=is_following
+                disabled=move || pending.get()
+                title="See what this user posts in your feed"
+                on:click=on_click.clone()
+            >
+                {move || if is_following() { "Following" } else { "Follow" }}
+            </button>
+        </Show>
+    }
+}
+
+/// What followed users posted lately.
+#[component]
+fn FeedView(nav: RwSignal<ForumPage>) -> impl IntoView {
+    let auth = expect_context::<AuthState>();
+    let Following(following) = expect_context::<Following>();
+    let items: RwSignal<Vec<ActivityItem>> = RwSignal::new(Vec::new());
+    let next: RwSignal<Option<String>> = RwSignal::new(None);
+    let loading = RwSignal::new(true);
+
+    let load = move |before: Option<String>| {
+        loading.set(true);
+        spawn_local(async move {
+            let url = routes::users::feed(before.as_deref());
+            if let Ok(page) = api::get::<PaginatedCursor<ActivityItem>>(&url).await {
+                items.update(|list| list.extend(page.items));
+                next.set(page.next);
+            }
+            loading.set(false);
+        });
+    };
+    Effect::new(move |_| {
+        if auth.user.get().is_some() {
+            items.set(Vec::new());
+            load(None);
+        }
+    });
+
+    view! {
+        <section class="mikaana-feed">
+            <nav class="mikaana-breadcrumbs">
+                <a href="javascript:void(0)" on:click=move |_| nav.set(ForumPage::Categories)>"Discuss"</a>
+                " \u{203A} "
+                <span>"Feed"</span>
+            </nav>
+            <Show
+                when=move || auth.user.get().is_some()
+                fallback=|| view! { <p class="mikaana-hint">"Log in to see posts from people you follow."</p> }
+            >
+                <p class="mikaana-following">
+                    {move || if following.with(|f| f.is_empty()) {
+                        "You don't follow anyone yet. Use the Follow button on a thread to add its author.".into_any()
+                    } else {
+                        view! {
+                            "Following: "
+                            {following
+                                .get()
+                                .into_iter()
+                                .map(|u| view! { <span>{u.name().to_string()} " " <FollowUserButton user=u /></span> " " })
+                                .collect_view()}
+                        }.into_any()
+                    }}
+                </p>
+                <Show when=move || !loading.get() && items.with(|i| i.is_empty())>
+                    <p class="mikaana-hint">"Nothing new from the people you follow."</p>
+                </Show>
+                <div class="mikaana-thread-list">
+                    <For
+                        each=move || items.get()
+                        key=|item| (item.kind.clone(), item.id)
+                        let:item
+                    >
+                        <ActivityCard item=item nav=nav />
+                    </For>
+                </div>
+                <Show when=move || loading.get()>
+                    <p class="mikaana-loading">"Loading..."</p>
+                </Show>
+                <Show when=move || next.get().is_some()>
+                    <button
+                        class="mikaana-btn mikaana-btn-sm"
+                        disabled=move || loading.get()
+                        on:click=move |_| load(next.get_untracked())
+                    >
+                        "Older"
+                    </button>
+                </Show>
+            </Show>
+        </section>
+    }
+}
+
+/// A feed or bookmark entry. Threads and replies open in the forum;
+/// comments link to their post.
+#[component]
+fn ActivityCard(item: ActivityItem, nav: RwSignal<ForumPage>) -> impl IntoView {
+    let thread_id = item.thread_id;
+
+    view! {
+        <a class="mikaana-thread-card"
+            href=activity::link(&item)
+            on:click=move |ev| {
+                if let Some(id) = thread_id {
+                    ev.prevent_default();
+                    nav.set(ForumPage::Thread { id });
+                }
+            }
+        >
+            <div class="mikaana-thread-title">
+                <img src={item.user.avatar_url.clone()} alt="" class="mikaana-avatar" width="20" height="20" />
+                " " {activity::describe(&item)}
+            </div>
+            <div class="mikaana-thread-meta">
+                <span>{item.excerpt.clone()}</span>
+                <time datetime=item.created_at.clone()>{time::ago(&item.created_at)}</time>
+            </div>
+        </a>
+    }
+}
+
+// ── Bookmarks ──
+
+#[component]
+fn BookmarkList(nav: RwSignal<ForumPage>) -> impl IntoView {
+    let auth = expect_context::<AuthState>();
+    let bookmarks: RwSignal<Vec<Bookmark>> = RwSignal::new(Vec::new());
+    let loading = RwSignal::new(true);
+
+    Effect::new(move |_| {
+        if auth.user.get().is_none() {
+            return;
+        }
+        spawn_local(async move {
+            if let Ok(list) = api::get::<Vec<Bookmark>>(routes::bookmarks::LIST).await {
+                bookmarks.set(list);
+            }
+            loading.set(false);
+        });
+    });
+
+    view! {
+        <section class="mikaana-bookmarks">
+            <nav class="mikaana-breadcrumbs">
+                <a href="javascript:void(0)" on:click=move |_| nav.set(ForumPage::Categories)>"Discuss"</a>
+                " \u{203A} "
+                <span>"Bookmarks"</span>
+            </nav>
+            <Show
+                when=move || auth.user.get().is_some()
+                fallback=|| view! { <p class="mikaana-hint">"Log in to see your bookmarks."</p> }
+            >
+                <Show when=move || loading.get()>
+                    <p class="mikaana-loading">"Loading..."</p>
+                </Show>
+                <Show when=move || !loading.get() && bookmarks.with(|b| b.is_empty())>
+                    <p class="mikaana-hint">"Nothing bookmarked yet. Use the \u{2606} on a thread, reply or comment to save it here."</p>
+                </Show>
+                <div class="mikaana-thread-list">
+                    <For
+                        each=move || bookmarks.get()
+                        key=|b| (b.item.kind.clone(), b.item.id)
+                        let:bookmark
+                    >
+                        <ActivityCard item=bookmark.item nav=nav />
+                    </For>
+                </div>
+            </Show>
+        </section>
+    }
+}
+
+// ── Private messages ──
+
+#[component]
+fn InboxView(nav: RwSignal<ForumPage>) -> impl IntoView {
+    let auth = expect_context::<AuthState>();
+    let inbox = RwSignal::new(Inbox::default());
+    let loading = RwSignal::new(true);
+
+    Effect::new(move |_| {
+        if auth.user.get().is_none() {
+            return;
+        }
+        spawn_local(async move {
+            if let Ok(i) = api::get::<Inbox>(routes::messages::INBOX).await {
+                inbox.set(i);
+            }
+            loading.set(false);
+        });
+    });
+
+    view! {
+        <section class="mikaana-inbox">
+            <nav class="mikaana-breadcrumbs">
+                <a href="javascript:void(0)" on:click=move |_| nav.set(ForumPage::Categories)>"Discuss"</a>
+                " \u{203A} "
+                <span>"Messages"</span>
+            </nav>
+            <Show
+                when=move || auth.user.get().is_some()
+                fallback=|| view! { <p class="mikaana-hint">"Log in to read your messages."</p> }
+            >
+                <NewMessageForm nav=nav />
+                <Show when=move || loading.get()>
+                    <p class="mikaana-loading">"Loading..."</p>
+                </Show>
+                <Show when=move || !loading.get() && inbox.with(|i| i.conversations.is_empty())>
+                    <p class="mikaana-hint">"No messages yet."</p>
+                </Show>
+                <div class="mikaana-thread-list">
+                    <For
+                        each=move || inbox.get().conversations
+                        key=|c| (c.with.id, c.last_message.id, c.unread)
+                        let:conv
+                    >
+                        {
+                            let user_id = conv.with.id;
+                            let preview: String = conv.last_message.body.chars().take(120).collect();
+                            view! {
+                                <a class="mikaana-thread-card"
+                                    href="javascript:void(0)"
+                                    on:click=move |_| nav.set(ForumPage::Conversation { user_id })
+                                >
+                                    <div class="mikaana-thread-title">
+                                        <img src={conv.with.avatar_url.clone()} alt="" class="mikaana-avatar" width="20" height="20" />
+                                        " " {conv.with.name().to_string()}
+                                        {(conv.unread > 0).then(|| view! {
+                                            " " <span class="mikaana-new-badge">{format!("{} new", conv.unread)}</span>
+                                        })}
+                                    </div>
+                                    <div class="mikaana-thread-meta">
+                                        <span>{preview}</span>
+                                        <time datetime=conv.last_message.created_at.clone()>
+                                            {time::ago(&conv.last_message.created_at)}
+                                        </time>
+                                    </div>
+                                </a>
+                            }
+                        }
+                    </For>
+                </div>
+            </Show>
+        </section>
+    }
+}
+
+/// Start a conversation with anyone by username.
+#[component]
+fn NewMessageForm(nav: RwSignal<ForumPage>) -> impl IntoView {
+    let to = RwSignal::new(String::new());
+    let body = RwSignal::new(String::new());
+    let sending = RwSignal::new(false);
+    let notice: RwSignal<Option<String>> = RwSignal::new(None);
+    let limits = config::limits();
+    let too_long = move || !body.with(|b| within_limit(b, limits.get().body));
+
+    let on_submit = move |ev: leptos::ev::SubmitEvent| {
+        ev.prevent_default();
+        let payload = SendMessage {
+            to: to.get_untracked().trim().trim_start_matches('@').to_string(),
+            body: body.get_untracked(),
+        };
+        if payload.to.is_empty() {
+            return;
+        }
+        sending.set(true);
+        notice.set(None);
+        spawn_local(async move {
+            match api::post::<Message, _>(routes::messages::INBOX, &payload).await {
+                Ok(m) => nav.set(ForumPage::Conversation { user_id: m.recipient.id }),
+                Err(e) => notice.set(Some(post_error(&e))),
+            }
+            sending.set(false);
+        });
+    };
+
+    view! {
+        <details class="mikaana-new-message">
+            <summary class="mikaana-btn mikaana-btn-sm">"New message"</summary>
+            <form class="mikaana-reply-form" on:submit=on_submit>
+                <input
+                    class="mikaana-input"
+                    type="text"
+                    placeholder="Username"
+                    prop:value=move || to.get()
+                    on:input=move |ev| to.set(event_target_value(&ev))
+                />
+                <AutosizeTextarea value=body placeholder="Write a message..." />
+                <button class="mikaana-btn" type="submit" disabled=move || sending.get() || too_long()>
+                    {move || if sending.get() { "Sending..." } else { "Send" }}
+                </button>
+                {move || notice.get().map(|n| view! { <p class="mikaana-hint">{n}</p> })}
+            </form>
+        </details>
+    }
+}
+
+#[component]
+fn ConversationView(user_id: UserId, nav: RwSignal<ForumPage>) -> impl IntoView {
+    let auth = expect_context::<AuthState>();
+    // Oldest first, as displayed
+    let messages: RwSignal<Vec<Message>> = RwSignal::new(Vec::new());
+    let next: RwSignal<Option<String>> = RwSignal::new(None);
+    let loading = RwSignal::new(true);
+    let body = RwSignal::new(String::new());
+    let sending = RwSignal::new(false);
+    let notice: RwSignal<Option<String>> = RwSignal::new(None);
+    let limits = config::limits();
+    let too_long = move || !body.with(|b| within_limit(b, limits.get().body));
+
+    let load = move |before: Option<String>| {
+        loading.set(true);
+        spawn_local(async move {
+            let url = routes::messages::conversation(user_id, before.as_deref());
+            if let Ok(page) = api::get::<PaginatedCursor<Message>>(&url).await {
+                messages.update(|list| {
+                    let mut older: Vec<Message> = page.items.into_iter().rev().collect();
+                    older.append(list);
+                    *list = older;
+                });
+                next.set(page.next);
+            }
+            loading.set(false);
+        });
+    };
+    Effect::new(move |_| {
+        if auth.user.get().is_some() {
+            messages.set(Vec::new());
+            load(None);
+        }
+    });
+
+    // The other side, as named on any message
+    let other = move || {
+        messages.with(|list| {
+            list.first().map(|m| {
+                if m.sender.id == user_id {
+                    m.sender.clone()
+                } else {
+                    m.recipient.clone()
+                }
+            })
+        })
+    };
+
+    let on_submit = move |ev: leptos::ev::SubmitEvent| {
+        ev.prevent_default();
+        let Some(to) = other() else {
+            return;
+        };
+        let payload = SendMessage {
+            to: to.username,
+            body: body.get_untracked(),
+        };
+        sending.set(true);
+        notice.set(None);
+        spawn_local(async move {
+            match api::post::<Message, _>(routes::messages::INBOX, &payload).await {
+                Ok(m) => {
+                    messages.update(|list| list.push(m));
+                    body.set(String::new());
+                }
+                Err(e) => notice.set(Some(post_error(&e))),
+            }
+            sending.set(false);
+        });
+    };
+
+    view! {
+        <section class="mikaana-conversation">
+            <nav class="mikaana-breadcrumbs">
+                <a href="javascript:void(0)" on:click=move |_| nav.set(ForumPage::Inbox)>"Messages"</a>
+                " \u{203A} "
+                <span>{move || other().map(|u| u.name().to_string()).unwrap_or_default()}</span>
+            </nav>
+            <Show when=move || next.get().is_some()>
+                <button
+                    class="mikaana-btn mikaana-btn-sm"
+                    disabled=move || loading.get()
+                    on:click=move |_| load(next.get_untracked())
+                >
+                    "Older messages"
+                </button>
+            </Show>
+            <Show when=move || loading.get()>
+                <p class="mikaana-loading">"Loading..."</p>
+            </Show>
+            <Show when=move || !loading.get() && messages.with(|m| m.is_empty())>
+                <p class="mikaana-hint">"No messages with this user yet."</p>
+            </Show>
+            <div class="mikaana-reply-list">
+                <For
+                    each=move || messages.get()
+                    key=|m| m.id
+                    let:message
+                >
+                    <div class="mikaana-reply" class:mikaana-message-own=message.sender.id != user_id>
+                        <div class="mikaana-reply-header">
+                            <img src={message.sender.avatar_url.clone()} alt="" class="mikaana-avatar" width="24" height="24" />
+                            <strong>{message.sender.name().to_string()}</strong>
+                            <time datetime=message.created_at.clone()>{time::ago(&message.created_at)}</time>
+                        </div>
+                        <p class="mikaana-reply-body">{message.body.clone()}</p>
+                    </div>
+                </For>
+            </div>
+            <Show when=move || other().is_some()>
+                <form class="mikaana-reply-form" on:submit=on_submit>
+                    <AutosizeTextarea value=body placeholder="Write a message..." />
+                    <button class="mikaana-btn" type="submit" disabled=move || sending.get() || too_long()>
+                        {move || if sending.get() { "Sending..." } else { "Send" }}
+                    </button>
+                    {move || notice.get().map(|n| view! { <p class="mikaana-hint">{n}</p> })}
+                </form>
+            </Show>
+        </section>
+    }
+}
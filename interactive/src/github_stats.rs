@@ -1,18 +1,21 @@
 use leptos::prelude::*;
-use mikaana_shared::GitHubStats;
+use mikaana_shared::{routes, GitHubStats};
 use wasm_bindgen_futures::spawn_local;
 
 use crate::api;
 
+/// Languages named in the breakdown when no single `language` is given.
+const TOP_LANGUAGES: usize = 3;
+
+/// Live stats for `repo` (`owner/name`). With `language`, shows that
+/// language's line count; otherwise the total and a per-language breakdown.
 #[component]
-pub fn RepoStats(repo: String) -> impl IntoView {
+pub fn RepoStats(repo: String, language: Option<String>) -> impl IntoView {
     let stats: RwSignal<Option<GitHubStats>> = RwSignal::new(None);
+    let href = format!("https://github.com/{repo}");
 
     spawn_local(async move {
-        let url = format!(
-            "/api/github-stats?repo={}",
-            web_sys::js_sys::encode_uri_component(&repo)
-        );
+        let url = routes::github_stats(&repo);
         if let Ok(s) = api::get::<GitHubStats>(&url).await {
             stats.set(Some(s));
         }
@@ -20,14 +23,29 @@ pub fn RepoStats(repo: String) -> impl IntoView {
 
     move || {
         stats.get().map(|s| {
-            let lines = format_lines(s.lines_of_code);
+            let lines = match &language {
+                Some(lang) => {
+                    let count = s
+                        .languages
+                        .iter()
+                        .find(|l| l.name.eq_ignore_ascii_case(lang))
+                        .map_or(0, |l| l.lines);
+                    format!(" | ~{} lines of {lang}", format_lines(count))
+                }
+                None => format!(
+                    " | ~{} lines{}",
+                    format_lines(s.lines_of_code),
+                    language_breakdown(&s)
+                ),
+            };
+            let crates = (s.crate_count > 0)
+                .then(|| format!(" | {} workspace crates", s.crate_count));
             let commits = format_number(s.commits);
             view! {
                 <span class="mikaana-repo-stats">
-                    <a href={format!("https://github.com/{}", "girivs82/skalp")}
-                       target="_blank" rel="noopener">"GitHub"</a>
-                    " | ~" {lines} " lines of Rust"
-                    " | " {s.crate_count.to_string()} " workspace crates"
+                    <a href=href.clone() target="_blank" rel="noopener">"GitHub"</a>
+                    {lines}
+                    {crates}
                     " | " {commits} " commits"
                 </span>
             }
@@ -35,6 +53,21 @@ pub fn RepoStats(repo: String) -> impl IntoView {
     }
 }
 
+/// ` (Rust 82%, Python 12%, Shell 6%)`, by share of bytes.
+fn language_breakdown(stats: &GitHubStats) -> String {
+    let total: i64 = stats.languages.iter().map(|l| l.bytes).sum();
+    if total == 0 {
+        return String::new();
+    }
+    let parts: Vec<String> = stats
+        .languages
+        .iter()
+        .take(TOP_LANGUAGES)
+        .map(|l| format!("{} {}%", l.name, l.bytes * 100 / total))
+        .collect();
+    format!(" ({})", parts.join(", "))
+}
+
 fn format_lines(lines: i64) -> String {
     if lines >= 1000 {
         format!("{}K", lines / 1000)
@@ -0,0 +1,132 @@
+use leptos::html;
+use leptos::prelude::*;
+use wasm_bindgen::prelude::*;
+use web_sys::js_sys::Array;
+use web_sys::{HtmlElement, IntersectionObserver, IntersectionObserverEntry, IntersectionObserverInit};
+
+/// How far outside the viewport a widget may be before it starts mounting,
+/// so content is usually ready by the time the reader reaches it.
+const ROOT_MARGIN: &str = "200px";
+
+/// Call `mount` once `el` scrolls near the viewport, deferring the widget's
+/// rendering and API calls until they are needed. Elements marked
+/// `data-eager` (above-the-fold placements) mount immediately, as does
+/// everything on browsers without `IntersectionObserver`.
+pub fn when_visible(el: HtmlElement, mount: fn(HtmlElement)) {
+    if el.has_attribute("data-eager") {
+        mount(el);
+        return;
+    }
+
+    let mut pending = Some(el.clone());
+    let callback = Closure::<dyn FnMut(Array, IntersectionObserver)>::new(
+        move |entries: Array, observer: IntersectionObserver| {
+            let visible = entries
+                .iter()
+                .any(|e| e.unchecked_into::<IntersectionObserverEntry>().is_intersecting());
+            if visible {
+                observer.disconnect();
+                if let Some(target) = pending.take() {
+                    mount(target);
+                }
+            }
+        },
+    );
+
+    let init = IntersectionObserverInit::new();
+    init.set_root_margin(ROOT_MARGIN);
+
+    match IntersectionObserver::new_with_options(callback.as_ref().unchecked_ref(), &init) {
+        Ok(observer) => {
+            observer.observe(&el);
+            callback.forget();
+        }
+        Err(_) => mount(el),
+    }
+}
+
+/// How a widget's lists get past their first page: `data-paging` (or
+/// `paging`) on the mount point, `pages` or `infinite`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Paging {
+    /// Prev/Next buttons, one page at a time.
+    #[default]
+    Pages,
+    /// Further pages are appended as the end of the list scrolls into view.
+    Infinite,
+}
+
+impl Paging {
+    pub fn parse(s: &str) -> Option<Paging> {
+        match s {
+            "pages" => Some(Paging::Pages),
+            "infinite" => Some(Paging::Infinite),
+            _ => None,
+        }
+    }
+}
+
+/// The widget's `Paging`, as provided at mount.
+pub fn paging() -> Paging {
+    use_context::<Paging>().unwrap_or_default()
+}
+
+/// "Load more" button at the end of an infinite list, shown while `more`.
+/// Besides working as a button, it calls `load` whenever it comes near the
+/// viewport, and again after each load if it's still in view.
+#[component]
+pub fn LoadMore(
+    #[prop(into)] more: Signal<bool>,
+    #[prop(into)] loading: Signal<bool>,
+    load: impl Fn() + Copy + Send + Sync + 'static,
+) -> impl IntoView {
+    let node = NodeRef::<html::Button>::new();
+    let observer: StoredValue<Option<IntersectionObserver>, LocalStorage> = StoredValue::new_local(None);
+
+    let callback = Closure::<dyn FnMut(Array)>::new(move |entries: Array| {
+        let visible = entries
+            .iter()
+            .any(|e| e.unchecked_into::<IntersectionObserverEntry>().is_intersecting());
+        // try_: a last callback may arrive after the list is gone
+        if visible && more.try_get_untracked() == Some(true) && loading.try_get_untracked() == Some(false) {
+            load();
+        }
+    });
+    let init = IntersectionObserverInit::new();
+    init.set_root_margin(ROOT_MARGIN);
+    if let Ok(o) = IntersectionObserver::new_with_options(callback.as_ref().unchecked_ref(), &init) {
+        observer.set_value(Some(o));
+    }
+    callback.forget();
+
+    // Observing again reports the current intersection, so a page that
+    // didn't fill the screen is followed by the next
+    Effect::new(move |_| {
+        let idle = more.get() && !loading.get();
+        let (Some(el), Some(o)) = (node.get(), observer.get_value()) else {
+            return;
+        };
+        o.unobserve(&el);
+        if idle {
+            o.observe(&el);
+        }
+    });
+    on_cleanup(move || {
+        if let Some(o) = observer.try_get_value().flatten() {
+            o.disconnect();
+        }
+    });
+
+    view! {
+        <Show when=move || more.get()>
+            <button
+                class="mikaana-btn mikaana-btn-sm mikaana-load-more"
+                node_ref=node
+                disabled=move || loading.get()
+                on:click=move |_| load()
+            >
+                {move || if loading.get() { "Loading..." } else { "Load more" }}
+            </button>
+        </Show>
+    }
+}
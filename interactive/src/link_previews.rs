@@ -0,0 +1,81 @@
+//! Cards for the leading links of a comment, thread or reply body, from
+//! the server's cache of linked pages (`GET /api/unfurl`).
+
+use leptos::prelude::*;
+use mikaana_shared::{routes, bare_urls, LinkPreview};
+use wasm_bindgen_futures::spawn_local;
+
+use crate::{api, config};
+
+/// The first `max` distinct http(s) links in `body`, normalized the way the
+/// server stores them.
+fn preview_urls(body: &str, max: usize) -> Vec<web_sys::Url> {
+    let mut urls: Vec<web_sys::Url> = Vec::new();
+    for link in bare_urls(body) {
+        if urls.len() == max {
+            break;
+        }
+        let Ok(url) = web_sys::Url::new(&link) else {
+            continue;
+        };
+        if matches!(url.protocol().as_str(), "http:" | "https:")
+            && !urls.iter().any(|u| u.href() == url.href())
+        {
+            urls.push(url);
+        }
+    }
+    urls
+}
+
+/// Nothing for links the server has no preview of (yet), or when previews
+/// are off.
+#[component]
+pub fn LinkPreviews(body: String) -> impl IntoView {
+    // Position in the body, the link, and its preview; filled as they arrive
+    let previews: RwSignal<Vec<(usize, String, String, LinkPreview)>> = RwSignal::new(Vec::new());
+
+    config::with_config(move |config| {
+        for (i, url) in preview_urls(&body, config.link_previews).into_iter().enumerate() {
+            spawn_local(async move {
+                let href = url.href();
+                let path = routes::unfurl(&href);
+                if let Ok(preview) = api::get::<LinkPreview>(&path).await {
+                    let _ = previews.try_update(|list| {
+                        list.push((i, href, url.hostname(), preview));
+                        list.sort_by_key(|(i, ..)| *i);
+                    });
+                }
+            });
+        }
+    });
+
+    view! {
+        <Show when=move || previews.with(|p| !p.is_empty())>
+            <div class="mikaana-link-previews">
+                <For each=move || previews.get() key=|(i, ..)| *i let:item>
+                    {
+                        let (_, href, host, preview) = item;
+                        // Plain http images would be blocked on https pages
+                        let image = preview.image.filter(|src| src.starts_with("https://"));
+                        view! {
+                            <a class="mikaana-link-preview" href=href target="_blank" rel="nofollow ugc noopener">
+                                {image.map(|src| view! {
+                                    <img src=src alt="" loading="lazy" decoding="async" referrerpolicy="no-referrer" />
+                                })}
+                                <span class="mikaana-link-preview-text">
+                                    <strong>{preview.title}</strong>
+                                    {(!preview.description.is_empty()).then(|| view! {
+                                        <span class="mikaana-link-preview-description">{preview.description}</span>
+                                    })}
+                                    <span class="mikaana-link-preview-site">
+                                        {preview.site_name.unwrap_or(host)}
+                                    </span>
+                                </span>
+                            </a>
+                        }
+                    }
+                </For>
+            </div>
+        </Show>
+    }
+}
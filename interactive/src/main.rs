@@ -1,11 +1,68 @@
+// Partial bundles leave some shared helpers (api, auth) unused
+#![cfg_attr(
+    not(all(
+        feature = "comments",
+        feature = "votes",
+        feature = "forum",
+        feature = "github-stats",
+        feature = "site-stats",
+        feature = "recent-activity",
+        feature = "admin"
+    )),
+    allow(dead_code)
+)]
+
+#[cfg(any(feature = "forum", feature = "recent-activity"))]
+mod activity;
+#[cfg(feature = "admin")]
+mod admin;
+#[cfg(any(feature = "comments", feature = "forum", feature = "votes"))]
+mod analytics;
 mod api;
 mod auth;
+#[cfg(any(feature = "comments", feature = "forum"))]
+mod blocks;
+#[cfg(any(feature = "comments", feature = "forum"))]
+mod bookmarks;
+#[cfg(any(feature = "comments", feature = "forum"))]
+mod captcha;
+mod config;
+#[cfg(feature = "comments")]
 mod comments;
+#[cfg(any(feature = "comments", feature = "forum"))]
+mod editor;
+mod elements;
+#[cfg(feature = "forum")]
 mod forum;
+#[cfg(feature = "github-stats")]
+mod github_stats;
+#[cfg(any(
+    feature = "comments",
+    feature = "forum",
+    feature = "github-stats",
+    feature = "site-stats",
+    feature = "recent-activity"
+))]
+mod lazy;
+#[cfg(any(feature = "comments", feature = "forum"))]
+mod link_previews;
+#[cfg(any(feature = "comments", feature = "forum"))]
+mod mentions;
+mod notifications;
+#[cfg(any(feature = "comments", feature = "forum"))]
+mod skeleton;
+#[cfg(feature = "site-stats")]
+mod site_stats;
+mod terms;
+mod time;
+#[cfg(feature = "votes")]
 mod votes;
+#[cfg(feature = "comments")]
+mod webmentions;
 
 use leptos::prelude::*;
 use wasm_bindgen::JsCast;
+use web_sys::HtmlElement;
 
 fn main() {
     console_error_panic_hook::set_once();
@@ -15,38 +72,144 @@ fn main() {
         .document()
         .expect("no document");
 
-    // Mount comment section if the mount point exists
+    terms::listen();
+
+    // Mount comment section once it scrolls into view
+    #[cfg(feature = "comments")]
     if let Some(el) = document.get_element_by_id("mikaana-comments") {
-        let slug = el.get_attribute("data-slug").unwrap_or_default();
-        let html_el: web_sys::HtmlElement = el.unchecked_into();
-        leptos::mount::mount_to(html_el, move || {
-            view! {
-                <auth::AuthProvider>
-                    <comments::CommentSection slug=slug.clone() />
-                </auth::AuthProvider>
-            }
-        })
-        .forget();
+        lazy::when_visible(el.unchecked_into(), mount_comments);
     }
 
     // Mount post-level vote buttons if the mount point exists
+    #[cfg(feature = "votes")]
     if let Some(el) = document.get_element_by_id("mikaana-votes") {
-        let slug = el.get_attribute("data-slug").unwrap_or_default();
-        let html_el: web_sys::HtmlElement = el.unchecked_into();
-        leptos::mount::mount_to(html_el, move || {
-            view! {
-                <auth::AuthProvider>
-                    <votes::PostVotes slug=slug.clone() />
-                </auth::AuthProvider>
-            }
-        })
-        .forget();
+        mount_votes(el.unchecked_into());
     }
 
     // Mount forum SPA if the mount point exists
+    #[cfg(feature = "forum")]
     if let Some(el) = document.get_element_by_id("mikaana-forum") {
-        let html_el: web_sys::HtmlElement = el.unchecked_into();
-        leptos::mount::mount_to(html_el, move || {
+        mount_forum(el.unchecked_into());
+    }
+
+    // Mount live GitHub stats over each shortcode's static fallback
+    #[cfg(feature = "github-stats")]
+    if let Ok(nodes) = document.query_selector_all(".mikaana-github-stats[data-repo]") {
+        for i in 0..nodes.length() {
+            if let Some(el) = nodes.item(i) {
+                lazy::when_visible(el.unchecked_into(), mount_github_stats);
+            }
+        }
+    }
+
+    // Mount community stats for each shortcode
+    #[cfg(feature = "site-stats")]
+    if let Ok(nodes) = document.query_selector_all(".mikaana-site-stats") {
+        for i in 0..nodes.length() {
+            if let Some(el) = nodes.item(i) {
+                lazy::when_visible(el.unchecked_into(), mount_site_stats);
+            }
+        }
+    }
+
+    // Mount recent activity for each sidebar widget
+    #[cfg(feature = "recent-activity")]
+    if let Ok(nodes) = document.query_selector_all(".mikaana-recent-activity") {
+        for i in 0..nodes.length() {
+            if let Some(el) = nodes.item(i) {
+                lazy::when_visible(el.unchecked_into(), mount_recent_activity);
+            }
+        }
+    }
+
+    // Mount moderation dashboard if the mount point exists
+    #[cfg(feature = "admin")]
+    if let Some(el) = document.get_element_by_id("mikaana-admin") {
+        mount_admin(el.unchecked_into());
+    }
+
+    // Register <mikaana-*> custom elements for pages without fixed mount ids
+    elements::define_all();
+}
+
+/// Post slug for a widget: `data-slug` (Hugo partials), `slug` (custom
+/// elements), or the current path as a last resort.
+#[cfg(any(feature = "comments", feature = "votes"))]
+fn widget_slug(el: &HtmlElement) -> String {
+    el.get_attribute("data-slug")
+        .or_else(|| el.get_attribute("slug"))
+        .filter(|s| !s.is_empty())
+        .or_else(|| web_sys::window()?.location().pathname().ok())
+        .unwrap_or_default()
+}
+
+/// Vote mode requested by a widget's `data-vote-mode` (or `vote-mode`)
+/// attribute: `updown`, `up-only` or `disabled`.
+#[cfg(feature = "votes")]
+fn widget_vote_mode(el: &HtmlElement) -> Option<mikaana_shared::VoteMode> {
+    el.get_attribute("data-vote-mode")
+        .or_else(|| el.get_attribute("vote-mode"))
+        .and_then(|m| mikaana_shared::VoteMode::parse(m.trim()))
+}
+
+/// Paging requested by a widget's `data-paging` (or `paging`) attribute:
+/// `pages` or `infinite`.
+#[cfg(any(feature = "comments", feature = "forum"))]
+fn widget_paging(el: &HtmlElement) -> lazy::Paging {
+    el.get_attribute("data-paging")
+        .or_else(|| el.get_attribute("paging"))
+        .and_then(|p| lazy::Paging::parse(p.trim()))
+        .unwrap_or_default()
+}
+
+#[cfg(feature = "comments")]
+pub(crate) fn mount_comments(el: HtmlElement) {
+    let slug = widget_slug(&el);
+    let vote_mode = widget_vote_mode(&el);
+    let paging = widget_paging(&el);
+    // Replace the comments rendered at build time (`/api/comments/html`)
+    el.set_inner_html("");
+    config::apply_branding(el.clone(), true);
+    leptos::mount::mount_to(el, move || {
+        votes::provide_vote_mode(vote_mode);
+        provide_context(paging);
+        view! {
+            <auth::AuthProvider>
+                <comments::CommentSection slug=slug.clone() />
+            </auth::AuthProvider>
+        }
+    })
+    .forget();
+}
+
+#[cfg(feature = "votes")]
+pub(crate) fn mount_votes(el: HtmlElement) {
+    let slug = widget_slug(&el);
+    let vote_mode = widget_vote_mode(&el);
+    config::apply_branding(el.clone(), false);
+    leptos::mount::mount_to(el, move || {
+        votes::provide_vote_mode(vote_mode);
+        view! {
+            <auth::AuthProvider>
+                <votes::PostVotes slug=slug.clone() />
+            </auth::AuthProvider>
+        }
+    })
+    .forget();
+}
+
+#[cfg(feature = "forum")]
+pub(crate) fn mount_forum(el: HtmlElement) {
+    config::with_config(move |config| {
+        if !config.features.forum {
+            return;
+        }
+        let vote_mode = widget_vote_mode(&el);
+        let paging = widget_paging(&el);
+        config::apply_branding(el.clone(), true);
+        leptos::mount::mount_to(el, move || {
+            votes::provide_vote_mode(vote_mode);
+            provide_context(paging);
             view! {
                 <auth::AuthProvider>
                     <forum::ForumApp />
@@ -54,5 +217,62 @@ fn main() {
             }
         })
         .forget();
-    }
+    });
+}
+
+#[cfg(feature = "admin")]
+pub(crate) fn mount_admin(el: HtmlElement) {
+    leptos::mount::mount_to(el, move || {
+        view! {
+            <auth::AuthProvider>
+                <admin::AdminApp />
+            </auth::AuthProvider>
+        }
+    })
+    .forget();
+}
+
+#[cfg(feature = "github-stats")]
+pub(crate) fn mount_github_stats(el: HtmlElement) {
+    config::with_config(move |config| {
+        // Without the proxy, the static numbers are all there is
+        if !config.features.github_stats {
+            return;
+        }
+        let repo = el.get_attribute("data-repo").unwrap_or_default();
+        let language = el.get_attribute("data-language").filter(|l| !l.is_empty());
+        // Replace the static numbers rendered at build time
+        el.set_inner_html("");
+        config::apply_branding(el.clone(), false);
+        leptos::mount::mount_to(el, move || {
+            view! { <github_stats::RepoStats repo=repo.clone() language=language.clone() /> }
+        })
+        .forget();
+    });
+}
+
+#[cfg(feature = "site-stats")]
+pub(crate) fn mount_site_stats(el: HtmlElement) {
+    el.set_inner_html("");
+    config::apply_branding(el.clone(), false);
+    leptos::mount::mount_to(el, move || {
+        view! { <site_stats::SiteStats /> }
+    })
+    .forget();
+}
+
+/// Items shown: `data-limit` (or `limit`), 10 by default.
+#[cfg(feature = "recent-activity")]
+pub(crate) fn mount_recent_activity(el: HtmlElement) {
+    let limit = el
+        .get_attribute("data-limit")
+        .or_else(|| el.get_attribute("limit"))
+        .and_then(|l| l.trim().parse().ok())
+        .unwrap_or(10);
+    el.set_inner_html("");
+    config::apply_branding(el.clone(), false);
+    leptos::mount::mount_to(el, move || {
+        view! { <activity::RecentActivity limit=limit /> }
+    })
+    .forget();
 }
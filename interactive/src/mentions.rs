@@ -0,0 +1,199 @@
+//! `@mention` autocomplete for the editors: typing `@` and the start of a
+//! name lists matching users (`GET /api/users/search`), and picking one
+//! completes their username.
+
+use std::time::Duration;
+
+use leptos::html;
+use leptos::prelude::*;
+use mikaana_shared::{routes, User};
+use wasm_bindgen_futures::spawn_local;
+use web_sys::HtmlTextAreaElement;
+
+use crate::api;
+use crate::editor::byte_offset;
+
+/// Pause in typing before searching.
+const SEARCH_DELAY: Duration = Duration::from_millis(150);
+
+/// Longest GitHub login; the server's `@mention` pattern stops there too.
+const MAX_USERNAME_LEN: usize = 39;
+
+/// The `@partial` name ending at byte `cursor` of `text`, as its byte offset
+/// and the name so far. Follows the server's rules for what counts as a
+/// mention, so e-mail addresses don't trigger it.
+fn mention_at(text: &str, cursor: usize) -> Option<(usize, &str)> {
+    let before = text.get(..cursor)?;
+    let at = before.rfind('@')?;
+    let partial = &before[at + 1..];
+    if partial.is_empty()
+        || partial.len() > MAX_USERNAME_LEN
+        || partial.starts_with('-')
+        || !partial.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    {
+        return None;
+    }
+    match before[..at].chars().next_back() {
+        Some(c) if c.is_alphanumeric() || matches!(c, '_' | '@' | '/' | '.') => None,
+        _ => Some((at, partial)),
+    }
+}
+
+/// Suggestions for the mention being typed in one textarea.
+#[derive(Clone, Copy)]
+pub struct Mentions {
+    /// Byte offset of the `@` being completed.
+    start: RwSignal<Option<usize>>,
+    results: RwSignal<Vec<User>>,
+    selected: RwSignal<usize>,
+    /// Bumped per keystroke, so only the latest search is shown.
+    generation: StoredValue<u32>,
+}
+
+impl Mentions {
+    pub fn new() -> Self {
+        Mentions {
+            start: RwSignal::new(None),
+            results: RwSignal::new(Vec::new()),
+            selected: RwSignal::new(0),
+            generation: StoredValue::new(0),
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        self.results.with(|r| !r.is_empty())
+    }
+
+    pub fn close(&self) {
+        self.generation.update_value(|g| *g += 1);
+        self.start.set(None);
+        self.results.set(Vec::new());
+    }
+
+    /// Look for a mention at the cursor after each input.
+    pub fn on_input(&self, el: &HtmlTextAreaElement) {
+        let text = el.value();
+        let cursor = el
+            .selection_start()
+            .ok()
+            .flatten()
+            .map(|pos| byte_offset(&text, pos))
+            .unwrap_or(text.len());
+        let Some((start, partial)) = mention_at(&text, cursor) else {
+            self.close();
+            return;
+        };
+
+        let this = *self;
+        let query = partial.to_string();
+        this.generation.update_value(|g| *g += 1);
+        let generation = this.generation.get_value();
+        set_timeout(
+            move || {
+                if this.generation.try_get_value() != Some(generation) {
+                    return;
+                }
+                spawn_local(async move {
+                    let path = routes::users::search(&query);
+                    let users = api::get::<Vec<User>>(&path).await.unwrap_or_default();
+                    if this.generation.try_get_value() != Some(generation) {
+                        return;
+                    }
+                    this.start.set(Some(start));
+                    this.selected.set(0);
+                    this.results.set(users);
+                });
+            },
+            SEARCH_DELAY,
+        );
+    }
+
+    /// Arrow keys move through the suggestions, Enter or Tab picks one and
+    /// Escape dismisses them.
+    pub fn on_keydown(&self, ev: &leptos::ev::KeyboardEvent, value: RwSignal<String>, node: NodeRef<html::Textarea>) {
+        if !self.is_open() {
+            return;
+        }
+        let count = self.results.with_untracked(Vec::len);
+        match ev.key().as_str() {
+            "ArrowDown" => self.selected.update(|i| *i = (*i + 1) % count),
+            "ArrowUp" => self.selected.update(|i| *i = (*i + count - 1) % count),
+            "Enter" | "Tab" => {
+                let user = self.results.with_untracked(|r| r.get(self.selected.get_untracked()).cloned());
+                if let Some(user) = user {
+                    self.pick(&user.username, value, node);
+                }
+            }
+            "Escape" => self.close(),
+            _ => return,
+        }
+        ev.prevent_default();
+    }
+
+    /// Replace the partial name with `@username `.
+    fn pick(&self, username: &str, value: RwSignal<String>, node: NodeRef<html::Textarea>) {
+        let Some(start) = self.start.get_untracked() else {
+            return;
+        };
+        let mut text = value.get_untracked();
+        let el = node.get_untracked();
+        let cursor = el
+            .as_ref()
+            .and_then(|el| el.selection_start().ok().flatten())
+            .map(|pos| byte_offset(&text, pos))
+            .unwrap_or(text.len());
+        if start >= cursor || !text.is_char_boundary(start) {
+            self.close();
+            return;
+        }
+        let insert = format!("@{username} ");
+        text.replace_range(start..cursor, &insert);
+        if let Some(el) = el {
+            el.set_value(&text);
+            let pos = text[..start + insert.len()].encode_utf16().count() as u32;
+            let _ = el.set_selection_range(pos, pos);
+            let _ = el.focus();
+        }
+        value.set(text);
+        self.close();
+    }
+}
+
+/// The suggestions under a textarea, while there are any.
+#[component]
+pub fn MentionList(mentions: Mentions, value: RwSignal<String>, node: NodeRef<html::Textarea>) -> impl IntoView {
+    view! {
+        <Show when=move || mentions.is_open()>
+            <ul class="mikaana-mention-list" role="listbox">
+                {move || {
+                    mentions
+                        .results
+                        .get()
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, user)| {
+                            let username = user.username.clone();
+                            // mousedown, so the textarea keeps focus
+                            let on_pick = move |ev: leptos::ev::MouseEvent| {
+                                ev.prevent_default();
+                                mentions.pick(&username, value, node);
+                            };
+                            view! {
+                                <li
+                                    role="option"
+                                    aria-selected=move || (mentions.selected.get() == i).to_string()
+                                    class:mikaana-mention-selected=move || mentions.selected.get() == i
+                                    on:mousedown=on_pick
+                                >
+                                    <img src=user.avatar_url.clone() alt="" class="mikaana-avatar" width="20" height="20" />
+                                    <span>{user.name().to_string()}</span>
+                                    <span class="mikaana-mention-username">"@"{user.username.clone()}</span>
+                                </li>
+                            }
+                        })
+                        .collect_view()
+                }}
+            </ul>
+        </Show>
+    }
+}
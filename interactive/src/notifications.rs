@@ -0,0 +1,187 @@
+//! Notification center: a bell next to the login button with the unread
+//! count, opening a list of recent replies, mentions, votes and moderation
+//! decisions. Unread private messages count towards the badge and link to
+//! the forum's inbox.
+
+use std::time::Duration;
+
+use leptos::prelude::*;
+use mikaana_shared::{routes, Notification, NotificationList};
+use wasm_bindgen_futures::spawn_local;
+use web_sys::window;
+
+use crate::{api, time};
+
+/// How often the unread count is refreshed while the page is open.
+const POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Path the forum is served at: the `mikaana-forum` meta tag, or `/discuss/`.
+pub(crate) fn forum_base() -> String {
+    window()
+        .and_then(|w| w.document())
+        .and_then(|d| d.query_selector("meta[name='mikaana-forum']").ok().flatten())
+        .and_then(|el| el.get_attribute("content"))
+        .filter(|url| !url.is_empty())
+        .map(|url| if url.ends_with('/') { url } else { format!("{url}/") })
+        .unwrap_or_else(|| "/discuss/".to_string())
+}
+
+/// Where clicking a notification leads.
+fn link(n: &Notification) -> Option<String> {
+    match (n.target_type.as_str(), &n.post_slug, n.thread_id) {
+        ("comment", Some(slug), _) => Some(format!("{slug}#comment-{}", n.target_id)),
+        ("thread", _, Some(thread)) => Some(format!("{}thread/{thread}", forum_base())),
+        ("reply", _, Some(thread)) => {
+            Some(format!("{}thread/{thread}#reply-{}", forum_base(), n.target_id))
+        }
+        _ => None,
+    }
+}
+
+fn describe(n: &Notification) -> String {
+    let actor = n.actor.as_ref().map_or("Someone", |u| u.name());
+    match n.kind.as_str() {
+        "reply" => format!("{actor} replied to {}", n.summary),
+        "mention" => format!("{actor} mentioned you in {}", n.summary),
+        "new_thread" => format!("{actor} started {}", n.summary),
+        // Milestones and moderation decisions are already full sentences
+        _ => n.summary.clone(),
+    }
+}
+
+#[component]
+pub fn NotificationBell() -> impl IntoView {
+    let list = RwSignal::new(NotificationList::default());
+    let open = RwSignal::new(false);
+    let loading_more = RwSignal::new(false);
+
+    let refresh = move || {
+        spawn_local(async move {
+            if let Ok(fresh) = api::get::<NotificationList>(routes::notifications::LIST).await {
+                let _ = list.try_set(fresh);
+            }
+        });
+    };
+    refresh();
+    if let Ok(handle) = set_interval_with_handle(refresh, POLL_INTERVAL) {
+        on_cleanup(move || handle.clear());
+    }
+
+    let load_more = move |_| {
+        let Some(before) = list.with_untracked(|l| l.next) else {
+            return;
+        };
+        loading_more.set(true);
+        spawn_local(async move {
+            let url = routes::notifications::list(Some(before));
+            if let Ok(page) = api::get::<NotificationList>(&url).await {
+                list.update(|l| {
+                    l.items.extend(page.items);
+                    l.next = page.next;
+                    l.unread = page.unread;
+                    l.unread_messages = page.unread_messages;
+                });
+            }
+            loading_more.set(false);
+        });
+    };
+
+    let mark_all_read = move |_| {
+        spawn_local(async move {
+            if api::post_empty(routes::notifications::READ_ALL, &()).await.is_ok() {
+                list.update(|l| {
+                    l.items.iter_mut().for_each(|n| n.read = true);
+                    l.unread = 0;
+                });
+            }
+        });
+    };
+
+    let follow = move |n: Notification| {
+        let target = link(&n);
+        spawn_local(async move {
+            if !n.read {
+                let url = routes::notifications::read(n.id);
+                let _ = api::post_empty(&url, &()).await;
+            }
+            match target.and_then(|url| Some((window()?, url))) {
+                Some((win, url)) => {
+                    let _ = win.location().set_href(&url);
+                }
+                None => {
+                    list.update(|l| {
+                        if let Some(item) = l.items.iter_mut().find(|i| i.id == n.id && !i.read) {
+                            item.read = true;
+                            l.unread -= 1;
+                        }
+                    });
+                }
+            }
+        });
+    };
+
+    view! {
+        <div class="mikaana-notifications">
+            <button
+                class="mikaana-bell mikaana-dropdown-toggle"
+                aria-label="Notifications"
+                aria-expanded=move || open.get().to_string()
+                on:click=move |_| open.update(|o| *o = !*o)
+            >
+                "\u{1F514}"
+                <Show when=move || list.with(|l| l.unread + l.unread_messages > 0)>
+                    <span class="mikaana-badge">{move || list.with(|l| l.unread + l.unread_messages)}</span>
+                </Show>
+            </button>
+            <Show when=move || open.get()>
+                <div class="mikaana-dropdown mikaana-notification-list">
+                    <div class="mikaana-form-actions">
+                        <strong>"Notifications"</strong>
+                        <Show when=move || list.with(|l| l.unread > 0)>
+                            <button class="mikaana-link-btn" on:click=mark_all_read>"Mark all read"</button>
+                        </Show>
+                    </div>
+                    <Show when=move || list.with(|l| l.unread_messages > 0)>
+                        <a class="mikaana-message-link" href=move || format!("{}messages/", forum_base())>
+                            {move || list.with(|l| format!("Messages ({})", l.unread_messages))}
+                        </a>
+                    </Show>
+                    <Show when=move || list.with(|l| l.items.is_empty())>
+                        <p class="mikaana-hint">"Nothing yet."</p>
+                    </Show>
+                    <ul>
+                        {move || {
+                            list.get()
+                                .items
+                                .into_iter()
+                                .map(|n| {
+                                    let text = describe(&n);
+                                    let when = time::ago(&n.created_at);
+                                    let unread = !n.read;
+                                    view! {
+                                        <li class:mikaana-unread=unread>
+                                            <button class="mikaana-link-btn" on:click=move |_| follow(n.clone())>
+                                                {text}
+                                            </button>
+                                            " "
+                                            <time>{when}</time>
+                                        </li>
+                                    }
+                                })
+                                .collect_view()
+                        }}
+                    </ul>
+                    <Show when=move || list.with(|l| l.next.is_some())>
+                        <button
+                            class="mikaana-btn mikaana-btn-sm"
+                            disabled=move || loading_more.get()
+                            on:click=load_more
+                        >
+                            "Older"
+                        </button>
+                    </Show>
+                </div>
+            </Show>
+        </div>
+    }
+}
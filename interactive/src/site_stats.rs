@@ -0,0 +1,53 @@
+use leptos::prelude::*;
+use mikaana_shared::{routes, SiteStats as Stats};
+use wasm_bindgen_futures::spawn_local;
+
+use crate::api;
+
+/// Community totals and the busiest posts, for an "about" page.
+#[component]
+pub fn SiteStats() -> impl IntoView {
+    let stats: RwSignal<Option<Stats>> = RwSignal::new(None);
+
+    spawn_local(async move {
+        if let Ok(s) = api::get::<Stats>(routes::STATS).await {
+            stats.set(Some(s));
+        }
+    });
+
+    move || {
+        stats.get().map(|s| {
+            let totals = [
+                ("members", s.users),
+                ("comments", s.comments),
+                ("threads", s.threads),
+                ("replies", s.replies),
+                ("votes", s.votes),
+            ];
+            view! {
+                <div class="mikaana-community-stats">
+                    <ul class="mikaana-stat-totals">
+                        {totals.into_iter().map(|(label, n)| view! {
+                            <li>
+                                <strong>{n.to_string()}</strong>
+                                " " {label}
+                            </li>
+                        }).collect_view()}
+                    </ul>
+                    {(!s.most_active_posts.is_empty()).then(|| view! {
+                        <h4>"Most discussed"</h4>
+                        <ol class="mikaana-active-posts">
+                            {s.most_active_posts.into_iter().map(|p| view! {
+                                <li>
+                                    <a href=p.post_slug.clone()>{p.post_slug.clone()}</a>
+                                    " — " {p.comments.to_string()}
+                                    {if p.comments == 1 { " comment" } else { " comments" }}
+                                </li>
+                            }).collect_view()}
+                        </ol>
+                    })}
+                </div>
+            }
+        })
+    }
+}
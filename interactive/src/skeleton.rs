@@ -0,0 +1,69 @@
+//! Grey placeholders shaped like the content they stand in for, shown while
+//! it loads so the page doesn't jump when it arrives.
+
+use leptos::prelude::*;
+
+/// Text bar `width` wide (any CSS length).
+#[component]
+fn Line(#[prop(into)] width: String) -> impl IntoView {
+    view! { <span class="mikaana-skeleton-line" style=format!("width: {width}")></span> }
+}
+
+/// `count` placeholder comments or replies: avatar, name, a few lines.
+#[component]
+pub fn CommentSkeleton(count: usize) -> impl IntoView {
+    view! {
+        <div class="mikaana-skeleton" role="status" aria-label="Loading">
+            {(0..count)
+                .map(|i| view! {
+                    <div class="mikaana-comment">
+                        <div class="mikaana-comment-header">
+                            <span class="mikaana-skeleton-avatar"></span>
+                            <Line width="8rem" />
+                        </div>
+                        <Line width="100%" />
+                        // Vary the last line so the rows don't look stamped
+                        <Line width=if i % 2 == 0 { "70%" } else { "45%" } />
+                    </div>
+                })
+                .collect_view()}
+        </div>
+    }
+}
+
+/// `count` placeholder cards for a thread list.
+#[component]
+pub fn ThreadListSkeleton(count: usize) -> impl IntoView {
+    view! {
+        <div class="mikaana-thread-list mikaana-skeleton" role="status" aria-label="Loading">
+            {(0..count)
+                .map(|i| view! {
+                    <div class="mikaana-thread-card">
+                        <Line width=if i % 2 == 0 { "60%" } else { "45%" } />
+                        <Line width="12rem" />
+                    </div>
+                })
+                .collect_view()}
+        </div>
+    }
+}
+
+/// A thread's heading, author line and body, then a couple of replies.
+#[component]
+pub fn ThreadDetailSkeleton() -> impl IntoView {
+    view! {
+        <div class="mikaana-skeleton" role="status" aria-label="Loading">
+            <div class="mikaana-thread-detail">
+                <Line width="55%" />
+                <div class="mikaana-thread-meta">
+                    <span class="mikaana-skeleton-avatar"></span>
+                    <Line width="8rem" />
+                </div>
+                <Line width="100%" />
+                <Line width="100%" />
+                <Line width="60%" />
+            </div>
+        </div>
+        <CommentSkeleton count=2 />
+    }
+}
@@ -0,0 +1,101 @@
+//! Dialog asking the user to accept the terms of service, shown when the
+//! server refuses a write until they do (see `api::TERMS_REQUIRED_EVENT`).
+//! One dialog serves every widget on the page; it's mounted on first use.
+
+use std::cell::Cell;
+
+use leptos::prelude::*;
+use mikaana_shared::{routes, AcceptTerms, ApiError, TermsRequired};
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::spawn_local;
+
+use crate::api;
+
+thread_local! {
+    static PENDING: Cell<Option<RwSignal<Option<TermsRequired>>>> = const { Cell::new(None) };
+}
+
+/// Show the dialog whenever a write asks for the terms.
+pub fn listen() {
+    // Lives as long as the page
+    let _ = window_event_listener_untyped(api::TERMS_REQUIRED_EVENT, |_| {
+        let Some(terms) = api::take_terms_required() else {
+            return;
+        };
+        match PENDING.get() {
+            Some(pending) => pending.set(Some(terms)),
+            None => mount(terms),
+        }
+    });
+}
+
+fn mount(terms: TermsRequired) {
+    let Some(body) = document().body() else {
+        return;
+    };
+    let Ok(el) = document().create_element("div") else {
+        return;
+    };
+    let _ = body.append_child(&el);
+    let pending = RwSignal::new(Some(terms));
+    PENDING.set(Some(pending));
+    leptos::mount::mount_to(el.unchecked_into(), move || view! { <TermsDialog pending /> })
+        .forget();
+}
+
+#[component]
+fn TermsDialog(pending: RwSignal<Option<TermsRequired>>) -> impl IntoView {
+    let busy = RwSignal::new(false);
+    let error: RwSignal<Option<String>> = RwSignal::new(None);
+
+    let accept = move |_| {
+        let Some(terms) = pending.get_untracked() else {
+            return;
+        };
+        busy.set(true);
+        error.set(None);
+        spawn_local(async move {
+            let body = AcceptTerms {
+                version: terms.version,
+            };
+            match api::post_empty(routes::TERMS_ACCEPT, &body).await {
+                Ok(()) => pending.set(None),
+                // The terms changed while the dialog was open; the next
+                // write brings up the new ones
+                Err(e) if e.api() == Some(&ApiError::Conflict) => pending.set(None),
+                Err(_) => error.set(Some("Couldn't save that, please try again.".to_string())),
+            }
+            busy.set(false);
+        });
+    };
+
+    move || {
+        let terms = pending.get()?;
+        Some(view! {
+            <div class="mikaana-terms-backdrop">
+                <div
+                    class="mikaana-terms-dialog"
+                    role="dialog"
+                    aria-modal="true"
+                    aria-labelledby="mikaana-terms-title"
+                >
+                    <h3 id="mikaana-terms-title">"Terms of service"</h3>
+                    <p>
+                        "To keep posting, please read and accept the "
+                        <a href=terms.url target="_blank" rel="noopener">"terms of service"</a>
+                        "."
+                    </p>
+                    {move || error.get().map(|e| view! { <p class="mikaana-error">{e}</p> })}
+                    <div class="mikaana-terms-actions">
+                        <button class="mikaana-btn" disabled=move || busy.get() on:click=accept>
+                            "Accept"
+                        </button>
+                        <button class="mikaana-btn" on:click=move |_| pending.set(None)>
+                            "Not now"
+                        </button>
+                    </div>
+                </div>
+            </div>
+        })
+    }
+}
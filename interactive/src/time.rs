@@ -0,0 +1,30 @@
+//! Relative timestamps. The API sends SQLite `datetime('now')` values:
+//! UTC, `YYYY-MM-DD HH:MM:SS`.
+
+use wasm_bindgen::JsValue;
+use web_sys::js_sys::Date;
+
+use crate::config;
+
+/// "just now", "5m ago", "2h ago", "3d ago", or the date for anything older
+/// than a month (in the server's locale, else the browser's). Unparseable
+/// input is returned as is.
+pub fn ago(timestamp: &str) -> String {
+    let then = Date::parse(&format!("{}Z", timestamp.replacen(' ', "T", 1)));
+    if then.is_nan() {
+        return timestamp.to_string();
+    }
+    let secs = ((Date::now() - then) / 1000.0).max(0.0) as i64;
+    match secs {
+        0..60 => "just now".to_string(),
+        60..3600 => format!("{}m ago", secs / 60),
+        3600..86400 => format!("{}h ago", secs / 3600),
+        86400..2592000 => format!("{}d ago", secs / 86400),
+        _ => match config::locale().or_else(|| web_sys::window()?.navigator().language()) {
+            Some(locale) => Date::new(&JsValue::from_f64(then))
+                .to_locale_date_string(&locale, &JsValue::UNDEFINED)
+                .into(),
+            None => timestamp.get(..10).unwrap_or(timestamp).to_string(),
+        },
+    }
+}
@@ -1,71 +1,192 @@
 use leptos::prelude::*;
-use mikaana_shared::{CreateVote, VoteResponse};
+use mikaana_shared::{
+    routes, AnalyticsEvent, CommentId, CreatePostVote, CreateVote, ReplyId, ThreadId, VoteMode,
+    VoteResponse,
+};
 use wasm_bindgen_futures::spawn_local;
 
-use crate::api;
+use crate::{analytics, api};
 use crate::auth::AuthState;
+use crate::config;
+
+/// Vote mode for every `VoteButton` in a widget.
+#[derive(Clone, Copy)]
+struct VoteModeState(RwSignal<VoteMode>);
+
+/// Provide the vote mode for a widget: the server's, made stricter by the
+/// widget's own attribute. The server enforces its mode either way; this
+/// only keeps the buttons from offering votes that would be refused.
+pub fn provide_vote_mode(widget: Option<VoteMode>) {
+    let widget = widget.unwrap_or_default();
+    let mode = RwSignal::new(widget);
+    provide_context(VoteModeState(mode));
+    config::with_config(move |config| {
+        let _ = mode.try_set(config.vote_mode.max(widget));
+    });
+}
+
+/// Layout of a `VoteButton`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VoteVariant {
+    /// Arrows and count on one line, for comment and reply lists.
+    #[default]
+    Inline,
+    /// Arrows above and below the count, for thread headers.
+    Stacked,
+    /// Prominent bordered control for the post-level widget.
+    Large,
+}
+
+impl VoteVariant {
+    fn class(self) -> &'static str {
+        match self {
+            VoteVariant::Inline => "mikaana-votes mikaana-votes-inline",
+            VoteVariant::Stacked => "mikaana-votes mikaana-votes-stacked",
+            VoteVariant::Large => "mikaana-votes mikaana-votes-large",
+        }
+    }
+}
+
+/// What a `VoteButton` votes on.
+#[derive(Clone, Debug)]
+pub enum VoteTarget {
+    /// A comment, thread or reply; made with `comment`, `thread` or
+    /// `reply` so the id matches the type.
+    Item { target_type: &'static str, id: i64 },
+    /// A blog post as a whole, by slug.
+    Post(String),
+}
+
+impl VoteTarget {
+    pub fn comment(id: CommentId) -> Self {
+        VoteTarget::Item { target_type: "comment", id: id.0 }
+    }
+
+    pub fn thread(id: ThreadId) -> Self {
+        VoteTarget::Item { target_type: "thread", id: id.0 }
+    }
+
+    pub fn reply(id: ReplyId) -> Self {
+        VoteTarget::Item { target_type: "reply", id: id.0 }
+    }
+
+    async fn fetch(&self) -> Result<VoteResponse, api::Error> {
+        match self {
+            VoteTarget::Item { target_type, id } => {
+                api::get(&routes::votes::tally(target_type, *id)).await
+            }
+            VoteTarget::Post(slug) => {
+                api::get(&routes::votes::post(slug)).await
+            }
+        }
+    }
+
+    async fn cast(&self, value: i32) -> Result<VoteResponse, api::Error> {
+        match self {
+            VoteTarget::Item { target_type, id } => {
+                let payload = CreateVote {
+                    target_type: target_type.to_string(),
+                    target_id: *id,
+                    value,
+                };
+                api::post(routes::votes::VOTES, &payload).await
+            }
+            VoteTarget::Post(slug) => {
+                let payload = CreatePostVote {
+                    post_slug: slug.clone(),
+                    value,
+                };
+                api::post(routes::votes::POST, &payload).await
+            }
+        }
+    }
+}
 
 /// Upvote / downvote button with count.
+///
+/// Pass `disabled_reason` when the target no longer accepts votes (e.g. an
+/// archived thread); the buttons are then disabled and the reason is shown
+/// as a tooltip. Under the `up-only` vote mode the down arrow is left out
+/// (unless the viewer has a downvote to take back); under `disabled` only the
+/// count remains.
 #[component]
-pub fn VoteButton(target_type: String, target_id: i64, initial_count: i64) -> impl IntoView {
+pub fn VoteButton(
+    target: VoteTarget,
+    initial_count: i64,
+    #[prop(default = None)] disabled_reason: Option<String>,
+    #[prop(optional)] variant: VoteVariant,
+    /// Text shown before the arrows, e.g. "Like this post?"
+    #[prop(optional, into)]
+    label: Option<String>,
+) -> impl IntoView {
     let count = RwSignal::new(initial_count);
     let user_vote: RwSignal<Option<i32>> = RwSignal::new(None);
     let auth = expect_context::<AuthState>();
+    let mode = use_context::<VoteModeState>()
+        .map(|VoteModeState(mode)| mode)
+        .unwrap_or_else(|| RwSignal::new(VoteMode::default()));
+    let show_up = move || mode.get() != VoteMode::Disabled;
+    let show_down = move || mode.get() == VoteMode::UpDown || user_vote.get() == Some(-1);
+    let frozen = disabled_reason.is_some();
+    let token = auth.token;
+
+    let disabled = move || frozen || token.get().is_none();
+    let tooltip = move || {
+        if let Some(reason) = disabled_reason.clone() {
+            reason
+        } else if token.get().is_none() && show_up() {
+            "Log in to vote".to_string()
+        } else {
+            String::new()
+        }
+    };
 
     // Fetch current user's vote on mount
     {
-        let tt = target_type.clone();
+        let target = target.clone();
         spawn_local(async move {
-            if let Ok(vr) =
-                api::get::<VoteResponse>(&format!("/api/votes?type={}&id={}", tt, target_id)).await
-            {
+            if let Ok(vr) = target.fetch().await {
                 count.set(vr.vote_count);
                 user_vote.set(vr.user_vote);
             }
         });
     }
 
-    let cast = {
-        let tt = target_type.clone();
-        move |value: i32| {
-            if auth.token.get_untracked().is_none() {
-                return; // must be logged in
-            }
-            // Optimistic update
-            let prev_vote = user_vote.get_untracked();
-            let prev_count = count.get_untracked();
-            let delta = match prev_vote {
-                Some(v) if v == value => -value, // toggling off
-                Some(v) => value - v,            // switching
-                None => value,                   // new vote
-            };
-            count.set(prev_count + delta as i64);
-            let new_user_vote = if prev_vote == Some(value) {
-                None
-            } else {
-                Some(value)
-            };
-            user_vote.set(new_user_vote);
-
-            let payload = CreateVote {
-                target_type: tt.clone(),
-                target_id,
-                value,
-            };
-            spawn_local(async move {
-                match api::post::<VoteResponse, _>("/api/votes", &payload).await {
-                    Ok(vr) => {
-                        count.set(vr.vote_count);
-                        user_vote.set(vr.user_vote);
-                    }
-                    Err(_) => {
-                        // Rollback
-                        count.set(prev_count);
-                        user_vote.set(prev_vote);
-                    }
-                }
-            });
+    let cast = move |value: i32| {
+        if frozen || !auth.is_logged_in() {
+            return; // must be logged in, and the target must accept votes
         }
+        // Optimistic update
+        let prev_vote = user_vote.get_untracked();
+        let prev_count = count.get_untracked();
+        let delta = match prev_vote {
+            Some(v) if v == value => -value, // toggling off
+            Some(v) => value - v,            // switching
+            None => value,                   // new vote
+        };
+        count.set(prev_count + delta as i64);
+        let new_user_vote = if prev_vote == Some(value) {
+            None
+        } else {
+            Some(value)
+        };
+        user_vote.set(new_user_vote);
+
+        let target = target.clone();
+        spawn_local(async move {
+            match target.cast(value).await {
+                Ok(vr) => {
+                    analytics::track(AnalyticsEvent::VoteCast);
+                    count.set(vr.vote_count);
+                    user_vote.set(vr.user_vote);
+                }
+                Err(_) => {
+                    // Rollback
+                    count.set(prev_count);
+                    user_vote.set(prev_vote);
+                }
+            }
+        });
     };
 
     let cast_up = {
@@ -75,25 +196,30 @@ pub fn VoteButton(target_type: String, target_id: i64, initial_count: i64) -> im
     let cast_down = move |_| cast(-1);
 
     view! {
-        <div class="mikaana-votes">
-            <button
-                class="mikaana-vote-btn"
-                class:active=move || user_vote.get() == Some(1)
-                on:click=cast_up
-                disabled=move || auth.token.get().is_none()
-            >
-                // Unicode up triangle
-                "\u{25B2}"
-            </button>
+        <div class=variant.class() title=tooltip>
+            {label.map(|l| view! { <span class="mikaana-vote-label">{l}</span> })}
+            <Show when=show_up>
+                <button
+                    class="mikaana-vote-btn"
+                    class:active=move || user_vote.get() == Some(1)
+                    on:click=cast_up.clone()
+                    disabled=disabled
+                >
+                    // Unicode up triangle
+                    "\u{25B2}"
+                </button>
+            </Show>
             <span class="mikaana-vote-count">{move || count.get()}</span>
-            <button
-                class="mikaana-vote-btn"
-                class:active=move || user_vote.get() == Some(-1)
-                on:click=cast_down
-                disabled=move || auth.token.get().is_none()
-            >
-                "\u{25BC}"
-            </button>
+            <Show when=show_down>
+                <button
+                    class="mikaana-vote-btn"
+                    class:active=move || user_vote.get() == Some(-1)
+                    on:click=cast_down.clone()
+                    disabled=disabled
+                >
+                    "\u{25BC}"
+                </button>
+            </Show>
         </div>
     }
 }
@@ -101,13 +227,14 @@ pub fn VoteButton(target_type: String, target_id: i64, initial_count: i64) -> im
 /// Standalone post-level votes (for embedding in extend_footer).
 #[component]
 pub fn PostVotes(slug: String) -> impl IntoView {
-    // Use slug hash as a stable target_id for post-level votes
-    let target_id = slug.bytes().fold(0i64, |acc, b| acc.wrapping_mul(31).wrapping_add(b as i64)).abs();
-
     view! {
         <div class="mikaana-post-votes">
-            <span>"Like this post? "</span>
-            <VoteButton target_type="post".to_string() target_id=target_id initial_count=0 />
+            <VoteButton
+                target=VoteTarget::Post(slug)
+                initial_count=0
+                variant=VoteVariant::Large
+                label="Like this post?"
+            />
         </div>
     }
 }
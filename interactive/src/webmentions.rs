@@ -0,0 +1,56 @@
+//! Pages elsewhere that link to a post (`GET /api/webmentions`), listed
+//! under its comments as "Mentioned elsewhere".
+
+use leptos::prelude::*;
+use mikaana_shared::{routes, Webmention};
+use wasm_bindgen_futures::spawn_local;
+
+use crate::{api, config, time};
+
+/// The linking page's title, or its host when it has none.
+fn label(mention: &Webmention) -> String {
+    if !mention.title.is_empty() {
+        return mention.title.clone();
+    }
+    web_sys::Url::new(&mention.source)
+        .map(|url| url.hostname())
+        .unwrap_or_else(|_| mention.source.clone())
+}
+
+/// Nothing until the post has a verified mention; not fetched at all when
+/// the server doesn't accept webmentions.
+#[component]
+pub fn Webmentions(slug: String) -> impl IntoView {
+    let mentions: RwSignal<Vec<Webmention>> = RwSignal::new(Vec::new());
+
+    config::with_config(move |config| {
+        if !config.webmentions {
+            return;
+        }
+        spawn_local(async move {
+            let path = routes::webmentions(&slug);
+            if let Ok(list) = api::get::<Vec<Webmention>>(&path).await {
+                let _ = mentions.try_set(list);
+            }
+        });
+    });
+
+    view! {
+        <Show when=move || mentions.with(|m| !m.is_empty())>
+            <aside class="mikaana-webmentions">
+                <h4>"Mentioned elsewhere"</h4>
+                <ul>
+                    <For each=move || mentions.get() key=|m| m.id let:mention>
+                        <li>
+                            <a href=mention.source.clone() rel="nofollow ugc noopener" target="_blank">
+                                {label(&mention)}
+                            </a>
+                            " "
+                            <span class="mikaana-hint">{time::ago(&mention.verified_at)}</span>
+                        </li>
+                    </For>
+                </ul>
+            </aside>
+        </Show>
+    }
+}
@@ -1,36 +1,719 @@
 use serde::{Deserialize, Serialize};
 
+pub mod routes;
+
+// ── Ids ──
+
+/// Ids of users, comments, threads and replies, so one can't be passed
+/// where another is meant. Serialized as the bare number. Ids that may name
+/// any kind of content (`target_id`, with a `target_type`) stay `i64`.
+macro_rules! id_type {
+    ($($name:ident),* $(,)?) => {$(
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+        #[serde(transparent)]
+        // `Int` in GraphQL, like the bare number
+        #[cfg_attr(feature = "async-graphql", derive(async_graphql::NewType))]
+        pub struct $name(pub i64);
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+
+        impl std::str::FromStr for $name {
+            type Err = std::num::ParseIntError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                s.parse().map($name)
+            }
+        }
+
+        // `NewType` derives the conversions both ways
+        #[cfg(not(feature = "async-graphql"))]
+        impl From<$name> for i64 {
+            fn from(id: $name) -> i64 {
+                id.0
+            }
+        }
+
+        #[cfg(feature = "rusqlite")]
+        impl rusqlite::ToSql for $name {
+            fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+                self.0.to_sql()
+            }
+        }
+
+        #[cfg(feature = "rusqlite")]
+        impl rusqlite::types::FromSql for $name {
+            fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+                i64::column_result(value).map($name)
+            }
+        }
+    )*};
+}
+
+id_type! {
+    UserId,
+    CommentId,
+    ThreadId,
+    ReplyId,
+}
+
 // ── Auth ──
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
-    pub id: i64,
+    pub id: UserId,
+    /// Handle for @mentions and messages. Set from the GitHub login when
+    /// the account is created and kept when the login changes later.
     pub username: String,
     pub avatar_url: String,
+    /// Name chosen in the profile; see `name`.
+    #[serde(default)]
+    pub display_name: Option<String>,
+    /// Shown next to the name. Only filled in where the widgets show them:
+    /// comments, and a thread and its replies when opened.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub badges: Vec<UserBadge>,
+}
+
+impl User {
+    /// The name to show: the display name when set, else the username.
+    pub fn name(&self) -> &str {
+        self.display_name.as_deref().unwrap_or(&self.username)
+    }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UserBadge {
+    /// Moderators and admins.
+    Moderator,
+    /// The blog's author (the server's `authors.user_ids`).
+    Author,
+    /// Posting here for less than `NEW_MEMBER_DAYS`.
+    NewMember,
+}
+
+impl UserBadge {
+    pub fn label(self) -> &'static str {
+        match self {
+            UserBadge::Moderator => "Moderator",
+            UserBadge::Author => "Author",
+            UserBadge::NewMember => "New member",
+        }
+    }
+}
+
+/// How long a user counts as a new member, from their account or their
+/// first post, whichever is older (imported posts predate their account).
+pub const NEW_MEMBER_DAYS: i64 = 30;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuthResponse {
     pub token: String,
     pub user: User,
 }
 
+/// The signed-in user's editable profile.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    pub user: User,
+    pub display_name: Option<String>,
+    pub bio: String,
+    pub website: Option<String>,
+}
+
+/// Body of `PUT /api/auth/me`. Empty strings clear optional fields.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateProfile {
+    pub display_name: String,
+    pub bio: String,
+    pub website: String,
+}
+
+/// Everything a user has posted, returned by `GET /api/auth/me/export`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserExport {
+    /// Bumped whenever fields change meaning, so archives stay readable.
+    pub schema_version: u32,
+    pub exported_at: String,
+    pub profile: Profile,
+    pub comments: Vec<Comment>,
+    pub threads: Vec<Thread>,
+    pub replies: Vec<Reply>,
+    pub votes: Vec<ExportedVote>,
+    #[serde(default)]
+    pub terms_accepted: Vec<TermsAcceptance>,
+}
+
+/// A version of the terms of service the user accepted, and when.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TermsAcceptance {
+    pub version: String,
+    pub accepted_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedVote {
+    pub target_type: String,
+    pub target_id: i64,
+    pub value: i32,
+    pub created_at: String,
+}
+
+pub const MAX_DISPLAY_NAME_LEN: usize = 50;
+pub const MAX_BIO_LEN: usize = 500;
+pub const MAX_WEBSITE_LEN: usize = 200;
+
+// ── Moderation ──
+
+/// A comment, thread or reply as moderators see it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModerationItem {
+    pub target_type: String,
+    pub target_id: i64,
+    pub author: User,
+    /// Threads only.
+    pub title: Option<String>,
+    pub body: String,
+    /// `published`, `pending` or `removed`.
+    pub status: String,
+    pub created_at: String,
+    /// Where the item lives: a post for comments, a thread otherwise.
+    pub post_slug: Option<String>,
+    pub thread_id: Option<ThreadId>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateReport {
+    pub target_type: String,
+    pub target_id: i64,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContentReport {
+    pub id: i64,
+    pub reporter: User,
+    pub reason: String,
+    pub created_at: String,
+    /// `None` once the reported content has been deleted outright.
+    pub item: Option<ModerationItem>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModerationAction {
+    Approve,
+    Remove,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModerateContent {
+    pub target_type: String,
+    pub target_id: i64,
+    pub action: ModerationAction,
+}
+
+/// A comment, thread or reply named in a bulk operation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContentRef {
+    pub target_type: String,
+    pub target_id: i64,
+}
+
+/// What `POST /api/admin/bulk` does to each item: approve or remove it as
+/// a moderator would one at a time, or credit it to another account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BulkAction {
+    Approve,
+    Remove,
+    /// Admins only; needs `BulkModerate::into_user_id`.
+    Reassign,
+}
+
+/// Body of `POST /api/admin/bulk`. All of it is applied or, when any item
+/// can't be changed, none of it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BulkModerate {
+    pub action: BulkAction,
+    #[serde(default)]
+    pub items: Vec<ContentRef>,
+    /// Also everything by this user the action applies to (their pending
+    /// posts for `approve`, e.g.), for clearing out a spammer.
+    #[serde(default)]
+    pub author_id: Option<UserId>,
+    /// New author for `reassign`.
+    #[serde(default)]
+    pub into_user_id: Option<UserId>,
+}
+
+/// Items changed by a bulk operation, by type.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BulkSummary {
+    pub comments: usize,
+    pub threads: usize,
+    pub replies: usize,
+}
+
+/// A user row in the admin dashboard.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdminUser {
+    pub user: User,
+    pub role: String,
+    pub created_at: String,
+    pub banned: bool,
+    pub ban_reason: Option<String>,
+    pub comments: i64,
+    pub threads: i64,
+    pub replies: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BanUser {
+    #[serde(default)]
+    pub reason: String,
+}
+
+/// A ban on the address some content was posted from. Addresses are only
+/// stored hashed, so bans are placed and listed by that content.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IpBan {
+    pub id: i64,
+    pub reason: String,
+    /// `comment`, `thread` or `reply` the ban was placed from.
+    pub source_type: String,
+    pub source_id: i64,
+    pub banned_by: User,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateIpBan {
+    pub target_type: String,
+    pub target_id: i64,
+    #[serde(default)]
+    pub reason: String,
+}
+
+pub const MAX_REPORT_REASON_LEN: usize = 500;
+
+// ── Public config ──
+
+/// Non-secret server settings the widgets read at mount.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PublicConfig {
+    #[serde(default)]
+    pub branding: Branding,
+    #[serde(default)]
+    pub vote_mode: VoteMode,
+    /// `None` when uploads are disabled.
+    #[serde(default)]
+    pub uploads: Option<UploadLimits>,
+    /// Challenge shown to new accounts when the server answers a post with
+    /// `428 Precondition Required`; `None` when disabled.
+    #[serde(default)]
+    pub captcha: Option<CaptchaInfo>,
+    /// Terms of service users accept when the server answers a write with
+    /// `428 Precondition Required` (`ApiError::TermsNotAccepted`); `None` when
+    /// disabled.
+    #[serde(default)]
+    pub terms: Option<TermsInfo>,
+    #[serde(default)]
+    pub features: Features,
+    #[serde(default)]
+    pub limits: ContentLimits,
+    /// BCP 47 tag (`en-GB`, `de`) for dates and numbers; `None` uses the
+    /// visitor's browser settings.
+    #[serde(default)]
+    pub locale: Option<String>,
+    #[serde(default)]
+    pub markup: Markup,
+    /// Whether the server accepts webmentions, so posts may have some.
+    #[serde(default)]
+    pub webmentions: bool,
+    /// Leading links per body with a preview (`GET /api/unfurl`); 0 when
+    /// previews are off.
+    #[serde(default)]
+    pub link_previews: usize,
+    /// Whether widgets report interactions to `POST /api/events`.
+    #[serde(default)]
+    pub analytics: bool,
+}
+
+/// Lengths the server enforces, in characters, so forms can stop at them.
+/// They count the text as sent: what's stored is expanded and sanitized
+/// afterwards, and HTML escaping can make it longer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContentLimits {
+    pub body: usize,
+    pub title: usize,
+    pub report_reason: usize,
+    pub display_name: usize,
+    pub bio: usize,
+    /// Attachments per comment, thread or reply.
+    pub attachments: usize,
+}
+
+impl Default for ContentLimits {
+    fn default() -> Self {
+        ContentLimits {
+            body: MAX_BODY_LEN,
+            title: MAX_TITLE_LEN,
+            report_reason: MAX_REPORT_REASON_LEN,
+            display_name: MAX_DISPLAY_NAME_LEN,
+            bio: MAX_BIO_LEN,
+            attachments: MAX_ATTACHMENTS,
+        }
+    }
+}
+
+/// How comment, thread and reply bodies are meant to be displayed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Markup {
+    /// Shown as typed, escaped; never rendered as HTML or Markdown. Only
+    /// `:shortcode:`s from `EMOJI` are expanded, when posting.
+    #[default]
+    Plain,
+}
+
+/// Subsystems this instance serves (`ENABLE_*` on the server). The routes
+/// of a disabled one aren't registered, so widgets for it should not mount.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Features {
+    pub forum: bool,
+    pub votes: bool,
+    pub github_stats: bool,
+}
+
+impl Default for Features {
+    fn default() -> Self {
+        Features {
+            forum: true,
+            votes: true,
+            github_stats: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptchaProvider {
+    /// Cloudflare Turnstile.
+    Turnstile,
+    Hcaptcha,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptchaInfo {
+    pub provider: CaptchaProvider,
+    pub site_key: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TermsInfo {
+    pub version: String,
+    pub url: String,
+}
+
+/// What `POST /api/uploads` accepts, so editors can check before sending.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadLimits {
+    pub max_bytes: usize,
+    pub allowed_types: Vec<String>,
+}
+
+/// Which votes a site accepts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum VoteMode {
+    /// Upvotes and downvotes.
+    #[default]
+    #[serde(rename = "updown")]
+    UpDown,
+    /// Likes only; existing downvotes can still be withdrawn.
+    UpOnly,
+    /// No voting; counts are still shown.
+    Disabled,
+}
+
+impl VoteMode {
+    pub fn parse(s: &str) -> Option<VoteMode> {
+        match s {
+            "updown" => Some(VoteMode::UpDown),
+            "up-only" => Some(VoteMode::UpOnly),
+            "disabled" => Some(VoteMode::Disabled),
+            _ => None,
+        }
+    }
+
+    /// Whether a new `value` (+1/-1) vote may be cast.
+    pub fn allows(self, value: i32) -> bool {
+        match self {
+            VoteMode::UpDown => true,
+            VoteMode::UpOnly => value > 0,
+            VoteMode::Disabled => false,
+        }
+    }
+}
+
+/// Per-deployment look of the widgets, so white-label sites don't need a
+/// rebuilt bundle.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Branding {
+    pub site_name: Option<String>,
+    /// CSS color (`#rgb`, `#rrggbb` or `#rrggbbaa`) for buttons and highlights.
+    pub accent_color: Option<String>,
+    /// Show the "Powered by mikaana" footer under comments and the forum.
+    pub powered_by: bool,
+    /// Extra stylesheet appended to the page once.
+    pub custom_css_url: Option<String>,
+}
+
+impl Default for Branding {
+    fn default() -> Self {
+        Branding {
+            site_name: None,
+            accent_color: None,
+            powered_by: true,
+            custom_css_url: None,
+        }
+    }
+}
+
 // ── Comments ──
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Comment {
-    pub id: i64,
+    pub id: CommentId,
     pub post_slug: String,
     pub user: User,
     pub body: String,
+    /// Length of `body` in characters, so clients can clamp long bodies
+    /// before laying them out.
+    pub body_length: usize,
     pub created_at: String,
     pub vote_count: i64,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    /// Only set in the response to creating it: a word filter held it for
+    /// moderation, so nobody else sees it yet.
+    #[serde(default)]
+    pub pending: bool,
+    /// Written by the blog's author (the server's `authors.user_ids`).
+    #[serde(default)]
+    pub by_author: bool,
+    /// By a user the viewer blocked: `body` and `attachments` are left
+    /// empty, for a placeholder in their place.
+    #[serde(default)]
+    pub blocked: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateComment {
     pub post_slug: String,
     pub body: String,
+    /// Uploads (see `POST /api/uploads`) to attach.
+    #[serde(default)]
+    pub attachment_ids: Vec<i64>,
+    /// Challenge response, needed while the account is new when the server
+    /// has a captcha configured (see `PublicConfig::captcha`).
+    #[serde(default)]
+    pub captcha_token: Option<String>,
+    /// The post's title (the page's `<title>`), for the forum thread opened
+    /// when a post is first commented on; the slug stands in without it.
+    #[serde(default)]
+    pub post_title: Option<String>,
+}
+
+/// Whether a post takes new comments (`GET /api/comments/state`).
+/// Existing comments stay readable either way.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CommentState {
+    pub closed: bool,
+    /// When comments close on their own (`PostSettings::close_after_days`),
+    /// past or to come.
+    #[serde(default)]
+    pub closes_at: Option<String>,
+    /// Forum thread discussing the post, when one was opened for it and the
+    /// server knows the forum's address.
+    #[serde(default)]
+    pub discussion_url: Option<String>,
+}
+
+/// Comment settings an admin has given a post; posts without any are open.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostSettings {
+    pub post_slug: String,
+    pub comments_closed: bool,
+    /// Close comments this many days after `published_at`, or after the
+    /// post's first comment when that isn't set.
+    pub close_after_days: Option<u32>,
+    pub published_at: Option<String>,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdatePostSettings {
+    pub post_slug: String,
+    #[serde(default)]
+    pub comments_closed: bool,
+    #[serde(default)]
+    pub close_after_days: Option<u32>,
+    /// Any date SQLite reads (`2026-10-15`, `2026-10-15 09:30:00`), UTC.
+    #[serde(default)]
+    pub published_at: Option<String>,
+}
+
+/// A page elsewhere that links to a post, received as a webmention and
+/// checked to really contain the link.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Webmention {
+    pub id: i64,
+    /// Address of the linking page.
+    pub source: String,
+    /// The page's `<title>`; empty when it has none.
+    pub title: String,
+    /// When the link was last confirmed.
+    pub verified_at: String,
+}
+
+/// Longest comment, thread or reply body, in characters as sent (see
+/// `ContentLimits`).
+pub const MAX_BODY_LEN: usize = 10_000;
+/// Longest thread title, in characters as sent.
+pub const MAX_TITLE_LEN: usize = 200;
+/// Attachments per comment, thread or reply.
+pub const MAX_ATTACHMENTS: usize = 10;
+
+/// Body of API error responses: the `ApiError` flattened in (its code as
+/// `error`, plus any fields of its own), a `message` for some, and always
+/// `request_id`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorBody {
+    #[serde(flatten)]
+    pub error: ApiError,
+    #[serde(default)]
+    pub message: Option<String>,
+    /// Quote this when reporting a problem; the server logs it.
+    #[serde(default)]
+    pub request_id: Option<String>,
+}
+
+/// What went wrong with an API request, sent as `error` (`not_found` and so
+/// on). Most errors are just the HTTP status they come with; the rest say
+/// more about a refusal the widgets handle themselves.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "error", rename_all = "snake_case")]
+pub enum ApiError {
+    BadRequest,
+    /// Not signed in, or the session has expired.
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    MethodNotAllowed,
+    Conflict,
+    Gone,
+    /// `413`, from the body size limit or an upload over its limit.
+    PayloadTooLarge,
+    UnsupportedMediaType,
+    /// `422` for a body that doesn't parse as what the endpoint takes.
+    UnprocessableEntity,
+    /// `422`: `field` is longer than its limit of `max` characters.
+    #[serde(rename = "too_long")]
+    ValidationFailed { field: String, max: usize },
+    /// `422`: JSON nested too deeply.
+    TooDeep,
+    /// `428`: a new account's post needs a captcha response (see
+    /// `PublicConfig::captcha`).
+    PreconditionRequired,
+    /// `428`: the user hasn't accepted the current terms of service.
+    TermsNotAccepted(TermsRequired),
+    /// `429` from rate limiting in front of the API.
+    #[serde(rename = "too_many_requests")]
+    RateLimited,
+    /// `429`: a post came too soon after the user's previous ones; it would
+    /// be taken in `retry_after` seconds (also sent as `Retry-After`).
+    PostingTooFast { retry_after: u64 },
+    /// `409`: a post repeats one the user made moments ago.
+    DuplicatePost,
+    /// `409`: a request with the same `Idempotency-Key` is still running.
+    IdempotencyKeyInUse,
+    /// `422`: the `Idempotency-Key` was already used for a different request.
+    IdempotencyKeyReused,
+    InternalServerError,
+    /// `502`, when a service the API relies on (GitHub, the summarizer)
+    /// failed.
+    BadGateway,
+    ServiceUnavailable,
+    /// Any other status, or an error this version doesn't know.
+    #[serde(other)]
+    Other,
+}
+
+impl ApiError {
+    /// The plain error for an HTTP `status`.
+    pub fn from_status(status: u16) -> Self {
+        match status {
+            400 => ApiError::BadRequest,
+            401 => ApiError::Unauthorized,
+            403 => ApiError::Forbidden,
+            404 => ApiError::NotFound,
+            405 => ApiError::MethodNotAllowed,
+            409 => ApiError::Conflict,
+            410 => ApiError::Gone,
+            413 => ApiError::PayloadTooLarge,
+            415 => ApiError::UnsupportedMediaType,
+            422 => ApiError::UnprocessableEntity,
+            428 => ApiError::PreconditionRequired,
+            429 => ApiError::RateLimited,
+            500 => ApiError::InternalServerError,
+            502 => ApiError::BadGateway,
+            503 => ApiError::ServiceUnavailable,
+            _ => ApiError::Other,
+        }
+    }
+}
+
+/// The terms a `TermsNotAccepted` error asks for. Accepting `version` with
+/// `POST /api/terms/accept` lets the write be sent again.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TermsRequired {
+    pub version: String,
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AcceptTerms {
+    /// Must be the current version, so nobody accepts terms they weren't
+    /// shown.
+    pub version: String,
+}
+
+// ── Attachments ──
+
+/// An uploaded file, attached to a comment, thread or reply once that is
+/// created with its id.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attachment {
+    pub id: i64,
+    /// Absolute URL the file is served from.
+    pub url: String,
+    pub filename: String,
+    pub content_type: String,
+    pub size: i64,
+}
+
+impl Attachment {
+    pub fn is_image(&self) -> bool {
+        self.content_type.starts_with("image/")
+    }
 }
 
 // ── Votes ──
@@ -42,6 +725,13 @@ pub struct CreateVote {
     pub value: i32,
 }
 
+/// A vote on a blog post as a whole, see `POST /api/votes/post`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreatePostVote {
+    pub post_slug: String,
+    pub value: i32,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VoteResponse {
     pub vote_count: i64,
@@ -56,17 +746,86 @@ pub struct ForumCategory {
     pub name: String,
     pub slug: String,
     pub description: String,
+    /// No new threads or replies; set by moderators.
+    #[serde(default)]
+    pub read_only: bool,
+    /// Whether the requesting user follows this category (false when anonymous).
+    #[serde(default)]
+    pub subscribed: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Thread {
-    pub id: i64,
+    pub id: ThreadId,
     pub category_id: i64,
     pub user: User,
     pub title: String,
     pub body: String,
     pub created_at: String,
     pub reply_count: i64,
+    /// Reply the author or a moderator accepted as the answer.
+    #[serde(default)]
+    pub solution_reply_id: Option<ReplyId>,
+    /// When the newest published reply was posted; `None` without replies.
+    #[serde(default)]
+    pub last_reply_at: Option<String>,
+    #[serde(default)]
+    pub last_reply_user: Option<User>,
+    /// Set on the stub left behind when a thread moves category; the stub
+    /// only points readers at this thread.
+    #[serde(default)]
+    pub moved_to: Option<ThreadId>,
+    /// Activity the viewer hasn't seen since they last opened the thread or
+    /// its category. Always false when anonymous.
+    #[serde(default)]
+    pub has_unread: bool,
+    /// Filled in for single threads, not listings.
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    /// See `Comment::pending`.
+    #[serde(default)]
+    pub pending: bool,
+    /// Listed ahead of the category's other threads.
+    #[serde(default)]
+    pub pinned: bool,
+    /// When a scheduled thread goes live (UTC, `YYYY-MM-DD HH:MM:SS`); it's
+    /// hidden from everyone until then. `None` once published.
+    #[serde(default)]
+    pub publish_at: Option<String>,
+}
+
+/// Body of `POST /api/forum/threads/{id}/move`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MoveThread {
+    pub category_slug: String,
+    /// Leave a "moved" stub in the old category's listing.
+    #[serde(default)]
+    pub leave_stub: bool,
+}
+
+/// Ordering of threads within a category.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThreadSort {
+    /// Most recent reply (or creation, for threads without replies) first.
+    #[default]
+    Activity,
+    Newest,
+}
+
+impl ThreadSort {
+    pub const ALL: [ThreadSort; 2] = [ThreadSort::Activity, ThreadSort::Newest];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ThreadSort::Activity => "activity",
+            ThreadSort::Newest => "newest",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|sort| sort.as_str() == s)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -74,21 +833,152 @@ pub struct CreateThread {
     pub category_slug: String,
     pub title: String,
     pub body: String,
+    #[serde(default)]
+    pub attachment_ids: Vec<i64>,
+    /// Challenge response, needed while the account is new when the server
+    /// has a captcha configured (see `PublicConfig::captcha`).
+    #[serde(default)]
+    pub captcha_token: Option<String>,
+    /// Moderators only: keep the thread hidden until then. Any date SQLite
+    /// reads, e.g. `2025-06-01T09:00:00Z`; without an offset it's UTC.
+    #[serde(default)]
+    pub publish_at: Option<String>,
+    /// Moderators only: pin the thread once it's published.
+    #[serde(default)]
+    pub pin: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Reply {
-    pub id: i64,
-    pub thread_id: i64,
+    pub id: ReplyId,
+    pub thread_id: ThreadId,
     pub user: User,
     pub body: String,
+    /// Length of `body` in characters (see `Comment::body_length`).
+    pub body_length: usize,
     pub created_at: String,
     pub vote_count: i64,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    /// See `Comment::pending`.
+    #[serde(default)]
+    pub pending: bool,
+    /// See `Comment::by_author`.
+    #[serde(default)]
+    pub by_author: bool,
+    /// See `Comment::blocked`.
+    #[serde(default)]
+    pub blocked: bool,
+}
+
+/// Ordering of replies within a thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplySort {
+    #[default]
+    Oldest,
+    Newest,
+    Top,
+}
+
+impl ReplySort {
+    pub const ALL: [ReplySort; 3] = [ReplySort::Oldest, ReplySort::Newest, ReplySort::Top];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReplySort::Oldest => "oldest",
+            ReplySort::Newest => "newest",
+            ReplySort::Top => "top",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|sort| sort.as_str() == s)
+    }
+}
+
+/// A thread with its replies, plus what the current viewer may do in it.
+/// Locked threads and read-only categories refuse replies; archived
+/// threads refuse replies and votes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThreadDetail {
+    pub thread: Thread,
+    pub replies: Vec<Reply>,
+    pub can_reply: bool,
+    pub can_vote: bool,
+    /// Human-readable explanation when replying or voting is disabled.
+    pub disabled_reason: Option<String>,
+    /// Whether the viewer (the author or a moderator) may pick the solution.
+    #[serde(default)]
+    pub can_mark_solution: bool,
+    /// When the viewer last opened the thread before this request; replies
+    /// created after it are new to them.
+    #[serde(default)]
+    pub last_read_at: Option<String>,
+}
+
+/// Body of `POST /api/forum/threads/{id}/solution`; `None` clears it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetSolution {
+    pub reply_id: Option<ReplyId>,
+}
+
+/// Machine-generated "summary so far" of a long thread.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThreadSummary {
+    pub summary: String,
+    /// Replies covered by the summary; fewer than the thread has when a
+    /// fresh summary could not be generated and an older one is served.
+    pub reply_count: i64,
+    pub generated_at: String,
+}
+
+/// A thread or reply matching a forum search (`/api/forum/search`), best
+/// match first.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchResult {
+    pub thread_id: ThreadId,
+    /// Set when the match is in a reply, for linking to it.
+    pub reply_id: Option<ReplyId>,
+    /// Title of the thread.
+    pub title: String,
+    /// Text around the match, in order.
+    pub excerpt: Vec<ExcerptPart>,
+    pub created_at: String,
+}
+
+/// A run of excerpt text; `matched` runs are search terms, for highlighting.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExcerptPart {
+    pub text: String,
+    pub matched: bool,
+}
+
+/// What a link previews as: the OpenGraph fields of a thread's page
+/// (`/api/forum/threads/{id}/preview`), or those read from a page linked in
+/// a body (`/api/unfurl`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkPreview {
+    pub title: String,
+    /// For threads, the start of the body on one line.
+    pub description: String,
+    /// For threads, the first image attachment, else the site's
+    /// `branding.card_image_url`.
+    pub image: Option<String>,
+    /// Canonical address; for threads, on the forum when the server knows it.
+    pub url: Option<String>,
+    pub site_name: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreateReply {
     pub body: String,
+    #[serde(default)]
+    pub attachment_ids: Vec<i64>,
+    /// Challenge response, needed while the account is new when the server
+    /// has a captcha configured (see `PublicConfig::captcha`).
+    #[serde(default)]
+    pub captcha_token: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -99,15 +989,538 @@ pub struct Paginated<T> {
     pub per_page: i64,
 }
 
+/// A page of a list fetched by cursor (`?after=`) instead of page number.
+/// Unlike page numbers, cursors don't skip or repeat items when new ones
+/// are posted between requests.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaginatedCursor<T> {
+    pub items: Vec<T>,
+    /// Cursor for the following page; `None` on the last one.
+    pub next: Option<String>,
+    pub per_page: i64,
+}
+
+// ── Notifications ──
+
+/// An entry in the notification center.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Notification {
+    pub id: i64,
+    /// `reply`, `mention`, `new_thread`, `vote_milestone` or `moderation`.
+    pub kind: String,
+    /// Who caused it; `None` for deleted accounts.
+    pub actor: Option<User>,
+    /// `comment`, `thread` or `reply`.
+    pub target_type: String,
+    pub target_id: i64,
+    pub summary: String,
+    /// Post a comment target is on, for linking to it.
+    pub post_slug: Option<String>,
+    /// Thread of a thread or reply target, for linking to it.
+    pub thread_id: Option<ThreadId>,
+    pub read: bool,
+    pub created_at: String,
+}
+
+/// A page of `GET /api/notifications`, newest first.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NotificationList {
+    pub items: Vec<Notification>,
+    /// Unread notifications in total, not just on this page.
+    pub unread: i64,
+    /// Unread private messages, so the bell can point at the inbox.
+    #[serde(default)]
+    pub unread_messages: i64,
+    /// Pass as `before` for the next page; `None` on the last one.
+    pub next: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationPrefs {
+    /// Also deliver notifications by email (in-app delivery is always on).
+    pub email: bool,
+    /// Whether an email address is known for the account; read-only.
+    #[serde(default)]
+    pub email_available: bool,
+    /// How often to email a digest of forum activity.
+    #[serde(default)]
+    pub digest: DigestFrequency,
+}
+
+/// Digest of new threads in followed categories and replies in threads the
+/// user took part in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DigestFrequency {
+    #[default]
+    Off,
+    Daily,
+    Weekly,
+}
+
+impl DigestFrequency {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DigestFrequency::Off => "off",
+            DigestFrequency::Daily => "daily",
+            DigestFrequency::Weekly => "weekly",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<DigestFrequency> {
+        match s {
+            "off" => Some(DigestFrequency::Off),
+            "daily" => Some(DigestFrequency::Daily),
+            "weekly" => Some(DigestFrequency::Weekly),
+            _ => None,
+        }
+    }
+}
+
+// ── Messages ──
+
+/// A private message between two users.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Message {
+    pub id: i64,
+    pub sender: User,
+    pub recipient: User,
+    pub body: String,
+    /// Whether the recipient has opened the conversation since.
+    pub read: bool,
+    pub created_at: String,
+}
+
+/// The exchange with one other user, as listed in the inbox.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Conversation {
+    pub with: User,
+    pub last_message: Message,
+    /// Messages from `with` the viewer hasn't read.
+    pub unread: i64,
+}
+
+/// `GET /api/messages`: conversations, most recently active first.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Inbox {
+    pub conversations: Vec<Conversation>,
+    /// Unread messages over all conversations.
+    pub unread: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SendMessage {
+    /// Recipient's username.
+    pub to: String,
+    pub body: String,
+}
+
+// ── Activity ──
+
+/// A comment, thread or reply, as listed in activity feeds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivityItem {
+    /// `comment`, `thread` or `reply`.
+    pub kind: String,
+    pub id: i64,
+    pub user: User,
+    /// Title of the thread, or the post slug for comments.
+    pub title: String,
+    /// Start of the body; the full text is on the post or thread.
+    pub excerpt: String,
+    /// Post a comment is on, for linking to it.
+    pub post_slug: Option<String>,
+    /// Thread of a thread or reply, for linking to it.
+    pub thread_id: Option<ThreadId>,
+    pub created_at: String,
+}
+
+// ── Drafts ──
+
+/// An unposted comment, thread or reply, saved while it is being written.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Draft {
+    /// Which form it belongs to: `comment:<post slug>`, `thread:<category
+    /// slug>` or `reply:<thread id>`.
+    pub key: String,
+    /// Only used by threads.
+    #[serde(default)]
+    pub title: String,
+    pub body: String,
+    /// Set by the server.
+    #[serde(default)]
+    pub updated_at: String,
+}
+
+pub const MAX_DRAFT_KEY_LEN: usize = 300;
+
+// ── Emoji ──
+
+/// Shortcodes the server expands in titles and bodies, and the emoji picker
+/// offers. Anything else between colons is left as typed.
+pub const EMOJI: &[(&str, &str)] = &[
+    ("smile", "\u{1F604}"),
+    ("grin", "\u{1F601}"),
+    ("joy", "\u{1F602}"),
+    ("wink", "\u{1F609}"),
+    ("blush", "\u{1F60A}"),
+    ("heart_eyes", "\u{1F60D}"),
+    ("thinking", "\u{1F914}"),
+    ("neutral_face", "\u{1F610}"),
+    ("confused", "\u{1F615}"),
+    ("cry", "\u{1F622}"),
+    ("sob", "\u{1F62D}"),
+    ("angry", "\u{1F620}"),
+    ("scream", "\u{1F631}"),
+    ("sunglasses", "\u{1F60E}"),
+    ("upside_down", "\u{1F643}"),
+    ("eyes", "\u{1F440}"),
+    ("+1", "\u{1F44D}"),
+    ("-1", "\u{1F44E}"),
+    ("clap", "\u{1F44F}"),
+    ("wave", "\u{1F44B}"),
+    ("pray", "\u{1F64F}"),
+    ("muscle", "\u{1F4AA}"),
+    ("raised_hands", "\u{1F64C}"),
+    ("heart", "\u{2764}\u{FE0F}"),
+    ("broken_heart", "\u{1F494}"),
+    ("fire", "\u{1F525}"),
+    ("sparkles", "\u{2728}"),
+    ("star", "\u{2B50}"),
+    ("tada", "\u{1F389}"),
+    ("rocket", "\u{1F680}"),
+    ("bulb", "\u{1F4A1}"),
+    ("bug", "\u{1F41B}"),
+    ("warning", "\u{26A0}\u{FE0F}"),
+    ("white_check_mark", "\u{2705}"),
+    ("x", "\u{274C}"),
+    ("question", "\u{2753}"),
+    ("100", "\u{1F4AF}"),
+    ("coffee", "\u{2615}"),
+];
+
+/// The emoji for `shortcode` (without colons), if it's in `EMOJI`.
+pub fn emoji(shortcode: &str) -> Option<&'static str> {
+    EMOJI
+        .iter()
+        .find(|(code, _)| *code == shortcode)
+        .map(|(_, emoji)| *emoji)
+}
+
+/// Replace each `:shortcode:` from `EMOJI` in `text` with its emoji.
+pub fn expand_shortcodes(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(':') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let expanded = after
+            .find(':')
+            .and_then(|end| Some((emoji(&after[..end])?, end)));
+        match expanded {
+            Some((emoji, end)) => {
+                out.push_str(emoji);
+                rest = &after[end + 1..];
+            }
+            None => {
+                // The colon may still open the next shortcode, as in `a::+1:`
+                out.push(':');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+// ── Links ──
+
+/// Bare `http(s)://` addresses in a stored body, in order. Punctuation
+/// ending a sentence (`see https://example.com.`) is left off, and the
+/// `&amp;`s the sanitizer wrote are decoded. Not validated; parse before use.
+pub fn bare_urls(body: &str) -> Vec<String> {
+    let mut urls = Vec::new();
+    let mut rest = body;
+    while let Some(start) = [rest.find("https://"), rest.find("http://")].into_iter().flatten().min() {
+        let tail = &rest[start..];
+        let mut end = tail
+            .find(|c: char| c.is_whitespace() || matches!(c, '<' | '>' | '"' | '\''))
+            .unwrap_or(tail.len());
+        // Other entities stand for characters that end a URL
+        for entity in ["&quot;", "&lt;", "&gt;", "&#39;"] {
+            if let Some(pos) = tail[..end].find(entity) {
+                end = pos;
+            }
+        }
+        let url = tail[..end].trim_end_matches(['.', ',', ';', ':', '!', '?', ')', ']']);
+        urls.push(url.replace("&amp;", "&"));
+        rest = &tail[end..];
+    }
+    urls
+}
+
+// ── Bookmarks ──
+
+/// Body of `POST /api/bookmarks`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateBookmark {
+    /// `thread`, `reply` or `comment`.
+    pub target_type: String,
+    pub target_id: i64,
+}
+
+/// A saved thread, reply or comment, as listed by `GET /api/bookmarks`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub item: ActivityItem,
+    pub bookmarked_at: String,
+}
+
+// ── Admin ──
+
+/// An email template rendered with sample data, for checking branding.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailPreview {
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+}
+
+/// Fold one account into another: all content and votes of `from_user_id`
+/// are reassigned to `into_user_id`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeUsers {
+    pub from_user_id: UserId,
+    pub into_user_id: UserId,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeSummary {
+    pub comments: usize,
+    pub threads: usize,
+    pub replies: usize,
+    pub subscriptions: usize,
+    pub votes_moved: usize,
+    /// Votes dropped because the target account had already voted on the
+    /// same item.
+    pub votes_dropped: usize,
+}
+
+/// Read-only session as another user, from
+/// `POST /api/admin/users/{id}/impersonate`. Every request made with the
+/// token is recorded in the audit log under the admin.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Impersonation {
+    pub token: String,
+    pub user: User,
+    /// Unix timestamp.
+    pub expires_at: i64,
+}
+
+/// A database snapshot written by `POST /api/admin/backup`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupInfo {
+    /// File path on the server, or `s3://bucket/key`.
+    pub location: String,
+    pub bytes: u64,
+    pub created_at: String,
+}
+
+/// Outcome of `POST /api/admin/checkpoint`, as reported by SQLite's
+/// `wal_checkpoint`. Frame counts are -1 when the database isn't in WAL mode.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckpointResult {
+    /// The checkpoint couldn't finish because of concurrent readers or
+    /// writers.
+    pub busy: bool,
+    /// Frames in the WAL.
+    pub log_frames: i64,
+    /// Frames copied into the database file.
+    pub checkpointed_frames: i64,
+}
+
+/// An outgoing webhook; see `POST /api/admin/webhooks`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    pub id: i64,
+    pub site_id: String,
+    pub url: String,
+    /// Subscribed events (`comment.created`, ...); empty means all of them.
+    pub events: Vec<String>,
+    pub created_at: String,
+    /// Deliveries still waiting for a successful attempt.
+    pub pending_deliveries: i64,
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateWebhook {
+    /// Defaults to the instance's `default` site.
+    #[serde(default)]
+    pub site_id: Option<String>,
+    pub url: String,
+    #[serde(default)]
+    pub events: Vec<String>,
+}
+
+/// Returned once on creation; the secret can't be read back later.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewWebhook {
+    pub webhook: Webhook,
+    /// HMAC-SHA256 key for the `X-Mikaana-Signature` header.
+    pub secret: String,
+}
+
+// ── Word filters ──
+
+/// What happens to a post matching a word filter.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterAction {
+    /// Refuse the post with `422 Unprocessable Entity`.
+    #[default]
+    Reject,
+    /// Accept it as `pending`, for a moderator to approve or remove.
+    Queue,
+}
+
+impl FilterAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            FilterAction::Reject => "reject",
+            FilterAction::Queue => "queue",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WordFilter {
+    pub id: i64,
+    /// A word or phrase matched whole and case-insensitively, or with
+    /// `is_regex` a regular expression (also case-insensitive).
+    pub pattern: String,
+    pub is_regex: bool,
+    pub action: FilterAction,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateWordFilter {
+    pub pattern: String,
+    #[serde(default)]
+    pub is_regex: bool,
+    #[serde(default)]
+    pub action: FilterAction,
+}
+
+// ── Analytics ──
+
+/// Widget interactions counted by `POST /api/events`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnalyticsEvent {
+    CommentPosted,
+    ThreadCreated,
+    ReplyPosted,
+    VoteCast,
+    ThreadViewed,
+}
+
+impl AnalyticsEvent {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AnalyticsEvent::CommentPosted => "comment_posted",
+            AnalyticsEvent::ThreadCreated => "thread_created",
+            AnalyticsEvent::ReplyPosted => "reply_posted",
+            AnalyticsEvent::VoteCast => "vote_cast",
+            AnalyticsEvent::ThreadViewed => "thread_viewed",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<AnalyticsEvent> {
+        match s {
+            "comment_posted" => Some(AnalyticsEvent::CommentPosted),
+            "thread_created" => Some(AnalyticsEvent::ThreadCreated),
+            "reply_posted" => Some(AnalyticsEvent::ReplyPosted),
+            "vote_cast" => Some(AnalyticsEvent::VoteCast),
+            "thread_viewed" => Some(AnalyticsEvent::ThreadViewed),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordEvent {
+    pub event: AnalyticsEvent,
+}
+
+/// `GET /api/admin/analytics`: how often each event happened over the last
+/// `days` days (today included), in total and per day.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalyticsSummary {
+    pub days: u32,
+    /// Most frequent first.
+    pub totals: Vec<EventCount>,
+    /// Oldest day first; days without events are left out.
+    pub daily: Vec<DailyEventCount>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventCount {
+    pub event: AnalyticsEvent,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DailyEventCount {
+    /// `YYYY-MM-DD`, UTC.
+    pub day: String,
+    pub event: AnalyticsEvent,
+    pub count: i64,
+}
+
 // ── GitHub Stats ──
 
+/// Community totals from `GET /api/stats`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SiteStats {
+    /// Accounts that have posted or voted on this site.
+    pub users: i64,
+    pub comments: i64,
+    pub threads: i64,
+    pub replies: i64,
+    pub votes: i64,
+    /// Posts with the most comments, busiest first.
+    pub most_active_posts: Vec<PostActivity>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostActivity {
+    pub post_slug: String,
+    pub comments: i64,
+    pub last_comment_at: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GitHubStats {
     pub commits: i64,
+    /// Estimated from `languages`, summed over all of them.
     pub lines_of_code: i64,
     pub crate_count: i64,
     pub stars: i64,
     pub forks: i64,
     pub open_issues: i64,
     pub last_push: String,
+    /// Largest first.
+    #[serde(default)]
+    pub languages: Vec<LanguageStats>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LanguageStats {
+    pub name: String,
+    /// Source size as reported by GitHub.
+    pub bytes: i64,
+    /// `bytes` over the configured bytes-per-line for the language.
+    pub lines: i64,
 }
@@ -0,0 +1,451 @@
+//! Paths of the API. Each route has a constant with its pattern as the
+//! router takes it (`{id}` for a path parameter), used to register it, and
+//! the ones clients call with parameters have a function building the path
+//! to request from that same pattern, so the two can't drift apart.
+
+use std::fmt::{self, Display, Write};
+
+use crate::{CommentId, ReplyId, ReplySort, ThreadId, ThreadSort, UserId};
+
+/// `pattern` with each `{...}` replaced by the next of `params`.
+fn fill(pattern: &str, params: &[&dyn Display]) -> String {
+    let mut path = String::with_capacity(pattern.len());
+    let mut params = params.iter();
+    let mut rest = pattern;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..].find('}').map_or(rest.len(), |e| start + e + 1);
+        path.push_str(&rest[..start]);
+        if let Some(param) = params.next() {
+            path.push_str(&encode(*param));
+        }
+        rest = &rest[end..];
+    }
+    path.push_str(rest);
+    path
+}
+
+/// `path` with `params` as its query string; `None`s are left out.
+fn query(mut path: String, params: &[(&str, Option<&dyn Display>)]) -> String {
+    let mut sep = '?';
+    for (name, value) in params {
+        if let Some(value) = value {
+            path.push(sep);
+            path.push_str(name);
+            path.push('=');
+            path.push_str(&encode(*value));
+            sep = '&';
+        }
+    }
+    path
+}
+
+/// Percent-encoded like `encodeURIComponent`.
+fn encode(value: &dyn Display) -> String {
+    struct Encoder(String);
+
+    impl Write for Encoder {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            for b in s.bytes() {
+                match b {
+                    b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' => self.0.push(b as char),
+                    b'-' | b'_' | b'.' | b'!' | b'~' | b'*' | b'\'' | b'(' | b')' => {
+                        self.0.push(b as char)
+                    }
+                    _ => write!(self.0, "%{b:02X}")?,
+                }
+            }
+            Ok(())
+        }
+    }
+
+    let mut encoder = Encoder(String::new());
+    let _ = write!(encoder, "{value}");
+    encoder.0
+}
+
+pub const HEALTH: &str = "/api/health";
+pub const HEALTH_LIVE: &str = "/api/health/live";
+pub const HEALTH_READY: &str = "/api/health/ready";
+pub const CONFIG: &str = "/api/config";
+pub const TERMS_ACCEPT: &str = "/api/terms/accept";
+pub const AVATAR: &str = "/api/avatars/{user_id}";
+pub const UNFURL: &str = "/api/unfurl";
+pub const WEBMENTION: &str = "/api/webmention";
+pub const WEBMENTIONS: &str = "/api/webmentions";
+pub const EVENTS: &str = "/api/events";
+pub const REPORTS: &str = "/api/reports";
+pub const GRAPHQL: &str = "/api/graphql";
+pub const STATS: &str = "/api/stats";
+pub const ACTIVITY: &str = "/api/activity";
+pub const GITHUB_STATS: &str = "/api/github-stats";
+pub const COMMENT_FEED: &str = "/api/feeds/comments.json";
+pub const CATEGORY_FEED: &str = "/api/feeds/category/{file}";
+pub const THREAD_FEED: &str = "/api/feeds/thread/{file}";
+pub const FORUM_SITEMAP: &str = "/sitemap-forum.xml";
+
+pub fn avatar(user_id: UserId) -> String {
+    fill(AVATAR, &[&user_id])
+}
+
+/// Link preview for `url`.
+pub fn unfurl(url: &str) -> String {
+    query(UNFURL.into(), &[("url", Some(&url))])
+}
+
+/// Webmentions received for the post `slug`.
+pub fn webmentions(slug: &str) -> String {
+    query(WEBMENTIONS.into(), &[("slug", Some(&slug))])
+}
+
+pub fn activity(limit: i64) -> String {
+    query(ACTIVITY.into(), &[("limit", Some(&limit))])
+}
+
+/// Stats of the GitHub repository `repo` (`owner/name`).
+pub fn github_stats(repo: &str) -> String {
+    query(GITHUB_STATS.into(), &[("repo", Some(&repo))])
+}
+
+pub mod auth {
+    use super::*;
+
+    pub const GITHUB: &str = "/api/auth/github";
+    pub const CALLBACK: &str = "/api/auth/callback";
+    pub const ME: &str = "/api/auth/me";
+    pub const PROFILE: &str = "/api/auth/me/profile";
+    pub const EXPORT: &str = "/api/auth/me/export";
+
+    /// Sign in with GitHub, coming back to `redirect`; with `popup`, the
+    /// callback answers with `postMessage` instead.
+    pub fn github(redirect: &str, popup: bool) -> String {
+        let popup = popup.then_some("true");
+        query(
+            GITHUB.into(),
+            &[
+                ("redirect", Some(&redirect)),
+                ("popup", popup.as_ref().map(|p| p as &dyn Display)),
+            ],
+        )
+    }
+}
+
+pub mod comments {
+    use super::*;
+
+    pub const LIST: &str = "/api/comments";
+    pub const HTML: &str = "/api/comments/html";
+    pub const STATE: &str = "/api/comments/state";
+    pub const COMMENT: &str = "/api/comments/{id}";
+
+    /// Comments on the post `slug`.
+    pub fn list(slug: &str) -> String {
+        query(LIST.into(), &[("slug", Some(&slug))])
+    }
+
+    pub fn state(slug: &str) -> String {
+        query(STATE.into(), &[("slug", Some(&slug))])
+    }
+
+    pub fn comment(id: CommentId) -> String {
+        fill(COMMENT, &[&id])
+    }
+}
+
+pub mod uploads {
+    use super::*;
+
+    pub const UPLOADS: &str = "/api/uploads";
+    pub const UPLOAD: &str = "/api/uploads/{id}";
+
+    /// Where to send a file called `filename`.
+    pub fn upload(filename: &str) -> String {
+        query(UPLOADS.into(), &[("filename", Some(&filename))])
+    }
+}
+
+pub mod votes {
+    use super::*;
+
+    pub const VOTES: &str = "/api/votes";
+    pub const POST: &str = "/api/votes/post";
+
+    /// Tally of the comment, thread or reply `target_id`.
+    pub fn tally(target_type: &str, target_id: i64) -> String {
+        query(
+            VOTES.into(),
+            &[("type", Some(&target_type)), ("id", Some(&target_id))],
+        )
+    }
+
+    /// Tally of the post `slug` as a whole.
+    pub fn post(slug: &str) -> String {
+        query(POST.into(), &[("slug", Some(&slug))])
+    }
+}
+
+pub mod forum {
+    use super::*;
+
+    pub const CATEGORIES: &str = "/api/forum/categories";
+    pub const CATEGORY: &str = "/api/forum/categories/{slug}";
+    pub const SUBSCRIBE: &str = "/api/forum/categories/{slug}/subscribe";
+    pub const THREADS: &str = "/api/forum/threads";
+    pub const THREAD: &str = "/api/forum/threads/{id}";
+    pub const THREAD_HTML: &str = "/api/forum/threads/{id}/html";
+    pub const PREVIEW: &str = "/api/forum/threads/{id}/preview";
+    pub const REPLIES: &str = "/api/forum/threads/{id}/replies";
+    pub const REPLY: &str = "/api/forum/replies/{id}";
+    pub const MOVE: &str = "/api/forum/threads/{id}/move";
+    pub const PIN: &str = "/api/forum/threads/{id}/pin";
+    pub const SOLUTION: &str = "/api/forum/threads/{id}/solution";
+    pub const SUMMARY: &str = "/api/forum/threads/{id}/summary";
+    pub const SEARCH: &str = "/api/forum/search";
+
+    pub fn category(slug: &str) -> String {
+        fill(CATEGORY, &[&slug])
+    }
+
+    pub fn subscribe(slug: &str) -> String {
+        fill(SUBSCRIBE, &[&slug])
+    }
+
+    /// Page `page` (from 1) of the threads in `category`.
+    pub fn threads(category: &str, page: i64, sort: ThreadSort) -> String {
+        query(
+            THREADS.into(),
+            &[
+                ("category", Some(&category)),
+                ("page", Some(&page)),
+                ("sort", Some(&sort.as_str())),
+            ],
+        )
+    }
+
+    /// Threads in `category` by cursor: `after` is `None` for the first
+    /// page, then the previous page's `next`.
+    pub fn threads_after(category: &str, after: Option<&str>, sort: ThreadSort) -> String {
+        query(
+            THREADS.into(),
+            &[
+                ("category", Some(&category)),
+                ("after", Some(&after.unwrap_or_default())),
+                ("sort", Some(&sort.as_str())),
+            ],
+        )
+    }
+
+    /// The thread `id` with its replies in `sort` order.
+    pub fn thread(id: ThreadId, sort: ReplySort) -> String {
+        query(fill(THREAD, &[&id]), &[("sort", Some(&sort.as_str()))])
+    }
+
+    pub fn preview(id: ThreadId) -> String {
+        fill(PREVIEW, &[&id])
+    }
+
+    pub fn replies(id: ThreadId) -> String {
+        fill(REPLIES, &[&id])
+    }
+
+    pub fn reply(id: ReplyId) -> String {
+        fill(REPLY, &[&id])
+    }
+
+    pub fn move_to(id: ThreadId) -> String {
+        fill(MOVE, &[&id])
+    }
+
+    pub fn pin(id: ThreadId) -> String {
+        fill(PIN, &[&id])
+    }
+
+    pub fn solution(id: ThreadId) -> String {
+        fill(SOLUTION, &[&id])
+    }
+
+    pub fn summary(id: ThreadId) -> String {
+        fill(SUMMARY, &[&id])
+    }
+
+    pub fn search(q: &str) -> String {
+        query(SEARCH.into(), &[("q", Some(&q))])
+    }
+}
+
+pub mod users {
+    use super::*;
+
+    pub const SEARCH: &str = "/api/users/search";
+    pub const FOLLOW: &str = "/api/users/{id}/follow";
+    pub const FOLLOWS: &str = "/api/follows";
+    pub const BLOCK: &str = "/api/users/{id}/block";
+    pub const BLOCKS: &str = "/api/blocks";
+    pub const FEED: &str = "/api/feed";
+
+    /// Users whose name starts with `q`, for @mentions.
+    pub fn search(q: &str) -> String {
+        query(SEARCH.into(), &[("q", Some(&q))])
+    }
+
+    pub fn follow(id: UserId) -> String {
+        fill(FOLLOW, &[&id])
+    }
+
+    pub fn block(id: UserId) -> String {
+        fill(BLOCK, &[&id])
+    }
+
+    /// Posts by followed users, older than the cursor `before` if given.
+    pub fn feed(before: Option<&str>) -> String {
+        query(
+            FEED.into(),
+            &[("before", before.as_ref().map(|b| b as &dyn Display))],
+        )
+    }
+}
+
+pub mod messages {
+    use super::*;
+
+    pub const INBOX: &str = "/api/messages";
+    pub const CONVERSATION: &str = "/api/messages/{user_id}";
+
+    /// Messages with `user_id`, older than the cursor `before` if given.
+    pub fn conversation(user_id: UserId, before: Option<&str>) -> String {
+        query(
+            fill(CONVERSATION, &[&user_id]),
+            &[("before", before.as_ref().map(|b| b as &dyn Display))],
+        )
+    }
+}
+
+pub mod notifications {
+    use super::*;
+
+    pub const LIST: &str = "/api/notifications";
+    pub const READ_ALL: &str = "/api/notifications/read-all";
+    pub const READ: &str = "/api/notifications/{id}/read";
+    pub const UNSUBSCRIBE: &str = "/api/notifications/unsubscribe";
+    pub const PREFERENCES: &str = "/api/notifications/preferences";
+
+    /// Notifications older than `before` (a `NotificationList::next`), or
+    /// the newest.
+    pub fn list(before: Option<i64>) -> String {
+        query(
+            LIST.into(),
+            &[("before", before.as_ref().map(|b| b as &dyn Display))],
+        )
+    }
+
+    pub fn read(id: i64) -> String {
+        fill(READ, &[&id])
+    }
+}
+
+pub mod bookmarks {
+    use super::*;
+
+    pub const LIST: &str = "/api/bookmarks";
+    pub const BOOKMARK: &str = "/api/bookmarks/{target_type}/{target_id}";
+
+    pub fn bookmark(target_type: &str, target_id: i64) -> String {
+        fill(BOOKMARK, &[&target_type, &target_id])
+    }
+}
+
+pub mod drafts {
+    use super::*;
+
+    pub const DRAFTS: &str = "/api/drafts";
+
+    pub fn draft(key: &str) -> String {
+        query(DRAFTS.into(), &[("key", Some(&key))])
+    }
+}
+
+pub mod admin {
+    use super::*;
+
+    pub const MERGE_USERS: &str = "/api/admin/users/merge";
+    pub const AUDIT_EXPORT: &str = "/api/admin/audit/export";
+    pub const CONFIG_RELOAD: &str = "/api/admin/config/reload";
+    pub const BACKUP: &str = "/api/admin/backup";
+    pub const CHECKPOINT: &str = "/api/admin/checkpoint";
+    pub const EMAILS: &str = "/api/admin/emails";
+    pub const EMAIL_PREVIEW: &str = "/api/admin/emails/{name}/preview";
+    pub const BULK: &str = "/api/admin/bulk";
+    pub const QUEUE: &str = "/api/admin/moderation/queue";
+    pub const SCHEDULED: &str = "/api/admin/scheduled";
+    pub const ANALYTICS: &str = "/api/admin/analytics";
+    pub const POST_SETTINGS: &str = "/api/admin/post-settings";
+    pub const REPORTS: &str = "/api/admin/moderation/reports";
+    pub const RESOLVE_REPORT: &str = "/api/admin/moderation/reports/{id}/resolve";
+    pub const MODERATE: &str = "/api/admin/moderation/content";
+    pub const USERS: &str = "/api/admin/users";
+    pub const BAN: &str = "/api/admin/users/{id}/ban";
+    pub const IMPERSONATE: &str = "/api/admin/users/{id}/impersonate";
+    pub const WEBHOOKS: &str = "/api/admin/webhooks";
+    pub const WEBHOOK: &str = "/api/admin/webhooks/{id}";
+    pub const WORD_FILTERS: &str = "/api/admin/word-filters";
+    pub const WORD_FILTER: &str = "/api/admin/word-filters/{id}";
+    pub const IP_BANS: &str = "/api/admin/ip-bans";
+    pub const IP_BAN: &str = "/api/admin/ip-bans/{id}";
+
+    /// Audit records from `from` (inclusive) to `to` (exclusive).
+    pub fn audit_export(from: Option<&str>, to: Option<&str>) -> String {
+        query(
+            AUDIT_EXPORT.into(),
+            &[
+                ("from", from.as_ref().map(|f| f as &dyn Display)),
+                ("to", to.as_ref().map(|t| t as &dyn Display)),
+            ],
+        )
+    }
+
+    /// Checkpoint the WAL in `mode` (`passive`, `full`, `restart`, `truncate`).
+    pub fn checkpoint(mode: &str) -> String {
+        query(CHECKPOINT.into(), &[("mode", Some(&mode))])
+    }
+
+    pub fn email_preview(name: &str) -> String {
+        fill(EMAIL_PREVIEW, &[&name])
+    }
+
+    /// Analytics for the last `days` days.
+    pub fn analytics(days: u32) -> String {
+        query(ANALYTICS.into(), &[("days", Some(&days))])
+    }
+
+    /// Settings of the post `slug`, for clearing them.
+    pub fn post_settings(slug: &str) -> String {
+        query(POST_SETTINGS.into(), &[("slug", Some(&slug))])
+    }
+
+    pub fn resolve_report(id: i64) -> String {
+        fill(RESOLVE_REPORT, &[&id])
+    }
+
+    pub fn users(limit: i64) -> String {
+        query(USERS.into(), &[("limit", Some(&limit))])
+    }
+
+    pub fn ban(id: UserId) -> String {
+        fill(BAN, &[&id])
+    }
+
+    pub fn impersonate(id: UserId) -> String {
+        fill(IMPERSONATE, &[&id])
+    }
+
+    pub fn webhook(id: i64) -> String {
+        fill(WEBHOOK, &[&id])
+    }
+
+    pub fn word_filter(id: i64) -> String {
+        fill(WORD_FILTER, &[&id])
+    }
+
+    pub fn ip_ban(id: i64) -> String {
+        fill(IP_BAN, &[&id])
+    }
+}